@@ -1,3 +1,4 @@
+pub mod aggregate;
 pub mod normalize;
 pub mod quantify;
 pub mod transform;
@@ -6,6 +7,8 @@ use clap::{Parser, Subcommand};
 
 #[derive(Subcommand)]
 pub enum Command {
+    /// Aggregate transcript-level estimates to gene level.
+    Aggregate(aggregate::Args),
     /// Normalize feature counts.
     Normalize(normalize::Args),
     /// Transform feature counts.
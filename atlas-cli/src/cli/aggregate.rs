@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+
+use atlas_core::counts::tximport;
+use clap::{Parser, ValueEnum};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Salmon,
+    Kallisto,
+    Rsem,
+}
+
+impl From<Format> for tximport::reader::Format {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Salmon => Self::Salmon,
+            Format::Kallisto => Self::Kallisto,
+            Format::Rsem => Self::Rsem,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum CountsFromAbundance {
+    /// Use the summed estimated counts.
+    #[default]
+    No,
+    /// Scale abundances to the library size.
+    ScaledTpm,
+    /// Scale abundances by the average gene length and then to the library size.
+    LengthScaledTpm,
+}
+
+impl From<CountsFromAbundance> for tximport::CountsFromAbundance {
+    fn from(counts_from_abundance: CountsFromAbundance) -> Self {
+        match counts_from_abundance {
+            CountsFromAbundance::No => Self::No,
+            CountsFromAbundance::ScaledTpm => Self::ScaledTpm,
+            CountsFromAbundance::LengthScaledTpm => Self::LengthScaledTpm,
+        }
+    }
+}
+
+#[derive(Parser)]
+pub struct Args {
+    /// Transcript feature type.
+    #[arg(long, default_value = "transcript")]
+    pub transcript_type: String,
+
+    /// Transcript feature ID.
+    ///
+    /// The `Parent` attribute of each transcript is its gene.
+    #[arg(long, default_value = "ID")]
+    pub transcript_id: String,
+
+    /// Input annotations file (GFF3).
+    ///
+    /// This can be uncompressed or (b)gzip-compressed.
+    #[arg(long)]
+    pub annotations: PathBuf,
+
+    /// The input format.
+    ///
+    /// By default, the format is autodetected.
+    #[arg(long, value_enum)]
+    pub format: Option<Format>,
+
+    /// The method used to derive counts from abundances.
+    #[arg(long, value_enum, default_value_t = CountsFromAbundance::No)]
+    pub counts_from_abundance: CountsFromAbundance,
+
+    /// Output destination.
+    ///
+    /// If not set, output is written to stdout as a features × samples matrix.
+    #[arg(long, conflicts_with = "output_directory")]
+    pub output: Option<PathBuf>,
+
+    /// Output directory.
+    ///
+    /// If set, counts are rounded and written per sample as `<sample-name>.txt` in the
+    /// htseq-count format.
+    #[arg(long)]
+    pub output_directory: Option<PathBuf>,
+
+    /// Input sources (Salmon, kallisto, or RSEM transcript estimates).
+    ///
+    /// The sample name is the name of the parent directory of `quant.sf` and `abundance.tsv`
+    /// files and otherwise the filename up to the first `.`. These can be uncompressed or
+    /// gzip-compressed.
+    #[arg(required = true)]
+    pub srcs: Vec<PathBuf>,
+}
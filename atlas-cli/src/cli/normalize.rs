@@ -5,6 +5,8 @@ use clap::{Parser, ValueEnum};
 
 #[derive(Clone, Copy, Debug, ValueEnum)]
 pub enum Method {
+    /// Counts per million (CPM) mapped reads.
+    Cpm,
     /// Fragments per kilobase per million (FPKM) mapped reads.
     Fpkm,
     /// Median of ratios.
@@ -13,6 +15,21 @@ pub enum Method {
     Tmm,
     /// Transcripts per million (TPM) mapped reads
     Tpm,
+    /// Variance stabilizing transformation (VST).
+    Vst,
+}
+
+impl From<Method> for core::counts::normalization::Method {
+    fn from(method: Method) -> Self {
+        match method {
+            Method::Cpm => Self::Cpm,
+            Method::Fpkm => Self::Fpkm,
+            Method::MedianOfRatios => Self::MedianOfRatios,
+            Method::Tmm => Self::Tmm,
+            Method::Tpm => Self::Tpm,
+            Method::Vst => Self::Vst,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Default, ValueEnum)]
@@ -68,6 +85,13 @@ pub struct Args {
     #[arg(long, value_enum, default_value_t = Method::Tpm)]
     pub method: Method,
 
+    /// Remove features with low counts before normalization.
+    ///
+    /// This uses edgeR's `filterByExpr` logic with all samples in a single group. Removed features
+    /// are not written to the output.
+    #[arg(long)]
+    pub filter_by_expression: bool,
+
     /// Strand specification.
     ///
     /// This is only used if the input format is STAR.
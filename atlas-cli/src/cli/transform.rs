@@ -1,3 +1,4 @@
+pub mod combat_seq;
 pub mod tsne;
 pub mod vst;
 
@@ -5,6 +6,8 @@ use clap::{Parser, Subcommand};
 
 #[derive(Subcommand)]
 pub enum Command {
+    /// Batch effect correction of raw counts using ComBat-seq.
+    CombatSeq(combat_seq::Args),
     /// Dimension reduction using t-distributed Stochastic Neighbor Embedding (t-SNE).
     Tsne(tsne::Args),
     /// Variance stabilizing transformation (VST).
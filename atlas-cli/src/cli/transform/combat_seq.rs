@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+#[derive(Parser)]
+pub struct Args {
+    /// Sample sheet (TSV).
+    ///
+    /// The first row is a header of column names, and the first column is the sample name.
+    #[arg(long)]
+    pub samples: PathBuf,
+
+    /// The sample sheet column of the batch of each sample.
+    #[arg(long, default_value = "batch")]
+    pub batch_column: String,
+
+    /// A sample sheet column of a biological covariate to preserve.
+    ///
+    /// This can be given multiple times. Numeric columns are used as is. Other columns are treated
+    /// as categorical.
+    #[arg(long = "covariate")]
+    pub covariates: Vec<String>,
+
+    /// Output destination.
+    ///
+    /// If not set, output is written to stdout.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
+    /// Input source (features × samples count matrix).
+    ///
+    /// This can be uncompressed or gzip-compressed.
+    pub src: PathBuf,
+}
@@ -1,9 +1,31 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum InputFormat {
+    /// One count file per sample, i.e., rows of `<feature name>\t<count>`.
+    ///
+    /// The sample name is the filename up to the first `.`.
+    Counts,
+    /// A features × samples count matrix with a header of sample names.
+    Matrix,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    /// Tab-separated values with a header of `sample_name`, `x`, `y`, and, for 3D, `z`.
+    Tsv,
+    /// A JSON object of `sampleNames`, `x`, `y`, and, for 3D, `z` arrays.
+    Json,
+}
 
 #[derive(Parser)]
 pub struct Args {
+    /// The number of output dimensions.
+    #[arg(long, value_parser = clap::value_parser!(u8).range(2..=3), default_value_t = 2)]
+    pub dimensions: u8,
+
     /// Perplexity of the conditional distribution.
     #[arg(long, default_value_t = 30.0)]
     pub perplexity: f64,
@@ -12,7 +34,47 @@ pub struct Args {
     #[arg(long, default_value_t = 0.5)]
     pub theta: f64,
 
+    /// Seed of the random number generator used to initialize the embedding.
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Remove features with low counts before normalization.
+    ///
+    /// This uses edgeR's `filterByExpr` logic with all samples in a single group.
+    #[arg(long)]
+    pub filter_by_expression: bool,
+
+    /// The number of features with the highest variance to keep.
+    ///
+    /// Set to 0 to use all features.
+    #[arg(long, default_value_t = 2000)]
+    pub variable_feature_count: usize,
+
+    /// The number of principal components to reduce the features to before embedding.
+    ///
+    /// Set to 0 to skip PCA.
+    #[arg(long, default_value_t = 50)]
+    pub principal_component_count: usize,
+
+    /// The input format.
+    ///
+    /// Multiple count matrices must have the same features in the same order.
+    #[arg(long, value_enum, default_value_t = InputFormat::Counts)]
+    pub input_format: InputFormat,
+
+    /// The output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Tsv)]
+    pub output_format: OutputFormat,
+
+    /// Output destination.
+    ///
+    /// If not set, output is written to stdout.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
     /// Input sources.
+    ///
+    /// These can be uncompressed or gzip-compressed.
     #[arg(required = true)]
     pub srcs: Vec<PathBuf>,
 }
@@ -1,5 +1,8 @@
+mod aggregate;
 mod normalize;
 mod quantify;
 pub mod transform;
 
-pub use self::{normalize::normalize, quantify::quantify, transform::transform};
+pub use self::{
+    aggregate::aggregate, normalize::normalize, quantify::quantify, transform::transform,
+};
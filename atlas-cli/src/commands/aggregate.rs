@@ -0,0 +1,210 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, bail};
+use atlas_core::{counts::tximport, features};
+use tracing::info;
+
+use crate::cli;
+
+const SEPARATOR: char = '\t';
+
+pub fn aggregate(args: cli::aggregate::Args) -> anyhow::Result<()> {
+    let annotations_src = &args.annotations;
+    let transcript_type = &args.transcript_type;
+    let transcript_id = &args.transcript_id;
+
+    info!(
+        src = ?annotations_src,
+        transcript_type, transcript_id, "reading transcripts"
+    );
+
+    let mut reader = crate::fs::open(annotations_src).map(BufReader::new)?;
+    let transcripts_to_genes = features::read_parents(&mut reader, transcript_type, transcript_id)?;
+
+    info!(
+        transcript_count = transcripts_to_genes.len(),
+        "read transcripts"
+    );
+
+    info!(sample_count = args.srcs.len(), "reading samples");
+
+    let format = args.format.map(|format| format.into());
+
+    let mut sample_names = Vec::with_capacity(args.srcs.len());
+    let mut samples = Vec::with_capacity(args.srcs.len());
+
+    for src in &args.srcs {
+        let sample_name = sample_name(src)?;
+
+        if sample_names.contains(&sample_name) {
+            bail!("duplicate sample name: {sample_name}");
+        }
+
+        let mut reader = crate::fs::open(src).map(BufReader::new)?;
+        let estimates = tximport::reader::read(&mut reader, format)
+            .with_context(|| format!("invalid estimates: {}", src.display()))?;
+
+        sample_names.push(sample_name);
+        samples.push(estimates);
+    }
+
+    info!("summarizing transcripts to genes");
+
+    let gene_estimates = tximport::summarize(
+        &samples,
+        &transcripts_to_genes,
+        args.counts_from_abundance.into(),
+    )?;
+
+    info!(gene_count = gene_estimates.names.len(), "writing counts");
+
+    if let Some(dst) = args.output_directory {
+        fs::create_dir_all(&dst)?;
+
+        for (sample_name, counts) in sample_names.iter().zip(&gene_estimates.counts) {
+            let dst = dst.join(format!("{sample_name}.txt"));
+            let mut writer = File::create(dst).map(BufWriter::new)?;
+            write_sample_counts(&mut writer, &gene_estimates.names, counts)?;
+            writer.flush()?;
+        }
+    } else {
+        let mut writer: Box<dyn Write> = if let Some(dst) = args.output {
+            File::create(dst).map(BufWriter::new).map(Box::new)?
+        } else {
+            let stdout = io::stdout().lock();
+            Box::new(BufWriter::new(stdout))
+        };
+
+        write_matrix(
+            &mut writer,
+            &sample_names,
+            &gene_estimates.names,
+            &gene_estimates.counts,
+        )?;
+
+        writer.flush()?;
+    }
+
+    info!("done");
+
+    Ok(())
+}
+
+// Returns the sample name of a transcript estimates file.
+//
+// Salmon and kallisto write estimates to files with fixed names in per-sample output
+// directories, so the name of the parent directory is used for these.
+fn sample_name(src: &Path) -> anyhow::Result<String> {
+    const DEFAULT_FILENAMES: [&str; 4] = [
+        "quant.sf",
+        "quant.sf.gz",
+        "abundance.tsv",
+        "abundance.tsv.gz",
+    ];
+
+    let Some(filename) = src.file_name().and_then(|s| s.to_str()) else {
+        bail!("invalid filename: {}", src.display());
+    };
+
+    let sample_name = if DEFAULT_FILENAMES.contains(&filename) {
+        src.parent()
+            .and_then(|dir| dir.file_name())
+            .and_then(|s| s.to_str())
+    } else {
+        filename.split('.').next()
+    };
+
+    match sample_name {
+        Some(name) if !name.is_empty() => Ok(name.into()),
+        _ => bail!("invalid sample name: {}", src.display()),
+    }
+}
+
+fn write_sample_counts<W>(writer: &mut W, gene_names: &[String], counts: &[f64]) -> io::Result<()>
+where
+    W: Write,
+{
+    for (name, count) in gene_names.iter().zip(counts) {
+        let n = count.round() as u32;
+        writeln!(writer, "{name}{SEPARATOR}{n}")?;
+    }
+
+    Ok(())
+}
+
+fn write_matrix<W>(
+    writer: &mut W,
+    sample_names: &[String],
+    gene_names: &[String],
+    counts: &[Vec<f64>],
+) -> io::Result<()>
+where
+    W: Write,
+{
+    for sample_name in sample_names {
+        write!(writer, "{SEPARATOR}{sample_name}")?;
+    }
+
+    writeln!(writer)?;
+
+    for (j, gene_name) in gene_names.iter().enumerate() {
+        write!(writer, "{gene_name}")?;
+
+        for sample_counts in counts {
+            let n = sample_counts[j];
+            write!(writer, "{SEPARATOR}{n}")?;
+        }
+
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_name() -> anyhow::Result<()> {
+        assert_eq!(sample_name(Path::new("salmon/s0/quant.sf"))?, "s0");
+        assert_eq!(
+            sample_name(Path::new("kallisto/s1/abundance.tsv.gz"))?,
+            "s1"
+        );
+        assert_eq!(sample_name(Path::new("rsem/s2.isoforms.results"))?, "s2");
+
+        assert!(sample_name(Path::new("quant.sf")).is_err());
+        assert!(sample_name(Path::new(".isoforms.results")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_sample_counts() -> io::Result<()> {
+        let gene_names = [String::from("g0"), String::from("g1")];
+
+        let mut buf = Vec::new();
+        write_sample_counts(&mut buf, &gene_names, &[2.4, 7.5])?;
+        assert_eq!(buf, b"g0\t2\ng1\t8\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_matrix() -> io::Result<()> {
+        let sample_names = [String::from("s0"), String::from("s1")];
+        let gene_names = [String::from("g0"), String::from("g1")];
+        let counts = [vec![2.5, 0.0], vec![3.0, 8.25]];
+
+        let mut buf = Vec::new();
+        write_matrix(&mut buf, &sample_names, &gene_names, &counts)?;
+        assert_eq!(buf, b"\ts0\ts1\ng0\t2.5\t3\ng1\t0\t8.25\n");
+
+        Ok(())
+    }
+}
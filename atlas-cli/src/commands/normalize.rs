@@ -7,13 +7,16 @@ use std::{
 
 use atlas_core::{
     StrandSpecification,
-    counts::normalization::{fpkm, median_of_ratios, tmm, tpm},
+    counts::{
+        feature_selection,
+        normalization::{self, NormalizeError as NormalizationError},
+    },
     features::{self, Feature, ReadFeaturesError},
 };
 use thiserror::Error;
 use tracing::info;
 
-use crate::cli::normalize::{self, Method};
+use crate::cli::normalize;
 
 const SEPARATOR: char = '\t';
 
@@ -43,7 +46,7 @@ pub fn normalize(args: normalize::Args) -> Result<(), NormalizeError> {
     let src = srcs.next().unwrap();
     let counts = read_counts(src, format, feature_id, strand_specification)?;
 
-    let names: Vec<_> = counts.iter().map(|(name, _)| name.clone()).collect();
+    let mut names: Vec<_> = counts.iter().map(|(name, _)| name.clone()).collect();
     let mut counts: Vec<_> = counts.into_iter().map(|(_, value)| value).collect();
 
     for src in srcs {
@@ -57,33 +60,44 @@ pub fn normalize(args: normalize::Args) -> Result<(), NormalizeError> {
         )?;
     }
 
+    if args.filter_by_expression {
+        let groups = vec![(); sample_count];
+
+        let indices = feature_selection::filter_by_expression(
+            &counts,
+            names.len(),
+            &groups,
+            &Default::default(),
+        )?;
+
+        info!(
+            feature_count = names.len(),
+            retained_feature_count = indices.len(),
+            "filtered features by expression"
+        );
+
+        counts = feature_selection::retain_features(&counts, names.len(), &indices);
+        names = indices.into_iter().map(|i| names[i].clone()).collect();
+    }
+
     let normalization_method = args.method;
 
     info!(?normalization_method, "normalizing counts");
 
-    let normalized_counts: Vec<Vec<f64>> = match normalization_method {
-        Method::Fpkm => {
-            let lengths = calculate_feature_lengths(&features, &names)?;
+    let method = normalization::Method::from(normalization_method);
 
-            counts
-                .chunks_exact(names.len())
-                .map(|sample| fpkm::normalize(&lengths, sample))
-                .collect()
-        }
-        Method::MedianOfRatios => {
-            median_of_ratios::normalize_vec(sample_count, names.len(), counts)?
-        }
-        Method::Tmm => tmm::normalize_vec(sample_count, names.len(), counts)?,
-        Method::Tpm => {
-            let lengths = calculate_feature_lengths(&features, &names)?;
-
-            counts
-                .chunks_exact(names.len())
-                .map(|sample| tpm::normalize(&lengths, sample))
-                .collect()
-        }
+    let lengths = if method.uses_feature_lengths() {
+        calculate_feature_lengths(&features, &names)?
+    } else {
+        Vec::new()
     };
 
+    let normalized_counts: Vec<Vec<f64>> =
+        normalization::normalize(method, sample_count, names.len(), &counts, &lengths)?
+            .chunks_exact(names.len())
+            .map(|sample| sample.to_vec())
+            .collect();
+
     assert!(!normalized_counts.is_empty());
 
     let stdout = io::stdout().lock();
@@ -106,6 +120,8 @@ pub enum NormalizeError {
     Io(#[from] io::Error),
     #[error("invalid features")]
     InvalidFeatures(#[from] ReadFeaturesError),
+    #[error("normalization failed")]
+    Normalization(#[from] NormalizationError),
 }
 
 fn read_features<P>(
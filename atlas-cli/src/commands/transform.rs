@@ -1,3 +1,5 @@
+mod combat_seq;
+mod counts;
 mod tsne;
 mod vst;
 
@@ -5,6 +7,7 @@ use crate::cli::{self, transform::Command};
 
 pub fn transform(args: cli::transform::Args) -> anyhow::Result<()> {
     match args.command {
+        Command::CombatSeq(args) => combat_seq::run(args),
         Command::Tsne(args) => tsne::run(args),
         Command::Vst(args) => vst::run(args),
     }
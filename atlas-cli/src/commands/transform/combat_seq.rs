@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+};
+
+use anyhow::{anyhow, bail};
+use atlas_core::counts::batch_correction::combat_seq;
+use tracing::info;
+
+use super::counts::{read_line, read_matrix, write_matrix};
+use crate::cli;
+
+const SEPARATOR: char = '\t';
+
+pub fn run(args: cli::transform::combat_seq::Args) -> anyhow::Result<()> {
+    info!(src = ?args.src, "reading counts");
+
+    let mut reader = crate::fs::open(&args.src).map(BufReader::new)?;
+    let (sample_names, feature_names, counts) = read_matrix(&mut reader)?;
+
+    info!(
+        sample_count = sample_names.len(),
+        feature_count = feature_names.len(),
+        "read counts"
+    );
+
+    let mut reader = crate::fs::open(&args.samples).map(BufReader::new)?;
+    let sample_sheet = SampleSheet::read(&mut reader)?;
+
+    let batches = sample_sheet.column(&args.batch_column, &sample_names)?;
+
+    let mut covariates = Vec::new();
+
+    for name in &args.covariates {
+        let values = sample_sheet.column(name, &sample_names)?;
+        covariates.extend(combat_seq::encode_covariate(&values));
+    }
+
+    info!("correcting counts");
+
+    let corrected_counts = combat_seq::correct_vec(
+        sample_names.len(),
+        feature_names.len(),
+        counts,
+        &batches,
+        &covariates,
+    )?;
+
+    let corrected_counts: Vec<_> = corrected_counts.into_iter().flatten().collect();
+
+    let mut writer: Box<dyn Write> = if let Some(dst) = args.output {
+        File::create(dst).map(BufWriter::new).map(Box::new)?
+    } else {
+        let stdout = io::stdout().lock();
+        Box::new(BufWriter::new(stdout))
+    };
+
+    write_matrix(
+        &mut writer,
+        &sample_names,
+        &feature_names,
+        &corrected_counts,
+    )?;
+    writer.flush()?;
+
+    info!("done");
+
+    Ok(())
+}
+
+struct SampleSheet {
+    column_names: Vec<String>,
+    rows: HashMap<String, Vec<String>>,
+}
+
+impl SampleSheet {
+    fn read<R>(reader: &mut R) -> anyhow::Result<Self>
+    where
+        R: BufRead,
+    {
+        let mut line = String::new();
+
+        if read_line(reader, &mut line)? == 0 {
+            bail!("sample sheet unexpectedly empty");
+        }
+
+        let column_names: Vec<_> = line.split(SEPARATOR).skip(1).map(String::from).collect();
+        let mut rows = HashMap::new();
+
+        loop {
+            line.clear();
+
+            if read_line(reader, &mut line)? == 0 {
+                break;
+            }
+
+            let mut fields = line.split(SEPARATOR);
+
+            // SAFETY: `split` always yields at least one item.
+            let sample_name = fields.next().unwrap();
+            let values: Vec<_> = fields.map(String::from).collect();
+
+            if values.len() != column_names.len() {
+                bail!("invalid sample sheet row: {sample_name}");
+            }
+
+            if rows.insert(sample_name.into(), values).is_some() {
+                bail!("duplicate sample sheet row: {sample_name}");
+            }
+        }
+
+        Ok(Self { column_names, rows })
+    }
+
+    // Returns the values of a column in the order of the given sample names.
+    fn column(&self, name: &str, sample_names: &[String]) -> anyhow::Result<Vec<&str>> {
+        let j = self
+            .column_names
+            .iter()
+            .position(|column_name| column_name == name)
+            .ok_or_else(|| anyhow!("missing sample sheet column: {name}"))?;
+
+        sample_names
+            .iter()
+            .map(|sample_name| {
+                self.rows
+                    .get(sample_name)
+                    .map(|values| values[j].as_str())
+                    .ok_or_else(|| anyhow!("missing sample sheet row: {sample_name}"))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_sheet() -> anyhow::Result<()> {
+        let src = b"sample_name\tbatch\tcondition\ns0\tb0\ttumor\ns1\tb1\tnormal\n";
+        let sample_sheet = SampleSheet::read(&mut &src[..])?;
+
+        let sample_names = [String::from("s1"), String::from("s0")];
+
+        assert_eq!(sample_sheet.column("batch", &sample_names)?, ["b1", "b0"]);
+        assert_eq!(
+            sample_sheet.column("condition", &sample_names)?,
+            ["normal", "tumor"]
+        );
+
+        assert!(sample_sheet.column("age", &sample_names).is_err());
+        assert!(sample_sheet.column("batch", &[String::from("s2")]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_sheet_with_invalid_rows() {
+        let src = b"sample_name\tbatch\ns0\tb0\ts1\n";
+        assert!(SampleSheet::read(&mut &src[..]).is_err());
+
+        let src = b"sample_name\tbatch\ns0\tb0\ns0\tb1\n";
+        assert!(SampleSheet::read(&mut &src[..]).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::{self, BufRead, Write};
+
+use anyhow::bail;
+
+const SEPARATOR: char = '\t';
+
+// Returns the sample names, feature names, and row-major samples × features counts of a matrix.
+pub(super) fn read_matrix<R>(reader: &mut R) -> anyhow::Result<(Vec<String>, Vec<String>, Vec<u32>)>
+where
+    R: BufRead,
+{
+    let mut line = String::new();
+
+    if read_line(reader, &mut line)? == 0 {
+        bail!("input unexpectedly empty");
+    }
+
+    let sample_names: Vec<String> = line.split(SEPARATOR).skip(1).map(String::from).collect();
+    let sample_count = sample_names.len();
+
+    let mut feature_names = Vec::new();
+    let mut rows = Vec::new();
+
+    loop {
+        line.clear();
+
+        if read_line(reader, &mut line)? == 0 {
+            break;
+        }
+
+        let mut fields = line.split(SEPARATOR);
+
+        // SAFETY: `split` always yields at least one item.
+        let feature_name = fields.next().unwrap();
+        feature_names.push(feature_name.into());
+
+        let start = rows.len();
+
+        for field in fields {
+            rows.push(field.parse::<u32>()?);
+        }
+
+        if rows.len() - start != sample_count {
+            bail!(
+                "invalid row: {feature_name}: expected {sample_count} counts, got {}",
+                rows.len() - start
+            );
+        }
+    }
+
+    let feature_count = feature_names.len();
+    let mut counts = vec![0; rows.len()];
+
+    for (j, row) in rows.chunks_exact(sample_count.max(1)).enumerate() {
+        for (i, &n) in row.iter().enumerate() {
+            counts[i * feature_count + j] = n;
+        }
+    }
+
+    Ok((sample_names, feature_names, counts))
+}
+
+pub(super) fn read_line<R>(reader: &mut R, dst: &mut String) -> io::Result<usize>
+where
+    R: BufRead,
+{
+    const LINE_FEED: char = '\n';
+    const CARRIAGE_RETURN: char = '\r';
+
+    match reader.read_line(dst)? {
+        0 => Ok(0),
+        n => {
+            if dst.ends_with(LINE_FEED) {
+                dst.pop();
+
+                if dst.ends_with(CARRIAGE_RETURN) {
+                    dst.pop();
+                }
+            }
+
+            Ok(n)
+        }
+    }
+}
+
+// Writes a row-major samples × features matrix as a features × samples matrix.
+pub(super) fn write_matrix<W>(
+    writer: &mut W,
+    sample_names: &[String],
+    feature_names: &[String],
+    counts: &[u32],
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let feature_count = feature_names.len();
+
+    for sample_name in sample_names {
+        write!(writer, "{SEPARATOR}{sample_name}")?;
+    }
+
+    writeln!(writer)?;
+
+    for (j, feature_name) in feature_names.iter().enumerate() {
+        write!(writer, "{feature_name}")?;
+
+        for i in 0..sample_names.len() {
+            let n = counts[i * feature_count + j];
+            write!(writer, "{SEPARATOR}{n}")?;
+        }
+
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_matrix() -> anyhow::Result<()> {
+        let src = b"\ts0\ts1\nf0\t3\t5\nf1\t8\t13\nf2\t21\t34\n";
+
+        let (sample_names, feature_names, counts) = read_matrix(&mut &src[..])?;
+
+        assert_eq!(sample_names, [String::from("s0"), String::from("s1")]);
+        assert_eq!(
+            feature_names,
+            [String::from("f0"), String::from("f1"), String::from("f2")]
+        );
+        assert_eq!(counts, [3, 8, 21, 5, 13, 34]);
+
+        let src = b"\ts0\ts1\nf0\t3\n";
+        assert!(read_matrix(&mut &src[..]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_matrix() -> io::Result<()> {
+        let sample_names = [String::from("s0"), String::from("s1")];
+        let feature_names = [String::from("f0"), String::from("f1"), String::from("f2")];
+        let counts = [3, 8, 21, 5, 13, 34];
+
+        let mut buf = Vec::new();
+        write_matrix(&mut buf, &sample_names, &feature_names, &counts)?;
+        assert_eq!(buf, b"\ts0\ts1\nf0\t3\t5\nf1\t8\t13\nf2\t21\t34\n");
+
+        Ok(())
+    }
+}
@@ -1,86 +1,300 @@
 use std::{
     fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, BufWriter, Write},
     path::Path,
 };
 
 use anyhow::bail;
-use atlas_core::counts::dimension_reduction::tsne;
+use atlas_core::counts::dimension_reduction::tsne::{self, Dimensions};
+use serde::Serialize;
+use tracing::info;
 
-use crate::cli;
+use super::counts::{read_line, read_matrix};
+use crate::cli::{
+    self,
+    transform::tsne::{InputFormat, OutputFormat},
+};
+
+const SEPARATOR: char = '\t';
 
 pub fn run(args: cli::transform::tsne::Args) -> anyhow::Result<()> {
-    let (labels, data, feature_count) = import(&args.srcs)?;
+    info!(src_count = args.srcs.len(), "reading counts");
+
+    let (sample_names, counts, feature_count) = match args.input_format {
+        InputFormat::Counts => read_sample_counts(&args.srcs)?,
+        InputFormat::Matrix => read_matrices(&args.srcs)?,
+    };
+
+    info!(
+        sample_count = sample_names.len(),
+        feature_count, "read counts"
+    );
+
+    let dimensions = match args.dimensions {
+        2 => Dimensions::Two,
+        3 => Dimensions::Three,
+        // SAFETY: The argument parser only accepts 2 or 3.
+        _ => unreachable!(),
+    };
 
-    let embedding = tsne::transform(args.perplexity, args.theta, data, feature_count);
+    let options = tsne::Options {
+        dimensions,
+        perplexity: args.perplexity,
+        theta: args.theta,
+        seed: args.seed,
+        filter_by_expression: args.filter_by_expression,
+        variable_feature_count: (args.variable_feature_count > 0)
+            .then_some(args.variable_feature_count),
+        principal_component_count: (args.principal_component_count > 0)
+            .then_some(args.principal_component_count),
+    };
 
-    for (label, point) in labels.iter().zip(embedding.chunks_exact(2)) {
-        let (x, y) = (point[0], point[1]);
-        println!("{label}\t{x}\t{y}");
+    info!("embedding samples");
+
+    let embedding = tsne::transform(&counts, feature_count, &options)?;
+
+    let mut writer: Box<dyn Write> = if let Some(dst) = args.output {
+        File::create(dst).map(BufWriter::new).map(Box::new)?
+    } else {
+        let stdout = io::stdout().lock();
+        Box::new(BufWriter::new(stdout))
+    };
+
+    let dimension_count = dimensions.count();
+
+    match args.output_format {
+        OutputFormat::Tsv => write_tsv(&mut writer, &sample_names, &embedding, dimension_count)?,
+        OutputFormat::Json => write_json(&mut writer, &sample_names, &embedding, dimension_count)?,
     }
 
+    writer.flush()?;
+
+    info!("done");
+
     Ok(())
 }
 
-fn import<P>(srcs: &[P]) -> anyhow::Result<(Vec<String>, Vec<i32>, usize)>
+// Reads one count file per sample into a row-major samples × features matrix.
+fn read_sample_counts<P>(srcs: &[P]) -> anyhow::Result<(Vec<String>, Vec<u32>, usize)>
 where
     P: AsRef<Path>,
 {
-    const LINE_FEED: char = '\n';
-    const CARRIAGE_RETURN: char = '\r';
+    let mut sample_names = Vec::with_capacity(srcs.len());
+    let mut feature_names: Vec<String> = Vec::new();
+    let mut counts = Vec::new();
+
+    for (i, src) in srcs.iter().enumerate() {
+        let src = src.as_ref();
+
+        let Some(sample_name) = src
+            .file_name()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.split_once('.'))
+            .map(|(name, _)| name)
+        else {
+            bail!("invalid filename: {}", src.display());
+        };
+
+        sample_names.push(sample_name.into());
+
+        let mut reader = crate::fs::open(src).map(BufReader::new)?;
+        let (names, sample_counts) = read_sample_counts_inner(&mut reader)?;
+
+        if i == 0 {
+            feature_names = names;
+        } else if names != feature_names {
+            bail!("feature names mismatch: {}", src.display());
+        }
 
-    const SEPARATOR: char = '\t';
+        counts.extend(sample_counts);
+    }
+
+    Ok((sample_names, counts, feature_names.len()))
+}
+
+fn read_sample_counts_inner<R>(reader: &mut R) -> anyhow::Result<(Vec<String>, Vec<u32>)>
+where
+    R: BufRead,
+{
     const META_PREFIX: &str = "__";
 
-    let mut labels = Vec::new();
-    let mut data = Vec::new();
-    let mut feature_count = 0;
+    let mut feature_names = Vec::new();
+    let mut counts = Vec::new();
 
     let mut line = String::new();
 
+    loop {
+        line.clear();
+
+        if read_line(reader, &mut line)? == 0 {
+            break;
+        }
+
+        let Some((name, raw_count)) = line.split_once(SEPARATOR) else {
+            bail!("invalid row");
+        };
+
+        if name.starts_with(META_PREFIX) {
+            break;
+        }
+
+        feature_names.push(name.into());
+        counts.push(raw_count.parse()?);
+    }
+
+    Ok((feature_names, counts))
+}
+
+// Reads features × samples count matrices into a row-major samples × features matrix.
+fn read_matrices<P>(srcs: &[P]) -> anyhow::Result<(Vec<String>, Vec<u32>, usize)>
+where
+    P: AsRef<Path>,
+{
+    let mut sample_names = Vec::new();
+    let mut feature_names: Vec<String> = Vec::new();
+    let mut counts = Vec::new();
+
     for (i, src) in srcs.iter().enumerate() {
         let src = src.as_ref();
 
-        match src.file_name().and_then(|s| s.to_str()) {
-            Some(filename) => match filename.split_once('.') {
-                Some((sample_name, _)) => labels.push(sample_name.into()),
-                None => bail!("invalid filename"),
-            },
-            None => bail!("invalid filename"),
-        }
+        let mut reader = crate::fs::open(src).map(BufReader::new)?;
+        let (names, matrix_feature_names, matrix_counts) = read_matrix(&mut reader)?;
 
-        let mut reader = File::open(src).map(BufReader::new)?;
+        if i == 0 {
+            feature_names = matrix_feature_names;
+        } else if matrix_feature_names != feature_names {
+            bail!("feature names mismatch: {}", src.display());
+        }
 
-        line.clear();
+        sample_names.extend(names);
+        counts.extend(matrix_counts);
+    }
 
-        while reader.read_line(&mut line)? != 0 {
-            if line.ends_with(LINE_FEED) {
-                line.pop();
+    Ok((sample_names, counts, feature_names.len()))
+}
 
-                if line.ends_with(CARRIAGE_RETURN) {
-                    line.pop();
-                }
-            }
+fn write_tsv<W>(
+    writer: &mut W,
+    sample_names: &[String],
+    embedding: &[f64],
+    dimension_count: usize,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    const AXIS_NAMES: [&str; 3] = ["x", "y", "z"];
 
-            let Some((name, raw_count)) = line.split_once(SEPARATOR) else {
-                bail!("invalid row");
-            };
+    write!(writer, "sample_name")?;
 
-            if name.starts_with(META_PREFIX) {
-                break;
-            }
+    for axis_name in &AXIS_NAMES[..dimension_count] {
+        write!(writer, "{SEPARATOR}{axis_name}")?;
+    }
 
-            let n = raw_count.parse()?;
+    writeln!(writer)?;
 
-            data.push(n);
+    for (sample_name, point) in sample_names
+        .iter()
+        .zip(embedding.chunks_exact(dimension_count))
+    {
+        write!(writer, "{sample_name}")?;
 
-            line.clear();
+        for value in point {
+            write!(writer, "{SEPARATOR}{value}")?;
         }
 
-        if i == 0 {
-            feature_count = data.len();
-        }
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Embedding<'a> {
+    sample_names: &'a [String],
+    x: Vec<f64>,
+    y: Vec<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    z: Option<Vec<f64>>,
+}
+
+fn write_json<W>(
+    writer: &mut W,
+    sample_names: &[String],
+    embedding: &[f64],
+    dimension_count: usize,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let axis = |d: usize| -> Vec<f64> {
+        embedding
+            .chunks_exact(dimension_count)
+            .map(|point| point[d])
+            .collect()
+    };
+
+    let body = Embedding {
+        sample_names,
+        x: axis(0),
+        y: axis(1),
+        z: (dimension_count > 2).then(|| axis(2)),
+    };
+
+    serde_json::to_writer(&mut *writer, &body)?;
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_sample_counts_inner() -> anyhow::Result<()> {
+        let src = b"f0\t3\nf1\t5\n__no_feature\t8\n";
+        let (feature_names, counts) = read_sample_counts_inner(&mut &src[..])?;
+        assert_eq!(feature_names, [String::from("f0"), String::from("f1")]);
+        assert_eq!(counts, [3, 5]);
+        Ok(())
     }
 
-    Ok((labels, data, feature_count))
+    #[test]
+    fn test_write_tsv() -> io::Result<()> {
+        let sample_names = [String::from("s0"), String::from("s1")];
+
+        let mut buf = Vec::new();
+        write_tsv(&mut buf, &sample_names, &[0.0, 1.5, -2.0, 3.0], 2)?;
+        assert_eq!(buf, b"sample_name\tx\ty\ns0\t0\t1.5\ns1\t-2\t3\n");
+
+        let mut buf = Vec::new();
+        write_tsv(&mut buf, &sample_names, &[0.0, 1.5, 2.0, -2.0, 3.0, 4.0], 3)?;
+        assert_eq!(buf, b"sample_name\tx\ty\tz\ns0\t0\t1.5\t2\ns1\t-2\t3\t4\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_json() -> io::Result<()> {
+        let sample_names = [String::from("s0"), String::from("s1")];
+
+        let mut buf = Vec::new();
+        write_json(&mut buf, &sample_names, &[0.0, 1.5, -2.0, 3.0], 2)?;
+        assert_eq!(
+            buf,
+            br#"{"sampleNames":["s0","s1"],"x":[0.0,-2.0],"y":[1.5,3.0]}
+"#
+        );
+
+        let mut buf = Vec::new();
+        write_json(&mut buf, &sample_names, &[0.0, 1.5, 2.0, -2.0, 3.0, 4.0], 3)?;
+        assert_eq!(
+            buf,
+            br#"{"sampleNames":["s0","s1"],"x":[0.0,-2.0],"y":[1.5,3.0],"z":[2.0,4.0]}
+"#
+        );
+
+        Ok(())
+    }
 }
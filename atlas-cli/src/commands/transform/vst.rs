@@ -1,6 +1,6 @@
 use std::{
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, BufWriter, Write},
     path::Path,
 };
 
@@ -12,9 +12,58 @@ use crate::cli;
 pub fn run(args: cli::transform::vst::Args) -> anyhow::Result<()> {
     let (sample_names, feature_names, counts) = import(&args.src)?;
 
-    let _stabilized_counts = vst::transform(counts, sample_names.len(), feature_names.len());
+    if sample_names.len() < 2 {
+        bail!("VST requires at least 2 samples");
+    }
+
+    let stabilized_counts = vst::transform(counts, feature_names.len(), sample_names.len());
+
+    let stdout = io::stdout().lock();
+    let mut writer = BufWriter::new(stdout);
+
+    write_counts(
+        &mut writer,
+        &sample_names,
+        &feature_names,
+        &stabilized_counts,
+    )?;
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn write_counts<W>(
+    writer: &mut W,
+    sample_names: &[String],
+    feature_names: &[String],
+    counts: &[f64],
+) -> io::Result<()>
+where
+    W: Write,
+{
+    const SEPARATOR: char = '\t';
+
+    for sample_name in sample_names {
+        write!(writer, "{SEPARATOR}{sample_name}")?;
+    }
 
-    todo!()
+    writeln!(writer)?;
+
+    for (feature_name, row) in feature_names
+        .iter()
+        .zip(counts.chunks_exact(sample_names.len()))
+    {
+        write!(writer, "{feature_name}")?;
+
+        for n in row {
+            write!(writer, "{SEPARATOR}{n}")?;
+        }
+
+        writeln!(writer)?;
+    }
+
+    Ok(())
 }
 
 fn import<P>(src: P) -> anyhow::Result<(Vec<String>, Vec<String>, Vec<u32>)>
@@ -106,4 +155,17 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_write_counts() -> io::Result<()> {
+        let sample_names = [String::from("s0"), String::from("s1")];
+        let feature_names = [String::from("f0")];
+
+        let mut buf = Vec::new();
+        write_counts(&mut buf, &sample_names, &feature_names, &[0.5, 1.0])?;
+
+        assert_eq!(buf, b"\ts0\ts1\nf0\t0.5\t1\n");
+
+        Ok(())
+    }
 }
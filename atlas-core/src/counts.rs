@@ -1,4 +1,9 @@
+pub mod batch_correction;
+pub mod differential_expression;
 pub mod dimension_reduction;
+pub mod feature_selection;
 pub mod normalization;
 pub mod reader;
+pub mod similarity;
 pub mod transforms;
+pub mod tximport;
@@ -0,0 +1,521 @@
+//! ComBat-seq batch effect correction.
+//!
+//! ComBat-seq adjusts raw counts for batch effects using a negative binomial regression model and
+//! maps each count to the quantile of the same probability in the batch-free distribution. The
+//! result is integer counts. See "[ComBat-seq: batch effect adjustment for RNA-seq count data]"
+//! (2020) by Zhang et al. for more details.
+//!
+//! This follows `ComBat_seq` in [sva] without empirical Bayes shrinkage (`shrink = FALSE`, its
+//! default). Instead of edgeR's Cox-Reid estimates, dispersions are estimated per batch using a
+//! moment estimator moderated toward the common dispersion of the batch.
+//!
+//! [ComBat-seq: batch effect adjustment for RNA-seq count data]: https://doi.org/10.1093/nargab/lqaa078
+//! [sva]: https://bioconductor.org/packages/release/bioc/html/sva.html
+
+use std::{collections::BTreeSet, hash::Hash, io};
+
+use indexmap::IndexSet;
+use ndarray::{Array1, Array2, ArrayView1, ArrayView2, Axis};
+
+use crate::stats::{glm, negative_binomial};
+
+// Prior degrees of freedom when moderating feature dispersions toward the common dispersion.
+const DISPERSION_PRIOR_DF: f64 = 10.0;
+
+// Counts with a cumulative probability this close to 1 are left as is.
+const MAX_PROBABILITY_DELTA: f64 = 1e-4;
+
+/// Adjusts raw counts for batch effects.
+///
+/// `data` is a row-major samples × features matrix. The result is the corrected counts of each
+/// sample. See [`correct`].
+pub fn correct_vec<B>(
+    sample_count: usize,
+    feature_count: usize,
+    data: Vec<u32>,
+    batches: &[B],
+    covariates: &[Vec<f64>],
+) -> io::Result<Vec<Vec<u32>>>
+where
+    B: Eq + Hash,
+{
+    let matrix = Array2::from_shape_vec((sample_count, feature_count), data)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let corrected_matrix = correct(matrix.view(), batches, covariates)?;
+
+    Ok(corrected_matrix
+        .axis_iter(Axis(0))
+        .map(|row| row.to_vec())
+        .collect())
+}
+
+/// Adjusts raw counts for batch effects.
+///
+/// `counts` is a samples × features matrix, and `batches` is the batch of each sample.
+/// `covariates` are columns of biological covariates to preserve, e.g., from [`encode_covariate`],
+/// without an intercept.
+///
+/// Each batch must have at least 2 samples, and the covariates must not be confounded with batch.
+/// Features with only zero counts in any batch are not adjusted.
+pub fn correct<B>(
+    counts: ArrayView2<'_, u32>,
+    batches: &[B],
+    covariates: &[Vec<f64>],
+) -> io::Result<Array2<u32>>
+where
+    B: Eq + Hash,
+{
+    let (sample_count, feature_count) = counts.dim();
+
+    if batches.len() != sample_count {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "batch count mismatch: expected {sample_count}, got {}",
+                batches.len()
+            ),
+        ));
+    }
+
+    if covariates.iter().any(|values| values.len() != sample_count) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "covariate length mismatch",
+        ));
+    }
+
+    let (batch_indices, batch_samples) = index_batches(batches);
+
+    if batch_samples.len() < 2 {
+        return Ok(counts.to_owned());
+    }
+
+    if batch_samples.iter().any(|samples| samples.len() < 2) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "batches must have at least 2 samples",
+        ));
+    }
+
+    let design = build_design(&batch_indices, batch_samples.len(), covariates);
+
+    if !glm::is_full_rank(design.view()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "covariates are confounded with batch",
+        ));
+    }
+
+    let library_sizes = counts.map_axis(Axis(1), |row| row.iter().copied().map(f64::from).sum());
+
+    if library_sizes.iter().any(|&n: &f64| n == 0.0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "samples must have at least one count",
+        ));
+    }
+
+    let offsets = library_sizes.mapv(f64::ln);
+
+    let feature_indices: Vec<_> = (0..feature_count)
+        .filter(|&j| {
+            let column = counts.column(j);
+            batch_samples
+                .iter()
+                .all(|samples| samples.iter().any(|&i| column[i] > 0))
+        })
+        .collect();
+
+    let dispersions = estimate_dispersions(
+        counts,
+        &feature_indices,
+        &batch_samples,
+        covariates,
+        offsets.view(),
+    )?;
+
+    if !dispersions.iter().all(|dispersion| dispersion.is_finite()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "dispersions must be finite",
+        ));
+    }
+
+    let mut corrected_counts = counts.to_owned();
+
+    for (k, &j) in feature_indices.iter().enumerate() {
+        let feature_dispersions = dispersions.row(k);
+        let y = counts.column(j).mapv(f64::from);
+
+        let sample_dispersions: Array1<f64> = batch_indices
+            .iter()
+            .map(|&b| feature_dispersions[b])
+            .collect();
+
+        let fit = glm::fit_negative_binomial(
+            design.view(),
+            y.view(),
+            offsets.view(),
+            sample_dispersions.view(),
+        );
+
+        let batch_effects = center_batch_effects(fit.coefficients.view(), &batch_samples);
+        let pooled_dispersion = feature_dispersions.mean().unwrap_or_default();
+
+        for (i, &b) in batch_indices.iter().enumerate() {
+            let mean = fit.fitted_values[i];
+            let adjusted_mean = mean * (-batch_effects[b]).exp();
+
+            corrected_counts[[i, j]] = match_quantile(
+                counts[[i, j]],
+                mean,
+                feature_dispersions[b],
+                adjusted_mean,
+                pooled_dispersion,
+            );
+        }
+    }
+
+    Ok(corrected_counts)
+}
+
+/// Encodes a covariate as design matrix columns.
+///
+/// If all values are numeric, this is a single column of the parsed values. Otherwise, the
+/// covariate is categorical and encoded as an indicator column for each level except the first
+/// (in lexicographical order).
+pub fn encode_covariate<S>(values: &[S]) -> Vec<Vec<f64>>
+where
+    S: AsRef<str>,
+{
+    let numeric_values: Option<Vec<f64>> = values
+        .iter()
+        .map(|value| value.as_ref().parse().ok())
+        .collect();
+
+    if let Some(numeric_values) = numeric_values {
+        return vec![numeric_values];
+    }
+
+    let levels: BTreeSet<_> = values.iter().map(|value| value.as_ref()).collect();
+
+    levels
+        .into_iter()
+        .skip(1)
+        .map(|level| {
+            values
+                .iter()
+                .map(|value| if value.as_ref() == level { 1.0 } else { 0.0 })
+                .collect()
+        })
+        .collect()
+}
+
+// Returns the batch index of each sample and the sample indices of each batch.
+fn index_batches<B>(batches: &[B]) -> (Vec<usize>, Vec<Vec<usize>>)
+where
+    B: Eq + Hash,
+{
+    let mut set = IndexSet::new();
+
+    let batch_indices: Vec<_> = batches
+        .iter()
+        .map(|batch| set.insert_full(batch).0)
+        .collect();
+
+    let mut batch_samples = vec![Vec::new(); set.len()];
+
+    for (i, &b) in batch_indices.iter().enumerate() {
+        batch_samples[b].push(i);
+    }
+
+    (batch_indices, batch_samples)
+}
+
+// Builds a design matrix of batch indicators followed by the covariates.
+fn build_design(
+    batch_indices: &[usize],
+    batch_count: usize,
+    covariates: &[Vec<f64>],
+) -> Array2<f64> {
+    let sample_count = batch_indices.len();
+
+    Array2::from_shape_fn((sample_count, batch_count + covariates.len()), |(i, k)| {
+        if k < batch_count {
+            if batch_indices[i] == k { 1.0 } else { 0.0 }
+        } else {
+            covariates[k - batch_count][i]
+        }
+    })
+}
+
+// Builds the design matrix of the samples of a single batch: an intercept followed by the
+// covariates. If the covariates are not estimable within the batch or leave no residual degrees
+// of freedom, only the intercept is used.
+fn build_batch_design(samples: &[usize], covariates: &[Vec<f64>]) -> Array2<f64> {
+    let design = Array2::from_shape_fn((samples.len(), covariates.len() + 1), |(i, k)| {
+        if k == 0 {
+            1.0
+        } else {
+            covariates[k - 1][samples[i]]
+        }
+    });
+
+    if design.nrows() > design.ncols() && glm::is_full_rank(design.view()) {
+        design
+    } else {
+        Array2::ones((samples.len(), 1))
+    }
+}
+
+// Returns a features × batches matrix of dispersions.
+fn estimate_dispersions(
+    counts: ArrayView2<'_, u32>,
+    feature_indices: &[usize],
+    batch_samples: &[Vec<usize>],
+    covariates: &[Vec<f64>],
+    offsets: ArrayView1<'_, f64>,
+) -> io::Result<Array2<f64>> {
+    let mut dispersions = Array2::zeros((feature_indices.len(), batch_samples.len()));
+
+    for (b, samples) in batch_samples.iter().enumerate() {
+        let design = build_batch_design(samples, covariates);
+        let batch_offsets: Array1<f64> = samples.iter().map(|&i| offsets[i]).collect();
+        let poisson_dispersions = Array1::zeros(samples.len());
+
+        let residual_df = design
+            .nrows()
+            .checked_sub(design.ncols())
+            .filter(|&n| n > 0)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "batches must have more samples than design columns",
+                )
+            })? as f64;
+
+        for (k, &j) in feature_indices.iter().enumerate() {
+            let y: Array1<f64> = samples.iter().map(|&i| f64::from(counts[[i, j]])).collect();
+
+            let fit = glm::fit_negative_binomial(
+                design.view(),
+                y.view(),
+                batch_offsets.view(),
+                poisson_dispersions.view(),
+            );
+
+            let sum: f64 = y
+                .iter()
+                .zip(&fit.fitted_values)
+                .map(|(&y, &mu)| ((y - mu).powi(2) - mu) / (mu * mu))
+                .sum();
+
+            dispersions[[k, b]] = (sum / residual_df).max(0.0);
+        }
+
+        let mut column = dispersions.column_mut(b);
+        let common_dispersion = column.mean().unwrap_or_default();
+
+        column.mapv_inplace(|dispersion| {
+            (residual_df * dispersion + DISPERSION_PRIOR_DF * common_dispersion)
+                / (residual_df + DISPERSION_PRIOR_DF)
+        });
+    }
+
+    Ok(dispersions)
+}
+
+// Returns the batch coefficients relative to their mean weighted by batch size.
+fn center_batch_effects(
+    coefficients: ArrayView1<'_, f64>,
+    batch_samples: &[Vec<usize>],
+) -> Vec<f64> {
+    let sample_count: usize = batch_samples.iter().map(|samples| samples.len()).sum();
+
+    let grand_mean: f64 = batch_samples
+        .iter()
+        .enumerate()
+        .map(|(b, samples)| coefficients[b] * (samples.len() as f64) / (sample_count as f64))
+        .sum();
+
+    (0..batch_samples.len())
+        .map(|b| coefficients[b] - grand_mean)
+        .collect()
+}
+
+fn match_quantile(
+    count: u32,
+    mean: f64,
+    dispersion: f64,
+    adjusted_mean: f64,
+    adjusted_dispersion: f64,
+) -> u32 {
+    if count <= 1 {
+        return count;
+    }
+
+    let p = negative_binomial::cdf(u64::from(count - 1), mean, dispersion);
+
+    if (p - 1.0).abs() < MAX_PROBABILITY_DELTA {
+        return count;
+    }
+
+    let n = negative_binomial::quantile(p, adjusted_mean, adjusted_dispersion);
+    u32::try_from(n + 1).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+
+    fn build_counts() -> (Array2<u32>, Vec<&'static str>) {
+        const FEATURE_COUNT: usize = 8;
+
+        let batches = vec!["a", "a", "a", "a", "b", "b", "b", "b"];
+
+        let counts = Array2::from_shape_fn((batches.len(), FEATURE_COUNT), |(i, j)| {
+            let base = 20 * (j as u32 + 1) + 3 * (i as u32 % 4);
+
+            if j == FEATURE_COUNT - 1 && batches[i] == "b" {
+                // A feature with only zeros in batch b.
+                0
+            } else if batches[i] == "b" {
+                // Batch b has 4x the expression of batch a.
+                4 * base
+            } else {
+                base
+            }
+        });
+
+        (counts, batches)
+    }
+
+    #[test]
+    fn test_correct() -> io::Result<()> {
+        let (counts, batches) = build_counts();
+
+        let corrected_counts = correct(counts.view(), &batches, &[])?;
+        assert_eq!(corrected_counts.dim(), counts.dim());
+
+        // Batch means of adjusted features are closer together.
+        let batch_mean = |m: &Array2<u32>, j: usize, samples: std::ops::Range<usize>| {
+            let n = samples.len() as f64;
+            samples.map(|i| f64::from(m[[i, j]])).sum::<f64>() / n
+        };
+
+        for j in 0..7 {
+            let before = batch_mean(&counts, j, 4..8) / batch_mean(&counts, j, 0..4);
+            let after =
+                batch_mean(&corrected_counts, j, 4..8) / batch_mean(&corrected_counts, j, 0..4);
+            assert!(
+                after.ln().abs() < before.ln().abs(),
+                "{j}: {after} >= {before}"
+            );
+        }
+
+        // Features with only zeros in a batch are not adjusted.
+        assert_eq!(corrected_counts.column(7), counts.column(7));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_correct_with_one_batch() -> io::Result<()> {
+        let (counts, _) = build_counts();
+        let batches = vec!["a"; counts.nrows()];
+        assert_eq!(correct(counts.view(), &batches, &[])?, counts);
+        Ok(())
+    }
+
+    #[test]
+    fn test_correct_with_covariate_saturating_batches() -> io::Result<()> {
+        // Each batch has as many samples as an intercept and the covariate.
+        let counts = array![[10, 20], [14, 26], [40, 80], [52, 100]];
+        let batches = ["a", "a", "b", "b"];
+        let covariates = [vec![1.0, 2.0, 3.0, 5.0]];
+
+        let corrected_counts = correct(counts.view(), &batches, &covariates)?;
+        assert_eq!(corrected_counts.dim(), counts.dim());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_correct_with_invalid_input() {
+        let (counts, batches) = build_counts();
+
+        assert!(matches!(
+            correct(counts.view(), &batches[..2], &[]),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+
+        let batches_with_single_sample = ["a", "a", "a", "a", "b", "b", "b", "c"];
+
+        assert!(matches!(
+            correct(counts.view(), &batches_with_single_sample, &[]),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+
+        let confounded_covariates = encode_covariate(&batches);
+
+        assert!(matches!(
+            correct(counts.view(), &batches, &confounded_covariates),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+    }
+
+    #[test]
+    fn test_encode_covariate() {
+        assert_eq!(
+            encode_covariate(&["1.5", "2", "-3"]),
+            [vec![1.5, 2.0, -3.0]]
+        );
+
+        assert_eq!(
+            encode_covariate(&["tumor", "normal", "tumor", "cell_line"]),
+            [vec![0.0, 1.0, 0.0, 0.0], vec![1.0, 0.0, 1.0, 0.0]]
+        );
+
+        assert!(encode_covariate(&["a", "a"]).is_empty());
+    }
+
+    #[test]
+    fn test_index_batches() {
+        let (batch_indices, batch_samples) = index_batches(&["b", "a", "b", "c"]);
+        assert_eq!(batch_indices, [0, 1, 0, 2]);
+        assert_eq!(batch_samples, [vec![0, 2], vec![1], vec![3]]);
+    }
+
+    #[test]
+    fn test_build_design() {
+        let design = build_design(&[0, 1, 0], 2, &[vec![0.5, 1.0, 2.0]]);
+        assert_eq!(
+            design,
+            array![[1.0, 0.0, 0.5], [0.0, 1.0, 1.0], [1.0, 0.0, 2.0]]
+        );
+    }
+
+    #[test]
+    fn test_center_batch_effects() {
+        let batch_effects = center_batch_effects(
+            array![1.0, 4.0, 8.0].view(),
+            &[vec![0, 1], vec![2], vec![3]],
+        );
+        assert_eq!(batch_effects, [-2.5, 0.5, 4.5]);
+    }
+
+    #[test]
+    fn test_match_quantile() {
+        assert_eq!(match_quantile(0, 5.0, 0.1, 10.0, 0.1), 0);
+        assert_eq!(match_quantile(1, 5.0, 0.1, 10.0, 0.1), 1);
+
+        // The same distribution maps counts to themselves.
+        assert_eq!(match_quantile(8, 5.0, 0.1, 5.0, 0.1), 8);
+
+        // A larger mean shifts counts up.
+        assert!(match_quantile(8, 5.0, 0.1, 10.0, 0.1) > 8);
+    }
+}
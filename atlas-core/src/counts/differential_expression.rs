@@ -0,0 +1,195 @@
+//! Differential expression between two groups of samples.
+
+mod voom;
+mod wald;
+
+use ndarray::ArrayView2;
+use thiserror::Error;
+
+use crate::stats::multiple_testing;
+
+/// The minimum number of samples in each group.
+pub const MIN_GROUP_SIZE: usize = 2;
+
+/// A differential expression test.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Method {
+    /// A Wald test of the group coefficient of a negative binomial GLM, as in DESeq2.
+    Wald,
+    /// A moderated t-test of precision weighted log-CPM values, as in limma-voom.
+    Voom,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DifferentialExpressionError {
+    #[error("invalid shape: expected {expected} counts, got {actual}")]
+    InvalidShape { expected: usize, actual: usize },
+    #[error("invalid groups: expected {expected} groups, got {actual}")]
+    InvalidGroupCount { expected: usize, actual: usize },
+    #[error("each group requires at least {MIN_GROUP_SIZE} samples")]
+    TooFewSamples,
+}
+
+/// Test results per feature.
+///
+/// Fold changes are of the treatment group relative to the reference group. Values are NaN for
+/// features that cannot be tested, e.g., those without counts.
+#[derive(Debug, Default, PartialEq)]
+pub struct Results {
+    pub log2_fold_changes: Vec<f64>,
+    pub p_values: Vec<f64>,
+    /// Benjamini-Hochberg adjusted p-values.
+    pub adjusted_p_values: Vec<f64>,
+}
+
+/// Tests each feature of a row-major samples × features count matrix for differential
+/// expression.
+///
+/// `is_treatment` marks the samples in the treatment group. All other samples are in the
+/// reference group.
+pub fn test(
+    method: Method,
+    sample_count: usize,
+    feature_count: usize,
+    counts: &[u32],
+    is_treatment: &[bool],
+) -> Result<Results, DifferentialExpressionError> {
+    let expected = sample_count * feature_count;
+
+    if counts.len() != expected {
+        return Err(DifferentialExpressionError::InvalidShape {
+            expected,
+            actual: counts.len(),
+        });
+    } else if is_treatment.len() != sample_count {
+        return Err(DifferentialExpressionError::InvalidGroupCount {
+            expected: sample_count,
+            actual: is_treatment.len(),
+        });
+    }
+
+    let treatment_count = is_treatment.iter().filter(|&&b| b).count();
+
+    if treatment_count < MIN_GROUP_SIZE || sample_count - treatment_count < MIN_GROUP_SIZE {
+        return Err(DifferentialExpressionError::TooFewSamples);
+    }
+
+    // SAFETY: The shape was validated above.
+    let counts = ArrayView2::from_shape((sample_count, feature_count), counts).unwrap();
+
+    let (log2_fold_changes, p_values) = match method {
+        Method::Wald => wald::test(counts, is_treatment),
+        Method::Voom => voom::test(counts, is_treatment),
+    };
+
+    let adjusted_p_values = multiple_testing::benjamini_hochberg(&p_values);
+
+    Ok(Results {
+        log2_fold_changes,
+        p_values,
+        adjusted_p_values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_COUNT: usize = 6;
+    const FEATURE_COUNT: usize = 20;
+
+    // Feature 0 is upregulated 8-fold in the treatment group (the last 3 samples), feature 1 is
+    // downregulated 8-fold, feature 19 has no counts, and the others are unchanged.
+    fn build_counts() -> (Vec<u32>, Vec<bool>) {
+        const NOISE: [f64; 6] = [1.0, 1.1, 0.9, 1.05, 0.95, 1.02];
+
+        let mut counts = Vec::with_capacity(SAMPLE_COUNT * FEATURE_COUNT);
+
+        for i in 0..SAMPLE_COUNT {
+            let is_treatment = i >= 3;
+
+            for j in 0..FEATURE_COUNT {
+                let base = 50.0 + 10.0 * j as f64;
+                let noise = NOISE[(i + j) % NOISE.len()];
+
+                let effect = match (j, is_treatment) {
+                    (0, true) => 8.0,
+                    (1, true) => 0.125,
+                    (19, _) => 0.0,
+                    _ => 1.0,
+                };
+
+                counts.push((base * noise * effect).round() as u32);
+            }
+        }
+
+        let is_treatment = (0..SAMPLE_COUNT).map(|i| i >= 3).collect();
+
+        (counts, is_treatment)
+    }
+
+    #[test]
+    fn test_test() -> Result<(), DifferentialExpressionError> {
+        let (counts, is_treatment) = build_counts();
+
+        for method in [Method::Wald, Method::Voom] {
+            let results = test(method, SAMPLE_COUNT, FEATURE_COUNT, &counts, &is_treatment)?;
+
+            assert!(results.log2_fold_changes[0] > 2.5, "{method:?}");
+            assert!(results.adjusted_p_values[0] < 0.01, "{method:?}");
+
+            assert!(results.log2_fold_changes[1] < -2.5, "{method:?}");
+            assert!(results.adjusted_p_values[1] < 0.01, "{method:?}");
+
+            for j in 2..FEATURE_COUNT - 1 {
+                assert!(results.log2_fold_changes[j].abs() < 0.5, "{method:?}: {j}");
+                assert!(results.adjusted_p_values[j] > 0.05, "{method:?}: {j}");
+            }
+
+            assert!(results.log2_fold_changes[19].is_nan(), "{method:?}");
+            assert!(results.p_values[19].is_nan(), "{method:?}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_test_with_invalid_input() {
+        let (counts, is_treatment) = build_counts();
+
+        assert_eq!(
+            test(Method::Wald, SAMPLE_COUNT, 3, &counts, &is_treatment),
+            Err(DifferentialExpressionError::InvalidShape {
+                expected: 18,
+                actual: 120
+            })
+        );
+
+        assert_eq!(
+            test(
+                Method::Wald,
+                SAMPLE_COUNT,
+                FEATURE_COUNT,
+                &counts,
+                &is_treatment[..5]
+            ),
+            Err(DifferentialExpressionError::InvalidGroupCount {
+                expected: 6,
+                actual: 5
+            })
+        );
+
+        let is_treatment = [false, false, false, false, false, true];
+
+        assert_eq!(
+            test(
+                Method::Voom,
+                SAMPLE_COUNT,
+                FEATURE_COUNT,
+                &counts,
+                &is_treatment
+            ),
+            Err(DifferentialExpressionError::TooFewSamples)
+        );
+    }
+}
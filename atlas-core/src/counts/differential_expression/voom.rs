@@ -0,0 +1,404 @@
+//! Moderated t-test of precision weighted log-CPM values.
+//!
+//! This follows limma-voom: counts are transformed to log-CPM using TMM normalized library sizes;
+//! the mean-variance trend of the features gives a precision weight for each observation; and a
+//! weighted linear model is fit per feature, whose residual variances are moderated by empirical
+//! Bayes. Unlike `voom`, the trend is a binned running mean rather than a lowess curve.
+//!
+//! See "[voom: precision weights unlock linear model analysis tools for RNA-seq read counts]"
+//! (2014) by Law et al. and "[Linear models and empirical Bayes methods for assessing
+//! differential expression in microarray experiments]" (2004) by Smyth.
+//!
+//! [voom: precision weights unlock linear model analysis tools for RNA-seq read counts]: https://doi.org/10.1186/gb-2014-15-2-r29
+//! [Linear models and empirical Bayes methods for assessing differential expression in microarray experiments]: https://doi.org/10.2202/1544-6115.1027
+
+use ndarray::{Array2, ArrayView2, Axis};
+use statrs::{
+    distribution::{ContinuousCDF, StudentsT},
+    function::{erf::erfc, gamma::digamma},
+};
+
+use crate::counts::normalization::tmm;
+
+// The number of features per bin of the mean-variance trend.
+const TREND_BIN_SIZE: usize = 50;
+
+const CPM_SCALE: f64 = 1e6;
+
+/// Returns the log2 fold change and p-value of each feature.
+pub(super) fn test(counts: ArrayView2<'_, u32>, is_treatment: &[bool]) -> (Vec<f64>, Vec<f64>) {
+    let (sample_count, feature_count) = counts.dim();
+
+    let library_sizes = calculate_library_sizes(counts);
+
+    let log_cpms = Array2::from_shape_fn((sample_count, feature_count), |(i, j)| {
+        log_cpm(f64::from(counts[[i, j]]), library_sizes[i])
+    });
+
+    let feature_indices: Vec<_> = (0..feature_count)
+        .filter(|&j| counts.column(j).iter().any(|&n| n > 0))
+        .collect();
+
+    let mut log2_fold_changes = vec![f64::NAN; feature_count];
+    let mut p_values = vec![f64::NAN; feature_count];
+
+    if feature_indices.is_empty() {
+        return (log2_fold_changes, p_values);
+    }
+
+    let residual_df = (sample_count - 2) as f64;
+    let mean_log_library_size = library_sizes
+        .iter()
+        .map(|&size| (size + 1.0).log2())
+        .sum::<f64>()
+        / sample_count as f64;
+
+    let ys: Vec<Vec<f64>> = feature_indices
+        .iter()
+        .map(|&j| log_cpms.column(j).to_vec())
+        .collect();
+
+    let unweighted_fits: Vec<_> = ys
+        .iter()
+        .map(|y| fit(y, &vec![1.0; sample_count], is_treatment))
+        .collect();
+
+    // The mean-variance trend is √s against the average log count.
+    let (trend_xs, trend_ys): (Vec<_>, Vec<_>) = ys
+        .iter()
+        .zip(&unweighted_fits)
+        .map(|(y, fit)| {
+            let mean = y.iter().sum::<f64>() / sample_count as f64;
+            let average_log_count = mean + mean_log_library_size - CPM_SCALE.log2();
+            (
+                average_log_count,
+                (fit.residual_sum_of_squares / residual_df).sqrt().sqrt(),
+            )
+        })
+        .unzip();
+
+    let trend = Trend::new(&trend_xs, &trend_ys);
+
+    let fits: Vec<_> = ys
+        .iter()
+        .zip(&unweighted_fits)
+        .map(|(y, unweighted_fit)| {
+            let weights: Vec<_> = (0..sample_count)
+                .map(|i| {
+                    let fitted_log_count = unweighted_fit.fitted_value(is_treatment[i])
+                        + (library_sizes[i] + 1.0).log2()
+                        - CPM_SCALE.log2();
+
+                    1.0 / trend.predict(fitted_log_count).powi(4)
+                })
+                .collect();
+
+            fit(y, &weights, is_treatment)
+        })
+        .collect();
+
+    let variances: Vec<_> = fits
+        .iter()
+        .map(|fit| fit.residual_sum_of_squares / residual_df)
+        .collect();
+
+    let prior = Prior::estimate(&variances, residual_df);
+
+    for ((&j, fit), &variance) in feature_indices.iter().zip(&fits).zip(&variances) {
+        let (posterior_variance, df) = prior.moderate(variance, residual_df);
+        let standard_error = (posterior_variance * fit.unscaled_variance).sqrt();
+        let t = fit.coefficient / standard_error;
+
+        log2_fold_changes[j] = fit.coefficient;
+        p_values[j] = two_sided_p_value(t, df);
+    }
+
+    (log2_fold_changes, p_values)
+}
+
+// Returns TMM normalized library sizes.
+//
+// TMM scaling factors map counts to the composition of the reference sample, so they divide the
+// library sizes.
+fn calculate_library_sizes(counts: ArrayView2<'_, u32>) -> Vec<f64> {
+    let totals: Vec<f64> = counts
+        .sum_axis(Axis(1))
+        .iter()
+        .map(|&n| f64::from(n))
+        .collect();
+
+    let scaling_factors = if totals.iter().all(|&total| total > 0.0) {
+        tmm::calculate_scaling_factors_centered(&counts.to_owned())
+    } else {
+        vec![1.0; totals.len()]
+    };
+
+    totals
+        .iter()
+        .zip(scaling_factors)
+        .map(|(&total, factor)| {
+            if factor.is_finite() && factor > 0.0 {
+                total / factor
+            } else {
+                total
+            }
+        })
+        .collect()
+}
+
+fn log_cpm(count: f64, library_size: f64) -> f64 {
+    ((count + 0.5) / (library_size + 1.0) * CPM_SCALE).log2()
+}
+
+struct Fit {
+    reference_mean: f64,
+    /// The difference between the treatment and reference means.
+    coefficient: f64,
+    /// The variance of the coefficient divided by the residual variance: 1 / W₀ + 1 / W₁.
+    unscaled_variance: f64,
+    /// The weighted residual sum of squares.
+    residual_sum_of_squares: f64,
+}
+
+impl Fit {
+    fn fitted_value(&self, is_treatment: bool) -> f64 {
+        if is_treatment {
+            self.reference_mean + self.coefficient
+        } else {
+            self.reference_mean
+        }
+    }
+}
+
+// Fits a weighted two-group linear model, i.e., weighted group means.
+fn fit(y: &[f64], weights: &[f64], is_treatment: &[bool]) -> Fit {
+    let mut sums = [0.0; 2];
+    let mut weight_sums = [0.0; 2];
+
+    for ((&y, &w), &is_treatment) in y.iter().zip(weights).zip(is_treatment) {
+        let g = usize::from(is_treatment);
+        sums[g] += w * y;
+        weight_sums[g] += w;
+    }
+
+    let means = [sums[0] / weight_sums[0], sums[1] / weight_sums[1]];
+
+    let residual_sum_of_squares = y
+        .iter()
+        .zip(weights)
+        .zip(is_treatment)
+        .map(|((&y, &w), &is_treatment)| w * (y - means[usize::from(is_treatment)]).powi(2))
+        .sum();
+
+    Fit {
+        reference_mean: means[0],
+        coefficient: means[1] - means[0],
+        unscaled_variance: 1.0 / weight_sums[0] + 1.0 / weight_sums[1],
+        residual_sum_of_squares,
+    }
+}
+
+// A piecewise linear mean-variance trend through the means of bins of features sorted by x.
+struct Trend {
+    points: Vec<(f64, f64)>,
+}
+
+impl Trend {
+    fn new(xs: &[f64], ys: &[f64]) -> Self {
+        let mut pairs: Vec<_> = xs.iter().copied().zip(ys.iter().copied()).collect();
+        pairs.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        let bin_count = pairs.len().div_ceil(TREND_BIN_SIZE);
+        let bin_size = pairs.len().div_ceil(bin_count);
+
+        let points = pairs
+            .chunks(bin_size)
+            .map(|chunk| {
+                let n = chunk.len() as f64;
+                let x = chunk.iter().map(|(x, _)| x).sum::<f64>() / n;
+                let y = chunk.iter().map(|(_, y)| y).sum::<f64>() / n;
+                (x, y)
+            })
+            .collect();
+
+        Self { points }
+    }
+
+    // Values outside the range of the trend are those of the nearest end.
+    fn predict(&self, x: f64) -> f64 {
+        let y = match self.points.iter().position(|&(x1, _)| x <= x1) {
+            Some(0) => self.points[0].1,
+            Some(k) => {
+                let (x0, y0) = self.points[k - 1];
+                let (x1, y1) = self.points[k];
+                y0 + (x - x0) / (x1 - x0) * (y1 - y0)
+            }
+            // SAFETY: `points` is non-empty.
+            None => self.points[self.points.len() - 1].1,
+        };
+
+        // A flat trend at 0, e.g., for replicates with identical values, would give infinite
+        // weights.
+        y.max(f64::EPSILON)
+    }
+}
+
+// The scaled inverse chi-squared prior of the feature variances.
+struct Prior {
+    variance: f64,
+    /// The prior degrees of freedom, which is infinite if the variances are no more dispersed
+    /// than expected by sampling.
+    df: f64,
+}
+
+impl Prior {
+    fn estimate(variances: &[f64], residual_df: f64) -> Self {
+        let half_df = residual_df / 2.0;
+
+        let es: Vec<_> = variances
+            .iter()
+            .filter(|&&s2| s2 > 0.0)
+            .map(|&s2| s2.ln() - digamma(half_df) + half_df.ln())
+            .collect();
+
+        if es.is_empty() {
+            return Self {
+                variance: 0.0,
+                df: f64::INFINITY,
+            };
+        }
+
+        let n = es.len() as f64;
+        let mean = es.iter().sum::<f64>() / n;
+
+        let excess_variance = if es.len() > 1 {
+            es.iter().map(|e| (e - mean).powi(2)).sum::<f64>() / (n - 1.0) - trigamma(half_df)
+        } else {
+            0.0
+        };
+
+        if excess_variance > 0.0 {
+            let df = 2.0 * trigamma_inverse(excess_variance);
+            let variance = (mean + digamma(df / 2.0) - (df / 2.0).ln()).exp();
+            Self { variance, df }
+        } else {
+            Self {
+                variance: mean.exp(),
+                df: f64::INFINITY,
+            }
+        }
+    }
+
+    // Returns the posterior variance and its degrees of freedom.
+    fn moderate(&self, variance: f64, residual_df: f64) -> (f64, f64) {
+        if self.df.is_infinite() {
+            (self.variance, f64::INFINITY)
+        } else {
+            let df = self.df + residual_df;
+            ((self.df * self.variance + residual_df * variance) / df, df)
+        }
+    }
+}
+
+fn two_sided_p_value(t: f64, df: f64) -> f64 {
+    if t.is_nan() {
+        f64::NAN
+    } else if df.is_infinite() {
+        erfc(t.abs() / std::f64::consts::SQRT_2)
+    } else {
+        // SAFETY: `df` is finite and > 0.
+        let distribution = StudentsT::new(0.0, 1.0, df).unwrap();
+        2.0 * distribution.sf(t.abs())
+    }
+}
+
+// Returns the trigamma function, ψ₁(x), for x > 0.
+fn trigamma(mut x: f64) -> f64 {
+    let mut value = 0.0;
+
+    // ψ₁(x) = ψ₁(x + 1) + 1 / x².
+    while x < 10.0 {
+        value += 1.0 / (x * x);
+        x += 1.0;
+    }
+
+    // Asymptotic expansion.
+    let x2 = 1.0 / (x * x);
+    value
+        + 1.0 / x
+        + x2 / 2.0
+        + x2 / x * (1.0 / 6.0 - x2 * (1.0 / 30.0 - x2 * (1.0 / 42.0 - x2 / 30.0)))
+}
+
+// Returns the tetragamma function, ψ₂(x), for x > 0.
+fn tetragamma(mut x: f64) -> f64 {
+    let mut value = 0.0;
+
+    // ψ₂(x) = ψ₂(x + 1) - 2 / x³.
+    while x < 10.0 {
+        value -= 2.0 / (x * x * x);
+        x += 1.0;
+    }
+
+    // Asymptotic expansion.
+    let x2 = 1.0 / (x * x);
+    value - x2 - x2 / x - x2 * x2 * (0.5 - x2 * (1.0 / 6.0 - x2 * (1.0 / 6.0 - x2 * 0.3)))
+}
+
+// Solves ψ₁(x) = y for x using Newton's method, as in limma's `trigammaInverse`.
+fn trigamma_inverse(y: f64) -> f64 {
+    const TOLERANCE: f64 = 1e-8;
+    const MAX_ITERATION_COUNT: usize = 50;
+
+    if y > 1e7 {
+        return 1.0 / y.sqrt();
+    } else if y < 1e-6 {
+        return 1.0 / y;
+    }
+
+    let mut x = 0.5 + 1.0 / y;
+
+    for _ in 0..MAX_ITERATION_COUNT {
+        let tri = trigamma(x);
+        let delta = tri * (1.0 - tri / y) / tetragamma(x);
+        x += delta;
+
+        if -delta / x < TOLERANCE {
+            break;
+        }
+    }
+
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trigamma() {
+        // ψ₁(1) = π² / 6.
+        assert!((trigamma(1.0) - std::f64::consts::PI.powi(2) / 6.0).abs() < 1e-10);
+        assert!((trigamma(10.0) - 0.10516633568168575).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_tetragamma() {
+        // ψ₂(1) = -2ζ(3).
+        assert!((tetragamma(1.0) + 2.0 * 1.2020569031595942).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_trigamma_inverse() {
+        for x in [0.1, 1.0, 2.5, 40.0] {
+            assert!((trigamma_inverse(trigamma(x)) - x).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_trend() {
+        let trend = Trend::new(&[0.0, 1.0, 2.0], &[1.0, 2.0, 3.0]);
+        assert_eq!(trend.points, [(1.0, 2.0)]);
+        assert_eq!(trend.predict(5.0), 2.0);
+    }
+}
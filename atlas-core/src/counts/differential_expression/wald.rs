@@ -0,0 +1,121 @@
+//! Wald test of a negative binomial GLM.
+//!
+//! Counts are normalized by median of ratios size factors, which enter the model as offsets.
+//! Instead of DESeq2's dispersion trend and maximum a posteriori estimates, feature dispersions
+//! are moment estimates moderated toward the common dispersion, as in [`combat_seq`].
+//!
+//! [`combat_seq`]: crate::counts::batch_correction::combat_seq
+
+use std::f64::consts::{LN_2, SQRT_2};
+
+use ndarray::{Array1, Array2, ArrayView2};
+use statrs::function::erf::erfc;
+
+use crate::{counts::normalization::median_of_ratios, stats::glm};
+
+// Prior degrees of freedom when moderating feature dispersions toward the common dispersion.
+const DISPERSION_PRIOR_DF: f64 = 10.0;
+
+/// Returns the log2 fold change and p-value of each feature.
+pub(super) fn test(counts: ArrayView2<'_, u32>, is_treatment: &[bool]) -> (Vec<f64>, Vec<f64>) {
+    let (sample_count, feature_count) = counts.dim();
+
+    let size_factors = median_of_ratios::calculate_size_factors(&counts.to_owned());
+    let offsets = size_factors.mapv(f64::ln);
+
+    let design = Array2::from_shape_fn((sample_count, 2), |(i, j)| match j {
+        0 => 1.0,
+        _ => f64::from(u8::from(is_treatment[i])),
+    });
+
+    let feature_indices: Vec<_> = (0..feature_count)
+        .filter(|&j| counts.column(j).iter().any(|&n| n > 0))
+        .collect();
+
+    let dispersions = estimate_dispersions(counts, &feature_indices, &design, &offsets);
+
+    let mut log2_fold_changes = vec![f64::NAN; feature_count];
+    let mut p_values = vec![f64::NAN; feature_count];
+
+    for (&j, &dispersion) in feature_indices.iter().zip(&dispersions) {
+        let y = counts.column(j).mapv(f64::from);
+        let sample_dispersions = Array1::from_elem(sample_count, dispersion);
+
+        let fit = glm::fit_negative_binomial(
+            design.view(),
+            y.view(),
+            offsets.view(),
+            sample_dispersions.view(),
+        );
+
+        // The variance of the group coefficient is the second diagonal element of (XᵀWX)⁻¹,
+        // which, for a two-group design, is 1 / W₀ + 1 / W₁, where W is the sum of the IRLS
+        // weights in each group.
+        let (mut reference_weight, mut treatment_weight) = (0.0, 0.0);
+
+        for (&mu, &is_treatment) in fit.fitted_values.iter().zip(is_treatment) {
+            let weight = mu / (1.0 + dispersion * mu);
+
+            if is_treatment {
+                treatment_weight += weight;
+            } else {
+                reference_weight += weight;
+            }
+        }
+
+        let coefficient = fit.coefficients[1];
+        let standard_error = (1.0 / reference_weight + 1.0 / treatment_weight).sqrt();
+        let z = coefficient / standard_error;
+
+        log2_fold_changes[j] = coefficient / LN_2;
+        p_values[j] = erfc(z.abs() / SQRT_2);
+    }
+
+    (log2_fold_changes, p_values)
+}
+
+fn estimate_dispersions(
+    counts: ArrayView2<'_, u32>,
+    feature_indices: &[usize],
+    design: &Array2<f64>,
+    offsets: &Array1<f64>,
+) -> Vec<f64> {
+    let poisson_dispersions = Array1::zeros(design.nrows());
+    let residual_df = (design.nrows() - design.ncols()) as f64;
+
+    let dispersions: Vec<_> = feature_indices
+        .iter()
+        .map(|&j| {
+            let y = counts.column(j).mapv(f64::from);
+
+            let fit = glm::fit_negative_binomial(
+                design.view(),
+                y.view(),
+                offsets.view(),
+                poisson_dispersions.view(),
+            );
+
+            let sum: f64 = y
+                .iter()
+                .zip(&fit.fitted_values)
+                .map(|(&y, &mu)| ((y - mu).powi(2) - mu) / (mu * mu))
+                .sum();
+
+            (sum / residual_df).max(0.0)
+        })
+        .collect();
+
+    if dispersions.is_empty() {
+        return dispersions;
+    }
+
+    let common_dispersion = dispersions.iter().sum::<f64>() / dispersions.len() as f64;
+
+    dispersions
+        .into_iter()
+        .map(|dispersion| {
+            (residual_df * dispersion + DISPERSION_PRIOR_DF * common_dispersion)
+                / (residual_df + DISPERSION_PRIOR_DF)
+        })
+        .collect()
+}
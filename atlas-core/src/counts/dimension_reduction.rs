@@ -1 +1,3 @@
+pub mod pca;
+pub mod preprocessing;
 pub mod tsne;
@@ -0,0 +1,231 @@
+//! Principal component analysis (PCA).
+
+use std::io;
+
+use faer::{Mat, MatRef, Side};
+
+/// Projects samples onto their first principal components.
+///
+/// `data` is a samples × features matrix. The result is a samples × components matrix of
+/// principal component scores. The number of components is capped at the rank bound of the input,
+/// i.e., `min(sample_count, feature_count)`.
+///
+/// Rather than a full singular value decomposition, this uses the eigendecomposition of the
+/// smaller of the sample Gram matrix (`XXᵀ`) or scatter matrix (`XᵀX`) of the centered data.
+pub fn transform(data: MatRef<'_, f64>, component_count: usize) -> io::Result<Mat<f64>> {
+    let (sample_count, feature_count) = data.shape();
+    let component_count = component_count.min(sample_count).min(feature_count);
+
+    let x = center(data);
+
+    if sample_count <= feature_count {
+        let gram = &x * x.transpose();
+        let (eigenvectors, eigenvalues) = top_eigenpairs(gram.as_ref(), component_count)?;
+
+        Ok(Mat::from_fn(sample_count, component_count, |i, j| {
+            eigenvectors[(i, j)] * eigenvalues[j].max(0.0).sqrt()
+        }))
+    } else {
+        let scatter = x.transpose() * &x;
+        let (eigenvectors, _) = top_eigenpairs(scatter.as_ref(), component_count)?;
+        Ok(&x * eigenvectors)
+    }
+}
+
+/// Principal components fitted to a set of samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Components {
+    /// The mean of each feature.
+    pub means: Vec<f64>,
+    /// A row-major features × components matrix of loadings.
+    pub loadings: Vec<f64>,
+    pub component_count: usize,
+}
+
+impl Components {
+    /// Projects samples onto the components.
+    ///
+    /// `data` is a samples × features matrix with the same features the components were fitted
+    /// to. The result is a samples × components matrix of scores.
+    pub fn apply(&self, data: MatRef<'_, f64>) -> Mat<f64> {
+        assert_eq!(data.ncols(), self.means.len());
+
+        let k = self.component_count;
+
+        Mat::from_fn(data.nrows(), k, |i, c| {
+            self.means
+                .iter()
+                .enumerate()
+                .map(|(j, mean)| (data[(i, j)] - mean) * self.loadings[j * k + c])
+                .sum()
+        })
+    }
+}
+
+/// Fits principal components to samples, returning the components and the scores of the samples.
+///
+/// The scores are the same as those of [`transform`].
+pub fn fit(data: MatRef<'_, f64>, component_count: usize) -> io::Result<(Components, Mat<f64>)> {
+    let (sample_count, feature_count) = data.shape();
+
+    let scores = transform(data, component_count)?;
+    let k = scores.ncols();
+
+    let means = (0..feature_count)
+        .map(|j| data.col(j).iter().sum::<f64>() / (sample_count as f64))
+        .collect();
+
+    // Each loading vector is `Xᵀs / ‖s‖²`, where `s` is the score vector of the component.
+    let x = center(data);
+    let products = x.transpose() * &scores;
+
+    let squared_norms: Vec<f64> = (0..k)
+        .map(|c| scores.col(c).iter().map(|n| n * n).sum())
+        .collect();
+
+    let mut loadings = vec![0.0; feature_count * k];
+
+    for j in 0..feature_count {
+        for (c, &squared_norm) in squared_norms.iter().enumerate() {
+            if squared_norm > 0.0 {
+                loadings[j * k + c] = products[(j, c)] / squared_norm;
+            }
+        }
+    }
+
+    let components = Components {
+        means,
+        loadings,
+        component_count: k,
+    };
+
+    Ok((components, scores))
+}
+
+// Returns the eigenvectors and eigenvalues of the `k` largest eigenvalues of a symmetric matrix,
+// in descending order.
+fn top_eigenpairs(m: MatRef<'_, f64>, k: usize) -> io::Result<(Mat<f64>, Vec<f64>)> {
+    let evd = m
+        .self_adjoint_eigen(Side::Lower)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{e:?}")))?;
+
+    // Eigenvalues are sorted in nondecreasing order.
+    let n = m.nrows();
+    let u = evd.U();
+    let s = evd.S().column_vector();
+
+    let eigenvectors = Mat::from_fn(n, k, |i, j| u[(i, n - 1 - j)]);
+    let eigenvalues = (0..k).map(|j| s[n - 1 - j]).collect();
+
+    Ok((eigenvectors, eigenvalues))
+}
+
+fn center(data: MatRef<'_, f64>) -> Mat<f64> {
+    let (sample_count, feature_count) = data.shape();
+    let mut centered_data = data.to_owned();
+
+    if sample_count == 0 {
+        return centered_data;
+    }
+
+    for j in 0..feature_count {
+        let mean = data.col(j).iter().sum::<f64>() / (sample_count as f64);
+
+        for i in 0..sample_count {
+            centered_data[(i, j)] -= mean;
+        }
+    }
+
+    centered_data
+}
+
+#[cfg(test)]
+mod tests {
+    use faer::mat;
+
+    use super::*;
+
+    fn assert_approx_eq(a: f64, b: f64) {
+        const EPSILON: f64 = 1e-9;
+        assert!((a - b).abs() < EPSILON, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_transform() -> io::Result<()> {
+        // All samples lie on the line y = 2x.
+        let data = mat![[1.0, 2.0, 0.0], [2.0, 4.0, 0.0], [3.0, 6.0, 0.0]];
+
+        let scores = transform(data.as_ref(), 2)?;
+        assert_eq!(scores.shape(), (3, 2));
+
+        // The first component explains all of the variance.
+        let norm = 5.0f64.sqrt();
+        assert_approx_eq(scores[(0, 0)].abs(), norm);
+        assert_approx_eq(scores[(1, 0)].abs(), 0.0);
+        assert_approx_eq(scores[(2, 0)].abs(), norm);
+        assert!(scores[(0, 0)].signum() != scores[(2, 0)].signum());
+
+        for i in 0..3 {
+            assert_approx_eq(scores[(i, 1)], 0.0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_with_more_samples_than_features() -> io::Result<()> {
+        let data = mat![[1.0, 2.0], [2.0, 4.0], [3.0, 6.0], [4.0, 8.0]];
+
+        let scores = transform(data.as_ref(), 1)?;
+        assert_eq!(scores.shape(), (4, 1));
+
+        let norm = 5.0f64.sqrt();
+        assert_approx_eq(scores[(0, 0)].abs(), 1.5 * norm);
+        assert_approx_eq(scores[(1, 0)].abs(), 0.5 * norm);
+        assert_approx_eq(scores[(2, 0)].abs(), 0.5 * norm);
+        assert_approx_eq(scores[(3, 0)].abs(), 1.5 * norm);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_with_component_count_greater_than_rank_bound() -> io::Result<()> {
+        let data = mat![[1.0, 2.0, 3.0], [2.0, 4.0, 8.0]];
+        let scores = transform(data.as_ref(), 50)?;
+        assert_eq!(scores.shape(), (2, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit() -> io::Result<()> {
+        let data = mat![
+            [1.0, 2.0, 0.5],
+            [2.0, 4.5, 0.0],
+            [3.0, 6.0, 1.0],
+            [5.0, 9.0, 0.0]
+        ];
+
+        let (components, scores) = fit(data.as_ref(), 2)?;
+        assert_eq!(components.means, [2.75, 5.375, 0.375]);
+        assert_eq!(components.component_count, 2);
+        assert_eq!(scores, transform(data.as_ref(), 2)?);
+
+        let projected_scores = components.apply(data.as_ref());
+
+        for i in 0..4 {
+            for c in 0..2 {
+                assert_approx_eq(projected_scores[(i, c)], scores[(i, c)]);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_center() {
+        let data = mat![[1.0, 8.0], [3.0, 13.0]];
+        let actual = center(data.as_ref());
+        let expected = mat![[-1.0, -2.5], [1.0, 2.5]];
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,61 @@
+//! Preprocessing of raw counts for dimension reduction.
+
+use faer::Mat;
+
+// Counts per million (CPM).
+const LIBRARY_SIZE_SCALE: f64 = 1e6;
+
+/// Normalizes raw counts by library size and applies a log transform, i.e., `ln(1 + CPM)`.
+///
+/// `counts` is a row-major samples × features matrix. Samples with no counts are left as zeros.
+pub fn log_normalize<T>(counts: &[T], sample_count: usize, feature_count: usize) -> Mat<f64>
+where
+    T: Copy,
+    f64: From<T>,
+{
+    assert_eq!(counts.len(), sample_count * feature_count);
+
+    let mut data = Mat::zeros(sample_count, feature_count);
+
+    if feature_count == 0 {
+        return data;
+    }
+
+    for (i, row) in counts.chunks_exact(feature_count).enumerate() {
+        let library_size: f64 = row.iter().copied().map(f64::from).sum();
+
+        if library_size <= 0.0 {
+            continue;
+        }
+
+        for (j, &n) in row.iter().enumerate() {
+            let cpm = f64::from(n) * LIBRARY_SIZE_SCALE / library_size;
+            data[(i, j)] = cpm.ln_1p();
+        }
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: f64, b: f64) {
+        const EPSILON: f64 = 1e-9;
+        assert!((a - b).abs() < EPSILON, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_log_normalize() {
+        let counts = [1, 3, 0, 0, 8, 0];
+        let data = log_normalize(&counts, 3, 2);
+
+        assert_approx_eq(data[(0, 0)], 250_000.0f64.ln_1p());
+        assert_approx_eq(data[(0, 1)], 750_000.0f64.ln_1p());
+        assert_approx_eq(data[(1, 0)], 0.0);
+        assert_approx_eq(data[(1, 1)], 0.0);
+        assert_approx_eq(data[(2, 0)], 1_000_000.0f64.ln_1p());
+        assert_approx_eq(data[(2, 1)], 0.0);
+    }
+}
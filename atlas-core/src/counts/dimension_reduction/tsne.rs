@@ -1,23 +1,752 @@
-pub fn transform(perplexity: f64, theta: f64, counts: Vec<i32>, feature_count: usize) -> Vec<f64> {
-    fn euclidean_distance(a: &&[f64], b: &&[f64]) -> f64 {
-        a.iter()
-            .zip(b.iter())
-            .map(|(p, q)| (p - q).powi(2))
-            .sum::<f64>()
-            .sqrt()
+//! t-distributed Stochastic Neighbor Embedding (t-SNE).
+//!
+//! This is an implementation of Barnes-Hut t-SNE. See "[Accelerating t-SNE using tree-based
+//! algorithms]" (2014) by van der Maaten for more details.
+//!
+//! Unlike the reference implementation, the initial embedding is sampled from a seeded random
+//! number generator, and the optimization is single-threaded. Given the same inputs and options,
+//! the embedding is reproducible.
+//!
+//! [Accelerating t-SNE using tree-based algorithms]: https://jmlr.org/papers/v15/vandermaaten14a.html
+
+mod reference;
+mod space_partitioning_tree;
+
+use std::{collections::BTreeMap, io};
+
+use faer::{Mat, MatRef};
+use rand_chacha::{ChaCha8Rng, rand_core::SeedableRng};
+use rand_distr::{Distribution, Normal};
+
+pub use self::reference::Reference;
+use self::space_partitioning_tree::SpacePartitioningTree;
+use super::{pca, preprocessing};
+use crate::counts::feature_selection;
+
+const ITERATION_COUNT: usize = 1000;
+const LEARNING_RATE: f64 = 200.0;
+const INITIAL_MOMENTUM: f64 = 0.5;
+const FINAL_MOMENTUM: f64 = 0.8;
+const MOMENTUM_SWITCH_ITERATION: usize = 250;
+const EARLY_EXAGGERATION: f64 = 12.0;
+const STOP_LYING_ITERATION: usize = 250;
+const MIN_GAIN: f64 = 0.01;
+
+/// The dimensionality of an embedding.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Dimensions {
+    /// 2D.
+    #[default]
+    Two,
+    /// 3D.
+    Three,
+}
+
+impl Dimensions {
+    /// Returns the number of dimensions.
+    pub fn count(&self) -> usize {
+        match self {
+            Self::Two => 2,
+            Self::Three => 3,
+        }
     }
+}
 
-    let sum: u64 = counts.iter().map(|n| *n as u64).sum();
+/// t-SNE options.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    /// The dimensionality of the embedding.
+    pub dimensions: Dimensions,
+    /// Perplexity of the conditional distribution.
+    pub perplexity: f64,
+    /// Barnes-Hut angular size (θ).
+    pub theta: f64,
+    /// Seed of the random number generator used to initialize the embedding.
+    pub seed: u64,
+    /// Whether to remove features with low counts before normalization.
+    ///
+    /// See [`feature_selection::filter_by_expression`]. All samples are treated as a single group.
+    pub filter_by_expression: bool,
+    /// The number of features with the highest variance to keep.
+    ///
+    /// If `None`, all features are used.
+    pub variable_feature_count: Option<usize>,
+    /// The number of principal components to reduce the features to before embedding.
+    ///
+    /// If `None`, PCA is skipped.
+    pub principal_component_count: Option<usize>,
+}
 
-    let normalized_counts: Vec<_> = counts
-        .into_iter()
-        .map(|count| (count as f64) / (sum as f64))
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            dimensions: Dimensions::default(),
+            perplexity: 30.0,
+            theta: 0.5,
+            seed: 0,
+            filter_by_expression: false,
+            variable_feature_count: Some(2000),
+            principal_component_count: Some(50),
+        }
+    }
+}
+
+/// Embeds samples of raw counts in two or three dimensions.
+///
+/// `counts` is a row-major samples × features matrix. Before embedding, counts are normalized by
+/// library size and log transformed (`ln(1 + CPM)`). Optionally, features with low counts are
+/// removed, only the most variable features are kept, and the result is reduced using PCA.
+///
+/// The result is a row-major samples × dimensions matrix.
+pub fn transform<T>(counts: &[T], feature_count: usize, options: &Options) -> io::Result<Vec<f64>>
+where
+    T: Copy,
+    f64: From<T>,
+{
+    if feature_count == 0 || !counts.len().is_multiple_of(feature_count) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "counts length is not a multiple of the feature count",
+        ));
+    }
+
+    let sample_count = counts.len() / feature_count;
+
+    let data = preprocess(counts, sample_count, feature_count, options)?;
+
+    Ok(embed_with_options(data.as_ref(), options))
+}
+
+/// Preprocessing fitted to a set of samples, to transform new samples in the same way.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preprocessing {
+    /// The number of features of the raw counts.
+    pub feature_count: usize,
+    /// The indices of features that passed the expression filter, if filtered.
+    pub expressed_feature_indices: Option<Vec<usize>>,
+    /// The indices of the most variable features after filtering, if selected.
+    pub variable_feature_indices: Option<Vec<usize>>,
+    /// The principal components, if reduced using PCA.
+    pub components: Option<pca::Components>,
+}
+
+impl Preprocessing {
+    fn fit<T>(
+        counts: &[T],
+        sample_count: usize,
+        feature_count: usize,
+        options: &Options,
+    ) -> io::Result<(Self, Mat<f64>)>
+    where
+        T: Copy,
+        f64: From<T>,
+    {
+        let mut expressed_feature_indices = None;
+
+        let mut data = if options.filter_by_expression {
+            let groups = vec![(); sample_count];
+
+            let indices = feature_selection::filter_by_expression(
+                counts,
+                feature_count,
+                &groups,
+                &Default::default(),
+            )?;
+
+            if indices.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "no features passed the expression filter",
+                ));
+            }
+
+            let counts = feature_selection::retain_features(counts, feature_count, &indices);
+            let data = preprocessing::log_normalize(&counts, sample_count, indices.len());
+            expressed_feature_indices = Some(indices);
+            data
+        } else {
+            preprocessing::log_normalize(counts, sample_count, feature_count)
+        };
+
+        let mut variable_feature_indices = None;
+
+        if let Some(n) = options.variable_feature_count
+            && n < data.ncols()
+        {
+            let indices = feature_selection::select_variable_features(data.as_ref(), n);
+            data = feature_selection::select_features(data.as_ref(), &indices);
+            variable_feature_indices = Some(indices);
+        }
+
+        let mut components = None;
+
+        if let Some(n) = options.principal_component_count {
+            let (c, scores) = pca::fit(data.as_ref(), n)?;
+            data = scores;
+            components = Some(c);
+        }
+
+        let preprocessing = Self {
+            feature_count,
+            expressed_feature_indices,
+            variable_feature_indices,
+            components,
+        };
+
+        Ok((preprocessing, data))
+    }
+
+    /// Transforms samples of raw counts.
+    ///
+    /// `counts` is a row-major samples × features matrix with the same features the
+    /// preprocessing was fitted to.
+    pub fn apply<T>(&self, counts: &[T]) -> io::Result<Mat<f64>>
+    where
+        T: Copy,
+        f64: From<T>,
+    {
+        let feature_count = self.feature_count;
+
+        if feature_count == 0 || !counts.len().is_multiple_of(feature_count) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "counts length is not a multiple of the feature count",
+            ));
+        }
+
+        let sample_count = counts.len() / feature_count;
+
+        let mut data = match &self.expressed_feature_indices {
+            Some(indices) => {
+                let counts = feature_selection::retain_features(counts, feature_count, indices);
+                preprocessing::log_normalize(&counts, sample_count, indices.len())
+            }
+            None => preprocessing::log_normalize(counts, sample_count, feature_count),
+        };
+
+        if let Some(indices) = &self.variable_feature_indices {
+            data = feature_selection::select_features(data.as_ref(), indices);
+        }
+
+        if let Some(components) = &self.components {
+            data = components.apply(data.as_ref());
+        }
+
+        Ok(data)
+    }
+}
+
+fn preprocess<T>(
+    counts: &[T],
+    sample_count: usize,
+    feature_count: usize,
+    options: &Options,
+) -> io::Result<Mat<f64>>
+where
+    T: Copy,
+    f64: From<T>,
+{
+    Preprocessing::fit(counts, sample_count, feature_count, options).map(|(_, data)| data)
+}
+
+// Returns a row-major samples × dimensions matrix.
+fn embed_with_options(data: MatRef<'_, f64>, options: &Options) -> Vec<f64> {
+    let (perplexity, theta, seed) = (options.perplexity, options.theta, options.seed);
+
+    match options.dimensions {
+        Dimensions::Two => embed::<2>(data, perplexity, theta, seed)
+            .into_iter()
+            .flatten()
+            .collect(),
+        Dimensions::Three => embed::<3>(data, perplexity, theta, seed)
+            .into_iter()
+            .flatten()
+            .collect(),
+    }
+}
+
+fn embed<const D: usize>(
+    data: MatRef<'_, f64>,
+    perplexity: f64,
+    theta: f64,
+    seed: u64,
+) -> Vec<[f64; D]> {
+    let sample_count = data.nrows();
+
+    if sample_count < 2 {
+        return vec![[0.0; D]; sample_count];
+    }
+
+    let rows = normalize_input(data);
+    let p = compute_input_similarities(&rows, perplexity);
+
+    let mut y = initialize_embedding::<D>(sample_count, seed);
+
+    let mut dy = vec![[0.0; D]; sample_count];
+    let mut uy = vec![[0.0; D]; sample_count];
+    let mut gains = vec![[1.0f64; D]; sample_count];
+
+    let mut momentum = INITIAL_MOMENTUM;
+    let mut exaggeration = EARLY_EXAGGERATION;
+
+    for iteration in 0..ITERATION_COUNT {
+        compute_gradient(&p, &y, theta, exaggeration, &mut dy);
+
+        for i in 0..sample_count {
+            for d in 0..D {
+                let gain = if sign(dy[i][d]) != sign(uy[i][d]) {
+                    gains[i][d] + 0.2
+                } else {
+                    gains[i][d] * 0.8
+                };
+
+                gains[i][d] = gain.max(MIN_GAIN);
+
+                uy[i][d] = momentum * uy[i][d] - LEARNING_RATE * gains[i][d] * dy[i][d];
+                y[i][d] += uy[i][d];
+            }
+        }
+
+        zero_mean(&mut y);
+
+        if iteration == STOP_LYING_ITERATION {
+            exaggeration = 1.0;
+        }
+
+        if iteration == MOMENTUM_SWITCH_ITERATION {
+            momentum = FINAL_MOMENTUM;
+        }
+    }
+
+    y
+}
+
+// Centers the input and scales it by its maximum absolute value to prevent numerical issues when
+// calculating the Gaussian kernels.
+fn normalize_input(data: MatRef<'_, f64>) -> Vec<Vec<f64>> {
+    let (sample_count, feature_count) = data.shape();
+
+    let means: Vec<_> = (0..feature_count)
+        .map(|j| data.col(j).iter().sum::<f64>() / (sample_count as f64))
         .collect();
 
-    let data: Vec<_> = normalized_counts.chunks(feature_count).collect();
+    let mut rows: Vec<Vec<f64>> = (0..sample_count)
+        .map(|i| {
+            (0..feature_count)
+                .map(|j| data[(i, j)] - means[j])
+                .collect()
+        })
+        .collect();
+
+    let max = rows
+        .iter()
+        .flatten()
+        .fold(0.0, |max, n| f64::max(max, n.abs()));
+
+    if max > 0.0 {
+        rows.iter_mut().flatten().for_each(|n| *n /= max);
+    }
+
+    rows
+}
+
+// Computes the symmetrized, sparse joint probabilities `P` over each sample's nearest neighbors.
+fn compute_input_similarities(rows: &[Vec<f64>], perplexity: f64) -> Vec<Vec<(usize, f64)>> {
+    let sample_count = rows.len();
+    let neighbor_count = ((3.0 * perplexity) as usize).clamp(1, sample_count - 1);
+
+    let mut distances = Vec::with_capacity(sample_count);
+    let mut joint_probabilities = vec![BTreeMap::new(); sample_count];
+
+    for (i, a) in rows.iter().enumerate() {
+        distances.clear();
+
+        for (j, b) in rows.iter().enumerate() {
+            if i != j {
+                distances.push((j, squared_euclidean_distance(a, b)));
+            }
+        }
+
+        let cmp = |(i, a): &(usize, f64), (j, b): &(usize, f64)| a.total_cmp(b).then(i.cmp(j));
+        distances.select_nth_unstable_by(neighbor_count - 1, cmp);
+        distances.truncate(neighbor_count);
+        distances.sort_unstable_by(cmp);
+
+        let neighbor_distances: Vec<_> = distances.iter().map(|(_, d)| *d).collect();
+        let conditional_probabilities =
+            compute_conditional_probabilities(&neighbor_distances, perplexity);
+
+        for (&(j, _), p) in distances.iter().zip(conditional_probabilities) {
+            *joint_probabilities[i].entry(j).or_insert(0.0) += p;
+            *joint_probabilities[j].entry(i).or_insert(0.0) += p;
+        }
+    }
+
+    let sum: f64 = joint_probabilities
+        .iter()
+        .flat_map(|row| row.values())
+        .sum();
+
+    joint_probabilities
+        .into_iter()
+        .map(|row| row.into_iter().map(|(j, p)| (j, p / sum)).collect())
+        .collect()
+}
+
+// Finds the Gaussian kernel precision (β) such that the entropy of the conditional distribution
+// matches `ln(perplexity)` using a binary search.
+//
+// `squared_distances` must be non-empty and sorted.
+fn compute_conditional_probabilities(squared_distances: &[f64], perplexity: f64) -> Vec<f64> {
+    const TOLERANCE: f64 = 1e-5;
+    const MAX_ITERATIONS: usize = 200;
+
+    let target_entropy = perplexity.ln();
+
+    // Distances are shifted by the minimum for numerical stability. This cancels out when the
+    // probabilities are normalized.
+    let min_distance = squared_distances[0];
+
+    let mut beta = 1.0;
+    let mut min_beta = f64::NEG_INFINITY;
+    let mut max_beta = f64::INFINITY;
+
+    let mut probabilities = vec![0.0; squared_distances.len()];
+    let mut sum = 0.0;
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut weighted_sum = 0.0;
+        sum = 0.0;
+
+        for (p, &d) in probabilities.iter_mut().zip(squared_distances) {
+            let shifted_distance = d - min_distance;
+            *p = (-beta * shifted_distance).exp();
+            sum += *p;
+            weighted_sum += shifted_distance * *p;
+        }
+
+        let entropy = beta * weighted_sum / sum + sum.ln();
+        let delta = entropy - target_entropy;
 
-    bhtsne::tSNE::new(&data)
-        .perplexity(perplexity)
-        .barnes_hut(theta, euclidean_distance)
-        .embedding()
+        if delta.abs() < TOLERANCE {
+            break;
+        }
+
+        if delta > 0.0 {
+            min_beta = beta;
+            beta = if max_beta.is_infinite() {
+                beta * 2.0
+            } else {
+                (beta + max_beta) / 2.0
+            };
+        } else {
+            max_beta = beta;
+            beta = if min_beta.is_infinite() {
+                beta / 2.0
+            } else {
+                (beta + min_beta) / 2.0
+            };
+        }
+    }
+
+    for p in &mut probabilities {
+        *p /= sum;
+    }
+
+    probabilities
+}
+
+fn initialize_embedding<const D: usize>(sample_count: usize, seed: u64) -> Vec<[f64; D]> {
+    const STANDARD_DEVIATION: f64 = 1e-4;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    // SAFETY: The standard deviation is finite and positive.
+    let distribution = Normal::new(0.0, STANDARD_DEVIATION).unwrap();
+
+    (0..sample_count)
+        .map(|_| std::array::from_fn(|_| distribution.sample(&mut rng)))
+        .collect()
+}
+
+fn compute_gradient<const D: usize>(
+    p: &[Vec<(usize, f64)>],
+    y: &[[f64; D]],
+    theta: f64,
+    exaggeration: f64,
+    dy: &mut [[f64; D]],
+) {
+    let tree = SpacePartitioningTree::new(y);
+
+    let mut repulsive_forces = vec![[0.0; D]; y.len()];
+    let mut sum_q = 0.0;
+
+    for (i, forces) in repulsive_forces.iter_mut().enumerate() {
+        tree.compute_non_edge_forces(i, theta, forces, &mut sum_q);
+    }
+
+    for (i, (row, gradient)) in p.iter().zip(dy.iter_mut()).enumerate() {
+        let mut attractive_forces = [0.0; D];
+
+        for &(j, p_ij) in row {
+            let mut diff = [0.0; D];
+            let mut squared_distance = 0.0;
+
+            for d in 0..D {
+                diff[d] = y[i][d] - y[j][d];
+                squared_distance += diff[d] * diff[d];
+            }
+
+            let mult = exaggeration * p_ij / (1.0 + squared_distance);
+
+            for d in 0..D {
+                attractive_forces[d] += mult * diff[d];
+            }
+        }
+
+        for d in 0..D {
+            gradient[d] = attractive_forces[d] - repulsive_forces[i][d] / sum_q;
+        }
+    }
+}
+
+fn zero_mean<const D: usize>(y: &mut [[f64; D]]) {
+    let n = y.len() as f64;
+    let mut means = [0.0; D];
+
+    for point in y.iter() {
+        for d in 0..D {
+            means[d] += point[d] / n;
+        }
+    }
+
+    for point in y.iter_mut() {
+        for d in 0..D {
+            point[d] -= means[d];
+        }
+    }
+}
+
+fn squared_euclidean_distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(p, q)| (p - q).powi(2)).sum()
+}
+
+fn sign(n: f64) -> i8 {
+    if n > 0.0 {
+        1
+    } else if n < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: f64, b: f64) {
+        const EPSILON: f64 = 1e-6;
+        assert!((a - b).abs() < EPSILON, "{a} != {b}");
+    }
+
+    pub(super) fn build_counts() -> (Vec<u32>, usize) {
+        const FEATURE_COUNT: usize = 8;
+
+        // Two groups of samples with different expression profiles.
+        let mut counts = Vec::new();
+
+        for i in 0..24u32 {
+            let offset = i % 5;
+
+            let row: [u32; FEATURE_COUNT] = if i % 2 == 0 {
+                [500, 400, 300, 10, 5, 3, 2, 1]
+            } else {
+                [3, 5, 10, 300, 400, 500, 2, 1]
+            };
+
+            counts.extend(row.iter().map(|n| n + offset * (n % 7)));
+        }
+
+        (counts, FEATURE_COUNT)
+    }
+
+    #[test]
+    fn test_transform() -> io::Result<()> {
+        let (counts, feature_count) = build_counts();
+
+        let options = Options {
+            perplexity: 5.0,
+            ..Default::default()
+        };
+
+        let embedding = transform(&counts, feature_count, &options)?;
+        assert_eq!(embedding.len(), 24 * 2);
+        assert!(embedding.iter().all(|n| n.is_finite()));
+
+        // Samples are closer to samples in their own group than to samples in the other group.
+        let points: Vec<_> = embedding.chunks_exact(2).collect();
+
+        for (i, a) in points.iter().enumerate() {
+            let (mut same, mut other) = (Vec::new(), Vec::new());
+
+            for (j, b) in points.iter().enumerate() {
+                if i != j {
+                    let d = squared_euclidean_distance(a, b);
+                    if i % 2 == j % 2 {
+                        same.push(d)
+                    } else {
+                        other.push(d)
+                    }
+                }
+            }
+
+            let max_same = same.iter().copied().fold(0.0, f64::max);
+            let min_other = other.iter().copied().fold(f64::INFINITY, f64::min);
+            assert!(max_same < min_other);
+        }
+
+        // The embedding is reproducible with the same seed.
+        assert_eq!(transform(&counts, feature_count, &options)?, embedding);
+
+        let options = Options { seed: 8, ..options };
+
+        assert_ne!(transform(&counts, feature_count, &options)?, embedding);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_with_three_dimensions() -> io::Result<()> {
+        let (counts, feature_count) = build_counts();
+
+        let options = Options {
+            dimensions: Dimensions::Three,
+            perplexity: 5.0,
+            ..Default::default()
+        };
+
+        let embedding = transform(&counts, feature_count, &options)?;
+        assert_eq!(embedding.len(), 24 * 3);
+        assert!(embedding.iter().all(|n| n.is_finite()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_with_invalid_shape() {
+        let counts = [1, 2, 3];
+
+        assert!(matches!(
+            transform(&counts, 2, &Options::default()),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+
+        assert!(matches!(
+            transform(&counts, 0, &Options::default()),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+    }
+
+    #[test]
+    fn test_preprocess() -> io::Result<()> {
+        let (counts, feature_count) = build_counts();
+
+        let options = Options {
+            variable_feature_count: Some(4),
+            principal_component_count: Some(3),
+            ..Default::default()
+        };
+
+        let data = preprocess(&counts, 24, feature_count, &options)?;
+        assert_eq!(data.shape(), (24, 3));
+
+        let options = Options {
+            variable_feature_count: None,
+            principal_component_count: None,
+            ..Default::default()
+        };
+
+        let data = preprocess(&counts, 24, feature_count, &options)?;
+        assert_eq!(data.shape(), (24, feature_count));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preprocess_with_filter_by_expression() -> io::Result<()> {
+        let counts = [
+            100, 200, 0, 1, //
+            120, 180, 1, 0, //
+            90, 210, 0, 0, //
+            110, 190, 2, 1, //
+        ];
+
+        let options = Options {
+            filter_by_expression: true,
+            variable_feature_count: None,
+            principal_component_count: None,
+            ..Default::default()
+        };
+
+        let data = preprocess(&counts, 4, 4, &options)?;
+        assert_eq!(data.shape(), (4, 2));
+
+        let counts = [0, 1, 1, 0];
+
+        assert!(matches!(
+            preprocess(&counts, 2, 2, &options),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compute_conditional_probabilities() {
+        let squared_distances = [1.0, 2.0, 3.0, 5.0, 8.0];
+        let perplexity = 3.0;
+
+        let probabilities = compute_conditional_probabilities(&squared_distances, perplexity);
+
+        assert_approx_eq(probabilities.iter().sum(), 1.0);
+
+        let entropy: f64 = probabilities
+            .iter()
+            .filter(|&&p| p > 0.0)
+            .map(|p| -p * p.ln())
+            .sum();
+
+        assert!((entropy - perplexity.ln()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_compute_input_similarities() {
+        let rows = vec![vec![0.0], vec![1.0], vec![3.0]];
+        let p = compute_input_similarities(&rows, 1.0);
+
+        let sum: f64 = p.iter().flatten().map(|(_, p)| p).sum();
+        assert_approx_eq(sum, 1.0);
+
+        // `P` is symmetric.
+        for (i, row) in p.iter().enumerate() {
+            for &(j, p_ij) in row {
+                let p_ji = p[j].iter().find(|(k, _)| *k == i).map(|(_, p)| *p);
+                assert_eq!(p_ji, Some(p_ij));
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_mean() {
+        let mut y = [[1.0, 2.0], [3.0, 8.0]];
+        zero_mean(&mut y);
+        assert_eq!(y, [[-1.0, -3.0], [1.0, 3.0]]);
+    }
+
+    #[test]
+    fn test_sign() {
+        assert_eq!(sign(-2.0), -1);
+        assert_eq!(sign(0.0), 0);
+        assert_eq!(sign(3.0), 1);
+    }
 }
@@ -0,0 +1,235 @@
+use std::io;
+
+use super::{Dimensions, Options, Preprocessing, embed_with_options, squared_euclidean_distance};
+
+/// A t-SNE embedding of reference samples that new samples can be projected onto.
+///
+/// New samples are preprocessed in the same way as the reference samples, i.e., with the same
+/// features, PCA means, and loadings. Each is then placed at the inverse distance weighted mean
+/// of the embedded positions of its nearest reference samples in the preprocessed space. The
+/// reference embedding is unchanged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub dimensions: Dimensions,
+    pub preprocessing: Preprocessing,
+    /// A row-major samples × inputs matrix of the preprocessed reference samples.
+    pub data: Vec<f64>,
+    /// A row-major samples × dimensions matrix of the embedding of the reference samples.
+    pub embedding: Vec<f64>,
+}
+
+impl Reference {
+    /// Embeds samples of raw counts and keeps the fitted preprocessing.
+    ///
+    /// The embedding is the same as that of [`super::transform`].
+    pub fn fit<T>(counts: &[T], feature_count: usize, options: &Options) -> io::Result<Self>
+    where
+        T: Copy,
+        f64: From<T>,
+    {
+        if feature_count == 0 || !counts.len().is_multiple_of(feature_count) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "counts length is not a multiple of the feature count",
+            ));
+        }
+
+        let sample_count = counts.len() / feature_count;
+
+        let (preprocessing, data) =
+            Preprocessing::fit(counts, sample_count, feature_count, options)?;
+
+        let embedding = embed_with_options(data.as_ref(), options);
+
+        let data = (0..data.nrows())
+            .flat_map(|i| (0..data.ncols()).map(move |j| (i, j)))
+            .map(|(i, j)| data[(i, j)])
+            .collect();
+
+        Ok(Self {
+            dimensions: options.dimensions,
+            preprocessing,
+            data,
+            embedding,
+        })
+    }
+
+    /// Returns the number of reference samples.
+    pub fn sample_count(&self) -> usize {
+        self.embedding.len() / self.dimensions.count()
+    }
+
+    /// Places samples of raw counts in the reference embedding.
+    ///
+    /// `counts` is a row-major samples × features matrix with the same features as the reference
+    /// samples. The result is a row-major samples × dimensions matrix.
+    pub fn project<T>(&self, counts: &[T], neighbor_count: usize) -> io::Result<Vec<f64>>
+    where
+        T: Copy,
+        f64: From<T>,
+    {
+        // Distances at or below this are treated as an exact match.
+        const EPSILON: f64 = 1e-12;
+
+        let sample_count = self.sample_count();
+
+        if sample_count == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "reference embedding is empty",
+            ));
+        }
+
+        let data = self.preprocessing.apply(counts)?;
+        let input_count = self.data.len() / sample_count;
+
+        if data.ncols() != input_count {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "preprocessed dimensions do not match the reference",
+            ));
+        }
+
+        let neighbor_count = neighbor_count.clamp(1, sample_count);
+        let dimension_count = self.dimensions.count();
+
+        let mut positions = Vec::with_capacity(data.nrows() * dimension_count);
+        let mut distances = Vec::with_capacity(sample_count);
+
+        for i in 0..data.nrows() {
+            let row: Vec<_> = (0..input_count).map(|j| data[(i, j)]).collect();
+
+            distances.clear();
+
+            for (j, reference_row) in self.data.chunks_exact(input_count).enumerate() {
+                let d = squared_euclidean_distance(&row, reference_row).sqrt();
+                distances.push((j, d));
+            }
+
+            let cmp = |(i, a): &(usize, f64), (j, b): &(usize, f64)| a.total_cmp(b).then(i.cmp(j));
+            distances.select_nth_unstable_by(neighbor_count - 1, cmp);
+            distances.truncate(neighbor_count);
+            distances.sort_unstable_by(cmp);
+
+            let weights: Vec<_> = if distances[0].1 <= EPSILON {
+                distances
+                    .iter()
+                    .map(|&(_, d)| if d <= EPSILON { 1.0 } else { 0.0 })
+                    .collect()
+            } else {
+                distances.iter().map(|&(_, d)| 1.0 / d).collect()
+            };
+
+            let weight_sum: f64 = weights.iter().sum();
+
+            for d in 0..dimension_count {
+                let position = distances
+                    .iter()
+                    .zip(&weights)
+                    .map(|(&(j, _), w)| w * self.embedding[j * dimension_count + d])
+                    .sum::<f64>()
+                    / weight_sum;
+
+                positions.push(position);
+            }
+        }
+
+        Ok(positions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::tests::build_counts, *};
+    use crate::counts::dimension_reduction::tsne::transform;
+
+    #[test]
+    fn test_fit() -> io::Result<()> {
+        let (counts, feature_count) = build_counts();
+
+        let options = Options {
+            perplexity: 5.0,
+            variable_feature_count: Some(6),
+            principal_component_count: Some(4),
+            ..Default::default()
+        };
+
+        let reference = Reference::fit(&counts, feature_count, &options)?;
+
+        assert_eq!(reference.sample_count(), 24);
+        assert_eq!(reference.data.len(), 24 * 4);
+        assert_eq!(
+            reference.embedding,
+            transform(&counts, feature_count, &options)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_project() -> io::Result<()> {
+        let (counts, feature_count) = build_counts();
+
+        let options = Options {
+            perplexity: 5.0,
+            filter_by_expression: true,
+            variable_feature_count: Some(6),
+            principal_component_count: Some(4),
+            ..Default::default()
+        };
+
+        let reference = Reference::fit(&counts, feature_count, &options)?;
+
+        // A reference sample is placed at the mean position of the reference samples identical to
+        // it, i.e., samples 0, 10, and 20.
+        let positions = reference.project(&counts[..feature_count], 5)?;
+
+        for (d, actual) in positions.iter().enumerate() {
+            let expected = [0, 10, 20]
+                .iter()
+                .map(|i| reference.embedding[2 * i + d])
+                .sum::<f64>()
+                / 3.0;
+
+            assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+        }
+
+        // A new sample is placed among the samples of its group.
+        let positions = reference.project(&[480u32, 420, 310, 12, 4, 3, 2, 1], 5)?;
+        assert_eq!(positions.len(), 2);
+
+        let squared_distance = |i: usize| {
+            squared_euclidean_distance(&positions, &reference.embedding[2 * i..2 * i + 2])
+        };
+
+        let max_same = (0..24).step_by(2).map(squared_distance).fold(0.0, f64::max);
+        let min_other = (1..24)
+            .step_by(2)
+            .map(squared_distance)
+            .fold(f64::INFINITY, f64::min);
+
+        assert!(max_same < min_other);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_project_with_invalid_shape() -> io::Result<()> {
+        let (counts, feature_count) = build_counts();
+        let reference = Reference::fit(
+            &counts,
+            feature_count,
+            &Options {
+                perplexity: 5.0,
+                ..Default::default()
+            },
+        )?;
+
+        assert!(matches!(
+            reference.project(&[1u32, 2, 3], 5),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+//! A space-partitioning tree (quadtree in 2D, octree in 3D) over embedded points.
+
+// Points that are inserted this deep are considered to be duplicates.
+const MAX_DEPTH: usize = 64;
+
+struct Node<const D: usize> {
+    center: [f64; D],
+    half_width: [f64; D],
+    center_of_mass: [f64; D],
+    cumulative_size: usize,
+    point: Option<usize>,
+    first_child: Option<usize>,
+}
+
+impl<const D: usize> Node<D> {
+    fn new(center: [f64; D], half_width: [f64; D]) -> Self {
+        Self {
+            center,
+            half_width,
+            center_of_mass: [0.0; D],
+            cumulative_size: 0,
+            point: None,
+            first_child: None,
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.first_child.is_none()
+    }
+
+    fn contains(&self, point: &[f64; D]) -> bool {
+        (0..D).all(|d| {
+            let (c, w) = (self.center[d], self.half_width[d]);
+            c - w <= point[d] && point[d] <= c + w
+        })
+    }
+
+    fn max_width(&self) -> f64 {
+        self.half_width.iter().copied().fold(0.0, f64::max)
+    }
+}
+
+pub(super) struct SpacePartitioningTree<'a, const D: usize> {
+    points: &'a [[f64; D]],
+    nodes: Vec<Node<D>>,
+}
+
+impl<'a, const D: usize> SpacePartitioningTree<'a, D> {
+    const CHILD_COUNT: usize = 1 << D;
+
+    pub fn new(points: &'a [[f64; D]]) -> Self {
+        let n = points.len().max(1) as f64;
+
+        let mut center = [0.0; D];
+
+        for point in points {
+            for d in 0..D {
+                center[d] += point[d];
+            }
+        }
+
+        center.iter_mut().for_each(|c| *c /= n);
+
+        let mut half_width = [0.0; D];
+
+        for point in points {
+            for d in 0..D {
+                half_width[d] = f64::max(half_width[d], (point[d] - center[d]).abs());
+            }
+        }
+
+        half_width.iter_mut().for_each(|w| *w += 1e-5);
+
+        let mut tree = Self {
+            points,
+            nodes: vec![Node::new(center, half_width)],
+        };
+
+        for i in 0..points.len() {
+            tree.insert(0, i, 0);
+        }
+
+        tree
+    }
+
+    fn insert(&mut self, node_index: usize, i: usize, depth: usize) -> bool {
+        let point = &self.points[i];
+        let node = &mut self.nodes[node_index];
+
+        if !node.contains(point) {
+            return false;
+        }
+
+        node.cumulative_size += 1;
+
+        let n = node.cumulative_size as f64;
+
+        for (c, x) in node.center_of_mass.iter_mut().zip(point) {
+            *c = *c * (n - 1.0) / n + x / n;
+        }
+
+        if node.is_leaf() {
+            match node.point {
+                None => {
+                    node.point = Some(i);
+                    return true;
+                }
+                Some(j) if self.points[j] == *point || depth >= MAX_DEPTH => return true,
+                Some(_) => self.subdivide(node_index),
+            }
+        }
+
+        // SAFETY: The node was subdivided if it was a leaf.
+        let first_child = self.nodes[node_index].first_child.unwrap();
+
+        (first_child..first_child + Self::CHILD_COUNT).any(|child| self.insert(child, i, depth + 1))
+    }
+
+    fn subdivide(&mut self, node_index: usize) {
+        let first_child = self.nodes.len();
+
+        let node = &self.nodes[node_index];
+        let (center, half_width) = (node.center, node.half_width);
+
+        for k in 0..Self::CHILD_COUNT {
+            let mut child_center = [0.0; D];
+            let mut child_half_width = [0.0; D];
+
+            for d in 0..D {
+                child_half_width[d] = half_width[d] / 2.0;
+
+                child_center[d] = if k & (1 << d) == 0 {
+                    center[d] - child_half_width[d]
+                } else {
+                    center[d] + child_half_width[d]
+                };
+            }
+
+            self.nodes.push(Node::new(child_center, child_half_width));
+        }
+
+        let node = &mut self.nodes[node_index];
+        node.first_child = Some(first_child);
+
+        // SAFETY: Only occupied leaves are subdivided.
+        let j = node.point.take().unwrap();
+
+        for child in first_child..first_child + Self::CHILD_COUNT {
+            if self.insert_into_empty_node(child, j) {
+                break;
+            }
+        }
+    }
+
+    // Moves a point that is already accounted for in its ancestors into a new child.
+    fn insert_into_empty_node(&mut self, node_index: usize, i: usize) -> bool {
+        let point = &self.points[i];
+        let node = &mut self.nodes[node_index];
+
+        if !node.contains(point) {
+            return false;
+        }
+
+        node.cumulative_size = 1;
+        node.center_of_mass = *point;
+        node.point = Some(i);
+
+        true
+    }
+
+    /// Accumulates the repulsive forces on point `i` and the partial sum of the normalization
+    /// term `Z`.
+    pub fn compute_non_edge_forces(
+        &self,
+        i: usize,
+        theta: f64,
+        forces: &mut [f64; D],
+        sum_q: &mut f64,
+    ) {
+        self.compute_non_edge_forces_inner(0, i, theta, forces, sum_q);
+    }
+
+    fn compute_non_edge_forces_inner(
+        &self,
+        node_index: usize,
+        i: usize,
+        theta: f64,
+        forces: &mut [f64; D],
+        sum_q: &mut f64,
+    ) {
+        let node = &self.nodes[node_index];
+
+        if node.cumulative_size == 0 || (node.is_leaf() && node.point == Some(i)) {
+            return;
+        }
+
+        let point = &self.points[i];
+
+        let mut diff = [0.0; D];
+        let mut squared_distance = 0.0;
+
+        for d in 0..D {
+            diff[d] = point[d] - node.center_of_mass[d];
+            squared_distance += diff[d] * diff[d];
+        }
+
+        if node.is_leaf() || node.max_width() / squared_distance.sqrt() < theta {
+            let q = 1.0 / (1.0 + squared_distance);
+            let mut mult = (node.cumulative_size as f64) * q;
+            *sum_q += mult;
+            mult *= q;
+
+            for d in 0..D {
+                forces[d] += mult * diff[d];
+            }
+        } else if let Some(first_child) = node.first_child {
+            for child in first_child..first_child + Self::CHILD_COUNT {
+                self.compute_non_edge_forces_inner(child, i, theta, forces, sum_q);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: f64, b: f64) {
+        const EPSILON: f64 = 1e-9;
+        assert!((a - b).abs() < EPSILON, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_new() {
+        let points = [[0.0, 0.0], [1.0, 1.0], [-1.0, 2.0], [0.5, -3.0]];
+        let tree = SpacePartitioningTree::new(&points);
+
+        let root = &tree.nodes[0];
+        assert_eq!(root.cumulative_size, points.len());
+        assert_approx_eq(root.center_of_mass[0], 0.125);
+        assert_approx_eq(root.center_of_mass[1], 0.0);
+
+        let leaf_point_count = tree
+            .nodes
+            .iter()
+            .filter(|node| node.is_leaf() && node.point.is_some())
+            .count();
+
+        assert_eq!(leaf_point_count, points.len());
+    }
+
+    #[test]
+    fn test_new_with_duplicate_points() {
+        let points = [[1.0, 1.0], [1.0, 1.0], [1.0, 1.0]];
+        let tree = SpacePartitioningTree::new(&points);
+        assert_eq!(tree.nodes[0].cumulative_size, points.len());
+    }
+
+    #[test]
+    fn test_compute_non_edge_forces() {
+        let points = [[0.0, 0.0], [3.0, 4.0]];
+        let tree = SpacePartitioningTree::new(&points);
+
+        let mut forces = [0.0; 2];
+        let mut sum_q = 0.0;
+        tree.compute_non_edge_forces(0, 0.5, &mut forces, &mut sum_q);
+
+        // q = 1 / (1 + 25)
+        let q = 1.0 / 26.0;
+        assert_approx_eq(sum_q, q);
+        assert_approx_eq(forces[0], q * q * -3.0);
+        assert_approx_eq(forces[1], q * q * -4.0);
+    }
+}
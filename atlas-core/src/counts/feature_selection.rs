@@ -0,0 +1,347 @@
+//! Feature filtering and selection.
+
+use std::{collections::HashMap, hash::Hash, io};
+
+use faer::{Mat, MatRef};
+
+// Counts per million (CPM).
+const LIBRARY_SIZE_SCALE: f64 = 1e6;
+
+// Tolerance of sample count and total count comparisons, as in edgeR.
+const TOLERANCE: f64 = 1e-14;
+
+/// Options for filtering features by expression.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterByExpressionOptions {
+    /// The minimum count for a sample with a median library size.
+    pub min_count: f64,
+    /// The minimum total count over all samples.
+    pub min_total_count: f64,
+    /// The number of samples per group above which the minimum proportion applies.
+    pub large_n: usize,
+    /// The minimum proportion of samples in the smallest group above `large_n`.
+    pub min_prop: f64,
+}
+
+impl Default for FilterByExpressionOptions {
+    fn default() -> Self {
+        Self {
+            min_count: 10.0,
+            min_total_count: 15.0,
+            large_n: 10,
+            min_prop: 0.7,
+        }
+    }
+}
+
+/// Returns the indices of features with sufficiently large counts to be retained in a statistical
+/// analysis.
+///
+/// This follows `filterByExpr` in [edgeR]. A feature is kept if it has a CPM of at least the
+/// equivalent of `min_count` in at least as many samples as the smallest group, and its total
+/// count is at least `min_total_count`. The minimum number of samples is reduced for large groups.
+///
+/// `counts` is a row-major samples × features matrix, and `groups` is the group of each sample.
+/// Without an experimental design, use the same group for all samples.
+///
+/// The returned indices are in ascending order.
+///
+/// [edgeR]: https://bioconductor.org/packages/release/bioc/html/edgeR.html
+pub fn filter_by_expression<T, G>(
+    counts: &[T],
+    feature_count: usize,
+    groups: &[G],
+    options: &FilterByExpressionOptions,
+) -> io::Result<Vec<usize>>
+where
+    T: Copy,
+    f64: From<T>,
+    G: Eq + Hash,
+{
+    if feature_count == 0 || !counts.len().is_multiple_of(feature_count) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "counts length is not a multiple of the feature count",
+        ));
+    }
+
+    let sample_count = counts.len() / feature_count;
+
+    if groups.len() != sample_count {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "groups length mismatch: expected {sample_count}, got {}",
+                groups.len()
+            ),
+        ));
+    }
+
+    let Some(mut min_sample_count) = min_group_size(groups).map(|n| n as f64) else {
+        return Ok(Vec::new());
+    };
+
+    let large_n = options.large_n as f64;
+
+    if min_sample_count > large_n {
+        min_sample_count = large_n + (min_sample_count - large_n) * options.min_prop;
+    }
+
+    let library_sizes: Vec<f64> = counts
+        .chunks_exact(feature_count)
+        .map(|row| row.iter().copied().map(f64::from).sum())
+        .collect();
+
+    let median_library_size = median(&library_sizes);
+    let cpm_cutoff = options.min_count / median_library_size * LIBRARY_SIZE_SCALE;
+
+    let mut expressed_sample_counts = vec![0usize; feature_count];
+    let mut total_counts = vec![0.0; feature_count];
+
+    for (row, &library_size) in counts.chunks_exact(feature_count).zip(&library_sizes) {
+        for (j, &n) in row.iter().enumerate() {
+            let n = f64::from(n);
+
+            total_counts[j] += n;
+
+            if library_size > 0.0 && n * LIBRARY_SIZE_SCALE / library_size >= cpm_cutoff {
+                expressed_sample_counts[j] += 1;
+            }
+        }
+    }
+
+    Ok(expressed_sample_counts
+        .into_iter()
+        .zip(total_counts)
+        .enumerate()
+        .filter(|(_, (expressed_sample_count, total_count))| {
+            *expressed_sample_count as f64 >= min_sample_count - TOLERANCE
+                && *total_count >= options.min_total_count - TOLERANCE
+        })
+        .map(|(j, _)| j)
+        .collect())
+}
+
+/// Returns a copy of a row-major samples × features matrix with only the given features.
+pub fn retain_features<T>(counts: &[T], feature_count: usize, indices: &[usize]) -> Vec<T>
+where
+    T: Copy,
+{
+    counts
+        .chunks_exact(feature_count)
+        .flat_map(|row| indices.iter().map(|&j| row[j]))
+        .collect()
+}
+
+/// Per feature summary statistics.
+#[derive(Debug, PartialEq)]
+pub struct FeatureStatistics {
+    /// The mean of each feature.
+    pub means: Vec<f64>,
+    /// The unbiased sample variance of each feature.
+    pub variances: Vec<f64>,
+}
+
+/// Computes the mean and variance of each feature (column) of a samples × features matrix.
+///
+/// Variances are zero when there are fewer than 2 samples.
+pub fn feature_statistics(data: MatRef<'_, f64>) -> FeatureStatistics {
+    let sample_count = data.nrows();
+    let n = sample_count as f64;
+
+    let means: Vec<_> = (0..data.ncols())
+        .map(|j| {
+            if sample_count == 0 {
+                0.0
+            } else {
+                data.col(j).iter().sum::<f64>() / n
+            }
+        })
+        .collect();
+
+    let variances = means
+        .iter()
+        .enumerate()
+        .map(|(j, mean)| {
+            if sample_count < 2 {
+                0.0
+            } else {
+                data.col(j).iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)
+            }
+        })
+        .collect();
+
+    FeatureStatistics { means, variances }
+}
+
+/// Returns the indices of the `count` features with the highest variance across samples.
+///
+/// Ties are broken by feature index. The returned indices are in ascending order.
+pub fn select_variable_features(data: MatRef<'_, f64>, count: usize) -> Vec<usize> {
+    let FeatureStatistics { variances, .. } = feature_statistics(data);
+
+    let mut indices: Vec<_> = (0..variances.len()).collect();
+    indices.sort_by(|&a, &b| variances[b].total_cmp(&variances[a]).then(a.cmp(&b)));
+    indices.truncate(count);
+    indices.sort_unstable();
+
+    indices
+}
+
+/// Returns a copy of `data` with only the given feature columns.
+pub fn select_features(data: MatRef<'_, f64>, indices: &[usize]) -> Mat<f64> {
+    Mat::from_fn(data.nrows(), indices.len(), |i, j| data[(i, indices[j])])
+}
+
+fn min_group_size<G>(groups: &[G]) -> Option<usize>
+where
+    G: Eq + Hash,
+{
+    let mut sizes: HashMap<&G, usize> = HashMap::new();
+
+    for group in groups {
+        *sizes.entry(group).or_default() += 1;
+    }
+
+    sizes.into_values().min()
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut values = values.to_vec();
+    values.sort_unstable_by(|a, b| a.total_cmp(b));
+
+    let n = values.len();
+
+    if n == 0 {
+        0.0
+    } else if n.is_multiple_of(2) {
+        (values[n / 2 - 1] + values[n / 2]) / 2.0
+    } else {
+        values[n / 2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use faer::mat;
+
+    use super::*;
+
+    #[test]
+    fn test_filter_by_expression() -> io::Result<()> {
+        // Library sizes are 1e6, so the CPM cutoff is 10.
+        let counts = [
+            999_970, 10, 0, 20, 0, //
+            999_968, 12, 0, 20, 0, //
+            999_959, 9, 16, 0, 16, //
+            999_958, 11, 15, 0, 16, //
+        ];
+
+        let options = FilterByExpressionOptions::default();
+
+        // All samples in one group require a CPM >= 10 in all 4 samples.
+        let actual = filter_by_expression(&counts, 5, &[0, 0, 0, 0], &options)?;
+        assert_eq!(actual, [0]);
+
+        // With 2 groups of 2 samples, 2 samples suffice, but the total count must be >= 15.
+        let actual = filter_by_expression(&counts, 5, &["a", "a", "b", "b"], &options)?;
+        assert_eq!(actual, [0, 1, 2, 3, 4]);
+
+        let options = FilterByExpressionOptions {
+            min_total_count: 35.0,
+            ..Default::default()
+        };
+
+        let actual = filter_by_expression(&counts, 5, &["a", "a", "b", "b"], &options)?;
+        assert_eq!(actual, [0, 1, 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_by_expression_with_large_groups() -> io::Result<()> {
+        // 20 samples require 10 + (20 - 10) * 0.7 = 17 expressed samples.
+        let mut counts = Vec::new();
+
+        for i in 0..20 {
+            let n = if i < 17 { 100 } else { 0 };
+            let m = if i < 16 { 100 } else { 0 };
+            counts.extend([1_000_000 - n - m, n, m]);
+        }
+
+        let actual = filter_by_expression(&counts, 3, &[(); 20], &Default::default())?;
+        assert_eq!(actual, [0, 1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_by_expression_with_invalid_input() {
+        let options = FilterByExpressionOptions::default();
+
+        assert!(matches!(
+            filter_by_expression(&[1, 2, 3], 2, &[0], &options),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+
+        assert!(matches!(
+            filter_by_expression(&[1, 2, 3, 4], 2, &[0], &options),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+    }
+
+    #[test]
+    fn test_retain_features() {
+        let counts = [0, 1, 2, 3, 4, 5];
+        assert_eq!(retain_features(&counts, 3, &[0, 2]), [0, 2, 3, 5]);
+        assert!(retain_features(&counts, 3, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_feature_statistics() {
+        let data = mat![[1.0, 2.0], [3.0, 2.0], [5.0, 2.0]];
+
+        assert_eq!(
+            feature_statistics(data.as_ref()),
+            FeatureStatistics {
+                means: vec![3.0, 2.0],
+                variances: vec![4.0, 0.0],
+            }
+        );
+
+        let data = mat![[1.0, 2.0]];
+
+        assert_eq!(
+            feature_statistics(data.as_ref()),
+            FeatureStatistics {
+                means: vec![1.0, 2.0],
+                variances: vec![0.0, 0.0],
+            }
+        );
+    }
+
+    #[test]
+    fn test_select_variable_features() {
+        let data = mat![[0.0, 5.0, 1.0, 2.0], [0.0, 1.0, 3.0, 4.0]];
+
+        assert_eq!(select_variable_features(data.as_ref(), 1), [1]);
+        assert_eq!(select_variable_features(data.as_ref(), 2), [1, 2]);
+        assert_eq!(select_variable_features(data.as_ref(), 3), [1, 2, 3]);
+        assert_eq!(select_variable_features(data.as_ref(), 8), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_select_features() {
+        let data = mat![[0.0, 5.0, 1.0], [2.0, 1.0, 3.0]];
+        let actual = select_features(data.as_ref(), &[0, 2]);
+        let expected = mat![[0.0, 1.0], [2.0, 3.0]];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[]), 0.0);
+        assert_eq!(median(&[3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&[4.0, 1.0, 3.0, 2.0]), 2.5);
+    }
+}
@@ -2,3 +2,361 @@ pub mod fpkm;
 pub mod median_of_ratios;
 pub mod tmm;
 pub mod tpm;
+
+use ndarray::Array2;
+use thiserror::Error;
+
+/// A normalization method.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Method {
+    /// Counts per million (CPM) mapped reads.
+    Cpm,
+    /// Fragments per kilobase per million (FPKM) mapped reads.
+    Fpkm,
+    /// Median of ratios.
+    MedianOfRatios,
+    /// Trimmed mean of M-values (TMM).
+    Tmm,
+    /// Transcripts per million (TPM) mapped reads.
+    Tpm,
+    /// Variance stabilizing transformation (VST) of median of ratios normalized counts.
+    Vst,
+}
+
+impl Method {
+    /// Returns whether the method uses feature lengths.
+    pub fn uses_feature_lengths(self) -> bool {
+        matches!(self, Self::Fpkm | Self::Tpm)
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum NormalizeError {
+    #[error("counts are empty")]
+    Empty,
+    #[error("invalid shape: expected {expected} counts, got {actual}")]
+    InvalidShape { expected: usize, actual: usize },
+    #[error("invalid feature lengths: expected {expected} lengths, got {actual}")]
+    InvalidFeatureLengthCount { expected: usize, actual: usize },
+    #[error("invalid feature length: feature {0} has a length of 0")]
+    ZeroFeatureLength(usize),
+    #[error("{method:?} requires at least {min} samples")]
+    TooFewSamples { method: Method, min: usize },
+    #[error("{0:?} factors are undefined for samples without counts")]
+    UndefinedFactors(Method),
+}
+
+/// Per-sample factors to normalize counts, calculated over a set of samples.
+///
+/// Factors can be stored and reapplied to any subset of the counts they were calculated from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Factors {
+    method: Method,
+    values: Vec<f64>,
+    dispersion: Option<f64>,
+}
+
+impl Factors {
+    /// Calculates the factors of a row-major samples × features count matrix.
+    ///
+    /// Feature lengths are only used by FPKM and TPM and may otherwise be empty.
+    pub fn calculate(
+        method: Method,
+        sample_count: usize,
+        feature_count: usize,
+        counts: &[u32],
+        feature_lengths: &[u32],
+    ) -> Result<Self, NormalizeError> {
+        validate(method, sample_count, feature_count, counts, feature_lengths)?;
+
+        let rows = counts.chunks_exact(feature_count);
+
+        let (values, dispersion) = match method {
+            Method::Cpm | Method::Fpkm => {
+                let library_sizes = rows
+                    .map(|row| row.iter().copied().map(f64::from).sum())
+                    .collect();
+
+                (library_sizes, None)
+            }
+            Method::Tpm => {
+                let sums = rows
+                    .map(|row| {
+                        row.iter()
+                            .zip(feature_lengths)
+                            .map(|(&n, &length)| f64::from(n) / f64::from(length))
+                            .sum()
+                    })
+                    .collect();
+
+                (sums, None)
+            }
+            Method::MedianOfRatios => {
+                let data = to_array(sample_count, feature_count, counts);
+                let size_factors = median_of_ratios::calculate_size_factors(&data);
+                (size_factors.to_vec(), None)
+            }
+            Method::Tmm => {
+                if rows.clone().any(|row| row.iter().all(|&n| n == 0)) {
+                    return Err(NormalizeError::UndefinedFactors(method));
+                }
+
+                let data = to_array(sample_count, feature_count, counts);
+                let scaling_factors = tmm::calculate_scaling_factors_centered(&data);
+
+                if !scaling_factors.iter().all(|n| n.is_finite()) {
+                    return Err(NormalizeError::UndefinedFactors(method));
+                }
+
+                (scaling_factors, None)
+            }
+            Method::Vst => {
+                use faer::MatRef;
+
+                use super::transforms::vst;
+
+                if sample_count < 2 {
+                    return Err(NormalizeError::TooFewSamples { method, min: 2 });
+                }
+
+                let data: Vec<_> = counts.iter().copied().map(f64::from).collect();
+                let counts =
+                    MatRef::from_row_major_slice(&data, sample_count, feature_count).transpose();
+
+                let size_factors = vst::calculate_size_factors(counts);
+                let dispersion = vst::estimate_dispersion(counts, &size_factors);
+
+                (size_factors, Some(dispersion))
+            }
+        };
+
+        Ok(Self {
+            method,
+            values,
+            dispersion,
+        })
+    }
+
+    /// Creates factors from previously calculated values.
+    pub fn from_parts(method: Method, values: Vec<f64>, dispersion: Option<f64>) -> Self {
+        Self {
+            method,
+            values,
+            dispersion,
+        }
+    }
+
+    /// Returns the normalization method the factors were calculated with.
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    /// Returns the factor of each sample.
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Returns the dispersion used by VST.
+    pub fn dispersion(&self) -> Option<f64> {
+        self.dispersion
+    }
+
+    /// Normalizes the count of a feature in the sample at index `i`.
+    ///
+    /// The feature length must be nonzero if the method uses feature lengths.
+    pub fn apply(&self, i: usize, count: u32, feature_length: u32) -> f64 {
+        let n = f64::from(count);
+        let factor = self.values[i];
+
+        match self.method {
+            // Samples with a library size of 0 only have counts of 0.
+            Method::Cpm | Method::Fpkm | Method::Tpm if factor == 0.0 => 0.0,
+            Method::Cpm => n * 1e6 / factor,
+            Method::Fpkm => n * 1e9 / (f64::from(feature_length) * factor),
+            Method::Tpm => n / f64::from(feature_length) * 1e6 / factor,
+            Method::MedianOfRatios => n / factor,
+            Method::Tmm => n * factor,
+            Method::Vst => {
+                use super::transforms::vst;
+
+                // SAFETY: VST factors always have a dispersion.
+                let dispersion = self.dispersion.unwrap();
+                vst::stabilize(n / factor, dispersion)
+            }
+        }
+    }
+}
+
+/// Normalizes a row-major samples × features count matrix.
+///
+/// Feature lengths are only used by FPKM and TPM and may otherwise be empty.
+pub fn normalize(
+    method: Method,
+    sample_count: usize,
+    feature_count: usize,
+    counts: &[u32],
+    feature_lengths: &[u32],
+) -> Result<Vec<f64>, NormalizeError> {
+    let factors = Factors::calculate(method, sample_count, feature_count, counts, feature_lengths)?;
+
+    let normalized_counts = counts
+        .chunks_exact(feature_count)
+        .enumerate()
+        .flat_map(|(i, row)| {
+            let factors = &factors;
+
+            row.iter().enumerate().map(move |(j, &n)| {
+                let length = feature_lengths.get(j).copied().unwrap_or_default();
+                factors.apply(i, n, length)
+            })
+        })
+        .collect();
+
+    Ok(normalized_counts)
+}
+
+fn validate(
+    method: Method,
+    sample_count: usize,
+    feature_count: usize,
+    counts: &[u32],
+    feature_lengths: &[u32],
+) -> Result<(), NormalizeError> {
+    if sample_count == 0 || feature_count == 0 {
+        return Err(NormalizeError::Empty);
+    }
+
+    let expected = sample_count * feature_count;
+
+    if counts.len() != expected {
+        return Err(NormalizeError::InvalidShape {
+            expected,
+            actual: counts.len(),
+        });
+    }
+
+    if method.uses_feature_lengths() {
+        if feature_lengths.len() != feature_count {
+            return Err(NormalizeError::InvalidFeatureLengthCount {
+                expected: feature_count,
+                actual: feature_lengths.len(),
+            });
+        }
+
+        if let Some(i) = feature_lengths.iter().position(|&length| length == 0) {
+            return Err(NormalizeError::ZeroFeatureLength(i));
+        }
+    }
+
+    Ok(())
+}
+
+fn to_array(sample_count: usize, feature_count: usize, counts: &[u32]) -> Array2<u32> {
+    // SAFETY: The shape is validated.
+    Array2::from_shape_vec((sample_count, feature_count), counts.to_vec()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COUNTS: [u32; 6] = [0, 8, 13, 21, 34, 55];
+    const FEATURE_LENGTHS: [u32; 3] = [5, 8, 13];
+
+    fn assert_approx_eq(a: &[f64], b: &[f64]) {
+        const EPSILON: f64 = 1e-9;
+
+        assert_eq!(a.len(), b.len());
+
+        for (n, m) in a.iter().zip(b) {
+            assert!((n - m).abs() < EPSILON, "{n} != {m}");
+        }
+    }
+
+    #[test]
+    fn test_normalize() -> Result<(), NormalizeError> {
+        let actual = normalize(Method::Cpm, 2, 3, &COUNTS, &[])?;
+        assert_approx_eq(&actual[..3], &[0.0, 8e6 / 21.0, 13e6 / 21.0]);
+
+        for (method, f) in [
+            (
+                Method::Fpkm,
+                fpkm::normalize as fn(&[u32], &[u32]) -> Vec<f64>,
+            ),
+            (Method::Tpm, tpm::normalize),
+        ] {
+            let actual = normalize(method, 2, 3, &COUNTS, &FEATURE_LENGTHS)?;
+            let expected: Vec<_> = COUNTS
+                .chunks_exact(3)
+                .flat_map(|row| f(&FEATURE_LENGTHS, row))
+                .collect();
+            assert_approx_eq(&actual, &expected);
+        }
+
+        let data = to_array(2, 3, &COUNTS);
+
+        let actual = normalize(Method::MedianOfRatios, 2, 3, &COUNTS, &[])?;
+        let expected = median_of_ratios::normalize(data);
+        assert_approx_eq(&actual, expected.as_slice().unwrap());
+
+        // TMM trims 30% of log ratios from each end, so it needs more features.
+        let counts: Vec<_> = (1..=20).map(|n| n * n).collect();
+        let actual = normalize(Method::Tmm, 2, 10, &counts, &[])?;
+        let expected = tmm::normalize(to_array(2, 10, &counts));
+        assert_approx_eq(&actual, expected.as_slice().unwrap());
+
+        let actual = normalize(Method::Vst, 2, 3, &COUNTS, &[])?;
+        assert!(actual.iter().all(|n| n.is_finite()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize_with_invalid_input() {
+        assert_eq!(
+            normalize(Method::Cpm, 0, 3, &[], &[]),
+            Err(NormalizeError::Empty)
+        );
+
+        assert_eq!(
+            normalize(Method::Cpm, 2, 3, &COUNTS[..5], &[]),
+            Err(NormalizeError::InvalidShape {
+                expected: 6,
+                actual: 5
+            })
+        );
+
+        assert_eq!(
+            normalize(Method::Fpkm, 2, 3, &COUNTS, &[]),
+            Err(NormalizeError::InvalidFeatureLengthCount {
+                expected: 3,
+                actual: 0
+            })
+        );
+
+        assert_eq!(
+            normalize(Method::Tpm, 2, 3, &COUNTS, &[5, 0, 13]),
+            Err(NormalizeError::ZeroFeatureLength(1))
+        );
+
+        assert_eq!(
+            normalize(Method::Vst, 1, 3, &COUNTS[..3], &[]),
+            Err(NormalizeError::TooFewSamples {
+                method: Method::Vst,
+                min: 2
+            })
+        );
+
+        assert_eq!(
+            normalize(Method::Tmm, 2, 3, &[0, 0, 0, 21, 34, 55], &[]),
+            Err(NormalizeError::UndefinedFactors(Method::Tmm))
+        );
+    }
+
+    #[test]
+    fn test_apply_with_an_empty_sample() -> Result<(), NormalizeError> {
+        let factors = Factors::calculate(Method::Fpkm, 1, 3, &[0, 0, 0], &FEATURE_LENGTHS)?;
+        assert_eq!(factors.apply(0, 0, 5), 0.0);
+        Ok(())
+    }
+}
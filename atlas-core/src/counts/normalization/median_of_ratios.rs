@@ -1,6 +1,6 @@
 use std::io;
 
-use ndarray::{Array2, Axis, Zip};
+use ndarray::{Array1, Array2, Axis, Zip};
 
 pub fn normalize_vec(
     sample_count: usize,
@@ -19,6 +19,22 @@ pub fn normalize_vec(
 }
 
 pub fn normalize(data: Array2<u32>) -> Array2<f64> {
+    let size_factors = calculate_size_factors(&data);
+
+    let mut normalized_data = data.mapv(|n| n as f64);
+
+    // Normalize counts by size factors.
+    Zip::from(normalized_data.rows_mut())
+        .and(&size_factors)
+        .for_each(|mut row, &size_factor| {
+            row /= size_factor;
+        });
+
+    normalized_data
+}
+
+/// Calculates the size factor of each sample in a samples × features matrix.
+pub fn calculate_size_factors(data: &Array2<u32>) -> Array1<f64> {
     use std::f64::consts::E;
 
     assert!(!data.is_empty());
@@ -53,7 +69,8 @@ pub fn normalize(data: Array2<u32>) -> Array2<f64> {
             .collect();
 
         if values.is_empty() {
-            1.0
+            // ln(1)
+            0.0
         } else {
             // SAFETY: All values are finite.
             values.sort_by(|a, b| a.partial_cmp(b).unwrap());
@@ -66,16 +83,7 @@ pub fn normalize(data: Array2<u32>) -> Array2<f64> {
     // Log to normal: e^n.
     medians.mapv_inplace(|n| E.powf(n));
 
-    let mut normalized_data = data.mapv(|n| n as f64);
-
-    // Normalize counts by medians.
-    Zip::from(normalized_data.rows_mut())
-        .and(&medians)
-        .for_each(|mut row, &median| {
-            row /= median;
-        });
-
-    normalized_data
+    medians
 }
 
 // `values` must be non-empty and sorted.
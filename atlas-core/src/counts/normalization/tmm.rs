@@ -30,8 +30,24 @@ pub fn normalize_vec(
 }
 
 pub fn normalize(data: Array2<u32>) -> Array2<f64> {
+    let scaling_factors = calculate_scaling_factors_centered(&data);
+
     let mut data = data.mapv(f64::from);
-    let mut normalized_data = data.clone();
+
+    info!("scaling counts");
+
+    for (mut row, &scaling_factor) in data.rows_mut().into_iter().zip(&scaling_factors) {
+        row *= scaling_factor;
+    }
+
+    data
+}
+
+/// Calculates the centered scaling factor of each sample in a samples × features matrix.
+///
+/// Each sample must have a nonzero library size.
+pub fn calculate_scaling_factors_centered(data: &Array2<u32>) -> Vec<f64> {
+    let mut normalized_data = data.mapv(f64::from);
 
     info!("normalizing counts");
     normalize_rows(normalized_data.view_mut());
@@ -46,13 +62,7 @@ pub fn normalize(data: Array2<u32>) -> Array2<f64> {
     info!("centering scaling factors");
     center(&mut scaling_factors);
 
-    info!("scaling counts");
-
-    for (mut row, &scaling_factor) in data.rows_mut().into_iter().zip(&scaling_factors) {
-        row *= scaling_factor;
-    }
-
-    data
+    scaling_factors
 }
 
 fn normalize_rows(mut data: ArrayViewMut2<'_, f64>) {
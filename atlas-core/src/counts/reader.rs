@@ -69,6 +69,29 @@ where
     }
 }
 
+/// Reads real-valued counts, e.g., expected counts or TPMs.
+///
+/// The input has the same layout as htseq-count output: tab-separated feature names and values.
+/// Values must be finite and nonnegative.
+pub fn read_real<R>(reader: &mut R) -> io::Result<Vec<(String, f64)>>
+where
+    R: BufRead,
+{
+    let counts: Vec<(String, f64)> = htseq_count::read(reader)?;
+
+    if let Some((name, value)) = counts
+        .iter()
+        .find(|(_, value)| !value.is_finite() || *value < 0.0)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid value: {name}: {value}"),
+        ));
+    }
+
+    Ok(counts)
+}
+
 fn detect_format<R>(reader: &mut R) -> io::Result<Format>
 where
     R: BufRead,
@@ -126,6 +149,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_real() -> io::Result<()> {
+        let mut src = &b"f0\t8.25\nf1\t0\n__no_feature\t0\n"[..];
+        let counts = read_real(&mut src)?;
+        assert_eq!(
+            counts,
+            [(String::from("f0"), 8.25), (String::from("f1"), 0.0)]
+        );
+
+        for data in [&b"f0\t-1.5\n"[..], b"f0\tNaN\n", b"f0\tinf\n"] {
+            let mut src = data;
+            assert!(matches!(
+                read_real(&mut src),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData
+            ));
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_read_line() -> io::Result<()> {
         fn t(buf: &mut String, mut data: &[u8], expected: &str) -> io::Result<()> {
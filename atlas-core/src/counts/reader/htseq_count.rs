@@ -1,12 +1,18 @@
-use std::io::{self, BufRead};
+use std::{
+    error::Error,
+    io::{self, BufRead},
+    str::FromStr,
+};
 
 use super::read_line;
 
 const HTSEQ_COUNT_META_PREFIX: &str = "__";
 
-pub(super) fn read<R>(reader: &mut R) -> io::Result<Vec<(String, u32)>>
+pub(super) fn read<R, T>(reader: &mut R) -> io::Result<Vec<(String, T)>>
 where
     R: BufRead,
+    T: FromStr,
+    T::Err: Into<Box<dyn Error + Send + Sync>>,
 {
     let mut line = String::new();
     let mut counts = Vec::new();
@@ -69,7 +75,11 @@ where
     Ok(())
 }
 
-fn parse_line(s: &str) -> io::Result<(&str, u32)> {
+fn parse_line<T>(s: &str) -> io::Result<(&str, T)>
+where
+    T: FromStr,
+    T::Err: Into<Box<dyn Error + Send + Sync>>,
+{
     const DELIMITER: char = '\t';
 
     let (name, raw_count) = s
@@ -92,8 +102,15 @@ mod tests {
         let data = b"f0\t8\nf1\t13\n__no_feature\t0\nf2\t21\n";
         let mut reader = &data[..];
         let actual = read(&mut reader)?;
-        let expected = [(String::from("f0"), 8), (String::from("f1"), 13)];
+        let expected = [(String::from("f0"), 8u32), (String::from("f1"), 13)];
         assert_eq!(actual, expected);
+
+        let data = b"f0\t8.5\nf1\t13\n";
+        let mut reader = &data[..];
+        let actual = read(&mut reader)?;
+        let expected = [(String::from("f0"), 8.5f64), (String::from("f1"), 13.0)];
+        assert_eq!(actual, expected);
+
         Ok(())
     }
 
@@ -113,15 +130,15 @@ mod tests {
 
     #[test]
     fn test_parse_line() -> io::Result<()> {
-        assert_eq!(parse_line("f0\t8")?, ("f0", 8));
+        assert_eq!(parse_line("f0\t8")?, ("f0", 8u32));
 
         assert!(matches!(
-            parse_line("f0 13"),
+            parse_line::<u32>("f0 13"),
             Err(e) if e.kind() == io::ErrorKind::InvalidData
         ));
 
         assert!(matches!(
-            parse_line("f0 13"),
+            parse_line::<u32>("f0 13"),
             Err(e) if e.kind() == io::ErrorKind::InvalidData
         ));
 
@@ -0,0 +1,207 @@
+//! Sample-sample similarity and hierarchical clustering.
+
+mod clustering;
+
+use faer::{Mat, MatRef};
+
+pub use self::clustering::average_linkage_order;
+
+/// A measure of similarity or dissimilarity between samples.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Metric {
+    /// The Pearson correlation coefficient.
+    Pearson,
+    /// The Spearman rank correlation coefficient.
+    Spearman,
+    /// The Euclidean distance.
+    Euclidean,
+}
+
+impl Metric {
+    /// Returns whether larger values are more similar.
+    pub fn is_correlation(self) -> bool {
+        matches!(self, Self::Pearson | Self::Spearman)
+    }
+}
+
+/// Calculates the symmetric samples × samples matrix of a samples × features matrix.
+///
+/// Correlations with a sample with constant values are NaN.
+pub fn matrix(data: MatRef<'_, f64>, metric: Metric) -> Mat<f64> {
+    match metric {
+        Metric::Pearson => correlation(data),
+        Metric::Spearman => {
+            let mut ranks = Mat::zeros(data.nrows(), data.ncols());
+
+            for i in 0..data.nrows() {
+                let values: Vec<_> = (0..data.ncols()).map(|j| data[(i, j)]).collect();
+
+                for (j, rank) in rank(&values).into_iter().enumerate() {
+                    ranks[(i, j)] = rank;
+                }
+            }
+
+            correlation(ranks.as_ref())
+        }
+        Metric::Euclidean => {
+            let n = data.nrows();
+            let mut distances = Mat::zeros(n, n);
+
+            for i in 0..n {
+                for j in (i + 1)..n {
+                    let d = (0..data.ncols())
+                        .map(|k| (data[(i, k)] - data[(j, k)]).powi(2))
+                        .sum::<f64>()
+                        .sqrt();
+
+                    distances[(i, j)] = d;
+                    distances[(j, i)] = d;
+                }
+            }
+
+            distances
+        }
+    }
+}
+
+/// Returns the order of samples given by average linkage hierarchical clustering of a matrix
+/// calculated by [`matrix`].
+///
+/// Correlations `r` are clustered by the distance `1 - r`.
+pub fn cluster(matrix: MatRef<'_, f64>, metric: Metric) -> Vec<usize> {
+    if metric.is_correlation() {
+        let distances = Mat::from_fn(matrix.nrows(), matrix.ncols(), |i, j| 1.0 - matrix[(i, j)]);
+        average_linkage_order(distances.as_ref())
+    } else {
+        average_linkage_order(matrix)
+    }
+}
+
+fn correlation(data: MatRef<'_, f64>) -> Mat<f64> {
+    let (n, m) = (data.nrows(), data.ncols());
+
+    let centered: Vec<Vec<f64>> = (0..n)
+        .map(|i| {
+            let mean = (0..m).map(|j| data[(i, j)]).sum::<f64>() / m as f64;
+            (0..m).map(|j| data[(i, j)] - mean).collect()
+        })
+        .collect();
+
+    let norms: Vec<f64> = centered
+        .iter()
+        .map(|row| row.iter().map(|x| x * x).sum::<f64>().sqrt())
+        .collect();
+
+    let mut correlations = Mat::zeros(n, n);
+
+    for i in 0..n {
+        correlations[(i, i)] = if norms[i] > 0.0 { 1.0 } else { f64::NAN };
+
+        for j in (i + 1)..n {
+            let r = if norms[i] > 0.0 && norms[j] > 0.0 {
+                let dot: f64 = centered[i]
+                    .iter()
+                    .zip(&centered[j])
+                    .map(|(a, b)| a * b)
+                    .sum();
+                (dot / (norms[i] * norms[j])).clamp(-1.0, 1.0)
+            } else {
+                f64::NAN
+            };
+
+            correlations[(i, j)] = r;
+            correlations[(j, i)] = r;
+        }
+    }
+
+    correlations
+}
+
+// Returns the 1-based ranks of values, using the average rank of ties.
+fn rank(values: &[f64]) -> Vec<f64> {
+    let mut indices: Vec<_> = (0..values.len()).collect();
+    indices.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+
+    while start < indices.len() {
+        let mut end = start + 1;
+
+        while end < indices.len() && values[indices[end]] == values[indices[start]] {
+            end += 1;
+        }
+
+        let rank = (start + end + 1) as f64 / 2.0;
+
+        for &i in &indices[start..end] {
+            ranks[i] = rank;
+        }
+
+        start = end;
+    }
+
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: f64, b: f64) {
+        const EPSILON: f64 = 1e-9;
+        assert!((a - b).abs() < EPSILON, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_rank() {
+        assert_eq!(rank(&[3.0, 1.0, 4.0, 1.0, 5.0]), [3.0, 1.5, 4.0, 1.5, 5.0]);
+    }
+
+    #[test]
+    fn test_matrix() {
+        let data = [
+            1.0, 2.0, 3.0, 4.0, //
+            2.0, 4.0, 6.0, 8.0, //
+            1.0, 4.0, 9.0, 16.0, //
+            4.0, 3.0, 2.0, 1.0, //
+            5.0, 5.0, 5.0, 5.0, //
+        ];
+        let data = MatRef::from_row_major_slice(&data, 5, 4);
+
+        let pearson = matrix(data, Metric::Pearson);
+        assert_approx_eq(pearson[(0, 0)], 1.0);
+        assert_approx_eq(pearson[(0, 1)], 1.0);
+        assert_approx_eq(pearson[(0, 2)], 0.984_374_038_697_318_9);
+        assert_approx_eq(pearson[(2, 0)], pearson[(0, 2)]);
+        assert_approx_eq(pearson[(0, 3)], -1.0);
+        assert!(pearson[(0, 4)].is_nan());
+        assert!(pearson[(4, 4)].is_nan());
+
+        let spearman = matrix(data, Metric::Spearman);
+        assert_approx_eq(spearman[(0, 2)], 1.0);
+        assert_approx_eq(spearman[(0, 3)], -1.0);
+
+        let euclidean = matrix(data, Metric::Euclidean);
+        assert_approx_eq(euclidean[(0, 0)], 0.0);
+        assert_approx_eq(euclidean[(0, 1)], 30.0f64.sqrt());
+        assert_approx_eq(euclidean[(1, 0)], 30.0f64.sqrt());
+    }
+
+    #[test]
+    fn test_cluster() {
+        let data = [
+            1.0, 2.0, 3.0, 4.0, //
+            4.0, 3.0, 2.0, 1.0, //
+            1.0, 2.0, 3.0, 5.0, //
+            5.0, 3.0, 2.0, 1.0, //
+        ];
+        let data = MatRef::from_row_major_slice(&data, 4, 4);
+        let correlations = matrix(data, Metric::Pearson);
+
+        assert_eq!(
+            cluster(correlations.as_ref(), Metric::Pearson),
+            [0, 2, 1, 3]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use faer::MatRef;
+
+/// Returns the leaf order of an average linkage (UPGMA) hierarchical clustering of a symmetric
+/// distance matrix.
+///
+/// Clusters are merged using the nearest-neighbor chain algorithm. In each merge, the cluster
+/// containing the smaller index is placed first. NaN distances are treated as infinite.
+pub fn average_linkage_order(distances: MatRef<'_, f64>) -> Vec<usize> {
+    let n = distances.nrows();
+    assert_eq!(distances.ncols(), n);
+
+    let mut d: Vec<Vec<f64>> = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| {
+                    let value = distances[(i, j)];
+                    if value.is_nan() { f64::INFINITY } else { value }
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut is_active = vec![true; n];
+    let mut members: Vec<Vec<usize>> = (0..n).map(|i| vec![i]).collect();
+    let mut chain: Vec<usize> = Vec::with_capacity(n);
+    let mut remaining = n;
+
+    while remaining > 1 {
+        if chain.is_empty() {
+            // SAFETY: At least 2 clusters are active.
+            chain.push(is_active.iter().position(|&active| active).unwrap());
+        }
+
+        let (a, b) = loop {
+            // SAFETY: `chain` is non-empty.
+            let a = *chain.last().unwrap();
+            let previous = chain.len().checked_sub(2).map(|i| chain[i]);
+
+            // Prefer the previous cluster on ties so that the chain terminates.
+            let mut nearest = previous;
+            let mut min_distance = previous.map(|p| d[a][p]).unwrap_or(f64::INFINITY);
+
+            for c in (0..n).filter(|&c| c != a && is_active[c]) {
+                if nearest.is_none() || d[a][c] < min_distance {
+                    nearest = Some(c);
+                    min_distance = d[a][c];
+                }
+            }
+
+            // SAFETY: At least 2 clusters are active.
+            let b = nearest.unwrap();
+
+            if Some(b) == previous {
+                chain.truncate(chain.len() - 2);
+                break (a, b);
+            }
+
+            chain.push(b);
+        };
+
+        let (size_a, size_b) = (members[a].len() as f64, members[b].len() as f64);
+
+        for k in (0..n).filter(|&k| k != a && k != b && is_active[k]) {
+            let distance = (size_a * d[a][k] + size_b * d[b][k]) / (size_a + size_b);
+            d[a][k] = distance;
+            d[k][a] = distance;
+        }
+
+        // The first leaf of a cluster is its smallest index.
+        let mut merged = std::mem::take(&mut members[b]);
+
+        if members[a][0] < merged[0] {
+            members[a].append(&mut merged);
+        } else {
+            merged.append(&mut members[a]);
+            members[a] = merged;
+        }
+
+        is_active[b] = false;
+        remaining -= 1;
+    }
+
+    is_active
+        .iter()
+        .position(|&active| active)
+        .map(|i| std::mem::take(&mut members[i]))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_linkage_order() {
+        let distances = [
+            0.0, 9.0, 1.0, 8.0, //
+            9.0, 0.0, 8.0, 2.0, //
+            1.0, 8.0, 0.0, 7.0, //
+            8.0, 2.0, 7.0, 0.0, //
+        ];
+        let distances = MatRef::from_row_major_slice(&distances, 4, 4);
+        assert_eq!(average_linkage_order(distances), [0, 2, 1, 3]);
+
+        let distances = [
+            0.0,
+            f64::NAN,
+            f64::NAN,
+            0.0, //
+        ];
+        let distances = MatRef::from_row_major_slice(&distances, 2, 2);
+        assert_eq!(average_linkage_order(distances), [0, 1]);
+
+        let distances = MatRef::from_row_major_slice(&[] as &[f64], 0, 0);
+        assert!(average_linkage_order(distances).is_empty());
+    }
+}
@@ -1,15 +1,78 @@
+//! Variance stabilizing transformation (VST).
+//!
+//! This uses the parametric transformation of [DESeq2] for the negative binomial variance
+//! function v(μ) = μ + αμ², with a single dispersion α estimated by the method of moments.
+//!
+//! [DESeq2]: https://bioconductor.org/packages/release/bioc/html/DESeq2.html
+
 use faer::{Col, MatRef};
 
+/// The smallest dispersion used, i.e., for data that is not overdispersed.
+const MIN_DISPERSION: f64 = 1e-8;
+
+/// Transforms a row-major features × samples count matrix.
 pub fn transform(raw_counts: Vec<u32>, feature_count: usize, sample_count: usize) -> Vec<f64> {
     let raw_counts: Vec<_> = raw_counts.into_iter().map(|n| n as f64).collect();
     let counts = MatRef::from_row_major_slice(&raw_counts, feature_count, sample_count);
 
-    let _size_factors = calculate_size_factors(counts);
+    let size_factors = calculate_size_factors(counts);
+    let dispersion = estimate_dispersion(counts, &size_factors);
+
+    let mut stabilized_counts = Vec::with_capacity(raw_counts.len());
+
+    for i in 0..feature_count {
+        for (j, size_factor) in size_factors.iter().enumerate() {
+            stabilized_counts.push(stabilize(counts[(i, j)] / size_factor, dispersion));
+        }
+    }
+
+    stabilized_counts
+}
+
+/// Estimates the dispersion of a features × samples count matrix.
+///
+/// This is the median of the per-feature moment estimates (σ² - μ) / μ² of the normalized counts,
+/// which requires at least 2 samples.
+pub(crate) fn estimate_dispersion(counts: MatRef<'_, f64>, size_factors: &[f64]) -> f64 {
+    let (feature_count, sample_count) = counts.shape();
+    assert!(sample_count > 1);
+
+    let n = sample_count as f64;
+    let mut dispersions = Vec::with_capacity(feature_count);
+
+    for i in 0..feature_count {
+        let normalized_counts = size_factors
+            .iter()
+            .enumerate()
+            .map(|(j, size_factor)| counts[(i, j)] / size_factor);
+
+        let mean = normalized_counts.clone().sum::<f64>() / n;
+
+        if mean <= 0.0 {
+            continue;
+        }
+
+        let variance = normalized_counts.map(|q| (q - mean).powi(2)).sum::<f64>() / (n - 1.0);
+
+        dispersions.push((variance - mean) / mean.powi(2));
+    }
+
+    if dispersions.is_empty() {
+        return MIN_DISPERSION;
+    }
 
-    todo!()
+    median(&mut dispersions).max(MIN_DISPERSION)
 }
 
-fn calculate_size_factors(counts: MatRef<'_, f64>) -> Vec<f64> {
+/// Stabilizes the variance of a normalized count, returning a value on the log2 scale.
+pub fn stabilize(normalized_count: f64, dispersion: f64) -> f64 {
+    let a = dispersion;
+    let q = normalized_count;
+
+    ((1.0 + 2.0 * a * q + 2.0 * (a * q * (1.0 + a * q)).sqrt()) / (4.0 * a)).log2()
+}
+
+pub(crate) fn calculate_size_factors(counts: MatRef<'_, f64>) -> Vec<f64> {
     use faer::stats::{NanHandling, col_mean};
 
     let (feature_count, sample_count) = counts.shape();
@@ -36,7 +99,11 @@ fn calculate_size_factors(counts: MatRef<'_, f64>) -> Vec<f64> {
             }
         }
 
-        size_factors[j] = median(&mut log_ratios).exp();
+        size_factors[j] = if log_ratios.is_empty() {
+            1.0
+        } else {
+            median(&mut log_ratios).exp()
+        };
     }
 
     size_factors
@@ -78,6 +145,38 @@ mod tests {
         assert_approx_eq(&actual, &expected);
     }
 
+    #[test]
+    fn test_estimate_dispersion() {
+        let counts = mat![[8.0, 8.0], [13.0, 13.0]];
+        assert_eq!(
+            estimate_dispersion(counts.as_ref(), &[1.0, 1.0]),
+            MIN_DISPERSION
+        );
+
+        let counts = mat![[10.0, 30.0], [0.0, 0.0]];
+        // μ = 20, σ² = 200
+        assert!((estimate_dispersion(counts.as_ref(), &[1.0, 1.0]) - 0.45).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_stabilize() {
+        // For large counts, the transformation approaches log2.
+        let n = 1e6;
+        assert!((stabilize(n, 0.1) - n.log2()).abs() < 1e-3);
+
+        assert!(stabilize(0.0, 0.1) < stabilize(1.0, 0.1));
+    }
+
+    #[test]
+    fn test_transform() {
+        let counts = vec![0, 21, 8, 34, 13, 55];
+        let actual = transform(counts, 3, 2);
+
+        assert_eq!(actual.len(), 6);
+        assert!(actual.iter().all(|n| n.is_finite()));
+        assert!(actual[0] < actual[2] && actual[2] < actual[4]);
+    }
+
     #[test]
     fn test_median() {
         let mut values = [2.0, 0.0, 1.0];
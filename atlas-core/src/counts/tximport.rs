@@ -0,0 +1,394 @@
+//! Transcript-level estimate aggregation.
+//!
+//! This summarizes transcript-level abundances, estimated counts, and effective lengths (e.g.,
+//! from Salmon, kallisto, or RSEM) to gene level, following `summarizeToGene` in [tximport].
+//! Gene abundances and counts are sums over transcripts, and gene lengths are the means of
+//! transcript lengths weighted by abundance. See "[Differential analyses for RNA-seq:
+//! transcript-level estimates improve gene-level inferences]" (2015) by Soneson et al. for more
+//! details.
+//!
+//! [tximport]: https://bioconductor.org/packages/release/bioc/html/tximport.html
+//! [Differential analyses for RNA-seq: transcript-level estimates improve gene-level inferences]: https://doi.org/10.12688/f1000research.7563.2
+
+pub mod reader;
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    io,
+};
+
+use tracing::warn;
+
+pub use self::reader::Estimates;
+
+/// The method used to derive counts from abundances.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CountsFromAbundance {
+    /// Use the summed estimated counts.
+    #[default]
+    No,
+    /// Scale abundances to the library size.
+    ScaledTpm,
+    /// Scale abundances by the average gene length and then to the library size.
+    LengthScaledTpm,
+}
+
+/// Gene-level estimates of multiple samples.
+#[derive(Debug, PartialEq)]
+pub struct GeneEstimates {
+    /// Gene names.
+    pub names: Vec<String>,
+    /// Abundances (TPM) per sample.
+    pub abundances: Vec<Vec<f64>>,
+    /// Counts per sample.
+    pub counts: Vec<Vec<f64>>,
+    /// Lengths per sample.
+    pub lengths: Vec<Vec<f64>>,
+}
+
+/// Summarizes transcript-level estimates to gene level.
+///
+/// `transcripts_to_genes` maps transcript names to gene names, e.g., from
+/// [`crate::features::read_parents`]. The resulting genes are all the genes in the map, sorted
+/// by name. Genes without any estimated transcripts have zero counts and lengths. Transcripts
+/// missing from the map are ignored.
+///
+/// All samples must have the same transcripts in the same order.
+pub fn summarize(
+    samples: &[Estimates],
+    transcripts_to_genes: &HashMap<String, String>,
+    counts_from_abundance: CountsFromAbundance,
+) -> io::Result<GeneEstimates> {
+    let Some((first_sample, samples_rest)) = samples.split_first() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no samples given",
+        ));
+    };
+
+    if samples_rest
+        .iter()
+        .any(|sample| sample.names != first_sample.names)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "transcript names mismatch",
+        ));
+    }
+
+    let gene_names: Vec<_> = transcripts_to_genes
+        .values()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .cloned()
+        .collect();
+
+    let gene_indices: HashMap<_, _> = gene_names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.as_str(), i))
+        .collect();
+
+    let transcript_gene_indices: Vec<_> = first_sample
+        .names
+        .iter()
+        .map(|name| {
+            transcripts_to_genes
+                .get(name)
+                .map(|gene_name| gene_indices[gene_name.as_str()])
+        })
+        .collect();
+
+    let unmapped_transcript_count = transcript_gene_indices
+        .iter()
+        .filter(|i| i.is_none())
+        .count();
+
+    if unmapped_transcript_count == transcript_gene_indices.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no transcripts in transcript-to-gene map",
+        ));
+    } else if unmapped_transcript_count > 0 {
+        warn!(
+            unmapped_transcript_count,
+            "skipping transcripts missing from transcript-to-gene map"
+        );
+    }
+
+    let gene_count = gene_names.len();
+
+    let average_transcript_lengths =
+        calculate_average_transcript_lengths(samples, &transcript_gene_indices, gene_count);
+
+    let mut abundances = Vec::with_capacity(samples.len());
+    let mut counts = Vec::with_capacity(samples.len());
+    let mut lengths = Vec::with_capacity(samples.len());
+
+    for sample in samples {
+        let mut sample_abundances = vec![0.0; gene_count];
+        let mut sample_counts = vec![0.0; gene_count];
+        let mut weighted_lengths = vec![0.0; gene_count];
+
+        for (i, gene_index) in transcript_gene_indices.iter().enumerate() {
+            let Some(j) = *gene_index else {
+                continue;
+            };
+
+            sample_abundances[j] += sample.abundances[i];
+            sample_counts[j] += sample.counts[i];
+            weighted_lengths[j] += sample.abundances[i] * sample.lengths[i];
+        }
+
+        // Lengths of genes with no abundance are undefined and replaced below.
+        let sample_lengths = weighted_lengths
+            .into_iter()
+            .zip(&sample_abundances)
+            .map(|(weighted_length, &abundance)| {
+                (abundance > 0.0).then(|| weighted_length / abundance)
+            })
+            .collect::<Vec<_>>();
+
+        abundances.push(sample_abundances);
+        counts.push(sample_counts);
+        lengths.push(sample_lengths);
+    }
+
+    let lengths = replace_missing_lengths(lengths, &average_transcript_lengths);
+
+    let counts = match counts_from_abundance {
+        CountsFromAbundance::No => counts,
+        CountsFromAbundance::ScaledTpm => scale_to_library_sizes(&counts, abundances.clone()),
+        CountsFromAbundance::LengthScaledTpm => {
+            let mean_lengths: Vec<_> = (0..gene_count)
+                .map(|j| lengths.iter().map(|ls| ls[j]).sum::<f64>() / lengths.len() as f64)
+                .collect();
+
+            let scaled_abundances = abundances
+                .iter()
+                .map(|sample_abundances| {
+                    sample_abundances
+                        .iter()
+                        .zip(&mean_lengths)
+                        .map(|(abundance, length)| abundance * length)
+                        .collect()
+                })
+                .collect();
+
+            scale_to_library_sizes(&counts, scaled_abundances)
+        }
+    };
+
+    Ok(GeneEstimates {
+        names: gene_names,
+        abundances,
+        counts,
+        lengths,
+    })
+}
+
+// Returns the mean over transcripts of the mean transcript length over samples for each gene.
+fn calculate_average_transcript_lengths(
+    samples: &[Estimates],
+    transcript_gene_indices: &[Option<usize>],
+    gene_count: usize,
+) -> Vec<Option<f64>> {
+    let mut sums = vec![0.0; gene_count];
+    let mut transcript_counts = vec![0; gene_count];
+
+    for (i, gene_index) in transcript_gene_indices.iter().enumerate() {
+        let Some(j) = *gene_index else {
+            continue;
+        };
+
+        let sum: f64 = samples.iter().map(|sample| sample.lengths[i]).sum();
+        sums[j] += sum / samples.len() as f64;
+        transcript_counts[j] += 1;
+    }
+
+    sums.into_iter()
+        .zip(transcript_counts)
+        .map(|(sum, n)| (n > 0).then(|| sum / f64::from(n)))
+        .collect()
+}
+
+// As in tximport, a missing length is replaced with the geometric mean of the gene's lengths in
+// other samples or, if missing in all samples, the average transcript length of the gene.
+fn replace_missing_lengths(
+    lengths: Vec<Vec<Option<f64>>>,
+    average_transcript_lengths: &[Option<f64>],
+) -> Vec<Vec<f64>> {
+    let replacements: Vec<_> = average_transcript_lengths
+        .iter()
+        .enumerate()
+        .map(|(j, average_transcript_length)| {
+            let (sum, n) = lengths
+                .iter()
+                .filter_map(|ls| ls[j])
+                .fold((0.0, 0), |(sum, n), length| (sum + length.ln(), n + 1));
+
+            if n > 0 {
+                (sum / f64::from(n)).exp()
+            } else {
+                average_transcript_length.unwrap_or_default()
+            }
+        })
+        .collect();
+
+    lengths
+        .into_iter()
+        .map(|ls| {
+            ls.into_iter()
+                .zip(&replacements)
+                .map(|(length, replacement)| length.unwrap_or(*replacement))
+                .collect()
+        })
+        .collect()
+}
+
+// Scales each sample of `values` to sum to the same total as the sample of `counts`.
+fn scale_to_library_sizes(counts: &[Vec<f64>], mut values: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    for (sample_counts, sample_values) in counts.iter().zip(&mut values) {
+        let library_size: f64 = sample_counts.iter().sum();
+        let sum: f64 = sample_values.iter().sum();
+
+        let factor = if sum > 0.0 { library_size / sum } else { 0.0 };
+
+        for value in sample_values.iter_mut() {
+            *value *= factor;
+        }
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: &[f64], b: &[f64]) {
+        const EPSILON: f64 = 1e-9;
+
+        assert_eq!(a.len(), b.len());
+
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < EPSILON, "{a:?} != {b:?}");
+        }
+    }
+
+    fn build_samples() -> Vec<Estimates> {
+        let names: Vec<_> = ["t0", "t1", "t2", "t3", "t4"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        vec![
+            Estimates {
+                names: names.clone(),
+                lengths: vec![100.0, 300.0, 200.0, 500.0, 50.0],
+                abundances: vec![10.0, 30.0, 0.0, 0.0, 960.0],
+                counts: vec![5.0, 45.0, 0.0, 0.0, 8.0],
+            },
+            Estimates {
+                names,
+                lengths: vec![100.0, 300.0, 250.0, 500.0, 50.0],
+                abundances: vec![20.0, 0.0, 60.0, 0.0, 920.0],
+                counts: vec![10.0, 0.0, 60.0, 0.0, 8.0],
+            },
+        ]
+    }
+
+    fn build_transcripts_to_genes() -> HashMap<String, String> {
+        [
+            ("t0", "g0"),
+            ("t1", "g0"),
+            ("t2", "g1"),
+            ("t3", "g2"),
+            ("t5", "g3"),
+        ]
+        .into_iter()
+        .map(|(t, g)| (t.into(), g.into()))
+        .collect()
+    }
+
+    #[test]
+    fn test_summarize() -> io::Result<()> {
+        let samples = build_samples();
+        let transcripts_to_genes = build_transcripts_to_genes();
+
+        let actual = summarize(&samples, &transcripts_to_genes, CountsFromAbundance::No)?;
+
+        assert_eq!(actual.names, ["g0", "g1", "g2", "g3"]);
+
+        assert_approx_eq(&actual.abundances[0], &[40.0, 0.0, 0.0, 0.0]);
+        assert_approx_eq(&actual.abundances[1], &[20.0, 60.0, 0.0, 0.0]);
+
+        assert_approx_eq(&actual.counts[0], &[50.0, 0.0, 0.0, 0.0]);
+        assert_approx_eq(&actual.counts[1], &[10.0, 60.0, 0.0, 0.0]);
+
+        assert_approx_eq(&actual.lengths[0], &[250.0, 250.0, 500.0, 0.0]);
+        assert_approx_eq(&actual.lengths[1], &[100.0, 250.0, 500.0, 0.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_summarize_with_scaled_tpm() -> io::Result<()> {
+        let samples = build_samples();
+        let transcripts_to_genes = build_transcripts_to_genes();
+
+        let actual = summarize(
+            &samples,
+            &transcripts_to_genes,
+            CountsFromAbundance::ScaledTpm,
+        )?;
+
+        assert_approx_eq(&actual.counts[0], &[50.0, 0.0, 0.0, 0.0]);
+        assert_approx_eq(&actual.counts[1], &[17.5, 52.5, 0.0, 0.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_summarize_with_length_scaled_tpm() -> io::Result<()> {
+        let samples = build_samples();
+        let transcripts_to_genes = build_transcripts_to_genes();
+
+        let actual = summarize(
+            &samples,
+            &transcripts_to_genes,
+            CountsFromAbundance::LengthScaledTpm,
+        )?;
+
+        assert_approx_eq(&actual.counts[0], &[50.0, 0.0, 0.0, 0.0]);
+        assert_approx_eq(
+            &actual.counts[1],
+            &[3500.0 * 70.0 / 18500.0, 15000.0 * 70.0 / 18500.0, 0.0, 0.0],
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_summarize_with_invalid_input() {
+        let transcripts_to_genes = build_transcripts_to_genes();
+
+        assert!(matches!(
+            summarize(&[], &transcripts_to_genes, CountsFromAbundance::No),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+
+        let mut samples = build_samples();
+        samples[1].names.reverse();
+
+        assert!(matches!(
+            summarize(&samples, &transcripts_to_genes, CountsFromAbundance::No),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+
+        assert!(matches!(
+            summarize(&build_samples(), &HashMap::new(), CountsFromAbundance::No),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+    }
+}
@@ -0,0 +1,224 @@
+mod format;
+
+use std::io::{self, BufRead};
+
+use tracing::warn;
+
+pub use self::format::Format;
+
+const SEPARATOR: char = '\t';
+
+/// Transcript-level estimates of a single sample.
+#[derive(Debug, Default, PartialEq)]
+pub struct Estimates {
+    /// Transcript names.
+    pub names: Vec<String>,
+    /// Effective lengths.
+    pub lengths: Vec<f64>,
+    /// Abundances (TPM).
+    pub abundances: Vec<f64>,
+    /// Estimated counts.
+    pub counts: Vec<f64>,
+}
+
+/// Reads transcript-level estimates from Salmon, kallisto, or RSEM output.
+///
+/// By default, the format is autodetected from the header.
+pub fn read<R>(reader: &mut R, format: Option<Format>) -> io::Result<Estimates>
+where
+    R: BufRead,
+{
+    let mut line = String::new();
+
+    if read_line(reader, &mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "missing header",
+        ));
+    }
+
+    let header: Vec<_> = line.split(SEPARATOR).collect();
+    let field_count = header.len();
+    let detected_format = detect_format(&header)?;
+
+    if let Some(expected_format) = format
+        && detected_format != expected_format
+    {
+        warn!(
+            expected = ?expected_format,
+            actual = ?detected_format,
+            "format mismatch"
+        );
+    }
+
+    let format = format.unwrap_or(detected_format);
+    let columns = Columns::new(&header, format)?;
+
+    let mut estimates = Estimates::default();
+
+    loop {
+        line.clear();
+
+        if read_line(reader, &mut line)? == 0 {
+            break;
+        }
+
+        let fields: Vec<_> = line.split(SEPARATOR).collect();
+
+        if fields.len() != field_count {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "invalid field count: expected {field_count}, got {}",
+                    fields.len()
+                ),
+            ));
+        }
+
+        estimates.names.push(fields[columns.name].into());
+        estimates.lengths.push(parse_value(fields[columns.length])?);
+        estimates
+            .abundances
+            .push(parse_value(fields[columns.abundance])?);
+        estimates.counts.push(parse_value(fields[columns.count])?);
+    }
+
+    Ok(estimates)
+}
+
+fn detect_format(header: &[&str]) -> io::Result<Format> {
+    [Format::Salmon, Format::Kallisto, Format::Rsem]
+        .into_iter()
+        .find(|format| header.first() == Some(&format.column_names()[0]))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown format"))
+}
+
+struct Columns {
+    name: usize,
+    length: usize,
+    abundance: usize,
+    count: usize,
+}
+
+impl Columns {
+    fn new(header: &[&str], format: Format) -> io::Result<Self> {
+        let position = |column_name: &str| {
+            header
+                .iter()
+                .position(|s| *s == column_name)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("missing column: {column_name}"),
+                    )
+                })
+        };
+
+        let [name, length, abundance, count] = format.column_names();
+
+        Ok(Self {
+            name: position(name)?,
+            length: position(length)?,
+            abundance: position(abundance)?,
+            count: position(count)?,
+        })
+    }
+}
+
+fn parse_value(s: &str) -> io::Result<f64> {
+    s.parse()
+        .ok()
+        .filter(|n: &f64| n.is_finite() && *n >= 0.0)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("invalid value: {s}")))
+}
+
+fn read_line<R>(reader: &mut R, buf: &mut String) -> io::Result<usize>
+where
+    R: BufRead,
+{
+    const LINE_FEED: char = '\n';
+    const CARRIAGE_RETURN: char = '\r';
+
+    match reader.read_line(buf)? {
+        0 => Ok(0),
+        n => {
+            if buf.ends_with(LINE_FEED) {
+                buf.pop();
+
+                if buf.ends_with(CARRIAGE_RETURN) {
+                    buf.pop();
+                }
+            }
+
+            Ok(n)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read() -> io::Result<()> {
+        fn t(src: &[u8], format: Format) -> io::Result<()> {
+            let expected = Estimates {
+                names: vec![String::from("t0"), String::from("t1")],
+                lengths: vec![80.5, 250.0],
+                abundances: vec![10.0, 990.0],
+                counts: vec![2.0, 60.25],
+            };
+
+            assert_eq!(read(&mut &src[..], None)?, expected);
+            assert_eq!(read(&mut &src[..], Some(format))?, expected);
+
+            Ok(())
+        }
+
+        t(
+            b"Name\tLength\tEffectiveLength\tTPM\tNumReads\nt0\t100\t80.5\t10\t2\nt1\t300\t250\t990\t60.25\n",
+            Format::Salmon,
+        )?;
+
+        t(
+            b"target_id\tlength\teff_length\test_counts\ttpm\nt0\t100\t80.5\t2\t10\nt1\t300\t250\t60.25\t990\n",
+            Format::Kallisto,
+        )?;
+
+        t(
+            b"transcript_id\tgene_id\tlength\teffective_length\texpected_count\tTPM\tFPKM\tIsoPct\n\
+t0\tg0\t100\t80.5\t2\t10\t8\t1\n\
+t1\tg0\t300\t250\t60.25\t990\t792\t99\n",
+            Format::Rsem,
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_with_invalid_input() {
+        let mut src = &b""[..];
+        assert!(matches!(
+            read(&mut src, None),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+
+        let mut src = &b"f0\t8\n"[..];
+        assert!(matches!(
+            read(&mut src, None),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData
+        ));
+
+        let mut src = &b"Name\tLength\tTPM\tNumReads\nt0\t100\t10\t2\n"[..];
+        assert!(matches!(
+            read(&mut src, None),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData
+        ));
+
+        let mut src = &b"Name\tLength\tEffectiveLength\tTPM\tNumReads\nt0\t100\t80\t-1\t2\n"[..];
+        assert!(matches!(
+            read(&mut src, None),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData
+        ));
+    }
+}
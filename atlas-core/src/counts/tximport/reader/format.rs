@@ -0,0 +1,20 @@
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// Salmon (`quant.sf`).
+    Salmon,
+    /// kallisto (`abundance.tsv`).
+    Kallisto,
+    /// RSEM (`*.isoforms.results`).
+    Rsem,
+}
+
+impl Format {
+    // Returns the names of the name, effective length, abundance, and count columns.
+    pub(super) fn column_names(&self) -> [&'static str; 4] {
+        match self {
+            Self::Salmon => ["Name", "EffectiveLength", "TPM", "NumReads"],
+            Self::Kallisto => ["target_id", "eff_length", "tpm", "est_counts"],
+            Self::Rsem => ["transcript_id", "effective_length", "TPM", "expected_count"],
+        }
+    }
+}
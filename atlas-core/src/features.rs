@@ -34,7 +34,7 @@ pub fn read_features<R>(
 where
     R: BufRead,
 {
-    use noodles::gff::{self, record::attributes::field::Value};
+    use noodles::gff;
 
     let mut reference_sequence_names = IndexSet::new();
     let mut features: HashMap<String, Vec<Feature>> = HashMap::new();
@@ -69,17 +69,7 @@ where
         let strand = record.strand()?;
         let feature = Feature::new(reference_sequence_id, start, end, strand);
 
-        let attributes = record.attributes();
-        let id = attributes
-            .get(feature_id.as_bytes())
-            .ok_or(ReadFeaturesError::MissingAttribute)?
-            .map_err(|_| ReadFeaturesError::InvalidAttribute)
-            .and_then(|value| match value {
-                Value::String(s) => str::from_utf8(&s)
-                    .map(String::from)
-                    .map_err(ReadFeaturesError::InvalidId),
-                Value::Array(_) => Err(ReadFeaturesError::InvalidAttribute),
-            })?;
+        let id = get_string_attribute(&record.attributes(), feature_id)?;
 
         let segments = features.entry(id).or_default();
         segments.push(feature);
@@ -88,6 +78,64 @@ where
     Ok((reference_sequence_names, features))
 }
 
+/// Reads the parent of each feature of the given type.
+///
+/// The result maps the value of the `feature_id` attribute to the value of the `Parent` attribute,
+/// e.g., transcript IDs to gene IDs. Features with multiple parents are invalid.
+pub fn read_parents<R>(
+    reader: &mut R,
+    feature_type: &str,
+    feature_id: &str,
+) -> Result<HashMap<String, String>, ReadFeaturesError>
+where
+    R: BufRead,
+{
+    use noodles::gff;
+
+    const PARENT: &str = "Parent";
+
+    let mut parents = HashMap::new();
+
+    let mut reader = gff::io::Reader::new(reader);
+    let mut line = gff::Line::default();
+
+    while reader.read_line(&mut line)? != 0 {
+        let Some(record) = line.as_record().transpose()? else {
+            continue;
+        };
+
+        if record.ty() != feature_type {
+            continue;
+        }
+
+        let attributes = record.attributes();
+        let id = get_string_attribute(&attributes, feature_id)?;
+        let parent = get_string_attribute(&attributes, PARENT)?;
+
+        parents.insert(id, parent);
+    }
+
+    Ok(parents)
+}
+
+fn get_string_attribute(
+    attributes: &noodles::gff::record::Attributes<'_>,
+    key: &str,
+) -> Result<String, ReadFeaturesError> {
+    use noodles::gff::record::attributes::field::Value;
+
+    attributes
+        .get(key.as_bytes())
+        .ok_or(ReadFeaturesError::MissingAttribute)?
+        .map_err(|_| ReadFeaturesError::InvalidAttribute)
+        .and_then(|value| match value {
+            Value::String(s) => str::from_utf8(&s)
+                .map(String::from)
+                .map_err(ReadFeaturesError::InvalidId),
+            Value::Array(_) => Err(ReadFeaturesError::InvalidAttribute),
+        })
+}
+
 pub fn merge_features(features: &[Feature]) -> Vec<Feature> {
     assert!(!features.is_empty());
 
@@ -190,6 +238,40 @@ sq0	.	exon	13	21	.	-	.	ID=3.0;gene_name=r2
         Ok(())
     }
 
+    #[test]
+    fn test_read_parents() -> Result<(), ReadFeaturesError> {
+        const DATA: &[u8] = b"\
+##gff-version 3
+sq0	.	gene	1	21	.	+	.	ID=g0
+sq0	.	transcript	1	8	.	+	.	ID=t0;Parent=g0
+sq0	.	transcript	1	21	.	+	.	ID=t1;Parent=g0
+sq0	.	exon	1	8	.	+	.	ID=e0;Parent=t0
+sq0	.	gene	30	40	.	-	.	ID=g1
+sq0	.	transcript	30	40	.	-	.	ID=t2;Parent=g1
+";
+
+        let mut reader = DATA;
+        let actual = read_parents(&mut reader, "transcript", "ID")?;
+
+        let expected = [
+            (String::from("t0"), String::from("g0")),
+            (String::from("t1"), String::from("g0")),
+            (String::from("t2"), String::from("g1")),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(actual, expected);
+
+        let mut reader = &b"sq0\t.\ttranscript\t1\t8\t.\t+\t.\tID=t0\n"[..];
+        assert!(matches!(
+            read_parents(&mut reader, "transcript", "ID"),
+            Err(ReadFeaturesError::MissingAttribute)
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_merge_features() -> Result<(), noodles::core::position::TryFromIntError> {
         const STRAND: Strand = Strand::None;
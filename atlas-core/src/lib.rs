@@ -1,6 +1,7 @@
 pub mod collections;
 pub mod counts;
 pub mod features;
+pub mod stats;
 mod strand_specification;
 
 pub use self::strand_specification::StrandSpecification;
@@ -0,0 +1,4 @@
+pub mod glm;
+pub mod multiple_testing;
+pub mod negative_binomial;
+pub mod summary;
@@ -0,0 +1,247 @@
+//! Generalized linear models (GLMs).
+
+use faer::{Mat, Side, linalg::solvers::Solve};
+use ndarray::{Array1, ArrayView1, ArrayView2};
+
+const MAX_ITERATION_COUNT: usize = 100;
+const TOLERANCE: f64 = 1e-8;
+
+// Bounds of the linear predictor (log mean) to avoid under- and overflow.
+const MIN_LINEAR_PREDICTOR: f64 = -30.0;
+const MAX_LINEAR_PREDICTOR: f64 = 30.0;
+
+// Dispersions below this are treated as Poisson.
+const MIN_DISPERSION: f64 = 1e-8;
+
+// Relative tolerance of singular values when determining the rank of a design matrix.
+const RANK_TOLERANCE: f64 = 1e-7;
+
+/// A fitted model.
+#[derive(Debug)]
+pub struct Fit {
+    /// The estimated coefficients.
+    pub coefficients: Array1<f64>,
+    /// The fitted means.
+    pub fitted_values: Array1<f64>,
+    /// Whether the estimates converged.
+    pub converged: bool,
+}
+
+/// Fits a negative binomial GLM with a log link.
+///
+/// `design` is a samples × coefficients matrix. `offsets` are on the log scale, e.g., log library
+/// sizes, and `dispersions` are per sample. The model is fit using iteratively reweighted least
+/// squares (IRLS).
+pub fn fit_negative_binomial(
+    design: ArrayView2<'_, f64>,
+    counts: ArrayView1<'_, f64>,
+    offsets: ArrayView1<'_, f64>,
+    dispersions: ArrayView1<'_, f64>,
+) -> Fit {
+    let (sample_count, coefficient_count) = design.dim();
+
+    assert_eq!(counts.len(), sample_count);
+    assert_eq!(offsets.len(), sample_count);
+    assert_eq!(dispersions.len(), sample_count);
+
+    // As in R's `glm.fit`, the initial means are derived from the counts.
+    let mut means = counts.mapv(|y| y + 0.1);
+    let mut coefficients = Array1::zeros(coefficient_count);
+
+    let mut converged = false;
+    let mut previous_deviance = f64::INFINITY;
+
+    for _ in 0..MAX_ITERATION_COUNT {
+        let mut weights = Array1::zeros(sample_count);
+        let mut working_responses = Array1::zeros(sample_count);
+
+        for i in 0..sample_count {
+            let (y, mu, phi) = (counts[i], means[i], dispersions[i]);
+            weights[i] = mu / (1.0 + phi * mu);
+            working_responses[i] = mu.ln() - offsets[i] + (y - mu) / mu;
+        }
+
+        let Some(next_coefficients) =
+            solve_weighted_least_squares(design, weights.view(), working_responses.view())
+        else {
+            break;
+        };
+
+        coefficients = next_coefficients;
+        means = fitted_values(design, coefficients.view(), offsets);
+
+        let deviance = deviance(counts, means.view(), dispersions);
+
+        if (deviance - previous_deviance).abs() / (deviance.abs() + 0.1) < TOLERANCE {
+            converged = true;
+            break;
+        }
+
+        previous_deviance = deviance;
+    }
+
+    Fit {
+        coefficients,
+        fitted_values: means,
+        converged,
+    }
+}
+
+/// Returns whether a design matrix has full column rank.
+pub fn is_full_rank(design: ArrayView2<'_, f64>) -> bool {
+    let (row_count, column_count) = design.dim();
+
+    if column_count == 0 {
+        return true;
+    } else if row_count < column_count {
+        return false;
+    }
+
+    let m = Mat::from_fn(row_count, column_count, |i, j| design[[i, j]]);
+
+    let Ok(svd) = m.thin_svd() else {
+        return false;
+    };
+
+    let singular_values = svd.S().column_vector();
+    let max_singular_value = singular_values.iter().copied().fold(0.0, f64::max);
+
+    max_singular_value > 0.0
+        && singular_values
+            .iter()
+            .all(|&s| s > max_singular_value * RANK_TOLERANCE)
+}
+
+fn solve_weighted_least_squares(
+    design: ArrayView2<'_, f64>,
+    weights: ArrayView1<'_, f64>,
+    responses: ArrayView1<'_, f64>,
+) -> Option<Array1<f64>> {
+    let (sample_count, coefficient_count) = design.dim();
+
+    let a = Mat::from_fn(coefficient_count, coefficient_count, |j, k| {
+        (0..sample_count)
+            .map(|i| design[[i, j]] * weights[i] * design[[i, k]])
+            .sum::<f64>()
+    });
+
+    let b = Mat::from_fn(coefficient_count, 1, |j, _| {
+        (0..sample_count)
+            .map(|i| design[[i, j]] * weights[i] * responses[i])
+            .sum::<f64>()
+    });
+
+    let llt = a.llt(Side::Lower).ok()?;
+    let x = llt.solve(&b);
+
+    let coefficients: Array1<f64> = (0..coefficient_count).map(|j| x[(j, 0)]).collect();
+
+    coefficients
+        .iter()
+        .all(|n| n.is_finite())
+        .then_some(coefficients)
+}
+
+fn fitted_values(
+    design: ArrayView2<'_, f64>,
+    coefficients: ArrayView1<'_, f64>,
+    offsets: ArrayView1<'_, f64>,
+) -> Array1<f64> {
+    let linear_predictors = design.dot(&coefficients) + offsets;
+    linear_predictors.mapv(|eta| eta.clamp(MIN_LINEAR_PREDICTOR, MAX_LINEAR_PREDICTOR).exp())
+}
+
+fn deviance(
+    counts: ArrayView1<'_, f64>,
+    means: ArrayView1<'_, f64>,
+    dispersions: ArrayView1<'_, f64>,
+) -> f64 {
+    counts
+        .iter()
+        .zip(means)
+        .zip(dispersions)
+        .map(|((&y, &mu), &phi)| unit_deviance(y, mu, phi))
+        .sum()
+}
+
+fn unit_deviance(y: f64, mu: f64, phi: f64) -> f64 {
+    let a = if y > 0.0 { y * (y / mu).ln() } else { 0.0 };
+
+    if phi < MIN_DISPERSION {
+        2.0 * (a - (y - mu))
+    } else {
+        let r = phi.recip();
+        2.0 * (a - (y + r) * ((y + r) / (mu + r)).ln())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+
+    fn assert_approx_eq(a: f64, b: f64) {
+        const EPSILON: f64 = 1e-6;
+        assert!((a - b).abs() < EPSILON, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_fit_negative_binomial() {
+        // With one indicator per group, the fitted means are the group means.
+        let design = array![[1.0, 0.0], [1.0, 0.0], [0.0, 1.0], [0.0, 1.0], [0.0, 1.0]];
+        let counts = array![4.0, 6.0, 20.0, 30.0, 40.0];
+        let offsets = Array1::zeros(5);
+        let dispersions = Array1::from_elem(5, 0.1);
+
+        let fit = fit_negative_binomial(
+            design.view(),
+            counts.view(),
+            offsets.view(),
+            dispersions.view(),
+        );
+
+        assert!(fit.converged);
+        assert_approx_eq(fit.coefficients[0], 5.0f64.ln());
+        assert_approx_eq(fit.coefficients[1], 30.0f64.ln());
+        assert_approx_eq(fit.fitted_values[0], 5.0);
+        assert_approx_eq(fit.fitted_values[4], 30.0);
+    }
+
+    #[test]
+    fn test_fit_negative_binomial_with_offsets() {
+        let design = array![[1.0], [1.0]];
+        let counts = array![10.0, 20.0];
+        let offsets = array![1.0f64.ln(), 2.0f64.ln()];
+        let dispersions = Array1::zeros(2);
+
+        let fit = fit_negative_binomial(
+            design.view(),
+            counts.view(),
+            offsets.view(),
+            dispersions.view(),
+        );
+
+        assert!(fit.converged);
+        assert_approx_eq(fit.coefficients[0], 10.0f64.ln());
+        assert_approx_eq(fit.fitted_values[1], 20.0);
+    }
+
+    #[test]
+    fn test_is_full_rank() {
+        assert!(is_full_rank(
+            array![[1.0, 0.0], [1.0, 1.0], [1.0, 0.0]].view()
+        ));
+        assert!(!is_full_rank(
+            array![[1.0, 2.0], [1.0, 2.0], [1.0, 2.0]].view()
+        ));
+        assert!(!is_full_rank(array![[1.0, 0.0, 1.0]].view()));
+    }
+
+    #[test]
+    fn test_unit_deviance() {
+        assert_approx_eq(unit_deviance(5.0, 5.0, 0.1), 0.0);
+        assert_approx_eq(unit_deviance(5.0, 5.0, 0.0), 0.0);
+        assert_approx_eq(unit_deviance(0.0, 2.0, 0.0), 4.0);
+    }
+}
@@ -0,0 +1,46 @@
+//! Multiple testing correction.
+
+/// Adjusts p-values for multiple testing using the Benjamini-Hochberg procedure.
+///
+/// The adjusted p-values control the false discovery rate (FDR). NaN p-values are not counted as
+/// tests and stay NaN.
+pub fn benjamini_hochberg(p_values: &[f64]) -> Vec<f64> {
+    let mut indices: Vec<usize> = (0..p_values.len())
+        .filter(|&i| !p_values[i].is_nan())
+        .collect();
+
+    indices.sort_unstable_by(|&a, &b| p_values[b].total_cmp(&p_values[a]));
+
+    let n = indices.len() as f64;
+    let mut adjusted_p_values = vec![f64::NAN; p_values.len()];
+    let mut min = 1.0f64;
+
+    // From the largest p-value, with rank n, down to the smallest, with rank 1.
+    for (k, &i) in indices.iter().enumerate() {
+        let rank = n - k as f64;
+        min = min.min(p_values[i] * n / rank);
+        adjusted_p_values[i] = min;
+    }
+
+    adjusted_p_values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benjamini_hochberg() {
+        // R: `p.adjust(c(0.01, 0.04, 0.03, 0.2), method = "BH")`.
+        let actual = benjamini_hochberg(&[0.01, 0.04, f64::NAN, 0.03, 0.2]);
+        let expected = [0.04, 0.04 * 4.0 / 3.0, f64::NAN, 0.04 * 4.0 / 3.0, 0.2];
+
+        assert_eq!(actual.len(), expected.len());
+
+        for (a, b) in actual.iter().zip(expected) {
+            assert!((a.is_nan() && b.is_nan()) || (a - b).abs() < 1e-12);
+        }
+
+        assert!(benjamini_hochberg(&[]).is_empty());
+    }
+}
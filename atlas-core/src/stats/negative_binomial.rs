@@ -0,0 +1,170 @@
+//! Negative binomial distribution.
+//!
+//! The distribution is parameterized by its mean (μ) and dispersion (φ), where the variance is
+//! `μ + φμ²`. This is the parameterization used by, e.g., edgeR and DESeq2.
+
+use statrs::distribution::{
+    ContinuousCDF, Discrete, DiscreteCDF, NegativeBinomial, Normal, Poisson,
+};
+
+// Dispersions below this are treated as Poisson.
+const MIN_DISPERSION: f64 = 1e-8;
+
+/// Returns the probability that a value is less than or equal to `k`.
+pub fn cdf(k: u64, mean: f64, dispersion: f64) -> f64 {
+    if mean <= 0.0 {
+        return 1.0;
+    }
+
+    Distribution::new(mean, dispersion).cdf(k)
+}
+
+/// Returns the smallest value `k` such that `cdf(k) >= p`.
+pub fn quantile(p: f64, mean: f64, dispersion: f64) -> u64 {
+    let p = p.clamp(0.0, 1.0);
+
+    if mean <= 0.0 || p == 0.0 {
+        return 0;
+    }
+
+    let distribution = Distribution::new(mean, dispersion);
+
+    if p == 1.0 {
+        return distribution.inverse_cdf(p);
+    }
+
+    // Start from a normal approximation and walk to the quantile using the recurrence of the
+    // probability mass function, which is much cheaper than bisecting on the CDF.
+    // SAFETY: The standard deviation is > 0.
+    let z = Normal::new(0.0, 1.0).unwrap().inverse_cdf(p);
+    let sd = (mean + dispersion.max(0.0) * mean * mean).sqrt();
+    let mut k = (mean + z * sd).round().max(0.0) as u64;
+
+    let mut cumulative_probability = distribution.cdf(k);
+    let mut probability = distribution.pmf(k);
+
+    if cumulative_probability >= p {
+        while k > 0 && cumulative_probability - probability >= p {
+            cumulative_probability -= probability;
+            probability /= distribution.pmf_ratio(k);
+            k -= 1;
+        }
+    } else {
+        while cumulative_probability < p {
+            k += 1;
+            probability *= distribution.pmf_ratio(k);
+
+            if probability == 0.0 || !probability.is_finite() {
+                return distribution.inverse_cdf(p);
+            }
+
+            cumulative_probability += probability;
+        }
+    }
+
+    // Correct for accumulated rounding errors.
+    while k > 0 && distribution.cdf(k - 1) >= p {
+        k -= 1;
+    }
+
+    while distribution.cdf(k) < p {
+        k += 1;
+    }
+
+    k
+}
+
+enum Distribution {
+    Poisson(Poisson),
+    NegativeBinomial(NegativeBinomial),
+}
+
+impl Distribution {
+    fn new(mean: f64, dispersion: f64) -> Self {
+        if dispersion < MIN_DISPERSION {
+            // SAFETY: `mean` is > 0.
+            Self::Poisson(Poisson::new(mean).unwrap())
+        } else {
+            let r = dispersion.recip();
+            let p = r / (r + mean);
+            // SAFETY: `r` is > 0, and `p` is in (0, 1].
+            Self::NegativeBinomial(NegativeBinomial::new(r, p).unwrap())
+        }
+    }
+
+    fn cdf(&self, k: u64) -> f64 {
+        match self {
+            Self::Poisson(d) => d.cdf(k),
+            Self::NegativeBinomial(d) => d.cdf(k),
+        }
+    }
+
+    fn inverse_cdf(&self, p: f64) -> u64 {
+        match self {
+            Self::Poisson(d) => d.inverse_cdf(p),
+            Self::NegativeBinomial(d) => d.inverse_cdf(p),
+        }
+    }
+
+    fn pmf(&self, k: u64) -> f64 {
+        match self {
+            Self::Poisson(d) => d.pmf(k),
+            Self::NegativeBinomial(d) => d.pmf(k),
+        }
+    }
+
+    // Returns `pmf(k) / pmf(k - 1)`.
+    fn pmf_ratio(&self, k: u64) -> f64 {
+        let k = k as f64;
+
+        match self {
+            Self::Poisson(d) => d.lambda() / k,
+            Self::NegativeBinomial(d) => (k - 1.0 + d.r()) / k * (1.0 - d.p()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: f64, b: f64) {
+        const EPSILON: f64 = 1e-9;
+        assert!((a - b).abs() < EPSILON, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_cdf() {
+        // pnbinom(3, mu = 5, size = 2)
+        assert_approx_eq(cdf(3, 5.0, 0.5), 0.4421967037543879);
+        // ppois(3, 5)
+        assert_approx_eq(cdf(3, 5.0, 0.0), 0.2650259152973617);
+        assert_approx_eq(cdf(0, 0.0, 0.5), 1.0);
+    }
+
+    #[test]
+    fn test_quantile() {
+        // qnbinom(0.5, mu = 5, size = 2)
+        assert_eq!(quantile(0.5, 5.0, 0.5), 4);
+        // qpois(0.5, 5)
+        assert_eq!(quantile(0.5, 5.0, 0.0), 5);
+
+        assert_eq!(quantile(0.0, 5.0, 0.5), 0);
+        assert_eq!(quantile(0.5, 0.0, 0.5), 0);
+
+        for k in [0, 1, 2, 8, 13, 40] {
+            let p = cdf(k, 5.0, 0.5);
+            assert_eq!(quantile(p, 5.0, 0.5), k);
+        }
+
+        for k in [0, 1, 2, 8, 13] {
+            let p = cdf(k, 5.0, 0.0);
+            assert_eq!(quantile(p, 5.0, 0.0), k);
+        }
+
+        for k in [100, 900, 1000, 1500, 4000] {
+            let p = cdf(k, 1000.0, 0.1);
+            assert_eq!(quantile(p, 1000.0, 0.1), k);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Five-number summaries.
+
+/// The minimum, quartiles, and maximum of a set of values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub q1: f64,
+    pub median: f64,
+    pub q3: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    /// Summarizes the given values.
+    ///
+    /// Quartiles are linearly interpolated between the closest ranks, as in R's default quantile
+    /// definition (type 7). NaN values are ignored. Returns `None` if there are no values.
+    pub fn new(values: &[f64]) -> Option<Self> {
+        let mut values: Vec<f64> = values.iter().copied().filter(|n| !n.is_nan()).collect();
+
+        if values.is_empty() {
+            return None;
+        }
+
+        values.sort_unstable_by(f64::total_cmp);
+
+        Some(Self {
+            count: values.len(),
+            min: values[0],
+            q1: quantile(&values, 0.25),
+            median: quantile(&values, 0.5),
+            q3: quantile(&values, 0.75),
+            max: values[values.len() - 1],
+        })
+    }
+}
+
+// `values` must be sorted and non-empty.
+fn quantile(values: &[f64], p: f64) -> f64 {
+    let h = (values.len() - 1) as f64 * p;
+    let i = h.floor() as usize;
+
+    match values.get(i + 1) {
+        Some(next) => values[i] + (h - i as f64) * (next - values[i]),
+        None => values[i],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(
+            Summary::new(&[7.0, 1.0, f64::NAN, 3.0, 10.0]),
+            Some(Summary {
+                count: 4,
+                min: 1.0,
+                q1: 2.5,
+                median: 5.0,
+                q3: 7.75,
+                max: 10.0,
+            })
+        );
+
+        assert_eq!(
+            Summary::new(&[2.0]),
+            Some(Summary {
+                count: 1,
+                min: 2.0,
+                q1: 2.0,
+                median: 2.0,
+                q3: 2.0,
+                max: 2.0,
+            })
+        );
+
+        assert!(Summary::new(&[]).is_none());
+        assert!(Summary::new(&[f64::NAN]).is_none());
+    }
+}
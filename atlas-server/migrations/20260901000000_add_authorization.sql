@@ -0,0 +1,85 @@
+-- Datasets that existed before authorization was added were readable by anyone, so they are
+-- kept public. New datasets are private by default.
+alter table datasets add column is_public boolean not null default false;
+update datasets set is_public = true;
+
+create table api_tokens (
+    id serial primary key,
+
+    subject text not null,
+    -- SHA-256 digest of the token. Tokens are never stored in plain text.
+    token_hash bytea not null,
+
+    created_at timestamptz not null default now(),
+
+    unique (token_hash)
+);
+
+create index api_tokens_subject_idx on api_tokens (subject);
+
+create table administrators (
+    subject text primary key,
+    created_at timestamptz not null default now()
+);
+
+create type dataset_permission as enum ('read', 'write');
+
+create table dataset_permissions (
+    dataset_id integer not null,
+    subject text not null,
+
+    permission dataset_permission not null,
+
+    created_at timestamptz not null default now(),
+
+    primary key (dataset_id, subject),
+    foreign key (dataset_id) references datasets (id)
+);
+
+create index dataset_permissions_subject_idx on dataset_permissions (subject);
+
+-- Whether a dataset is readable by a subject. A null subject is an anonymous user.
+create function dataset_is_readable(dataset_id integer, subject text, is_admin boolean)
+returns boolean
+language sql stable
+as $$
+    select is_admin
+        or exists (select 1 from datasets where id = dataset_id and is_public)
+        or exists (
+            select 1
+            from dataset_permissions
+            where dataset_permissions.dataset_id = dataset_is_readable.dataset_id
+                and dataset_permissions.subject = dataset_is_readable.subject
+        )
+$$;
+
+-- Whether a dataset is writable by a subject. Write permission is never implied by a dataset
+-- being public.
+create function dataset_is_writable(dataset_id integer, subject text, is_admin boolean)
+returns boolean
+language sql stable
+as $$
+    select is_admin
+        or exists (
+            select 1
+            from dataset_permissions
+            where dataset_permissions.dataset_id = dataset_is_writable.dataset_id
+                and dataset_permissions.subject = dataset_is_writable.subject
+                and dataset_permissions.permission = 'write'
+        )
+$$;
+
+-- Whether a run is readable by a subject, i.e., whether it is in at least one readable dataset.
+-- Runs not in any dataset are only readable by administrators.
+create function run_is_readable(run_id integer, subject text, is_admin boolean)
+returns boolean
+language sql stable
+as $$
+    select is_admin
+        or exists (
+            select 1
+            from datasets_runs
+            where datasets_runs.run_id = run_is_readable.run_id
+                and dataset_is_readable(datasets_runs.dataset_id, subject, false)
+        )
+$$;
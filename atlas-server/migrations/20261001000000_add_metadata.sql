@@ -0,0 +1,10 @@
+alter table samples
+    add column metadata jsonb not null default '{}'
+    check (jsonb_typeof(metadata) = 'object');
+
+alter table runs
+    add column metadata jsonb not null default '{}'
+    check (jsonb_typeof(metadata) = 'object');
+
+create index samples_metadata_idx on samples using gin (metadata jsonb_path_ops);
+create index runs_metadata_idx on runs using gin (metadata jsonb_path_ops);
@@ -0,0 +1,15 @@
+-- Cached per-run normalization factors of a dataset. `run_ids` is the sorted set of runs the
+-- factors were calculated over; an entry is stale when the dataset's runs differ.
+create table normalization_factors (
+    dataset_id integer not null,
+    method text not null,
+
+    run_ids integer[] not null,
+    factors double precision[] not null,
+    dispersion double precision,
+
+    created_at timestamptz not null default now(),
+
+    primary key (dataset_id, method),
+    foreign key (dataset_id) references datasets (id)
+);
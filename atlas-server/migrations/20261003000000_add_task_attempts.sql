@@ -0,0 +1,13 @@
+alter type status add value 'cancelled';
+
+-- `heartbeat_at` is refreshed by the worker processing a running task. A running task whose
+-- heartbeat is older than the worker's lease duration is considered abandoned and is re-queued.
+alter table tasks
+    add column attempts integer not null default 0,
+    add column progress real not null default 0,
+    add column error text,
+    add column started_at timestamptz,
+    add column heartbeat_at timestamptz,
+    add column finished_at timestamptz;
+
+create index tasks_status_created_at_idx on tasks (status, created_at);
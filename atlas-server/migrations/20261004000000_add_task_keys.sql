@@ -0,0 +1,5 @@
+-- A content hash of a task's message and the runs it selects. Pending and successful tasks are
+-- unique by key so identical requests share a task and its result.
+alter table tasks add column key bytea;
+
+create unique index tasks_key_idx on tasks (key) where status in ('queued', 'running', 'success');
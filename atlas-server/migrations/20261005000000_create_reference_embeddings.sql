@@ -0,0 +1,28 @@
+-- Reference t-SNE embeddings of datasets that new samples are projected onto, with the
+-- preprocessing fitted to the reference samples. `run_ids` is the sorted set of runs the
+-- embedding was fitted to and `options` are the plot options; an entry is stale when either
+-- differs.
+create table reference_embeddings (
+    dataset_id integer not null,
+
+    run_ids integer[] not null,
+    options jsonb not null,
+
+    feature_count integer not null,
+    expressed_feature_indices integer[],
+    variable_feature_indices integer[],
+    -- The PCA feature means and row-major features × components loadings, if PCA is applied.
+    means double precision[],
+    loadings double precision[],
+    component_count integer,
+
+    -- Row-major samples × inputs and samples × dimensions matrices.
+    data double precision[] not null,
+    embedding double precision[] not null,
+    dimension_count integer not null,
+
+    created_at timestamptz not null default now(),
+
+    primary key (dataset_id),
+    foreign key (dataset_id) references datasets (id)
+);
@@ -0,0 +1,15 @@
+-- The kind of a task's message, so workers can pull only the kinds they handle.
+alter table tasks add column kind text;
+
+update tasks
+set kind = case
+    when message = '"Noop"' then 'noop'
+    when message ? 'Plot' then 'plot'
+    when message ? 'BatchCorrection' then 'batch_correction'
+    when message ? 'DifferentialExpression' then 'differential_expression'
+    when message ? 'SampleSimilarity' then 'sample_similarity'
+end;
+
+alter table tasks alter column kind set not null;
+
+create index tasks_kind_status_created_at_idx on tasks (kind, status, created_at);
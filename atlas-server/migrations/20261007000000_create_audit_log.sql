@@ -0,0 +1,14 @@
+-- Deletions and renames of runs, samples, datasets, and configurations. `subject` is the
+-- authenticated subject that made the change, or null if it was made with the CLI.
+create table audit_log (
+    id bigserial primary key,
+
+    subject text,
+    action text not null,
+    target_id integer not null,
+    details jsonb not null default '{}',
+
+    created_at timestamptz not null default now()
+);
+
+create index audit_log_action_target_id_idx on audit_log (action, target_id);
@@ -0,0 +1,25 @@
+-- Dense per-run counts. `values` is aligned to the features of the run's configuration ordered
+-- by ID, so reading a matrix of runs does not join over a row per (run, feature).
+create table run_counts (
+    run_id integer not null,
+
+    values integer[] not null,
+
+    primary key (run_id),
+    foreign key (run_id) references runs (id)
+);
+
+insert into run_counts (run_id, values)
+select
+    runs.id,
+    array(
+        select coalesce(counts.value, 0)
+        from features
+        left join counts
+            on counts.run_id = runs.id and counts.feature_id = features.id
+        where features.configuration_id = runs.configuration_id
+        order by features.id
+    )
+from runs;
+
+drop table counts;
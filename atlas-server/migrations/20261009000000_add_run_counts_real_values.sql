@@ -0,0 +1,6 @@
+-- Real-valued counts, e.g., expected counts or TPMs. A run stores either integer `values` or
+-- `real_values`, both aligned to the features of its configuration ordered by ID.
+alter table run_counts
+    alter column values drop not null,
+    add column real_values double precision[],
+    add constraint run_counts_values_check check ((values is null) <> (real_values is null));
@@ -0,0 +1,3 @@
+-- The subject that submitted a task, if authenticated. Identical requests share a task, so only
+-- its submitter or an administrator may cancel it.
+alter table tasks add column subject text;
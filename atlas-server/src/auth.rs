@@ -0,0 +1,44 @@
+pub mod jwt;
+
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// The prefix of API tokens, which distinguishes them from JWTs.
+pub(crate) const TOKEN_PREFIX: &str = "atlas_";
+const TOKEN_LENGTH: usize = 32;
+
+/// Generates a random API token.
+pub fn generate_token() -> String {
+    let mut buf = [0; TOKEN_LENGTH];
+    rand::rng().fill_bytes(&mut buf);
+    format!("{TOKEN_PREFIX}{}", hex::encode(buf))
+}
+
+/// Returns the SHA-256 digest of an API token.
+///
+/// API tokens are random with high entropy, so a fast, unsalted hash is sufficient to avoid
+/// storing them in plain text and still allows lookups by hash.
+pub fn hash_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token();
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert_eq!(token.len(), TOKEN_PREFIX.len() + 2 * TOKEN_LENGTH);
+        assert_ne!(generate_token(), token);
+    }
+
+    #[test]
+    fn test_hash_token() {
+        assert_eq!(
+            hex::encode(hash_token("atlas")),
+            "7c82602500857aa6ed0cf38c4c3e4ec645bdcaa82c00b9155eb08be100c778a9"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use std::{fs, io, path::Path};
+
+use jsonwebtoken::{
+    DecodingKey, Validation,
+    jwk::{AlgorithmParameters, Jwk, JwkSet},
+};
+use serde::Deserialize;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid token")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+    #[error("unknown key ID: {0:?}")]
+    UnknownKey(Option<String>),
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: String,
+}
+
+struct Key {
+    id: Option<String>,
+    decoding_key: DecodingKey,
+    validation: Validation,
+}
+
+/// A validator of JWT bearer tokens, e.g., OIDC access tokens.
+///
+/// Tokens must be signed by a key in a JSON Web Key Set (JWKS) and include `sub` and `exp`
+/// claims. The subject is the `sub` claim.
+pub struct Validator {
+    keys: Vec<Key>,
+}
+
+impl Validator {
+    /// Creates a validator from a JWKS.
+    ///
+    /// If an issuer or audience is given, tokens must have a matching `iss` or `aud` claim,
+    /// respectively.
+    pub fn new(jwks: &JwkSet, issuer: Option<&str>, audience: Option<&str>) -> io::Result<Self> {
+        if jwks.keys.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "JWKS has no keys",
+            ));
+        }
+
+        let keys = jwks
+            .keys
+            .iter()
+            .map(|jwk| build_key(jwk, issuer, audience))
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self { keys })
+    }
+
+    /// Reads a JWKS file and creates a validator from it.
+    pub fn read<P>(src: P, issuer: Option<&str>, audience: Option<&str>) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let data = fs::read(src)?;
+        let jwks: JwkSet = serde_json::from_slice(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Self::new(&jwks, issuer, audience)
+    }
+
+    /// Validates a token and returns its subject.
+    pub fn validate(&self, token: &str) -> Result<String, Error> {
+        let header = jsonwebtoken::decode_header(token)?;
+
+        let key = match (&header.kid, &self.keys[..]) {
+            (None, [key]) => key,
+            (Some(kid), keys) => keys
+                .iter()
+                .find(|key| key.id.as_ref() == Some(kid))
+                .ok_or_else(|| Error::UnknownKey(header.kid.clone()))?,
+            (None, _) => return Err(Error::UnknownKey(None)),
+        };
+
+        let data = jsonwebtoken::decode::<Claims>(token, &key.decoding_key, &key.validation)?;
+
+        Ok(data.claims.sub)
+    }
+}
+
+fn build_key(jwk: &Jwk, issuer: Option<&str>, audience: Option<&str>) -> io::Result<Key> {
+    use jsonwebtoken::AlgorithmFamily;
+
+    let decoding_key =
+        DecodingKey::from_jwk(jwk).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    // Only algorithms of the key's type are accepted, so a token cannot choose, e.g., HMAC to
+    // be verified with a public key.
+    let mut validation = if let Some(key_algorithm) = jwk.common.key_algorithm {
+        let algorithm = key_algorithm
+            .to_string()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Validation::new(algorithm)
+    } else {
+        let family = match jwk.algorithm {
+            AlgorithmParameters::EllipticCurve(_) => AlgorithmFamily::Ec,
+            AlgorithmParameters::RSA(_) => AlgorithmFamily::Rsa,
+            AlgorithmParameters::OctetKey(_) => AlgorithmFamily::Hmac,
+            AlgorithmParameters::OctetKeyPair(_) => AlgorithmFamily::Ed,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unsupported key type",
+                ));
+            }
+        };
+
+        Validation::new_for_family(family)
+    };
+
+    validation.set_required_spec_claims(&["exp", "sub"]);
+
+    if let Some(issuer) = issuer {
+        validation.set_issuer(&[issuer]);
+    }
+
+    if let Some(audience) = audience {
+        validation.set_audience(&[audience]);
+    } else {
+        validation.validate_aud = false;
+    }
+
+    Ok(Key {
+        id: jwk.common.key_id.clone(),
+        decoding_key,
+        validation,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+    use serde_json::json;
+
+    use super::*;
+
+    pub(crate) const SECRET: &[u8] = b"atlas-secret-atlas-secret-atlas!";
+    pub(crate) const KEY_ID: &str = "k0";
+
+    pub(crate) fn jwks() -> JwkSet {
+        use jsonwebtoken::jwk::{CommonParameters, KeyAlgorithm, OctetKeyParameters, OctetKeyType};
+
+        let value = {
+            use base64::Engine;
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(SECRET)
+        };
+
+        JwkSet {
+            keys: vec![Jwk {
+                common: CommonParameters {
+                    key_algorithm: Some(KeyAlgorithm::HS256),
+                    key_id: Some(KEY_ID.into()),
+                    ..Default::default()
+                },
+                algorithm: AlgorithmParameters::OctetKey(OctetKeyParameters {
+                    key_type: OctetKeyType::Octet,
+                    value,
+                }),
+            }],
+        }
+    }
+
+    pub(crate) fn encode(claims: serde_json::Value, kid: Option<&str>) -> String {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = kid.map(String::from);
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(SECRET)).unwrap()
+    }
+
+    pub(crate) fn expiration() -> u64 {
+        jsonwebtoken::get_current_timestamp() + 3600
+    }
+
+    #[test]
+    fn test_validate() -> Result<(), Box<dyn std::error::Error>> {
+        let validator = Validator::new(&jwks(), Some("https://idp.example"), Some("atlas"))?;
+
+        let token = encode(
+            json!({
+                "sub": "user",
+                "iss": "https://idp.example",
+                "aud": "atlas",
+                "exp": expiration(),
+            }),
+            Some(KEY_ID),
+        );
+        assert_eq!(validator.validate(&token)?, "user");
+
+        let token = encode(
+            json!({ "sub": "user", "iss": "https://idp.example", "aud": "atlas", "exp": 0 }),
+            Some(KEY_ID),
+        );
+        assert!(matches!(
+            validator.validate(&token),
+            Err(Error::InvalidToken(_))
+        ));
+
+        let token = encode(
+            json!({ "sub": "user", "iss": "https://other.example", "aud": "atlas", "exp": expiration() }),
+            Some(KEY_ID),
+        );
+        assert!(matches!(
+            validator.validate(&token),
+            Err(Error::InvalidToken(_))
+        ));
+
+        let token = encode(
+            json!({ "sub": "user", "iss": "https://idp.example", "aud": "atlas", "exp": expiration() }),
+            Some("k1"),
+        );
+        assert!(matches!(
+            validator.validate(&token),
+            Err(Error::UnknownKey(Some(_)))
+        ));
+
+        assert!(validator.validate("atlas_0123").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_without_issuer_or_audience() -> Result<(), Box<dyn std::error::Error>> {
+        let validator = Validator::new(&jwks(), None, None)?;
+
+        let token = encode(json!({ "sub": "user", "exp": expiration() }), None);
+        assert_eq!(validator.validate(&token)?, "user");
+
+        let token = encode(json!({ "exp": expiration() }), None);
+        assert!(validator.validate(&token).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_with_empty_jwks() {
+        let jwks = JwkSet { keys: Vec::new() };
+        assert!(Validator::new(&jwks, None, None).is_err());
+    }
+}
@@ -1,7 +1,10 @@
+pub mod admin;
 pub mod configuration;
 pub mod dataset;
 pub mod run;
+pub mod sample;
 mod server;
+pub mod token;
 mod worker;
 
 use clap::{Parser, Subcommand};
@@ -17,6 +20,9 @@ pub struct Cli {
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
+    /// Manage administrators
+    #[clap(subcommand)]
+    Admin(admin::Command),
     /// Manage configurations
     #[clap(subcommand)]
     Configuration(configuration::Command),
@@ -26,8 +32,14 @@ pub enum Commands {
     /// Manage runs
     #[clap(subcommand)]
     Run(run::Command),
+    /// Manage samples
+    #[clap(subcommand)]
+    Sample(sample::Command),
     /// Starts an atlas server and blocks indefinitely
     Server(ServerConfig),
+    /// Manage API tokens
+    #[clap(subcommand)]
+    Token(token::Command),
     /// Starts an atlas worker.
     Worker(WorkerConfig),
 }
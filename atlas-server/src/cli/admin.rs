@@ -0,0 +1,29 @@
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Make a subject an administrator
+    Add(AddConfig),
+    /// Remove a subject from the administrators
+    Remove(RemoveConfig),
+}
+
+#[derive(Debug, Parser)]
+pub struct AddConfig {
+    /// The PostgreSQL database connection URL.
+    #[clap(long, env)]
+    pub database_url: String,
+
+    /// The subject.
+    pub subject: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct RemoveConfig {
+    /// The PostgreSQL database connection URL.
+    #[clap(long, env)]
+    pub database_url: String,
+
+    /// The subject.
+    pub subject: String,
+}
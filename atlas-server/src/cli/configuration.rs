@@ -4,10 +4,22 @@ use clap::{Parser, Subcommand};
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
+    /// Delete a configuration that has no runs
+    Delete(DeleteConfig),
     /// Import a configuration
     Import(ImportConfig),
 }
 
+#[derive(Debug, Parser)]
+pub struct DeleteConfig {
+    /// The PostgreSQL database connection URL.
+    #[clap(long, env)]
+    pub database_url: String,
+
+    /// The configuration ID.
+    pub id: i32,
+}
+
 #[derive(Debug, Parser)]
 pub struct ImportConfig {
     /// The PostgreSQL database connection URL.
@@ -1,11 +1,19 @@
 use clap::{Parser, Subcommand};
 
+use crate::store::DatasetPermission;
+
 #[derive(Debug, Subcommand)]
 pub enum Command {
     /// Add a run to a dataset
     Add(AddConfig),
     /// Create a dataset
     Create(CreateConfig),
+    /// Grant a subject a permission on a dataset
+    Grant(GrantConfig),
+    /// Rename a dataset
+    Rename(RenameConfig),
+    /// Revoke a subject's permission on a dataset
+    Revoke(RevokeConfig),
 }
 
 #[derive(Debug, Parser)]
@@ -28,6 +36,56 @@ pub struct CreateConfig {
     #[clap(long, env)]
     pub database_url: String,
 
+    /// Whether the dataset is readable by anyone, including anonymous users.
+    #[clap(long)]
+    pub public: bool,
+
     /// The dataset name.
     pub name: String,
 }
+
+#[derive(Debug, Parser)]
+pub struct GrantConfig {
+    /// The PostgreSQL database connection URL.
+    #[clap(long, env)]
+    pub database_url: String,
+
+    /// The dataset ID.
+    #[clap(long)]
+    pub dataset_id: i32,
+
+    /// The permission to grant.
+    #[clap(long, value_enum)]
+    pub permission: DatasetPermission,
+
+    /// The subject, e.g., the `sub` claim of a JWT or the subject of an API token.
+    pub subject: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct RenameConfig {
+    /// The PostgreSQL database connection URL.
+    #[clap(long, env)]
+    pub database_url: String,
+
+    /// The dataset ID.
+    #[clap(long)]
+    pub dataset_id: i32,
+
+    /// The new dataset name.
+    pub name: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct RevokeConfig {
+    /// The PostgreSQL database connection URL.
+    #[clap(long, env)]
+    pub database_url: String,
+
+    /// The dataset ID.
+    #[clap(long)]
+    pub dataset_id: i32,
+
+    /// The subject.
+    pub subject: String,
+}
@@ -2,14 +2,27 @@ use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 
-use crate::{counts, store::StrandSpecification};
+use crate::{counts, import::OnConflict, store::StrandSpecification};
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
+    /// Delete runs with their counts and dataset memberships
+    Delete(DeleteConfig),
     /// Import a run
     Import(ImportConfig),
 }
 
+#[derive(Debug, Parser)]
+pub struct DeleteConfig {
+    /// The PostgreSQL database connection URL.
+    #[clap(long, env)]
+    pub database_url: String,
+
+    /// Run IDs.
+    #[clap(required = true)]
+    pub ids: Vec<i32>,
+}
+
 #[derive(Debug, Parser)]
 pub struct ImportConfig {
     /// The PostgreSQL database connection URL.
@@ -42,7 +55,8 @@ pub struct ImportConfig {
 
     /// The input format.
     ///
-    /// By default, the format is autodetected.
+    /// By default, the format is autodetected as either htseq-count or STAR. Real-valued counts
+    /// must be given explicitly.
     #[clap(long)]
     pub format: Option<counts::Format>,
 
@@ -54,6 +68,28 @@ pub struct ImportConfig {
     #[clap(long)]
     pub sample_sheet: bool,
 
+    /// A sample metadata table.
+    ///
+    /// The input format is tab-separated plain text with a header. The first
+    /// column is the sample name, and each other column is a metadata
+    /// attribute, e.g., tissue or diagnosis. Empty cells are skipped.
+    #[clap(long)]
+    pub metadata: Option<PathBuf>,
+
+    /// How to import a sample that already has a run with the configuration.
+    ///
+    /// Each sample is imported in its own transaction, so samples imported before an error are
+    /// kept. Rerunning with `skip` resumes the import.
+    #[clap(value_enum, long, default_value_t)]
+    pub on_conflict: OnConflict,
+
+    /// Report what would be imported without changing the database.
+    ///
+    /// The action for each sample, i.e., "create", "skip", or "replace", and its name are
+    /// written to stdout.
+    #[clap(long)]
+    pub dry_run: bool,
+
     /// The input sources.
     ///
     /// The inputs can be feature count outputs from either htseq-count or STAR.
@@ -0,0 +1,21 @@
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Rename a sample
+    Rename(RenameConfig),
+}
+
+#[derive(Debug, Parser)]
+pub struct RenameConfig {
+    /// The PostgreSQL database connection URL.
+    #[clap(long, env)]
+    pub database_url: String,
+
+    /// The sample ID.
+    #[clap(long)]
+    pub sample_id: i32,
+
+    /// The new sample name.
+    pub name: String,
+}
@@ -1,4 +1,4 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, path::PathBuf};
 
 use clap::Parser;
 
@@ -11,4 +11,18 @@ pub struct ServerConfig {
     /// The socket address the server binds to.
     #[clap(long, env = "BIND_ADDRESS", default_value = "127.0.0.1:3000")]
     pub bind: SocketAddr,
+
+    /// A JSON Web Key Set (JWKS) file used to validate JWT bearer tokens.
+    ///
+    /// If not set, only API tokens are accepted.
+    #[clap(long, env = "JWT_JWKS")]
+    pub jwt_jwks: Option<PathBuf>,
+
+    /// The required issuer (`iss`) of JWT bearer tokens.
+    #[clap(long, env = "JWT_ISSUER", requires = "jwt_jwks")]
+    pub jwt_issuer: Option<String>,
+
+    /// The required audience (`aud`) of JWT bearer tokens.
+    #[clap(long, env = "JWT_AUDIENCE", requires = "jwt_jwks")]
+    pub jwt_audience: Option<String>,
 }
@@ -0,0 +1,29 @@
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Create an API token for a subject and print it
+    Create(CreateConfig),
+    /// Revoke all API tokens of a subject
+    Revoke(RevokeConfig),
+}
+
+#[derive(Debug, Parser)]
+pub struct CreateConfig {
+    /// The PostgreSQL database connection URL.
+    #[clap(long, env)]
+    pub database_url: String,
+
+    /// The subject the token authenticates as.
+    pub subject: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct RevokeConfig {
+    /// The PostgreSQL database connection URL.
+    #[clap(long, env)]
+    pub database_url: String,
+
+    /// The subject.
+    pub subject: String,
+}
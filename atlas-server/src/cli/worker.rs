@@ -1,3 +1,5 @@
+use std::num::NonZeroUsize;
+
 use clap::Parser;
 
 #[derive(Debug, Parser)]
@@ -5,4 +7,31 @@ pub struct WorkerConfig {
     /// The PostgreSQL database connection URL.
     #[clap(long, env)]
     pub database_url: String,
+
+    /// The number of times a failed task is retried before it is marked as failed.
+    #[clap(long, env = "MAX_RETRIES", default_value_t = 2)]
+    pub max_retries: u32,
+
+    /// The number of seconds a running task may go without a heartbeat before it is re-queued.
+    #[clap(long, env = "LEASE_DURATION", default_value_t = 60)]
+    pub lease_duration: u64,
+
+    /// The number of days finished tasks and their results are kept.
+    #[clap(long, env = "TASK_RETENTION_DAYS", default_value_t = 7)]
+    pub task_retention_days: u64,
+
+    /// The number of tasks processed concurrently.
+    #[clap(long, env = "WORKER_CONCURRENCY", default_value_t = NonZeroUsize::MIN)]
+    pub concurrency: NonZeroUsize,
+
+    /// A comma-separated list of the kinds of tasks to process, e.g., "plot,batch_correction".
+    ///
+    /// If unset, all kinds are processed.
+    #[clap(long, env = "WORKER_KINDS", value_delimiter = ',')]
+    pub kinds: Vec<String>,
+
+    /// The number of seconds running tasks are given to finish on shutdown before they are
+    /// re-queued.
+    #[clap(long, env = "DRAIN_TIMEOUT", default_value_t = 30)]
+    pub drain_timeout: u64,
 }
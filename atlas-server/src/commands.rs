@@ -1,9 +1,13 @@
+mod admin;
 mod configuration;
 mod dataset;
 mod run;
+mod sample;
 mod server;
+mod token;
 mod worker;
 
 pub use self::{
-    configuration::configuration, dataset::dataset, run::run, server::server, worker::worker,
+    admin::admin, configuration::configuration, dataset::dataset, run::run, sample::sample,
+    server::server, token::token, worker::worker,
 };
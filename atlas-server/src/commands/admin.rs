@@ -0,0 +1,12 @@
+mod add;
+mod remove;
+
+use self::{add::add, remove::remove};
+use crate::cli::admin::Command;
+
+pub async fn admin(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Add(config) => add(config).await,
+        Command::Remove(config) => remove(config).await,
+    }
+}
@@ -0,0 +1,15 @@
+use sqlx::postgres::PgPoolOptions;
+use tracing::info;
+
+use crate::{cli::admin::AddConfig, store::principal};
+
+pub(super) async fn add(config: AddConfig) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new().connect(&config.database_url).await?;
+    sqlx::migrate!().run(&pool).await?;
+
+    principal::add_administrator(&pool, &config.subject).await?;
+
+    info!(subject = config.subject, "added administrator");
+
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use anyhow::bail;
+use sqlx::postgres::PgPoolOptions;
+use tracing::info;
+
+use crate::{cli::admin::RemoveConfig, store::principal};
+
+pub(super) async fn remove(config: RemoveConfig) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new().connect(&config.database_url).await?;
+    sqlx::migrate!().run(&pool).await?;
+
+    if !principal::remove_administrator(&pool, &config.subject).await? {
+        bail!("subject '{}' is not an administrator", config.subject);
+    }
+
+    info!(subject = config.subject, "removed administrator");
+
+    Ok(())
+}
@@ -1,10 +1,12 @@
+mod delete;
 mod import;
 
-use self::import::import;
+use self::{delete::delete, import::import};
 use crate::cli::configuration::Command;
 
 pub async fn configuration(command: Command) -> anyhow::Result<()> {
     match command {
+        Command::Delete(config) => delete(config).await,
         Command::Import(config) => import(config).await,
     }
 }
@@ -0,0 +1,35 @@
+use anyhow::bail;
+use serde_json::json;
+use sqlx::postgres::PgPoolOptions;
+use tracing::info;
+
+use crate::{
+    cli::configuration::DeleteConfig,
+    store::{
+        audit_log::{self, Action},
+        configuration::{self, Deletion},
+    },
+};
+
+pub(super) async fn delete(config: DeleteConfig) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new().connect(&config.database_url).await?;
+    sqlx::migrate!().run(&pool).await?;
+
+    let id = config.id;
+
+    let mut tx = pool.begin().await?;
+
+    match configuration::delete(&mut tx, id).await? {
+        Deletion::Deleted => {}
+        Deletion::NotFound => bail!("configuration {id} does not exist"),
+        Deletion::InUse => bail!("configuration {id} has runs"),
+    }
+
+    audit_log::create(&mut *tx, None, Action::DeleteConfiguration, id, &json!({})).await?;
+
+    tx.commit().await?;
+
+    info!(id, "deleted configuration");
+
+    Ok(())
+}
@@ -1,12 +1,18 @@
 mod add;
 mod create;
+mod grant;
+mod rename;
+mod revoke;
 
-use self::{add::add, create::create};
+use self::{add::add, create::create, grant::grant, rename::rename, revoke::revoke};
 use crate::cli::dataset::Command;
 
 pub async fn dataset(command: Command) -> anyhow::Result<()> {
     match command {
         Command::Add(config) => add(config).await,
         Command::Create(config) => create(config).await,
+        Command::Grant(config) => grant(config).await,
+        Command::Rename(config) => rename(config).await,
+        Command::Revoke(config) => revoke(config).await,
     }
 }
@@ -7,7 +7,7 @@ pub(super) async fn create(config: CreateConfig) -> anyhow::Result<()> {
     let pool = PgPoolOptions::new().connect(&config.database_url).await?;
     sqlx::migrate!().run(&pool).await?;
 
-    let dataset_id = dataset::create(&pool, &config.name).await?;
+    let dataset_id = dataset::create(&pool, &config.name, config.public).await?;
 
     info!(id = dataset_id, "created dataset");
 
@@ -0,0 +1,25 @@
+use anyhow::bail;
+use sqlx::postgres::PgPoolOptions;
+use tracing::info;
+
+use crate::{cli::dataset::GrantConfig, store::dataset};
+
+pub(super) async fn grant(config: GrantConfig) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new().connect(&config.database_url).await?;
+    sqlx::migrate!().run(&pool).await?;
+
+    if !dataset::exists(&pool, config.dataset_id).await? {
+        bail!("dataset {} does not exist", config.dataset_id);
+    }
+
+    dataset::grant(&pool, config.dataset_id, &config.subject, config.permission).await?;
+
+    info!(
+        dataset_id = config.dataset_id,
+        subject = config.subject,
+        permission = ?config.permission,
+        "granted dataset permission"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use anyhow::bail;
+use serde_json::json;
+use sqlx::postgres::PgPoolOptions;
+use tracing::info;
+
+use crate::{
+    cli::dataset::RenameConfig,
+    store::{
+        audit_log::{self, Action},
+        dataset,
+    },
+};
+
+pub(super) async fn rename(config: RenameConfig) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new().connect(&config.database_url).await?;
+    sqlx::migrate!().run(&pool).await?;
+
+    let id = config.dataset_id;
+
+    if config.name.trim().is_empty() {
+        bail!("dataset name is empty");
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let Some(previous_name) = dataset::rename(&mut *tx, id, &config.name).await? else {
+        bail!("dataset {id} does not exist");
+    };
+
+    audit_log::create(
+        &mut *tx,
+        None,
+        Action::RenameDataset,
+        id,
+        &json!({ "from": previous_name, "to": config.name }),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    info!(
+        id,
+        from = previous_name,
+        to = config.name,
+        "renamed dataset"
+    );
+
+    Ok(())
+}
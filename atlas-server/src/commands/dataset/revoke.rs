@@ -0,0 +1,26 @@
+use anyhow::bail;
+use sqlx::postgres::PgPoolOptions;
+use tracing::info;
+
+use crate::{cli::dataset::RevokeConfig, store::dataset};
+
+pub(super) async fn revoke(config: RevokeConfig) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new().connect(&config.database_url).await?;
+    sqlx::migrate!().run(&pool).await?;
+
+    if !dataset::revoke(&pool, config.dataset_id, &config.subject).await? {
+        bail!(
+            "subject '{}' has no permission on dataset {}",
+            config.subject,
+            config.dataset_id
+        );
+    }
+
+    info!(
+        dataset_id = config.dataset_id,
+        subject = config.subject,
+        "revoked dataset permission"
+    );
+
+    Ok(())
+}
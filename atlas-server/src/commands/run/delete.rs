@@ -0,0 +1,41 @@
+use anyhow::bail;
+use serde_json::json;
+use sqlx::postgres::PgPoolOptions;
+use tracing::info;
+
+use crate::{
+    cli::run::DeleteConfig,
+    store::{
+        audit_log::{self, Action},
+        run,
+    },
+};
+
+pub(super) async fn delete(config: DeleteConfig) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new().connect(&config.database_url).await?;
+    sqlx::migrate!().run(&pool).await?;
+
+    let mut tx = pool.begin().await?;
+
+    for &id in &config.ids {
+        let Some(dataset_ids) = run::delete(&mut tx, id).await? else {
+            tx.rollback().await?;
+            bail!("run {id} does not exist");
+        };
+
+        audit_log::create(
+            &mut *tx,
+            None,
+            Action::DeleteRun,
+            id,
+            &json!({ "dataset_ids": dataset_ids }),
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    info!(ids = ?config.ids, "deleted runs");
+
+    Ok(())
+}
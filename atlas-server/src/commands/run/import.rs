@@ -1,26 +1,27 @@
-use std::{collections::HashMap, io, path::Path};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
 
-use sqlx::{Postgres, Transaction, postgres::PgPoolOptions};
+use anyhow::Context;
+use sqlx::{Acquire, Postgres, Transaction, postgres::PgPoolOptions};
 use tokio::{
     fs::File,
     io::{AsyncBufReadExt, BufReader},
 };
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
     cli::run::ImportConfig,
-    counts::{Format, feature_names_eq},
-    store::{StrandSpecification, dataset},
+    counts::{Counts, Format},
+    import::{self, Outcome, import_sample},
+    store::{StrandSpecification, feature::find_features, sample},
 };
 
-const BATCH_CHUNK_SIZE: usize = 128;
-
 pub async fn import(config: ImportConfig) -> anyhow::Result<()> {
     let pool = PgPoolOptions::new().connect(&config.database_url).await?;
     sqlx::migrate!().run(&pool).await?;
 
-    let mut tx = pool.begin().await?;
-
     let configuration_id = config.configuration_id;
 
     let feature_name = sqlx::query!(
@@ -31,65 +32,143 @@ pub async fn import(config: ImportConfig) -> anyhow::Result<()> {
     .await
     .map(|record| record.feature_name)?;
 
-    info!(src_count = config.srcs.len(), "reading srcs");
+    let features = find_features(&pool, configuration_id).await?;
 
-    let result = if config.sample_sheet {
-        import_from_sample_sheets(
-            &mut tx,
-            &config.srcs,
-            configuration_id,
-            config.dataset_id,
+    if features.is_empty() {
+        return Err(import::Error::MissingFeatures(configuration_id).into());
+    }
+
+    let samples = if config.sample_sheet {
+        read_sample_sheets(&config.srcs).await?
+    } else {
+        sample_srcs(&config.srcs, &config.sample_name_delimiter)?
+    };
+
+    info!(sample_count = samples.len(), "reading samples");
+
+    // A dry run imports each sample in a savepoint of a transaction that is rolled back, so that
+    // later samples see the changes of earlier ones.
+    let mut dry_run_tx = if config.dry_run {
+        Some(pool.begin().await?)
+    } else {
+        None
+    };
+
+    let mut summary = Summary::default();
+
+    for (sample_name, src) in samples {
+        let counts = read_counts(
+            &src,
             config.format,
             &feature_name,
             config.strand_specification,
-            &config.data_type,
         )
         .await
-    } else {
-        import_from_paths(
+        .with_context(|| format!("failed to read {}", src.display()))?;
+
+        let sample = (sample_name, counts);
+
+        let mut tx = match dry_run_tx.as_mut() {
+            Some(dry_run_tx) => dry_run_tx.begin().await?,
+            None => pool.begin().await?,
+        };
+
+        let outcome = match import_sample(
             &mut tx,
-            &config.srcs,
+            &features,
             configuration_id,
             config.dataset_id,
-            &config.sample_name_delimiter,
-            config.format,
-            &feature_name,
             config.strand_specification,
             &config.data_type,
+            config.on_conflict,
+            &sample,
         )
         .await
-    };
+        {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                tx.rollback().await?;
+                return Err(anyhow::Error::new(e))
+                    .with_context(|| format!("failed to import sample {}", sample.0));
+            }
+        };
 
-    match result {
-        Ok(()) => tx.commit().await?,
-        Err(e) => {
-            tx.rollback().await?;
-            anyhow::bail!("{e}");
-        }
+        tx.commit().await?;
+
+        summary.add(&sample.0, outcome, config.dry_run);
+    }
+
+    if let Some(src) = &config.metadata {
+        let mut tx = match dry_run_tx.as_mut() {
+            Some(dry_run_tx) => dry_run_tx.begin().await?,
+            None => pool.begin().await?,
+        };
+
+        import_metadata(&mut tx, src).await?;
+
+        tx.commit().await?;
+    }
+
+    if let Some(dry_run_tx) = dry_run_tx {
+        dry_run_tx.rollback().await?;
     }
 
+    info!(
+        created = summary.created,
+        skipped = summary.skipped,
+        replaced = summary.replaced,
+        dry_run = config.dry_run,
+        "imported samples"
+    );
+
     Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
-async fn import_from_paths<P>(
-    tx: &mut Transaction<'_, Postgres>,
-    srcs: &[P],
-    configuration_id: i32,
-    dataset_id: Option<i32>,
-    sample_name_delimiter: &str,
-    format: Option<Format>,
-    feature_name: &str,
-    strand_specification: StrandSpecification,
-    data_type: &str,
-) -> anyhow::Result<()>
+#[derive(Default)]
+struct Summary {
+    created: usize,
+    skipped: usize,
+    replaced: usize,
+}
+
+impl Summary {
+    fn add(&mut self, sample_name: &str, outcome: Outcome, dry_run: bool) {
+        let action = match outcome {
+            Outcome::Created(run_id) => {
+                self.created += 1;
+                info!(sample_name, run_id, "created run");
+                "create"
+            }
+            Outcome::Skipped(run_id) => {
+                self.skipped += 1;
+                info!(sample_name, run_id, "skipped existing run");
+                "skip"
+            }
+            Outcome::Replaced {
+                previous_run_id,
+                run_id,
+            } => {
+                self.replaced += 1;
+                info!(sample_name, previous_run_id, run_id, "replaced run");
+                "replace"
+            }
+        };
+
+        if dry_run {
+            println!("{action}\t{sample_name}");
+        }
+    }
+}
+
+/// Returns the sample names and sources of count files.
+///
+/// The sample name is the filename up to the first delimiter.
+fn sample_srcs<P>(srcs: &[P], sample_name_delimiter: &str) -> anyhow::Result<Vec<(String, PathBuf)>>
 where
     P: AsRef<Path>,
 {
-    for src_chunk in srcs.chunks(BATCH_CHUNK_SIZE) {
-        let mut chunk = Vec::with_capacity(src_chunk.len());
-
-        for src in src_chunk {
+    srcs.iter()
+        .map(|src| {
             let path = src.as_ref();
 
             let filename = path
@@ -99,138 +178,59 @@ where
             // SAFETY: `str::Split` always has at least one item.
             let sample_name = filename.split(sample_name_delimiter).next().unwrap();
 
-            let counts = read_counts(path, format, feature_name, strand_specification).await?;
-
-            chunk.push((sample_name.into(), counts));
-        }
-
-        import_batch(
-            tx,
-            configuration_id,
-            dataset_id,
-            strand_specification,
-            data_type,
-            &chunk,
-        )
-        .await?;
-    }
-
-    info!(sample_count = srcs.len(), "imported samples");
-
-    Ok(())
+            Ok((sample_name.into(), path.to_path_buf()))
+        })
+        .collect()
 }
 
-#[allow(clippy::too_many_arguments)]
-async fn import_from_sample_sheets<P>(
-    tx: &mut Transaction<'_, Postgres>,
-    srcs: &[P],
-    configuration_id: i32,
-    dataset_id: Option<i32>,
-    format: Option<Format>,
-    feature_name: &str,
-    strand_specification: StrandSpecification,
-    data_type: &str,
-) -> anyhow::Result<()>
+/// Reads the sample names and sources of sample sheets.
+async fn read_sample_sheets<P>(srcs: &[P]) -> anyhow::Result<Vec<(String, PathBuf)>>
 where
     P: AsRef<Path>,
 {
     const DELIMITER: char = '\t';
 
-    let mut sample_count = 0;
-
-    for src_chunk in srcs.chunks(BATCH_CHUNK_SIZE) {
-        let mut chunk = Vec::new();
+    let mut samples = Vec::new();
 
-        for src in src_chunk {
-            let f = File::open(src).await.map(BufReader::new)?;
+    for src in srcs {
+        let f = File::open(src).await.map(BufReader::new)?;
 
-            let mut lines = f.lines();
+        let mut lines = f.lines();
 
-            while let Some(line) = lines.next_line().await? {
-                let (sample_name, src) = line
-                    .split_once(DELIMITER)
-                    .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+        while let Some(line) = lines.next_line().await? {
+            let (sample_name, src) = line
+                .split_once(DELIMITER)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
 
-                let counts = read_counts(src, format, feature_name, strand_specification).await?;
-
-                chunk.push((sample_name.into(), counts));
-
-                sample_count += 1;
-            }
-
-            import_batch(
-                tx,
-                configuration_id,
-                dataset_id,
-                strand_specification,
-                data_type,
-                &chunk,
-            )
-            .await?;
+            samples.push((sample_name.into(), PathBuf::from(src)));
         }
     }
 
-    info!(sample_count, "imported samples");
-
-    Ok(())
+    Ok(samples)
 }
 
-async fn import_batch(
-    tx: &mut Transaction<'_, Postgres>,
-    configuration_id: i32,
-    dataset_id: Option<i32>,
-    strand_specification: StrandSpecification,
-    data_type: &str,
-    chunk: &[(String, HashMap<String, u32>)],
-) -> anyhow::Result<()> {
-    use crate::store::{
-        count::create_counts,
-        feature::find_features,
-        run::{create_runs, runs_exists},
-        sample::find_or_create_samples,
-    };
-
-    assert!(!chunk.is_empty());
-
-    let features = find_features(&mut **tx, configuration_id).await?;
-
-    if features.is_empty() {
-        anyhow::bail!("configuration {configuration_id} is missing features");
-    }
-
-    let sample_names: Vec<_> = chunk
-        .iter()
-        .map(|(sample_name, _)| sample_name.into())
-        .collect();
-    let sample_ids = find_or_create_samples(&mut **tx, &sample_names).await?;
-
-    if runs_exists(&mut **tx, configuration_id, &sample_ids).await? {
-        anyhow::bail!("run already exists for the sample and configuration");
-    }
-
-    let run_ids = create_runs(
-        tx,
-        configuration_id,
-        &sample_ids,
-        strand_specification,
-        data_type,
-    )
-    .await?;
+async fn import_metadata<P>(tx: &mut Transaction<'_, Postgres>, src: P) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let src = src.as_ref().to_path_buf();
 
-    if let Some(dataset_id) = dataset_id {
-        dataset::create_runs(&mut **tx, dataset_id, &run_ids).await?;
-    }
+    let metadata = tokio::task::spawn_blocking(move || {
+        let mut reader = std::fs::File::open(src).map(std::io::BufReader::new)?;
+        crate::import::read_metadata(&mut reader)
+    })
+    .await??;
 
-    for ((sample_name, counts), &run_id) in chunk.iter().zip(run_ids.iter()) {
-        info!(name = sample_name, "loaded sample");
+    let names = sample::merge_metadata(&mut **tx, &metadata).await?;
 
-        if !feature_names_eq(&features, counts) {
-            anyhow::bail!("feature name set mismatch");
+    for (sample_name, _) in &metadata {
+        if !names.contains(sample_name) {
+            warn!(sample_name, "sample not found; skipping metadata");
         }
-
-        create_counts(tx, run_id, &features, counts).await?;
     }
 
+    info!(sample_count = names.len(), "imported sample metadata");
+
     Ok(())
 }
 
@@ -239,7 +239,7 @@ async fn read_counts<P>(
     format: Option<Format>,
     feature_name: &str,
     strand_specification: StrandSpecification,
-) -> anyhow::Result<HashMap<String, u32>>
+) -> anyhow::Result<Counts>
 where
     P: AsRef<Path>,
 {
@@ -248,15 +248,9 @@ where
 
     let counts = tokio::task::spawn_blocking(move || {
         let mut reader = std::fs::File::open(src).map(std::io::BufReader::new)?;
-
-        atlas_core::counts::reader::read(
-            &mut reader,
-            format.map(|f| f.into()),
-            &feature_name,
-            strand_specification.into(),
-        )
+        crate::import::read_counts(&mut reader, format, &feature_name, strand_specification)
     })
     .await??;
 
-    Ok(counts.into_iter().collect())
+    Ok(counts)
 }
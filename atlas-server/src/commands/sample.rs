@@ -0,0 +1,10 @@
+mod rename;
+
+use self::rename::rename;
+use crate::cli::sample::Command;
+
+pub async fn sample(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Rename(config) => rename(config).await,
+    }
+}
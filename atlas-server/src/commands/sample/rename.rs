@@ -0,0 +1,44 @@
+use anyhow::bail;
+use serde_json::json;
+use sqlx::postgres::PgPoolOptions;
+use tracing::info;
+
+use crate::{
+    cli::sample::RenameConfig,
+    store::{
+        audit_log::{self, Action},
+        sample,
+    },
+};
+
+pub(super) async fn rename(config: RenameConfig) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new().connect(&config.database_url).await?;
+    sqlx::migrate!().run(&pool).await?;
+
+    let id = config.sample_id;
+
+    if config.name.trim().is_empty() {
+        bail!("sample name is empty");
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let Some(previous_name) = sample::rename(&mut *tx, id, &config.name).await? else {
+        bail!("sample {id} does not exist");
+    };
+
+    audit_log::create(
+        &mut *tx,
+        None,
+        Action::RenameSample,
+        id,
+        &json!({ "from": previous_name, "to": config.name }),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    info!(id, from = previous_name, to = config.name, "renamed sample");
+
+    Ok(())
+}
@@ -0,0 +1,12 @@
+mod create;
+mod revoke;
+
+use self::{create::create, revoke::revoke};
+use crate::cli::token::Command;
+
+pub async fn token(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Create(config) => create(config).await,
+        Command::Revoke(config) => revoke(config).await,
+    }
+}
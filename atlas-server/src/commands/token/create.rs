@@ -0,0 +1,23 @@
+use sqlx::postgres::PgPoolOptions;
+use tracing::info;
+
+use crate::{
+    auth::{generate_token, hash_token},
+    cli::token::CreateConfig,
+    store::api_token,
+};
+
+pub(super) async fn create(config: CreateConfig) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new().connect(&config.database_url).await?;
+    sqlx::migrate!().run(&pool).await?;
+
+    let token = generate_token();
+    let id = api_token::create(&pool, &config.subject, &hash_token(&token)).await?;
+
+    info!(id, subject = config.subject, "created API token");
+
+    // The token is only stored hashed, so this is the only time it is shown.
+    println!("{token}");
+
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use sqlx::postgres::PgPoolOptions;
+use tracing::info;
+
+use crate::{cli::token::RevokeConfig, store::api_token};
+
+pub(super) async fn revoke(config: RevokeConfig) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new().connect(&config.database_url).await?;
+    sqlx::migrate!().run(&pool).await?;
+
+    let count = api_token::delete_by_subject(&pool, &config.subject).await?;
+
+    info!(count, subject = config.subject, "revoked API tokens");
+
+    Ok(())
+}
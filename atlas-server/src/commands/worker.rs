@@ -1,87 +1,481 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
 use serde::Serialize;
-use sqlx::postgres::{PgListener, PgPoolOptions};
-use tokio::{signal, sync::watch};
-use tracing::{info, info_span};
+use sqlx::{
+    PgPool,
+    postgres::{PgListener, PgPoolOptions},
+};
+use tokio::{
+    signal,
+    sync::watch,
+    task::{self, JoinError, JoinHandle, JoinSet},
+    time,
+};
+use tracing::{Instrument, error, info, info_span, warn};
+use uuid::Uuid;
 
 use crate::{
     cli::WorkerConfig,
-    queue::{Message, PlotMessage, Queue, task::plot},
+    queue::{
+        BatchCorrectionMessage, DifferentialExpressionMessage, Message, Options, PlotMessage,
+        Progress, Queue, SampleSimilarityMessage, Task,
+        task::{
+            batch_correction, batch_correction::CorrectedCounts, differential_expression,
+            differential_expression::DifferentialExpression, plot, plot::Plot, sample_similarity,
+            sample_similarity::SampleSimilarity,
+        },
+    },
+    store::Metadata,
 };
 
+/// How often expired tasks and results are deleted.
+const GARBAGE_COLLECTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct PlotBody {
     sample_names: Vec<String>,
+    metadata: Vec<Metadata>,
     x: Vec<f64>,
     y: Vec<f64>,
 }
 
+impl From<Plot> for PlotBody {
+    fn from(plot: Plot) -> Self {
+        Self {
+            sample_names: plot.sample_names,
+            metadata: plot.metadata,
+            x: plot.xs,
+            y: plot.ys,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchCorrectionBody {
+    sample_names: Vec<String>,
+    feature_names: Vec<String>,
+    counts: Vec<Vec<u32>>,
+}
+
+impl From<CorrectedCounts> for BatchCorrectionBody {
+    fn from(corrected_counts: CorrectedCounts) -> Self {
+        Self {
+            sample_names: corrected_counts.sample_names,
+            feature_names: corrected_counts.feature_names,
+            counts: corrected_counts.counts,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DifferentialExpressionFeature {
+    name: String,
+    log2_fold_change: Option<f64>,
+    p_value: Option<f64>,
+    adjusted_p_value: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct DifferentialExpressionBody {
+    features: Vec<DifferentialExpressionFeature>,
+}
+
+impl From<DifferentialExpression> for DifferentialExpressionBody {
+    fn from(results: DifferentialExpression) -> Self {
+        fn finite(n: f64) -> Option<f64> {
+            n.is_finite().then_some(n)
+        }
+
+        let features = results
+            .feature_names
+            .into_iter()
+            .zip(results.log2_fold_changes)
+            .zip(results.p_values)
+            .zip(results.adjusted_p_values)
+            .map(|(((name, log2_fold_change), p_value), adjusted_p_value)| {
+                DifferentialExpressionFeature {
+                    name,
+                    log2_fold_change: finite(log2_fold_change),
+                    p_value: finite(p_value),
+                    adjusted_p_value: finite(adjusted_p_value),
+                }
+            })
+            .collect();
+
+        Self { features }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SampleSimilarityBody {
+    sample_names: Vec<String>,
+    metadata: Vec<Metadata>,
+    values: Vec<Vec<Option<f64>>>,
+    order: Vec<usize>,
+}
+
+impl From<SampleSimilarity> for SampleSimilarityBody {
+    fn from(similarity: SampleSimilarity) -> Self {
+        let values = similarity
+            .values
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|n| n.is_finite().then_some(n))
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            sample_names: similarity.sample_names,
+            metadata: similarity.metadata,
+            values,
+            order: similarity.order,
+        }
+    }
+}
+
+/// An error from processing a task.
+#[derive(Debug, thiserror::Error)]
+enum TaskError {
+    #[error(transparent)]
+    Plot(#[from] plot::Error),
+    #[error(transparent)]
+    BatchCorrection(#[from] batch_correction::Error),
+    #[error(transparent)]
+    DifferentialExpression(#[from] differential_expression::Error),
+    #[error(transparent)]
+    SampleSimilarity(#[from] sample_similarity::Error),
+    #[error("invalid result: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+impl TaskError {
+    fn is_transient(&self) -> bool {
+        match self {
+            Self::Plot(e) => e.is_transient(),
+            Self::BatchCorrection(e) => e.is_transient(),
+            Self::DifferentialExpression(e) => e.is_transient(),
+            Self::SampleSimilarity(e) => e.is_transient(),
+            Self::Serialization(_) => false,
+        }
+    }
+}
+
 pub async fn worker(config: WorkerConfig) -> anyhow::Result<()> {
+    let kinds = parse_kinds(&config.kinds)?;
+
     let pool = PgPoolOptions::new().connect(&config.database_url).await?;
     sqlx::migrate!().run(&pool).await?;
 
     let mut rx = PgListener::connect_with(&pool).await?;
     rx.listen("queue").await?;
 
-    let queue = Queue::new(pool.clone());
+    let options = Options {
+        max_retries: config.max_retries,
+        lease_duration: Duration::from_secs(config.lease_duration),
+        retention: Duration::from_secs(config.task_retention_days * 24 * 60 * 60),
+    };
+    let queue = Queue::with_options(pool.clone(), options).with_kinds(kinds.clone());
+
+    let concurrency = config.concurrency.get();
+    let drain_timeout = Duration::from_secs(config.drain_timeout);
 
     let (signal_tx, signal_rx) = watch::channel(());
 
     tokio::spawn(async move {
-        signal::ctrl_c()
-            .await
-            .expect("failed to install Ctrl+C listener");
-
+        shutdown_signal().await;
         info!("received shutdown signal");
-
         drop(signal_rx);
     });
 
-    info!("worker initialized");
+    info!(concurrency, ?kinds, "worker initialized");
+
+    let mut tasks = JoinSet::new();
+    let mut running_task_ids = HashMap::new();
 
     let mut wait_for_notification = false;
+    let mut collected_at: Option<Instant> = None;
 
     loop {
-        if wait_for_notification {
+        let is_full = tasks.len() >= concurrency;
+
+        if wait_for_notification || is_full {
+            // Expired leases do not notify, so they are also checked periodically.
             tokio::select! {
-                _notification = rx.recv() => {},
+                _notification = rx.recv(), if !is_full => {},
+                Some(result) = tasks.join_next_with_id(), if !tasks.is_empty() => {
+                    finish(&mut running_task_ids, result);
+                    wait_for_notification = false;
+                    continue;
+                }
+                _ = time::sleep(options.lease_duration) => {},
                 _ = signal_tx.closed() => break,
             }
         } else if signal_tx.is_closed() {
             break;
         }
 
-        if let Some(task) = queue.pull_front().await? {
-            let span = info_span!("task", id = ?task.id);
-            let _guard = span.enter();
-
-            info!("started processing task");
-
-            match task.message.0 {
-                Message::Noop => queue.success(task.id, Option::<()>::None).await?,
-                Message::Plot(PlotMessage {
-                    configuration_id,
-                    additional_runs,
-                    options,
-                }) => match plot(&pool, configuration_id, &additional_runs, options).await {
-                    Ok((sample_names, xs, ys)) => {
-                        let body = PlotBody {
-                            sample_names,
-                            x: xs,
-                            y: ys,
-                        };
-
-                        queue.success(task.id, body).await?;
-                    }
-                    Err(_) => queue.failed(task.id).await?,
-                },
+        let abandoned_task_count = queue.requeue_expired().await?;
+
+        if abandoned_task_count > 0 {
+            warn!(abandoned_task_count, "re-queued abandoned tasks");
+        }
+
+        if collected_at.is_none_or(|t| t.elapsed() >= GARBAGE_COLLECTION_INTERVAL) {
+            let deleted_task_count = queue.delete_expired().await?;
+
+            if deleted_task_count > 0 {
+                info!(deleted_task_count, "deleted expired tasks");
             }
 
-            info!("finished processing task");
+            collected_at = Some(Instant::now());
+        }
+
+        if tasks.len() >= concurrency {
+            continue;
+        }
+
+        if let Some(task) = queue.pull_front().await? {
+            let id = task.id;
+            let span = info_span!("task", ?id, attempt = task.attempts);
+            let handle =
+                tasks.spawn(process(pool.clone(), queue.clone(), options, task).instrument(span));
+            running_task_ids.insert(handle.id(), id);
+            wait_for_notification = false;
         } else {
             wait_for_notification = true;
         }
     }
 
+    drain(&queue, tasks, running_task_ids, drain_timeout).await
+}
+
+/// Parses and validates the kinds of tasks a worker subscribes to.
+fn parse_kinds(names: &[String]) -> anyhow::Result<Vec<&'static str>> {
+    names
+        .iter()
+        .map(|name| {
+            Message::KINDS
+                .into_iter()
+                .find(|kind| kind == name)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "invalid task kind: {name} (expected one of {})",
+                        Message::KINDS.join(", ")
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Completes when the process receives SIGINT (Ctrl+C) or SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C listener");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM listener")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+type TaskResult = Result<(task::Id, anyhow::Result<()>), JoinError>;
+
+/// Removes a joined task from the running tasks, logging its error, if any.
+///
+/// A task that fails to update its status, e.g., due to a database error, or that panics keeps
+/// its lease, so it is re-queued or marked as failed after the lease expires.
+fn finish(running_task_ids: &mut HashMap<task::Id, Uuid>, result: TaskResult) {
+    match result {
+        Ok((handle_id, result)) => {
+            let id = running_task_ids.remove(&handle_id);
+
+            if let Err(e) = result {
+                error!(?id, error = ?e, "task errored");
+            }
+        }
+        Err(e) => {
+            let id = running_task_ids.remove(&e.id());
+            warn!(?id, error = ?e, "task panicked");
+        }
+    }
+}
+
+/// Waits for running tasks to finish, re-queueing those that do not finish within the timeout.
+async fn drain(
+    queue: &Queue,
+    mut tasks: JoinSet<anyhow::Result<()>>,
+    mut running_task_ids: HashMap<task::Id, Uuid>,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    if tasks.is_empty() {
+        return Ok(());
+    }
+
+    info!(running_task_count = tasks.len(), "draining running tasks");
+
+    let result = time::timeout(timeout, async {
+        while let Some(result) = tasks.join_next_with_id().await {
+            finish(&mut running_task_ids, result);
+        }
+    })
+    .await;
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(_) => {
+            tasks.abort_all();
+
+            for (_, id) in running_task_ids {
+                if queue.release(id).await? {
+                    warn!(?id, "re-queued unfinished task");
+                }
+            }
+
+            // Computations on the blocking thread pool cannot be aborted, and the runtime waits
+            // for them on shutdown, so exit without waiting.
+            std::process::exit(0);
+        }
+    }
+}
+
+async fn process(pool: PgPool, queue: Queue, options: Options, task: Task) -> anyhow::Result<()> {
+    info!("started processing task");
+
+    let id = task.id;
+    let progress = Progress::default();
+    let mut heartbeat = AbortOnDrop(tokio::spawn(heartbeat(
+        queue.clone(),
+        id,
+        options.lease_duration / 3,
+        progress.clone(),
+    )));
+
+    let result = tokio::select! {
+        result = execute(&pool, task.message.0, &progress) => result,
+        _ = &mut heartbeat.0 => {
+            info!("task cancelled");
+            return Ok(());
+        }
+    };
+
+    drop(heartbeat);
+
+    match result {
+        Ok(body) => queue.success(id, body).await?,
+        Err(e) if e.is_transient() => {
+            warn!(error = ?e, "task failed");
+
+            if let Some(status) = queue.retry(id, &e.to_string()).await? {
+                info!(?status, "updated task status");
+            }
+        }
+        Err(e) => {
+            warn!(error = ?e, "task failed");
+            queue.failed(id, &e.to_string()).await?;
+        }
+    }
+
+    info!("finished processing task");
+
     Ok(())
 }
+
+/// Aborts a spawned task when dropped, e.g., when the task that owns it is aborted.
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+async fn execute(
+    pool: &PgPool,
+    message: Message,
+    progress: &Progress,
+) -> Result<serde_json::Value, TaskError> {
+    let body = match message {
+        Message::Noop => serde_json::Value::Null,
+        Message::Plot(PlotMessage {
+            runs,
+            additional_runs,
+            options,
+            mode,
+        }) => {
+            let result = plot(pool, &runs, &additional_runs, options, mode, progress).await?;
+            serde_json::to_value(PlotBody::from(result))?
+        }
+        Message::BatchCorrection(BatchCorrectionMessage {
+            dataset_id,
+            batches,
+            covariates,
+        }) => {
+            let corrected_counts =
+                batch_correction(pool, dataset_id, &batches, &covariates, progress).await?;
+            serde_json::to_value(BatchCorrectionBody::from(corrected_counts))?
+        }
+        Message::DifferentialExpression(DifferentialExpressionMessage {
+            method,
+            reference_run_ids,
+            treatment_run_ids,
+            ..
+        }) => {
+            let results = differential_expression::differential_expression(
+                pool,
+                method,
+                &reference_run_ids,
+                &treatment_run_ids,
+                progress,
+            )
+            .await?;
+
+            serde_json::to_value(DifferentialExpressionBody::from(results))?
+        }
+        Message::SampleSimilarity(SampleSimilarityMessage { dataset_id, metric }) => {
+            let similarity =
+                sample_similarity::sample_similarity(pool, dataset_id, metric, progress).await?;
+            serde_json::to_value(SampleSimilarityBody::from(similarity))?
+        }
+    };
+
+    Ok(body)
+}
+
+/// Refreshes the lease of a running task until it is no longer running.
+async fn heartbeat(queue: Queue, id: Uuid, period: Duration, progress: Progress) {
+    let mut interval = time::interval(period);
+
+    loop {
+        interval.tick().await;
+
+        match queue.heartbeat(id, progress.get()).await {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => warn!(error = ?e, "failed to update task heartbeat"),
+        }
+    }
+}
@@ -4,7 +4,25 @@ pub use self::format::Format;
 
 use std::collections::HashMap;
 
-pub fn feature_names_eq(features: &[(i32, String)], counts: &HashMap<String, u32>) -> bool {
+/// The counts of a sample by feature name.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Counts {
+    Integer(HashMap<String, u32>),
+    /// Real-valued counts, e.g., expected counts or TPMs.
+    Real(HashMap<String, f64>),
+}
+
+impl Counts {
+    /// Returns whether the counts have exactly the names of the given features.
+    pub fn feature_names_eq(&self, features: &[(i32, String)]) -> bool {
+        match self {
+            Self::Integer(counts) => feature_names_eq(features, counts),
+            Self::Real(counts) => feature_names_eq(features, counts),
+        }
+    }
+}
+
+pub fn feature_names_eq<T>(features: &[(i32, String)], counts: &HashMap<String, T>) -> bool {
     if features.len() != counts.len() {
         return false;
     }
@@ -25,7 +43,7 @@ mod tests {
     #[test]
     fn test_feature_names_eq() {
         let features = [];
-        let counts = HashMap::new();
+        let counts: HashMap<String, u32> = HashMap::new();
         assert!(feature_names_eq(&features, &counts));
 
         let features = [(1, String::from("f1"))];
@@ -6,13 +6,8 @@ pub enum Format {
     HtseqCount,
     /// STAR counts.
     Star,
-}
-
-impl From<Format> for atlas_core::counts::reader::Format {
-    fn from(format: Format) -> Self {
-        match format {
-            Format::HtseqCount => Self::HtseqCount,
-            Format::Star => Self::Star,
-        }
-    }
+    /// Real-valued counts, e.g., expected counts or TPMs.
+    ///
+    /// The layout is the same as htseq-count counts: tab-separated feature names and values.
+    Real,
 }
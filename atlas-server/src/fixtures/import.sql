@@ -0,0 +1,22 @@
+insert into annotations (name, genome_build) values ('GENCODE 40', 'GRCh38.p13');
+
+insert into configurations
+  (annotation_id, feature_type, feature_name)
+values
+  (1, 'gene', 'gene_name');
+
+insert into features
+  (configuration_id, name, length)
+values
+  (1, 'feature_1', 8),
+  (1, 'feature_2', 13);
+
+insert into samples (name) values ('sample_1');
+
+insert into runs
+  (sample_id, configuration_id, strand_specification, data_type)
+values
+  (1, 1, 'reverse', 'RNA-Seq');
+
+insert into datasets (name) values ('dataset_1'), ('dataset_2');
+insert into datasets_runs (dataset_id, run_id) values (1, 1);
@@ -0,0 +1,510 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead},
+};
+
+use atlas_core::features::{Feature, calculate_feature_lengths};
+use clap::ValueEnum;
+use serde_json::json;
+use sqlx::{Postgres, Transaction};
+use tracing::info;
+
+use crate::{
+    counts::{Counts, Format},
+    store::{
+        Metadata, StrandSpecification,
+        count::{Values, align_counts},
+        dataset,
+    },
+};
+
+/// The maximum number of samples imported in a single batch.
+pub const BATCH_CHUNK_SIZE: usize = 128;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("database error")]
+    Database(#[from] sqlx::Error),
+    #[error("I/O error")]
+    Io(#[from] io::Error),
+    #[error("configuration {0} is missing features")]
+    MissingFeatures(i32),
+    #[error("run already exists for the sample and configuration")]
+    RunExists,
+    #[error("feature name set mismatch: {0}")]
+    FeatureNameSetMismatch(String),
+    #[error("invalid counts")]
+    InvalidCounts(#[source] anyhow::Error),
+}
+
+/// How to import a sample that already has a run with the configuration.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum OnConflict {
+    /// Fail with [`Error::RunExists`].
+    #[default]
+    Error,
+    /// Keep the existing run.
+    Skip,
+    /// Delete the existing run and import the sample again.
+    Replace,
+}
+
+/// The result of [`import_sample`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    /// The sample had no run, and a run with the given ID was created.
+    Created(i32),
+    /// The sample already had a run with the given ID.
+    Skipped(i32),
+    /// The sample's run was deleted and replaced by a new run.
+    Replaced { previous_run_id: i32, run_id: i32 },
+}
+
+/// Creates a configuration and its features.
+///
+/// Returns the configuration ID and the number of features created.
+pub async fn import_configuration(
+    tx: &mut Transaction<'_, Postgres>,
+    annotations_name: &str,
+    annotations_genome_build: &str,
+    feature_type: &str,
+    feature_name: &str,
+    features: &HashMap<String, Vec<Feature>>,
+) -> Result<(i32, usize), Error> {
+    use crate::store::{
+        annotations::find_or_create_annotations, configuration, feature::create_features,
+    };
+
+    let annotations =
+        find_or_create_annotations(tx, annotations_name, annotations_genome_build).await?;
+
+    info!(id = annotations.id, "loaded annotations");
+
+    let configuration_id =
+        configuration::create(tx, annotations.id, feature_type, feature_name).await?;
+
+    info!(id = configuration_id, "imported configuration");
+
+    let mut names: Vec<_> = features.keys().cloned().collect();
+    names.sort();
+
+    let lengths: Vec<_> = calculate_feature_lengths(features, &names)?
+        .into_iter()
+        .map(i32::try_from)
+        .collect::<Result<_, _>>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    create_features(tx, configuration_id, &names, &lengths).await?;
+
+    info!("imported {} features", names.len());
+
+    Ok((configuration_id, names.len()))
+}
+
+/// Reads features from GFF3.
+pub fn read_features<R>(
+    reader: &mut R,
+    feature_type: &str,
+    feature_name: &str,
+) -> io::Result<HashMap<String, Vec<Feature>>>
+where
+    R: BufRead,
+{
+    use atlas_core::features::read_features;
+
+    let (_, features) = read_features(reader, feature_type, feature_name)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    Ok(features)
+}
+
+/// Reads htseq-count, STAR, or real-valued feature counts.
+pub fn read_counts<R>(
+    reader: &mut R,
+    format: Option<Format>,
+    feature_name: &str,
+    strand_specification: StrandSpecification,
+) -> io::Result<Counts>
+where
+    R: BufRead,
+{
+    use atlas_core::counts::reader::{self, Format as ReaderFormat};
+
+    let format = match format {
+        Some(Format::HtseqCount) => Some(ReaderFormat::HtseqCount),
+        Some(Format::Star) => Some(ReaderFormat::Star),
+        Some(Format::Real) => {
+            let counts = reader::read_real(reader)?;
+            return Ok(Counts::Real(counts.into_iter().collect()));
+        }
+        None => None,
+    };
+
+    let counts = reader::read(reader, format, feature_name, strand_specification.into())?;
+
+    Ok(Counts::Integer(counts.into_iter().collect()))
+}
+
+/// Reads sample metadata from a tab-separated table.
+///
+/// The first row is a header. The first column is the sample name, and each other column is a
+/// metadata attribute named by its header. Empty cells are skipped.
+pub fn read_metadata<R>(reader: &mut R) -> io::Result<Vec<(String, Metadata)>>
+where
+    R: BufRead,
+{
+    const DELIMITER: char = '\t';
+
+    let mut lines = reader.lines();
+
+    let header = lines
+        .next()
+        .transpose()?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing header"))?;
+
+    let keys: Vec<_> = header.split(DELIMITER).skip(1).collect();
+
+    let mut records = Vec::new();
+
+    for result in lines {
+        let line = result?;
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut fields = line.split(DELIMITER);
+        // SAFETY: `str::Split` always has at least one item.
+        let sample_name = fields.next().unwrap();
+
+        let values: Vec<_> = fields.collect();
+
+        if values.len() != keys.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{sample_name}: expected {} attributes", keys.len()),
+            ));
+        }
+
+        let metadata = keys
+            .iter()
+            .zip(values)
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| ((*key).into(), value.into()))
+            .collect();
+
+        records.push((sample_name.into(), metadata));
+    }
+
+    Ok(records)
+}
+
+/// Creates samples, runs, and counts for a batch of sample counts.
+///
+/// If a dataset ID is given, the runs are added to the dataset. Returns the created run IDs.
+pub async fn import_batch(
+    tx: &mut Transaction<'_, Postgres>,
+    configuration_id: i32,
+    dataset_id: Option<i32>,
+    strand_specification: StrandSpecification,
+    data_type: &str,
+    chunk: &[(String, Counts)],
+) -> Result<Vec<i32>, Error> {
+    use crate::store::feature::find_features;
+
+    let features = find_features(&mut **tx, configuration_id).await?;
+
+    if features.is_empty() {
+        return Err(Error::MissingFeatures(configuration_id));
+    }
+
+    create_batch(
+        tx,
+        &features,
+        configuration_id,
+        dataset_id,
+        strand_specification,
+        data_type,
+        chunk,
+    )
+    .await
+}
+
+/// Creates a sample, run, and counts, handling an existing run of the sample with the
+/// configuration according to `on_conflict`.
+///
+/// `features` are the features of the configuration. If a dataset ID is given, the run is added
+/// to the dataset. A replacing run keeps the metadata of the run it replaces and is also added to
+/// its datasets.
+#[allow(clippy::too_many_arguments)]
+pub async fn import_sample(
+    tx: &mut Transaction<'_, Postgres>,
+    features: &[(i32, String)],
+    configuration_id: i32,
+    dataset_id: Option<i32>,
+    strand_specification: StrandSpecification,
+    data_type: &str,
+    on_conflict: OnConflict,
+    sample: &(String, Counts),
+) -> Result<Outcome, Error> {
+    use crate::store::{
+        audit_log::{self, Action},
+        run,
+        sample::find_or_create_samples,
+    };
+
+    let sample_ids = find_or_create_samples(&mut **tx, std::slice::from_ref(&sample.0)).await?;
+    let previous_run_id = run::find_id(&mut **tx, configuration_id, sample_ids[0]).await?;
+
+    let (previous_dataset_ids, previous_metadata) = match (previous_run_id, on_conflict) {
+        (None, _) => (Vec::new(), None),
+        (Some(_), OnConflict::Error) => return Err(Error::RunExists),
+        (Some(id), OnConflict::Skip) => return Ok(Outcome::Skipped(id)),
+        (Some(id), OnConflict::Replace) => {
+            let metadata = run::find_metadata(&mut **tx, id).await?;
+            let dataset_ids = run::delete(tx, id).await?.unwrap_or_default();
+            (dataset_ids, metadata)
+        }
+    };
+
+    let run_ids = create_batch(
+        tx,
+        features,
+        configuration_id,
+        dataset_id,
+        strand_specification,
+        data_type,
+        std::slice::from_ref(sample),
+    )
+    .await?;
+
+    let run_id = run_ids[0];
+
+    let Some(previous_run_id) = previous_run_id else {
+        return Ok(Outcome::Created(run_id));
+    };
+
+    if let Some(metadata) = previous_metadata {
+        run::set_metadata(&mut **tx, run_id, &metadata).await?;
+    }
+
+    for &id in &previous_dataset_ids {
+        if Some(id) != dataset_id {
+            dataset::add(&mut **tx, id, run_id).await?;
+        }
+    }
+
+    audit_log::create(
+        &mut **tx,
+        None,
+        Action::DeleteRun,
+        previous_run_id,
+        &json!({ "dataset_ids": previous_dataset_ids, "replaced_by": run_id }),
+    )
+    .await?;
+
+    Ok(Outcome::Replaced {
+        previous_run_id,
+        run_id,
+    })
+}
+
+async fn create_batch(
+    tx: &mut Transaction<'_, Postgres>,
+    features: &[(i32, String)],
+    configuration_id: i32,
+    dataset_id: Option<i32>,
+    strand_specification: StrandSpecification,
+    data_type: &str,
+    chunk: &[(String, Counts)],
+) -> Result<Vec<i32>, Error> {
+    use crate::store::{
+        count::copy_counts,
+        run::{create_runs, runs_exists},
+        sample::find_or_create_samples,
+    };
+
+    assert!(!chunk.is_empty());
+
+    let sample_names: Vec<_> = chunk
+        .iter()
+        .map(|(sample_name, _)| sample_name.into())
+        .collect();
+    let sample_ids = find_or_create_samples(&mut **tx, &sample_names).await?;
+
+    if runs_exists(&mut **tx, configuration_id, &sample_ids).await? {
+        return Err(Error::RunExists);
+    }
+
+    let run_ids = create_runs(
+        tx,
+        configuration_id,
+        &sample_ids,
+        strand_specification,
+        data_type,
+    )
+    .await?;
+
+    if let Some(dataset_id) = dataset_id {
+        dataset::create_runs(&mut **tx, dataset_id, &run_ids).await?;
+    }
+
+    let mut run_counts = Vec::with_capacity(chunk.len());
+
+    for ((sample_name, counts), &run_id) in chunk.iter().zip(run_ids.iter()) {
+        info!(name = sample_name, "loaded sample");
+
+        if !counts.feature_names_eq(features) {
+            return Err(Error::FeatureNameSetMismatch(sample_name.into()));
+        }
+
+        let values = align(features, counts).map_err(|e| Error::InvalidCounts(e.into()))?;
+
+        run_counts.push((run_id, values));
+    }
+
+    copy_counts(tx, &run_counts).await?;
+
+    Ok(run_ids)
+}
+
+fn align(features: &[(i32, String)], counts: &Counts) -> io::Result<Values> {
+    match counts {
+        Counts::Integer(counts) => align_counts(features, counts)?
+            .into_iter()
+            .map(|n| i32::try_from(n).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+            .collect::<io::Result<_>>()
+            .map(Values::Integer),
+        Counts::Real(counts) => align_counts(features, counts).map(Values::Real),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test(fixtures("import"))]
+    async fn test_import_sample(pool: PgPool) -> anyhow::Result<()> {
+        use crate::store::{count, feature::find_features, run};
+
+        let features = find_features(&pool, 1).await?;
+
+        let build_sample = |name: &str| {
+            let counts = HashMap::from([
+                (String::from("feature_1"), 8),
+                (String::from("feature_2"), 0),
+            ]);
+            (String::from(name), Counts::Integer(counts))
+        };
+
+        let mut tx = pool.begin().await?;
+
+        let metadata = Metadata::from([(String::from("tissue"), String::from("liver"))]);
+        run::set_metadata(&mut *tx, 1, &metadata).await?;
+
+        let mut import = async |sample, on_conflict| {
+            import_sample(
+                &mut tx,
+                &features,
+                1,
+                Some(2),
+                StrandSpecification::Reverse,
+                "RNA-Seq",
+                on_conflict,
+                &sample,
+            )
+            .await
+        };
+
+        assert_eq!(
+            import(build_sample("sample_2"), OnConflict::Error).await?,
+            Outcome::Created(2)
+        );
+        assert!(matches!(
+            import(build_sample("sample_1"), OnConflict::Error).await,
+            Err(Error::RunExists)
+        ));
+        assert_eq!(
+            import(build_sample("sample_1"), OnConflict::Skip).await?,
+            Outcome::Skipped(1)
+        );
+        assert_eq!(
+            import(build_sample("sample_1"), OnConflict::Replace).await?,
+            Outcome::Replaced {
+                previous_run_id: 1,
+                run_id: 3
+            }
+        );
+
+        let counts = HashMap::from([
+            (String::from("feature_1"), 1.5),
+            (String::from("feature_2"), 0.25),
+        ]);
+        assert_eq!(
+            import(
+                (String::from("sample_3"), Counts::Real(counts)),
+                OnConflict::Error
+            )
+            .await?,
+            Outcome::Created(4)
+        );
+
+        assert!(dataset::contains(&mut *tx, 1, 3).await?);
+        assert!(dataset::contains(&mut *tx, 2, 3).await?);
+
+        assert_eq!(run::find_metadata(&mut *tx, 3).await?, Some(metadata));
+        assert_eq!(
+            run::find_metadata(&mut *tx, 4).await?,
+            Some(Metadata::new())
+        );
+
+        let run_ids = sqlx::query_scalar!("select id from runs order by id")
+            .fetch_all(&mut *tx)
+            .await?;
+        assert_eq!(run_ids, [2, 3, 4]);
+
+        let values = count::find_values(&mut *tx, 1, &[3, 4]).await?;
+        assert_eq!(
+            values,
+            [Values::Integer(vec![8, 0]), Values::Real(vec![1.5, 0.25])]
+        );
+
+        let details = sqlx::query_scalar!("select details from audit_log where target_id = 1")
+            .fetch_one(&mut *tx)
+            .await?;
+        assert_eq!(details, json!({ "dataset_ids": [1], "replaced_by": 3 }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_metadata() -> io::Result<()> {
+        let data = b"sample\ttissue\tsex\nsample_1\tliver\tF\nsample_2\tlung\t\n";
+        let actual = read_metadata(&mut &data[..])?;
+
+        let expected = [
+            (
+                String::from("sample_1"),
+                Metadata::from([
+                    (String::from("sex"), String::from("F")),
+                    (String::from("tissue"), String::from("liver")),
+                ]),
+            ),
+            (
+                String::from("sample_2"),
+                Metadata::from([(String::from("tissue"), String::from("lung"))]),
+            ),
+        ];
+
+        assert_eq!(actual, expected);
+
+        let data = b"sample\ttissue\nsample_1\tliver\tF\n";
+        assert!(read_metadata(&mut &data[..]).is_err());
+
+        assert!(read_metadata(&mut &b""[..]).is_err());
+
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
+pub(crate) mod auth;
 pub mod cli;
 pub mod commands;
 pub(crate) mod counts;
+pub(crate) mod import;
 pub mod queue;
 pub mod server;
 pub(crate) mod store;
@@ -12,10 +12,13 @@ async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
+        Commands::Admin(command) => commands::admin(command).await?,
         Commands::Configuration(command) => commands::configuration(command).await?,
         Commands::Dataset(command) => commands::dataset(command).await?,
         Commands::Run(command) => commands::run(command).await?,
+        Commands::Sample(command) => commands::sample(command).await?,
         Commands::Server(config) => commands::server(config).await?,
+        Commands::Token(command) => commands::token(command).await?,
         Commands::Worker(config) => commands::worker(config).await?,
     }
 
@@ -1,17 +1,53 @@
 pub mod task;
 
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
 
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, types::Json};
+use sha2::{Digest, Sha256};
+use sqlx::{PgExecutor, PgPool, types::Json};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
-use self::task::plot;
+use self::task::{differential_expression, plot, sample_similarity};
+
+/// The notification channel on which the ID of a task is sent when its status changes after it
+/// starts, i.e., when it finishes or is re-queued.
+pub const STATUS_CHANNEL: &str = "task_status";
 
 #[derive(Clone)]
 pub struct Queue {
     pool: PgPool,
+    options: Options,
+    /// The kinds of tasks pulled. If empty, all kinds are pulled.
+    kinds: Vec<&'static str>,
+}
+
+/// Retry and lease settings of a queue.
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// The number of times a failed or abandoned task is re-queued before it is marked as failed.
+    pub max_retries: u32,
+    /// How long a running task may go without a heartbeat before it is considered abandoned.
+    pub lease_duration: Duration,
+    /// How long finished tasks and their results are kept.
+    pub retention: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            lease_duration: Duration::from_secs(60),
+            retention: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, sqlx::Type)]
@@ -22,43 +58,227 @@ pub enum Status {
     Running,
     Success,
     Failed,
+    Cancelled,
+}
+
+impl Status {
+    /// Returns whether the task will not be processed again.
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Success | Self::Failed | Self::Cancelled)
+    }
 }
 
 pub struct Task {
     pub id: Uuid,
     pub status: Status,
     pub message: Json<Message>,
+    /// The number of times the task was pulled from the queue, including this one.
+    pub attempts: i32,
     pub created_at: OffsetDateTime,
 }
 
+/// The progress of a running task, from 0 to 1.
+///
+/// This is shared between the task and its heartbeat, which persists it.
+#[derive(Clone, Debug, Default)]
+pub struct Progress(Arc<AtomicU32>);
+
+impl Progress {
+    pub fn set(&self, value: f32) {
+        self.0
+            .store(value.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// A selection of runs.
+///
+/// This is the union of the runs in the given datasets and the runs with the given IDs.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct RunSelection {
+    #[serde(default)]
+    pub dataset_ids: Vec<i32>,
+    #[serde(default)]
+    pub run_ids: Vec<i32>,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct PlotMessage {
-    pub configuration_id: i32,
+    pub runs: RunSelection,
     pub additional_runs: Vec<(String, HashMap<String, i32>)>,
     pub options: plot::Options,
+    #[serde(default)]
+    pub mode: plot::Mode,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct BatchCorrectionMessage {
+    pub dataset_id: i32,
+    /// Batch by sample name.
+    pub batches: HashMap<String, String>,
+    /// Covariate values by sample name, keyed by covariate name.
+    pub covariates: BTreeMap<String, HashMap<String, String>>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DifferentialExpressionMessage {
+    pub dataset_id: i32,
+    pub method: differential_expression::Method,
+    pub reference_run_ids: Vec<i32>,
+    pub treatment_run_ids: Vec<i32>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SampleSimilarityMessage {
+    pub dataset_id: i32,
+    pub metric: sample_similarity::Metric,
 }
 
 #[derive(Deserialize, Serialize)]
 pub enum Message {
     Noop,
     Plot(PlotMessage),
+    BatchCorrection(BatchCorrectionMessage),
+    DifferentialExpression(DifferentialExpressionMessage),
+    SampleSimilarity(SampleSimilarityMessage),
+}
+
+impl Message {
+    /// The names of all kinds of tasks.
+    pub const KINDS: [&'static str; 5] = [
+        "noop",
+        "plot",
+        "batch_correction",
+        "differential_expression",
+        "sample_similarity",
+    ];
+
+    /// Returns the name of the kind of task.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Noop => "noop",
+            Self::Plot(_) => "plot",
+            Self::BatchCorrection(_) => "batch_correction",
+            Self::DifferentialExpression(_) => "differential_expression",
+            Self::SampleSimilarity(_) => "sample_similarity",
+        }
+    }
+
+    /// Returns a content hash of the message and the runs it selects.
+    ///
+    /// `run_ids` must be sorted. Because the selected runs are part of the key, changing the
+    /// runs in a dataset changes the key of messages that select it.
+    pub fn key(&self, run_ids: &[i32]) -> Vec<u8> {
+        #[derive(Serialize)]
+        struct Key<'a, T> {
+            kind: &'static str,
+            run_ids: &'a [i32],
+            parameters: T,
+        }
+
+        fn sorted<K: Ord, V>(map: &HashMap<K, V>) -> BTreeMap<&K, &V> {
+            map.iter().collect()
+        }
+
+        fn digest<T: Serialize>(key: &Key<'_, T>) -> Vec<u8> {
+            // SAFETY: Keys only contain maps with string keys.
+            let buf = serde_json::to_vec(key).unwrap();
+            Sha256::digest(buf).to_vec()
+        }
+
+        let kind = self.kind();
+
+        match self {
+            Self::Noop => digest(&Key {
+                kind,
+                run_ids,
+                parameters: (),
+            }),
+            Self::Plot(message) => {
+                let additional_runs: BTreeMap<_, _> = message
+                    .additional_runs
+                    .iter()
+                    .map(|(name, counts)| (name, sorted(counts)))
+                    .collect();
+
+                digest(&Key {
+                    kind,
+                    run_ids,
+                    parameters: (additional_runs, &message.options, message.mode),
+                })
+            }
+            Self::BatchCorrection(message) => {
+                let covariates: BTreeMap<_, _> = message
+                    .covariates
+                    .iter()
+                    .map(|(name, values)| (name, sorted(values)))
+                    .collect();
+
+                digest(&Key {
+                    kind,
+                    run_ids,
+                    parameters: (sorted(&message.batches), covariates),
+                })
+            }
+            Self::DifferentialExpression(message) => digest(&Key {
+                kind,
+                run_ids,
+                parameters: (
+                    message.method,
+                    &message.reference_run_ids,
+                    &message.treatment_run_ids,
+                ),
+            }),
+            Self::SampleSimilarity(message) => digest(&Key {
+                kind,
+                run_ids,
+                parameters: message.metric,
+            }),
+        }
+    }
 }
 
 impl Queue {
     pub fn new(pool: PgPool) -> Self {
-        Queue { pool }
+        Self::with_options(pool, Options::default())
     }
 
+    pub fn with_options(pool: PgPool, options: Options) -> Self {
+        Queue {
+            pool,
+            options,
+            kinds: Vec::new(),
+        }
+    }
+
+    /// Restricts the tasks pulled from the queue to the given kinds.
+    ///
+    /// See [`Message::KINDS`]. If `kinds` is empty, all kinds are pulled.
+    pub fn with_kinds(mut self, kinds: Vec<&'static str>) -> Self {
+        self.kinds = kinds;
+        self
+    }
+
+    /// Marks the oldest queued task of a subscribed kind as running and returns it.
     pub async fn pull_front(&self) -> sqlx::Result<Option<Task>> {
         sqlx::query_as!(
             Task,
             r#"
             update tasks
-            set status = $1
+            set
+                status = $1,
+                attempts = attempts + 1,
+                progress = 0,
+                started_at = now(),
+                heartbeat_at = now()
             where id = (
                 select id
                 from tasks
                 where status = $2
+                    and (cardinality($3::text[]) = 0 or kind = any($3))
                 order by created_at
                 for update skip locked
                 limit 1
@@ -67,23 +287,78 @@ impl Queue {
                 id,
                 status "status: Status",
                 message "message: Json<Message>",
+                attempts,
                 created_at
         "#,
             Status::Running as Status,
             Status::Queued as Status,
+            &self.kinds as &[&str],
         )
         .fetch_optional(&self.pool)
         .await
     }
 
+    /// Records that a running task is still being processed.
+    ///
+    /// Returns false if the task is no longer running, e.g., because it was cancelled.
+    pub async fn heartbeat(&self, id: Uuid, progress: f32) -> sqlx::Result<bool> {
+        sqlx::query!(
+            "
+            update tasks
+            set heartbeat_at = now(), progress = $1
+            where id = $2 and status = $3
+            ",
+            progress,
+            id,
+            Status::Running as Status,
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+    }
+
+    /// Re-queues running tasks whose lease expired.
+    ///
+    /// Tasks that exhausted their retries are marked as failed instead. Returns the number of
+    /// abandoned tasks.
+    pub async fn requeue_expired(&self) -> sqlx::Result<u64> {
+        let ids = sqlx::query_scalar!(
+            "
+            update tasks
+            set
+                status = case when attempts > $1 then $2::status else $3::status end,
+                error = 'worker stopped responding',
+                finished_at = case when attempts > $1 then now() end
+            where status = $4
+                and heartbeat_at < now() - make_interval(secs => $5)
+            returning id
+            ",
+            self.options.max_retries as i32,
+            Status::Failed as Status,
+            Status::Queued as Status,
+            Status::Running as Status,
+            self.options.lease_duration.as_secs_f64(),
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        for &id in &ids {
+            notify_status(&self.pool, id).await?;
+        }
+
+        Ok(ids.len() as u64)
+    }
+
     pub async fn push_back(&self, message: Message) -> sqlx::Result<Uuid> {
         let id = Uuid::new_v4();
+        let kind = message.kind();
         let message = Json(message);
 
         sqlx::query!(
-            r#"insert into tasks (id, status, message) values ($1, $2, $3)"#,
+            r#"insert into tasks (id, status, kind, message) values ($1, $2, $3, $4)"#,
             id,
             Status::Queued as Status,
+            kind,
             message as Json<Message>,
         )
         .execute(&self.pool)
@@ -94,18 +369,106 @@ impl Queue {
         Ok(id)
     }
 
+    /// Returns the ID of a queued, running, or successful task with the given key or pushes a
+    /// new task.
+    ///
+    /// `subject` is the submitter of a new task. An existing task keeps its submitter.
+    pub async fn find_or_push_back(
+        &self,
+        message: Message,
+        key: &[u8],
+        subject: Option<&str>,
+    ) -> sqlx::Result<Uuid> {
+        let kind = message.kind();
+        let message = Json(message);
+
+        loop {
+            let id = Uuid::new_v4();
+
+            let result = sqlx::query!(
+                r#"
+                insert into tasks (id, status, kind, message, key, subject)
+                values ($1, $2, $3, $4, $5, $6)
+                on conflict (key) where status in ('queued', 'running', 'success') do nothing
+                "#,
+                id,
+                Status::Queued as Status,
+                kind,
+                &message as &Json<Message>,
+                key,
+                subject,
+            )
+            .execute(&self.pool)
+            .await?;
+
+            if result.rows_affected() > 0 {
+                sqlx::query!("notify queue").execute(&self.pool).await?;
+                return Ok(id);
+            }
+
+            let id = sqlx::query_scalar!(
+                "
+                select id
+                from tasks
+                where key = $1 and status in ('queued', 'running', 'success')
+                ",
+                key,
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+
+            // Otherwise, the conflicting task failed or was cancelled in the meantime.
+            if let Some(id) = id {
+                return Ok(id);
+            }
+        }
+    }
+
+    /// Deletes tasks that finished before the retention period and their results.
+    ///
+    /// Returns the number of deleted tasks.
+    pub async fn delete_expired(&self) -> sqlx::Result<i64> {
+        sqlx::query_scalar!(
+            r#"
+            with deleted_tasks as (
+                delete from tasks
+                where status in ('success', 'failed', 'cancelled')
+                    and coalesce(finished_at, created_at) < now() - make_interval(secs => $1)
+                returning id
+            ), deleted_results as (
+                delete from results
+                where id in (select id from deleted_tasks)
+            )
+            select count(*) as "count!" from deleted_tasks
+            "#,
+            self.options.retention.as_secs_f64(),
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
     pub async fn success<S>(&self, id: Uuid, body: S) -> sqlx::Result<()>
     where
         S: Serialize + Send + Sync,
     {
-        sqlx::query!(
-            "update tasks set status = $1 where id = $2",
+        let result = sqlx::query!(
+            "
+            update tasks
+            set status = $1, progress = 1, error = null, finished_at = now()
+            where id = $2 and status = $3
+            ",
             Status::Success as Status,
             id,
+            Status::Running as Status,
         )
         .execute(&self.pool)
         .await?;
 
+        // The task was cancelled or abandoned while running.
+        if result.rows_affected() == 0 {
+            return Ok(());
+        }
+
         sqlx::query!(
             "insert into results (id, body) values ($1, $2)",
             id,
@@ -114,19 +477,140 @@ impl Queue {
         .execute(&self.pool)
         .await?;
 
+        notify_status(&self.pool, id).await
+    }
+
+    /// Marks a running task as failed with the given reason.
+    pub async fn failed(&self, id: Uuid, error: &str) -> sqlx::Result<()> {
+        let result = sqlx::query!(
+            "
+            update tasks
+            set status = $1, error = $2, finished_at = now()
+            where id = $3 and status = $4
+            ",
+            Status::Failed as Status,
+            error,
+            id,
+            Status::Running as Status,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() > 0 {
+            notify_status(&self.pool, id).await?;
+        }
+
         Ok(())
     }
 
-    pub async fn failed(&self, id: Uuid) -> sqlx::Result<()> {
-        sqlx::query!(
-            "update tasks set status = $1 where id = $2",
+    /// Re-queues a running task that failed with the given reason.
+    ///
+    /// The task is marked as failed instead if it exhausted its retries. Returns the new status,
+    /// or `None` if the task is no longer running.
+    pub async fn retry(&self, id: Uuid, error: &str) -> sqlx::Result<Option<Status>> {
+        let status = sqlx::query_scalar!(
+            r#"
+            update tasks
+            set
+                status = case when attempts > $1 then $2::status else $3::status end,
+                error = $4,
+                finished_at = case when attempts > $1 then now() end
+            where id = $5 and status = $6
+            returning status as "status: Status"
+            "#,
+            self.options.max_retries as i32,
             Status::Failed as Status,
+            Status::Queued as Status,
+            error,
             id,
+            Status::Running as Status,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if status.is_some() {
+            notify_status(&self.pool, id).await?;
+        }
+
+        Ok(status)
+    }
+
+    /// Re-queues a running task that the worker stopped before it finished, e.g., on shutdown.
+    ///
+    /// Unlike [`Self::retry`], this does not count as an attempt. Returns false if the task is no
+    /// longer running.
+    pub async fn release(&self, id: Uuid) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            "
+            update tasks
+            set
+                status = $1,
+                attempts = greatest(attempts - 1, 0),
+                progress = 0,
+                started_at = null,
+                heartbeat_at = null
+            where id = $2 and status = $3
+            ",
+            Status::Queued as Status,
+            id,
+            Status::Running as Status,
         )
         .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query!("notify queue").execute(&self.pool).await?;
+        notify_status(&self.pool, id).await?;
+
+        Ok(true)
+    }
+
+    /// Cancels a queued or running task.
+    ///
+    /// Returns the status of the task before cancellation, or `None` if the task does not exist.
+    /// Finished tasks are left as is.
+    pub async fn cancel(&self, id: Uuid) -> sqlx::Result<Option<Status>> {
+        let mut tx = self.pool.begin().await?;
+
+        let status = sqlx::query_scalar!(
+            r#"select status as "status: Status" from tasks where id = $1 for update"#,
+            id,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(status) = status
+            && !status.is_finished()
+        {
+            sqlx::query!(
+                "update tasks set status = $1, finished_at = now() where id = $2",
+                Status::Cancelled as Status,
+                id,
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            // This is sent when the transaction commits.
+            notify_status(&mut *tx, id).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(status)
+    }
+}
+
+async fn notify_status<'a, E>(executor: E, id: Uuid) -> sqlx::Result<()>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!("select pg_notify($1, $2)", STATUS_CHANNEL, id.to_string())
+        .execute(executor)
         .await
         .map(|_| ())
-    }
 }
 
 #[cfg(test)]
@@ -159,6 +643,7 @@ mod tests {
                 id,
                 status "status: Status",
                 message "message: Json<Message>",
+                attempts,
                 created_at
             from tasks
             order by created_at
@@ -188,6 +673,7 @@ mod tests {
                 id,
                 status "status: Status",
                 message "message: Json<Message>",
+                attempts,
                 created_at
             from tasks
             where id = $1
@@ -201,4 +687,250 @@ mod tests {
 
         Ok(())
     }
+
+    async fn find_status(pool: &PgPool, id: Uuid) -> sqlx::Result<(Status, Option<String>)> {
+        sqlx::query!(
+            r#"select status as "status: Status", error from tasks where id = $1"#,
+            id,
+        )
+        .fetch_one(pool)
+        .await
+        .map(|row| (row.status, row.error))
+    }
+
+    #[sqlx::test]
+    async fn test_retry(pool: PgPool) -> sqlx::Result<()> {
+        let options = Options {
+            max_retries: 1,
+            ..Default::default()
+        };
+        let queue = Queue::with_options(pool.clone(), options);
+        let id = queue.push_back(Message::Noop).await?;
+
+        let task = queue.pull_front().await?.unwrap();
+        assert_eq!(task.attempts, 1);
+        assert_eq!(queue.retry(id, "timeout").await?, Some(Status::Queued));
+        assert_eq!(
+            find_status(&pool, id).await?,
+            (Status::Queued, Some(String::from("timeout")))
+        );
+
+        let task = queue.pull_front().await?.unwrap();
+        assert_eq!(task.attempts, 2);
+        assert_eq!(queue.retry(id, "timeout").await?, Some(Status::Failed));
+        assert_eq!(queue.retry(id, "timeout").await?, None);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_requeue_expired(pool: PgPool) -> sqlx::Result<()> {
+        let options = Options {
+            max_retries: 0,
+            lease_duration: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let queue = Queue::with_options(pool.clone(), options);
+        let id = queue.push_back(Message::Noop).await?;
+        queue.pull_front().await?;
+
+        assert_eq!(queue.requeue_expired().await?, 0);
+        assert!(queue.heartbeat(id, 0.5).await?);
+
+        sqlx::query!(
+            "update tasks set heartbeat_at = now() - interval '2 minutes' where id = $1",
+            id,
+        )
+        .execute(&pool)
+        .await?;
+
+        assert_eq!(queue.requeue_expired().await?, 1);
+        assert_eq!(
+            find_status(&pool, id).await?,
+            (
+                Status::Failed,
+                Some(String::from("worker stopped responding"))
+            )
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_cancel(pool: PgPool) -> sqlx::Result<()> {
+        let queue = Queue::new(pool.clone());
+        let id = queue.push_back(Message::Noop).await?;
+        queue.pull_front().await?;
+
+        assert_eq!(queue.cancel(id).await?, Some(Status::Running));
+        assert!(!queue.heartbeat(id, 0.5).await?);
+
+        queue.success(id, Option::<()>::None).await?;
+        assert_eq!(find_status(&pool, id).await?.0, Status::Cancelled);
+
+        assert_eq!(queue.cancel(id).await?, Some(Status::Cancelled));
+        assert_eq!(queue.cancel(Uuid::new_v4()).await?, None);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_pull_front_with_kinds(pool: PgPool) -> sqlx::Result<()> {
+        let queue = Queue::new(pool.clone());
+        let noop_id = queue.push_back(Message::Noop).await?;
+        let plot_id = queue
+            .push_back(Message::Plot(PlotMessage {
+                runs: RunSelection::default(),
+                additional_runs: Vec::new(),
+                options: plot::Options::default(),
+                mode: plot::Mode::default(),
+            }))
+            .await?;
+
+        let plot_queue = queue.clone().with_kinds(vec!["plot", "batch_correction"]);
+        assert_eq!(
+            plot_queue.pull_front().await?.map(|task| task.id),
+            Some(plot_id)
+        );
+        assert!(plot_queue.pull_front().await?.is_none());
+
+        assert_eq!(queue.pull_front().await?.map(|task| task.id), Some(noop_id));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_release(pool: PgPool) -> sqlx::Result<()> {
+        let queue = Queue::new(pool.clone());
+        let id = queue.push_back(Message::Noop).await?;
+        queue.pull_front().await?;
+
+        assert!(queue.release(id).await?);
+        assert!(!queue.release(id).await?);
+
+        let task = queue.pull_front().await?.unwrap();
+        assert_eq!(task.id, id);
+        assert_eq!(task.attempts, 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_status_notifications(pool: PgPool) -> sqlx::Result<()> {
+        use sqlx::postgres::PgListener;
+
+        let mut listener = PgListener::connect_with(&pool).await?;
+        listener.listen(STATUS_CHANNEL).await?;
+
+        let queue = Queue::new(pool.clone());
+
+        let id = queue.push_back(Message::Noop).await?;
+        queue.pull_front().await?;
+        queue.success(id, Option::<()>::None).await?;
+        assert_eq!(listener.recv().await?.payload(), id.to_string());
+
+        let id = queue.push_back(Message::Noop).await?;
+        queue.cancel(id).await?;
+        assert_eq!(listener.recv().await?.payload(), id.to_string());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_find_or_push_back(pool: PgPool) -> sqlx::Result<()> {
+        let queue = Queue::new(pool.clone());
+
+        let key = Message::Noop.key(&[1, 2]);
+        assert_ne!(key, Message::Noop.key(&[1]));
+
+        let id = queue.find_or_push_back(Message::Noop, &key, None).await?;
+        assert_eq!(
+            queue.find_or_push_back(Message::Noop, &key, None).await?,
+            id
+        );
+
+        queue.pull_front().await?;
+        queue.success(id, Option::<()>::None).await?;
+        assert_eq!(
+            queue.find_or_push_back(Message::Noop, &key, None).await?,
+            id
+        );
+
+        sqlx::query!("update tasks set status = 'failed' where id = $1", id)
+            .execute(&pool)
+            .await?;
+        assert_ne!(
+            queue.find_or_push_back(Message::Noop, &key, None).await?,
+            id
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_key() {
+        let message = |batches: &[(&str, &str)]| {
+            Message::BatchCorrection(BatchCorrectionMessage {
+                dataset_id: 1,
+                batches: batches
+                    .iter()
+                    .map(|&(name, batch)| (name.into(), batch.into()))
+                    .collect(),
+                covariates: BTreeMap::new(),
+            })
+        };
+
+        let key = message(&[("s1", "a"), ("s2", "b"), ("s3", "a")]).key(&[1, 2, 3]);
+
+        for _ in 0..8 {
+            assert_eq!(
+                message(&[("s3", "a"), ("s2", "b"), ("s1", "a")]).key(&[1, 2, 3]),
+                key
+            );
+        }
+
+        assert_ne!(
+            message(&[("s1", "a"), ("s2", "a"), ("s3", "a")]).key(&[1, 2, 3]),
+            key
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_delete_expired(pool: PgPool) -> sqlx::Result<()> {
+        let options = Options {
+            retention: Duration::from_secs(60 * 60),
+            ..Default::default()
+        };
+        let queue = Queue::with_options(pool.clone(), options);
+
+        let finished_id = queue.push_back(Message::Noop).await?;
+        queue.pull_front().await?;
+        queue.success(finished_id, Option::<()>::None).await?;
+        let queued_id = queue.push_back(Message::Noop).await?;
+
+        assert_eq!(queue.delete_expired().await?, 0);
+
+        sqlx::query!(
+            "
+            update tasks
+            set created_at = now() - interval '2 hours', finished_at = now() - interval '2 hours'
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        assert_eq!(queue.delete_expired().await?, 1);
+
+        let ids = sqlx::query_scalar!("select id from tasks")
+            .fetch_all(&pool)
+            .await?;
+        assert_eq!(ids, [queued_id]);
+
+        let result_count = sqlx::query_scalar!(r#"select count(*) as "count!" from results"#)
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(result_count, 0);
+
+        Ok(())
+    }
 }
@@ -1,3 +1,27 @@
+pub mod batch_correction;
+mod counts;
+pub mod differential_expression;
 pub mod plot;
+pub mod sample_similarity;
 
-pub use self::plot::plot;
+pub use self::{
+    batch_correction::batch_correction, differential_expression::differential_expression,
+    plot::plot, sample_similarity::sample_similarity,
+};
+
+/// Runs CPU-bound work on the blocking thread pool so that it does not stall the async runtime.
+///
+/// Panics in `f` are resumed in the caller.
+async fn compute<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(e) => match e.try_into_panic() {
+            Ok(payload) => std::panic::resume_unwind(payload),
+            Err(e) => panic!("blocking task failed: {e}"),
+        },
+    }
+}
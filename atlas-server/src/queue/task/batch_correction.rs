@@ -0,0 +1,171 @@
+mod error;
+
+use std::collections::{BTreeMap, HashMap};
+
+use atlas_core::counts::batch_correction::combat_seq;
+use sqlx::PgPool;
+
+pub use self::error::Error;
+use super::{
+    compute,
+    counts::{Counts, read_counts},
+};
+use crate::{queue::Progress, store::count::Values};
+
+/// Batch corrected counts.
+pub struct CorrectedCounts {
+    pub sample_names: Vec<String>,
+    pub feature_names: Vec<String>,
+    /// Counts per sample.
+    pub counts: Vec<Vec<u32>>,
+}
+
+pub async fn batch_correction(
+    pool: &PgPool,
+    dataset_id: i32,
+    batches: &HashMap<String, String>,
+    covariates: &BTreeMap<String, HashMap<String, String>>,
+    progress: &Progress,
+) -> Result<CorrectedCounts, Error> {
+    use crate::store::{dataset, run};
+
+    let configuration_ids = dataset::configuration_ids(pool, dataset_id).await?;
+
+    if configuration_ids.len() != 1 {
+        return Err(Error::NonhomogeneousDataset);
+    }
+
+    // SAFETY: `configuration_ids` is non-empty.
+    let configuration_id = configuration_ids[0];
+
+    let run_ids = run::select_ids(pool, &[dataset_id], &[]).await?;
+
+    let Counts {
+        sample_names,
+        feature_names,
+        values,
+        ..
+    } = read_counts(pool, configuration_id, &run_ids).await?;
+
+    let sample_batches = sample_names
+        .iter()
+        .map(|sample_name| {
+            batches
+                .get(sample_name)
+                .cloned()
+                .ok_or_else(|| Error::MissingBatch(sample_name.into()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut covariate_columns = Vec::new();
+
+    for (name, values) in covariates {
+        let column = sample_names
+            .iter()
+            .map(|sample_name| {
+                values
+                    .get(sample_name)
+                    .ok_or_else(|| Error::MissingCovariate {
+                        name: name.into(),
+                        sample_name: sample_name.into(),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        covariate_columns.extend(combat_seq::encode_covariate(&column));
+    }
+
+    // Count models require integer counts.
+    let Values::Integer(values) = values else {
+        return Err(Error::RealValuedCounts);
+    };
+
+    let raw_counts = values
+        .into_iter()
+        .map(|n| u32::try_from(n).map_err(|_| Error::InvalidCount(n)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    progress.set(0.25);
+
+    let (sample_count, feature_count) = (sample_names.len(), feature_names.len());
+
+    let counts = compute(move || {
+        combat_seq::correct_vec(
+            sample_count,
+            feature_count,
+            raw_counts,
+            &sample_batches,
+            &covariate_columns,
+        )
+    })
+    .await?;
+
+    Ok(CorrectedCounts {
+        sample_names,
+        feature_names,
+        counts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    fn build_batches(batches: &[(&str, &str)]) -> HashMap<String, String> {
+        batches
+            .iter()
+            .map(|(sample_name, batch)| ((*sample_name).into(), (*batch).into()))
+            .collect()
+    }
+
+    #[sqlx::test(fixtures("batch_correction"))]
+    async fn test_batch_correction(pool: PgPool) -> anyhow::Result<()> {
+        let progress = Progress::default();
+        let covariates = BTreeMap::new();
+
+        let batches = build_batches(&[
+            ("sample_1", "a"),
+            ("sample_2", "a"),
+            ("sample_3", "b"),
+            ("sample_4", "b"),
+        ]);
+
+        let actual = batch_correction(&pool, 1, &batches, &covariates, &progress).await?;
+        assert_eq!(
+            actual.sample_names,
+            ["sample_1", "sample_2", "sample_3", "sample_4"]
+        );
+        assert_eq!(
+            actual.feature_names,
+            ["feature_1", "feature_2", "feature_3"]
+        );
+        assert_eq!(actual.counts.len(), 4);
+        assert!(actual.counts.iter().all(|counts| counts.len() == 3));
+
+        let partial_batches = build_batches(&[("sample_1", "a"), ("sample_2", "a")]);
+        assert!(matches!(
+            batch_correction(&pool, 1, &partial_batches, &covariates, &progress).await,
+            Err(Error::MissingBatch(sample_name)) if sample_name == "sample_3"
+        ));
+
+        let covariates = BTreeMap::from([(
+            String::from("tissue"),
+            HashMap::from([(String::from("sample_1"), String::from("liver"))]),
+        )]);
+        assert!(matches!(
+            batch_correction(&pool, 1, &batches, &covariates, &progress).await,
+            Err(Error::MissingCovariate { name, sample_name })
+                if name == "tissue" && sample_name == "sample_2"
+        ));
+
+        let batches = build_batches(&[("sample_5", "a"), ("sample_6", "b")]);
+        assert!(matches!(
+            batch_correction(&pool, 2, &batches, &BTreeMap::new(), &progress).await,
+            Err(Error::RealValuedCounts)
+        ));
+
+        Ok(())
+    }
+}
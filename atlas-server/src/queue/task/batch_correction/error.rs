@@ -0,0 +1,37 @@
+use crate::store::count;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("database error")]
+    Database(#[from] sqlx::Error),
+    #[error("correction error")]
+    Correction(#[from] std::io::Error),
+    #[error("dataset is nonhomogeneous")]
+    NonhomogeneousDataset,
+    #[error("missing batch: {0}")]
+    MissingBatch(String),
+    #[error("missing covariate value: {name}: {sample_name}")]
+    MissingCovariate { name: String, sample_name: String },
+    #[error("invalid count: {0}")]
+    InvalidCount(i32),
+    #[error("counts are real-valued")]
+    RealValuedCounts,
+    #[error("runs have both integer and real-valued counts")]
+    MixedCounts,
+}
+
+impl Error {
+    /// Returns whether the task may succeed if retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Database(_))
+    }
+}
+
+impl From<count::Error> for Error {
+    fn from(e: count::Error) -> Self {
+        match e {
+            count::Error::Database(e) => Self::Database(e),
+            count::Error::MixedValues => Self::MixedCounts,
+        }
+    }
+}
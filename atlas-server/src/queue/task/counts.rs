@@ -0,0 +1,153 @@
+use sqlx::{PgPool, types::Json};
+
+use crate::store::{
+    Metadata,
+    count::{self, Values},
+    feature,
+};
+
+/// Raw counts of runs in a configuration.
+pub(super) struct Counts {
+    pub sample_names: Vec<String>,
+    /// The sample metadata merged with the run metadata, per sample.
+    pub metadata: Vec<Metadata>,
+    pub feature_names: Vec<String>,
+    /// A row-major samples × features matrix.
+    ///
+    /// This is real-valued if the counts of the runs are.
+    pub values: Values,
+}
+
+/// Reads the raw counts of the given runs in a configuration.
+///
+/// Samples are ordered by run ID. Runs not in the configuration are ignored. Fails if runs have
+/// both integer and real-valued counts.
+pub(super) async fn read_counts(
+    pool: &PgPool,
+    configuration_id: i32,
+    run_ids: &[i32],
+) -> Result<Counts, count::Error> {
+    let feature_count = feature::count(pool, configuration_id).await? as usize;
+
+    let runs = sqlx::query!(
+        "
+        select
+            runs.id,
+            samples.name
+        from runs
+        inner join samples
+            on runs.sample_id = samples.id
+        where runs.configuration_id = $1
+            and runs.id = any($2)
+        order by runs.id
+        ",
+        configuration_id,
+        run_ids,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if feature_count == 0 || runs.is_empty() {
+        return Ok(Counts {
+            sample_names: Vec::new(),
+            metadata: Vec::new(),
+            feature_names: Vec::new(),
+            values: Values::Integer(Vec::new()),
+        });
+    }
+
+    let feature_names = sqlx::query_scalar!(
+        "select name from features where configuration_id = $1 order by id",
+        configuration_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let run_ids: Vec<_> = runs.iter().map(|run| run.id).collect();
+    let sample_names = runs.into_iter().map(|run| run.name).collect();
+
+    let runs = count::find_values(pool, configuration_id, &run_ids).await?;
+    let values = Values::concat(runs, feature_count)?;
+
+    // Run attributes take precedence over sample attributes of the same name.
+    let metadata = sqlx::query_scalar!(
+        r#"
+        select samples.metadata || runs.metadata as "metadata!: Json<Metadata>"
+        from runs
+        inner join samples
+            on runs.sample_id = samples.id
+        where runs.configuration_id = $1
+            and runs.id = any($2)
+        order by runs.id
+        "#,
+        configuration_id,
+        &run_ids,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|Json(metadata)| metadata)
+    .collect();
+
+    Ok(Counts {
+        sample_names,
+        metadata,
+        feature_names,
+        values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test(fixtures("counts"))]
+    async fn test_read_counts(pool: PgPool) -> anyhow::Result<()> {
+        let counts = read_counts(&pool, 1, &[1, 3]).await?;
+        assert_eq!(counts.sample_names, ["sample_1"]);
+        assert_eq!(counts.feature_names, ["feature_1", "feature_2"]);
+        assert_eq!(counts.values, Values::Integer(vec![8, 13]));
+
+        sqlx::query(
+            r#"update samples set metadata = '{"tissue": "liver", "sex": "F"}' where id = 1"#,
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(r#"update runs set metadata = '{"tissue": "lung"}' where id = 1"#)
+            .execute(&pool)
+            .await?;
+
+        let counts = read_counts(&pool, 1, &[2, 1]).await?;
+        assert_eq!(counts.sample_names, ["sample_1", "sample_2"]);
+        assert_eq!(
+            counts.metadata,
+            [
+                Metadata::from([
+                    (String::from("sex"), String::from("F")),
+                    (String::from("tissue"), String::from("lung")),
+                ]),
+                Metadata::new(),
+            ]
+        );
+        assert_eq!(counts.values, Values::Integer(vec![8, 13, 21, 0]));
+
+        sqlx::query(
+            "update run_counts set values = null, real_values = '{1.5, 0.25}' where run_id = 2",
+        )
+        .execute(&pool)
+        .await?;
+
+        let counts = read_counts(&pool, 1, &[2]).await?;
+        assert_eq!(counts.values, Values::Real(vec![1.5, 0.25]));
+
+        assert!(matches!(
+            read_counts(&pool, 1, &[1, 2]).await,
+            Err(count::Error::MixedValues)
+        ));
+
+        let counts = read_counts(&pool, 1, &[]).await?;
+        assert!(counts.sample_names.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+mod error;
+
+use atlas_core::counts::differential_expression::{self, test};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+pub use self::error::Error;
+use super::{
+    compute,
+    counts::{Counts, read_counts},
+};
+use crate::{queue::Progress, store::count::Values};
+
+/// A differential expression test.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    /// A Wald test of a negative binomial GLM (DESeq2-style).
+    #[default]
+    Wald,
+    /// A moderated t-test of precision weighted log-CPM values (limma-voom-style).
+    Voom,
+}
+
+impl From<Method> for differential_expression::Method {
+    fn from(method: Method) -> Self {
+        match method {
+            Method::Wald => Self::Wald,
+            Method::Voom => Self::Voom,
+        }
+    }
+}
+
+/// Test results per feature of the treatment group relative to the reference group.
+///
+/// Values are NaN for features that cannot be tested.
+pub struct DifferentialExpression {
+    pub feature_names: Vec<String>,
+    pub log2_fold_changes: Vec<f64>,
+    pub p_values: Vec<f64>,
+    pub adjusted_p_values: Vec<f64>,
+}
+
+pub async fn differential_expression(
+    pool: &PgPool,
+    method: Method,
+    reference_run_ids: &[i32],
+    treatment_run_ids: &[i32],
+    progress: &Progress,
+) -> Result<DifferentialExpression, Error> {
+    use crate::store::run;
+
+    let mut run_ids: Vec<_> = reference_run_ids
+        .iter()
+        .chain(treatment_run_ids)
+        .copied()
+        .collect();
+
+    run_ids.sort_unstable();
+    run_ids.dedup();
+
+    let configuration_ids = run::configuration_ids(pool, &run_ids).await?;
+
+    let [configuration_id] = configuration_ids[..] else {
+        return Err(Error::NonhomogeneousRuns);
+    };
+
+    // Samples are ordered by run ID.
+    let Counts {
+        sample_names,
+        feature_names,
+        values,
+        ..
+    } = read_counts(pool, configuration_id, &run_ids).await?;
+
+    // Count models require integer counts.
+    let Values::Integer(values) = values else {
+        return Err(Error::RealValuedCounts);
+    };
+
+    let counts = values
+        .into_iter()
+        .map(|n| u32::try_from(n).map_err(|_| Error::InvalidCount(n)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let is_treatment: Vec<_> = run_ids
+        .iter()
+        .map(|id| treatment_run_ids.contains(id))
+        .collect();
+
+    progress.set(0.25);
+
+    let (sample_count, feature_count) = (sample_names.len(), feature_names.len());
+
+    let results = compute(move || {
+        test(
+            method.into(),
+            sample_count,
+            feature_count,
+            &counts,
+            &is_treatment,
+        )
+    })
+    .await?;
+
+    Ok(DifferentialExpression {
+        feature_names,
+        log2_fold_changes: results.log2_fold_changes,
+        p_values: results.p_values,
+        adjusted_p_values: results.adjusted_p_values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test(fixtures("plot"))]
+    async fn test_differential_expression(pool: PgPool) -> anyhow::Result<()> {
+        let progress = Progress::default();
+
+        // Groups are matched to runs regardless of their order.
+        let actual =
+            differential_expression(&pool, Method::Wald, &[6, 4, 5], &[3, 1, 2], &progress).await?;
+
+        assert_eq!(
+            actual.feature_names,
+            ["feature_1", "feature_2", "feature_3"]
+        );
+        assert!(actual.log2_fold_changes[0] > 4.0);
+        assert!(actual.log2_fold_changes[1].abs() < 1.0);
+        assert!(actual.log2_fold_changes[2] < -4.0);
+        assert!(actual.adjusted_p_values[0] < 0.05);
+
+        assert!(matches!(
+            differential_expression(&pool, Method::Wald, &[8], &[9], &progress).await,
+            Err(Error::NonhomogeneousRuns)
+        ));
+
+        sqlx::query("update run_counts set real_values = values::float8[], values = null")
+            .execute(&pool)
+            .await?;
+
+        assert!(matches!(
+            differential_expression(&pool, Method::Wald, &[4, 5, 6], &[1, 2, 3], &progress).await,
+            Err(Error::RealValuedCounts)
+        ));
+
+        Ok(())
+    }
+}
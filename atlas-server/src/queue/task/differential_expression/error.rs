@@ -0,0 +1,35 @@
+use crate::store::count;
+
+use atlas_core::counts::differential_expression::DifferentialExpressionError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("database error")]
+    Database(#[from] sqlx::Error),
+    #[error(transparent)]
+    Test(#[from] DifferentialExpressionError),
+    #[error("runs are nonhomogeneous")]
+    NonhomogeneousRuns,
+    #[error("invalid count: {0}")]
+    InvalidCount(i32),
+    #[error("counts are real-valued")]
+    RealValuedCounts,
+    #[error("runs have both integer and real-valued counts")]
+    MixedCounts,
+}
+
+impl Error {
+    /// Returns whether the task may succeed if retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Database(_))
+    }
+}
+
+impl From<count::Error> for Error {
+    fn from(e: count::Error) -> Self {
+        match e {
+            count::Error::Database(e) => Self::Database(e),
+            count::Error::MixedValues => Self::MixedCounts,
+        }
+    }
+}
@@ -0,0 +1,41 @@
+insert into annotations (name, genome_build) values ('GENCODE 40', 'GRCh38.p13');
+
+insert into configurations
+  (annotation_id, feature_type, feature_name)
+values
+  (1, 'exon', 'gene_name');
+
+insert into features
+  (configuration_id, name, length)
+values
+  (1, 'feature_1', 8),
+  (1, 'feature_2', 13),
+  (1, 'feature_3', 21);
+
+insert into samples
+  (name)
+values
+  ('sample_1'), ('sample_2'), ('sample_3'), ('sample_4'), ('sample_5'), ('sample_6');
+
+insert into runs
+  (sample_id, configuration_id, strand_specification, data_type)
+values
+  (1, 1, 'reverse', 'RNA-Seq'),
+  (2, 1, 'reverse', 'RNA-Seq'),
+  (3, 1, 'reverse', 'RNA-Seq'),
+  (4, 1, 'reverse', 'RNA-Seq'),
+  (5, 1, 'reverse', 'RNA-Seq'),
+  (6, 1, 'reverse', 'RNA-Seq');
+
+insert into datasets (name) values ('dataset_1');
+insert into datasets_runs (dataset_id, run_id) values (1, 1), (1, 2), (1, 3), (1, 4), (1, 5), (1, 6);
+
+insert into run_counts
+  (run_id, values)
+values
+  (1, '{500, 400, 10}'),
+  (2, '{510, 390, 12}'),
+  (3, '{490, 410, 9}'),
+  (4, '{8, 420, 480}'),
+  (5, '{10, 400, 500}'),
+  (6, '{9, 410, 490}');
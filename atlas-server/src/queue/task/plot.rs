@@ -3,26 +3,67 @@ mod options;
 
 use std::collections::HashMap;
 
-use atlas_core::counts::dimension_reduction::tsne;
+use atlas_core::counts::dimension_reduction::tsne::{self, Reference};
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
 pub use self::{error::Error, options::Options};
+use super::{
+    compute,
+    counts::{Counts, read_counts},
+};
+use crate::{
+    queue::{Progress, RunSelection},
+    store::Metadata,
+};
 
-struct Count {
-    sample_name: String,
-    feature_name: String,
-    count: i32,
+/// The number of nearest reference samples used to place a projected sample.
+const NEIGHBOR_COUNT: usize = 10;
+
+/// How samples are placed in the embedding.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Embed the selected and additional runs together.
+    #[default]
+    Embed,
+    /// Project the additional runs onto the reference embedding of a dataset.
+    ///
+    /// The reference embedding is stored and reused until the runs in the dataset or the options
+    /// change.
+    Project,
+}
+
+/// A 2D embedding of samples.
+pub struct Plot {
+    pub sample_names: Vec<String>,
+    /// Sample attributes, e.g., to color points by.
+    pub metadata: Vec<Metadata>,
+    pub xs: Vec<f64>,
+    pub ys: Vec<f64>,
 }
 
 pub async fn plot(
     pool: &PgPool,
-    dataset_id: i32,
+    runs: &RunSelection,
     additional_runs: &[(String, HashMap<String, i32>)],
     options: Options,
-) -> Result<(Vec<String>, Vec<f64>, Vec<f64>), Error> {
-    use crate::store::{dataset, feature};
+    mode: Mode,
+    progress: &Progress,
+) -> Result<Plot, Error> {
+    use crate::store::run;
+
+    if mode == Mode::Project {
+        return project(pool, runs, additional_runs, options, progress).await;
+    }
+
+    let run_ids = run::select_ids(pool, &runs.dataset_ids, &runs.run_ids).await?;
+
+    if run_ids.is_empty() {
+        return Err(Error::EmptySelection);
+    }
 
-    let configuration_ids = dataset::configuration_ids(pool, dataset_id).await?;
+    let configuration_ids = run::configuration_ids(pool, &run_ids).await?;
 
     if configuration_ids.len() != 1 {
         return Err(Error::NonhomogeoneousDataset);
@@ -31,49 +72,25 @@ pub async fn plot(
     // SAFETY: `configuration_ids` is non-empty;
     let configuration_id = configuration_ids[0];
 
-    let feature_count = feature::count(pool, configuration_id).await? as usize;
-
-    let rows = sqlx::query_as!(
-        Count,
-        r#"
-        select
-            samples.name as sample_name,
-            features.name as feature_name,
-            coalesce(counts.value, 0) as "count!"
-        from runs
-        inner join configurations
-            on runs.configuration_id = configurations.id
-        inner join features
-            on runs.configuration_id = features.configuration_id
-        inner join samples
-            on runs.sample_id = samples.id
-        left join counts
-            on runs.id = counts.run_id and counts.feature_id = features.id
-        where configurations.id = $1
-        order by runs.id, features.id
-        "#,
-        configuration_id,
-    )
-    .fetch_all(pool)
-    .await?;
-
-    let mut sample_names = Vec::new();
+    let Counts {
+        mut sample_names,
+        mut metadata,
+        feature_names,
+        values,
+    } = read_counts(pool, configuration_id, &run_ids).await?;
 
-    for chunk in rows.chunks_exact(feature_count) {
-        let Count { sample_name, .. } = &chunk[0];
-        sample_names.push(sample_name.into());
+    // Additional runs have integer counts.
+    if values.is_real() && !additional_runs.is_empty() {
+        return Err(Error::MixedCounts);
     }
 
-    let feature_names: Vec<_> = rows[..feature_count]
-        .iter()
-        .map(|count| count.feature_name.clone())
-        .collect();
-
-    let mut raw_counts: Vec<_> = rows.into_iter().map(|count| count.count).collect();
+    let feature_count = feature_names.len();
+    let mut raw_counts = values.into_real();
 
     for (sample_name, counts) in additional_runs {
-        raw_counts.extend(feature_names.iter().map(|name| counts[name]));
+        raw_counts.extend(feature_names.iter().map(|name| f64::from(counts[name])));
         sample_names.push(sample_name.into());
+        metadata.push(Metadata::new());
     }
 
     let sample_count = sample_names.len();
@@ -85,7 +102,10 @@ pub async fn plot(
         });
     }
 
-    let embedding = tsne::transform(options.perplexity, options.theta, raw_counts, feature_count);
+    progress.set(0.25);
+
+    let options = tsne::Options::from(options);
+    let embedding = compute(move || tsne::transform(&raw_counts, feature_count, &options)).await?;
 
     let mut xs = Vec::with_capacity(sample_count);
     let mut ys = Vec::with_capacity(sample_count);
@@ -95,10 +115,114 @@ pub async fn plot(
         ys.push(chunk[1]);
     }
 
-    Ok((sample_names, xs, ys))
+    Ok(Plot {
+        sample_names,
+        metadata,
+        xs,
+        ys,
+    })
+}
+
+async fn project(
+    pool: &PgPool,
+    runs: &RunSelection,
+    additional_runs: &[(String, HashMap<String, i32>)],
+    options: Options,
+    progress: &Progress,
+) -> Result<Plot, Error> {
+    use crate::store::{reference_embedding, run};
+
+    let ([dataset_id], []) = (&runs.dataset_ids[..], &runs.run_ids[..]) else {
+        return Err(Error::InvalidProjectionSelection);
+    };
+
+    let run_ids = run::select_ids(pool, &[*dataset_id], &[]).await?;
+
+    if run_ids.is_empty() {
+        return Err(Error::EmptySelection);
+    }
+
+    let configuration_ids = run::configuration_ids(pool, &run_ids).await?;
+
+    let [configuration_id] = configuration_ids[..] else {
+        return Err(Error::NonhomogeoneousDataset);
+    };
+
+    let Counts {
+        mut sample_names,
+        mut metadata,
+        feature_names,
+        values: raw_counts,
+    } = read_counts(pool, configuration_id, &run_ids).await?;
+
+    // Additional runs have integer counts.
+    if raw_counts.is_real() && !additional_runs.is_empty() {
+        return Err(Error::MixedCounts);
+    }
+
+    // SAFETY: `Options` only contains numbers and booleans.
+    let options_value = serde_json::to_value(&options).unwrap();
+
+    let reference = match reference_embedding::find(pool, *dataset_id, &run_ids, &options_value)
+        .await?
+    {
+        Some(reference) => reference,
+        None => {
+            let sample_count = sample_names.len();
+
+            if is_perplexity_too_large(options.perplexity, sample_count) {
+                return Err(Error::PerplexityTooLarge {
+                    sample_count,
+                    perplexity: options.perplexity,
+                });
+            }
+
+            let feature_count = feature_names.len();
+            let options = tsne::Options::from(options);
+            let reference =
+                compute(move || Reference::fit(&raw_counts.into_real(), feature_count, &options))
+                    .await?;
+
+            reference_embedding::upsert(pool, *dataset_id, &run_ids, &options_value, &reference)
+                .await?;
+            reference
+        }
+    };
+
+    progress.set(0.75);
+
+    let mut additional_counts = Vec::with_capacity(additional_runs.len() * feature_names.len());
+
+    for (sample_name, counts) in additional_runs {
+        additional_counts.extend(feature_names.iter().map(|name| counts[name]));
+        sample_names.push(sample_name.into());
+        metadata.push(Metadata::new());
+    }
+
+    let embedding = compute(move || {
+        let projected_embedding = reference.project(&additional_counts, NEIGHBOR_COUNT)?;
+        let mut embedding = reference.embedding;
+        embedding.extend(projected_embedding);
+        Ok::<_, std::io::Error>(embedding)
+    })
+    .await?;
+
+    let (xs, ys) = embedding
+        .chunks_exact(2)
+        .map(|chunk| (chunk[0], chunk[1]))
+        .unzip();
+
+    Ok(Plot {
+        sample_names,
+        metadata,
+        xs,
+        ys,
+    })
 }
 
-// See <https://github.com/frjnn/bhtsne/blob/a0dc63f7d967a748b9297a4108b1530e68eebf87/src/tsne/mod.rs#L46>.
+// t-SNE uses the `3 * perplexity` nearest neighbors of each sample to calculate its input
+// similarities, so there must be at least that many other samples, i.e., `n - 1 >= 3 *
+// perplexity`.
 fn is_perplexity_too_large(perplexity: f64, sample_count: usize) -> bool {
     let n = sample_count as f64;
     sample_count > 0 && (n - 1.0 < 3.0 * perplexity)
@@ -106,8 +230,81 @@ fn is_perplexity_too_large(perplexity: f64, sample_count: usize) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use sqlx::PgPool;
+
     use super::*;
 
+    #[sqlx::test(fixtures("plot"))]
+    async fn test_plot_with_project_mode(pool: PgPool) -> anyhow::Result<()> {
+        let runs = RunSelection {
+            dataset_ids: vec![1],
+            run_ids: Vec::new(),
+        };
+
+        let options = || Options {
+            perplexity: 1.0,
+            ..Default::default()
+        };
+
+        let progress = Progress::default();
+
+        let reference = plot(&pool, &runs, &[], options(), Mode::Project, &progress).await?;
+        assert_eq!(reference.sample_names.len(), 6);
+
+        let reference_count: i64 =
+            sqlx::query_scalar("select count(*) from reference_embeddings where dataset_id = 1")
+                .fetch_one(&pool)
+                .await?;
+        assert_eq!(reference_count, 1);
+
+        let additional_runs = [(
+            String::from("sample_7"),
+            HashMap::from([
+                (String::from("feature_1"), 9),
+                (String::from("feature_2"), 405),
+                (String::from("feature_3"), 495),
+            ]),
+        )];
+
+        let actual = plot(
+            &pool,
+            &runs,
+            &additional_runs,
+            options(),
+            Mode::Project,
+            &progress,
+        )
+        .await?;
+
+        // The reference samples keep their positions.
+        assert_eq!(actual.sample_names.len(), 7);
+        assert_eq!(actual.sample_names[6], "sample_7");
+        assert_eq!(actual.xs[..6], reference.xs);
+        assert_eq!(actual.ys[..6], reference.ys);
+
+        // The additional sample is placed closer to the samples it resembles (4-6).
+        let squared_distance_to_centroid = |range: std::ops::Range<usize>| {
+            let n = range.len() as f64;
+            let x = reference.xs[range.clone()].iter().sum::<f64>() / n;
+            let y = reference.ys[range].iter().sum::<f64>() / n;
+            (actual.xs[6] - x).powi(2) + (actual.ys[6] - y).powi(2)
+        };
+
+        assert!(squared_distance_to_centroid(3..6) < squared_distance_to_centroid(0..3));
+
+        let runs = RunSelection {
+            dataset_ids: vec![1],
+            run_ids: vec![1],
+        };
+
+        assert!(matches!(
+            plot(&pool, &runs, &[], options(), Mode::Project, &progress).await,
+            Err(Error::InvalidProjectionSelection)
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_is_perplexity_too_large() {
         assert!(is_perplexity_too_large(30.0, 3));
@@ -1,12 +1,38 @@
+use crate::store::count;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("database error")]
     Database(#[from] sqlx::Error),
+    #[error("transform error")]
+    Transform(#[from] std::io::Error),
+    #[error("no runs selected")]
+    EmptySelection,
     #[error("dataset is nonhomogeneous")]
     NonhomogeoneousDataset,
+    #[error("projection requires exactly one dataset and no runs")]
+    InvalidProjectionSelection,
     #[error("perplexity too large: perplexity ({perplexity}) must be < ({sample_count} - 1) / 3")]
     PerplexityTooLarge {
         sample_count: usize,
         perplexity: f64,
     },
+    #[error("runs have both integer and real-valued counts")]
+    MixedCounts,
+}
+
+impl Error {
+    /// Returns whether the task may succeed if retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Database(_))
+    }
+}
+
+impl From<count::Error> for Error {
+    fn from(e: count::Error) -> Self {
+        match e {
+            count::Error::Database(e) => Self::Database(e),
+            count::Error::MixedValues => Self::MixedCounts,
+        }
+    }
 }
@@ -1,12 +1,26 @@
+use atlas_core::counts::dimension_reduction::tsne;
 use serde::{Deserialize, Serialize};
 
 /// Barnes-Hut t-SNE options.
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Options {
     /// Perplexity of the conditional distribution.
     pub perplexity: f64,
     /// Barnes-Hut theta.
     pub theta: f64,
+    /// Seed of the random number generator used to initialize the embedding.
+    pub seed: u64,
+    /// Whether to remove features with low counts before normalization.
+    pub filter_by_expression: bool,
+    /// The number of features with the highest variance to keep.
+    ///
+    /// If `None`, all features are used.
+    pub variable_feature_count: Option<usize>,
+    /// The number of principal components to reduce the features to before embedding.
+    ///
+    /// If `None`, PCA is skipped.
+    pub principal_component_count: Option<usize>,
 }
 
 impl Default for Options {
@@ -14,6 +28,24 @@ impl Default for Options {
         Self {
             perplexity: 30.0,
             theta: 0.5,
+            seed: 0,
+            filter_by_expression: false,
+            variable_feature_count: Some(2000),
+            principal_component_count: Some(50),
+        }
+    }
+}
+
+impl From<Options> for tsne::Options {
+    fn from(options: Options) -> Self {
+        Self {
+            dimensions: tsne::Dimensions::Two,
+            perplexity: options.perplexity,
+            theta: options.theta,
+            seed: options.seed,
+            filter_by_expression: options.filter_by_expression,
+            variable_feature_count: options.variable_feature_count,
+            principal_component_count: options.principal_component_count,
         }
     }
 }
@@ -27,5 +59,21 @@ mod tests {
         let options = Options::default();
         assert_eq!(options.perplexity, 30.0);
         assert_eq!(options.theta, 0.5);
+        assert_eq!(options.seed, 0);
+        assert!(!options.filter_by_expression);
+        assert_eq!(options.variable_feature_count, Some(2000));
+        assert_eq!(options.principal_component_count, Some(50));
+    }
+
+    #[test]
+    fn test_deserialize_with_missing_fields() -> serde_json::Result<()> {
+        let options: Options = serde_json::from_str(r#"{"perplexity":10.0,"theta":0.3}"#)?;
+        assert_eq!(options.perplexity, 10.0);
+        assert_eq!(options.theta, 0.3);
+        assert_eq!(options.seed, 0);
+        assert!(!options.filter_by_expression);
+        assert_eq!(options.variable_feature_count, Some(2000));
+        assert_eq!(options.principal_component_count, Some(50));
+        Ok(())
     }
 }
@@ -0,0 +1,149 @@
+mod error;
+
+use atlas_core::counts::{dimension_reduction::preprocessing::log_normalize, similarity};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+pub use self::error::Error;
+use super::{
+    compute,
+    counts::{Counts, read_counts},
+};
+use crate::{
+    queue::Progress,
+    store::{Metadata, count::Values},
+};
+
+/// A measure of similarity between samples.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// The Pearson correlation coefficient.
+    #[default]
+    Pearson,
+    /// The Spearman rank correlation coefficient.
+    Spearman,
+    /// The Euclidean distance.
+    Euclidean,
+}
+
+impl From<Metric> for similarity::Metric {
+    fn from(metric: Metric) -> Self {
+        match metric {
+            Metric::Pearson => Self::Pearson,
+            Metric::Spearman => Self::Spearman,
+            Metric::Euclidean => Self::Euclidean,
+        }
+    }
+}
+
+/// A samples × samples matrix of log-normalized counts.
+pub struct SampleSimilarity {
+    pub sample_names: Vec<String>,
+    pub metadata: Vec<Metadata>,
+    /// Values by sample, ordered by run ID. Correlations with samples with constant values are
+    /// NaN.
+    pub values: Vec<Vec<f64>>,
+    /// The indices of samples in hierarchical clustering order.
+    pub order: Vec<usize>,
+}
+
+pub async fn sample_similarity(
+    pool: &PgPool,
+    dataset_id: i32,
+    metric: Metric,
+    progress: &Progress,
+) -> Result<SampleSimilarity, Error> {
+    use crate::store::run;
+
+    let run_ids = run::select_ids(pool, &[dataset_id], &[]).await?;
+
+    if run_ids.is_empty() {
+        return Err(Error::EmptySelection);
+    }
+
+    let configuration_ids = run::configuration_ids(pool, &run_ids).await?;
+
+    let [configuration_id] = configuration_ids[..] else {
+        return Err(Error::NonhomogeneousDataset);
+    };
+
+    let Counts {
+        sample_names,
+        metadata,
+        feature_names,
+        values,
+    } = read_counts(pool, configuration_id, &run_ids).await?;
+
+    progress.set(0.25);
+
+    let (sample_count, feature_count) = (sample_names.len(), feature_names.len());
+    let metric = similarity::Metric::from(metric);
+
+    let (values, order) = compute(move || {
+        let data = match &values {
+            Values::Integer(values) => log_normalize(values, sample_count, feature_count),
+            Values::Real(values) => log_normalize(values, sample_count, feature_count),
+        };
+        let matrix = similarity::matrix(data.as_ref(), metric);
+        let order = similarity::cluster(matrix.as_ref(), metric);
+
+        let values = (0..sample_count)
+            .map(|i| (0..sample_count).map(|j| matrix[(i, j)]).collect())
+            .collect();
+
+        (values, order)
+    })
+    .await;
+
+    Ok(SampleSimilarity {
+        sample_names,
+        metadata,
+        values,
+        order,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test(fixtures("plot"))]
+    async fn test_sample_similarity(pool: PgPool) -> anyhow::Result<()> {
+        let progress = Progress::default();
+
+        let actual = sample_similarity(&pool, 1, Metric::Pearson, &progress).await?;
+
+        assert_eq!(
+            actual.sample_names,
+            [
+                "sample_1", "sample_2", "sample_3", "sample_4", "sample_5", "sample_6"
+            ]
+        );
+        assert_eq!(actual.metadata.len(), 6);
+        assert_eq!(actual.values.len(), 6);
+        assert!((actual.values[0][0] - 1.0).abs() < 1e-9);
+        assert!(actual.values[0][1] > actual.values[0][3]);
+
+        // Samples 1-3 and 4-6 are clustered together.
+        let mut first_cluster = actual.order[..3].to_vec();
+        first_cluster.sort_unstable();
+        assert!(first_cluster == [0, 1, 2] || first_cluster == [3, 4, 5]);
+
+        sqlx::query("update run_counts set real_values = values::float8[], values = null")
+            .execute(&pool)
+            .await?;
+
+        let actual = sample_similarity(&pool, 1, Metric::Pearson, &progress).await?;
+        assert_eq!(actual.values.len(), 6);
+
+        assert!(matches!(
+            sample_similarity(&pool, 2, Metric::Pearson, &progress).await,
+            Err(Error::EmptySelection)
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use crate::store::count;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("database error")]
+    Database(#[from] sqlx::Error),
+    #[error("no runs selected")]
+    EmptySelection,
+    #[error("dataset is nonhomogeneous")]
+    NonhomogeneousDataset,
+    #[error("runs have both integer and real-valued counts")]
+    MixedCounts,
+}
+
+impl Error {
+    /// Returns whether the task may succeed if retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Database(_))
+    }
+}
+
+impl From<count::Error> for Error {
+    fn from(e: count::Error) -> Self {
+        match e {
+            count::Error::Database(e) => Self::Database(e),
+            count::Error::MixedValues => Self::MixedCounts,
+        }
+    }
+}
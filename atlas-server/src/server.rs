@@ -1,11 +1,19 @@
 mod analyses;
+mod auth;
 mod configurations;
 mod counts;
 mod datasets;
 mod error;
 mod features;
+mod metadata;
+mod normalization;
+mod pagination;
 mod runs;
 mod samples;
+mod tasks;
+mod upload;
+
+use std::sync::Arc;
 
 use axum::{Json, Router, routing::get};
 use sqlx::PgPool;
@@ -13,10 +21,13 @@ use tokio::{net::TcpListener, signal};
 use tower::ServiceBuilder;
 use tower_http::{ServiceBuilderExt, services::ServeFile};
 use tracing::info;
-use utoipa::OpenApi;
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
 
 pub use self::error::Error;
-use super::{Queue, cli::ServerConfig, store};
+use super::{Queue, auth::jwt, cli::ServerConfig, store};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -30,38 +41,98 @@ pub type Result<T> = std::result::Result<T, Error>;
         ),
     )),
     paths(
+        analyses::batch_correction::create,
+        analyses::batch_correction::show,
+        analyses::differential_expression::create,
+        analyses::differential_expression::show,
         analyses::plot::create,
         analyses::plot::show,
+        analyses::sample_similarity::create,
+        analyses::sample_similarity::show,
+        configurations::expression::index,
         configurations::features::index,
         configurations::features::show,
+        configurations::create,
+        configurations::delete,
         configurations::index,
         configurations::show,
         counts::index,
+        datasets::counts::columnar,
+        datasets::counts::json,
+        datasets::counts::tsv,
+        datasets::create,
         datasets::index,
+        datasets::runs::add,
         datasets::runs::index,
+        datasets::runs::remove,
         datasets::show,
+        datasets::update,
         features::runs::index,
+        runs::create,
+        runs::delete,
         runs::show,
+        runs::update_metadata,
         runs::counts::index,
         samples::index,
         samples::runs::index,
         samples::show,
+        samples::update,
+        samples::update_metadata,
+        tasks::cancel,
+        tasks::events,
+        tasks::show,
     ),
     components(schemas(store::StrandSpecification)),
+    modifiers(&SecurityAddon),
 )]
 struct ApiDoc;
 
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Context {
     pool: PgPool,
     queue: Queue,
+    jwt: Option<Arc<jwt::Validator>>,
+    status_changes: tasks::StatusChanges,
 }
 
 pub async fn serve(config: &ServerConfig, pool: PgPool) -> anyhow::Result<()> {
     let service = ServiceBuilder::new().trace_for_http();
 
     let queue = Queue::new(pool.clone());
-    let ctx = Context { pool, queue };
+    let jwt = config
+        .jwt_jwks
+        .as_ref()
+        .map(|src| {
+            jwt::Validator::read(
+                src,
+                config.jwt_issuer.as_deref(),
+                config.jwt_audience.as_deref(),
+            )
+        })
+        .transpose()?
+        .map(Arc::new);
+
+    let status_changes = tasks::StatusChanges::listen(&pool).await?;
+
+    let ctx = Context {
+        pool,
+        queue,
+        jwt,
+        status_changes,
+    };
 
     let app = router().layer(service).with_state(ctx);
 
@@ -89,12 +160,18 @@ fn router() -> Router<Context> {
         .merge(runs::counts::router())
         .merge(runs::router())
         .merge(features::runs::router())
+        .merge(datasets::counts::router())
         .merge(datasets::runs::router())
         .merge(datasets::router())
         .merge(counts::router())
+        .merge(configurations::expression::router())
         .merge(configurations::features::router())
         .merge(configurations::router())
+        .merge(analyses::batch_correction::router())
+        .merge(analyses::differential_expression::router())
         .merge(analyses::plot::router())
+        .merge(analyses::sample_similarity::router())
+        .merge(tasks::router())
         .merge(api_doc_router())
 }
 
@@ -103,3 +180,33 @@ pub fn api_doc_router() -> Router<Context> {
         .route("/openapi.json", get(Json(ApiDoc::openapi())))
         .nest_service("/docs", ServeFile::new("atlas-server/static/docs.html"))
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    /// Sends a GET request and returns the response status and body.
+    pub(super) async fn get(app: Router, uri: &str) -> anyhow::Result<(StatusCode, Vec<u8>)> {
+        let request = Request::get(uri).body(Body::empty())?;
+        let response = app.oneshot(request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        Ok((status, body.to_vec()))
+    }
+
+    /// Sends a GET request and returns the response status and JSON body.
+    ///
+    /// The body is null if it is not JSON.
+    pub(super) async fn get_json(app: Router, uri: &str) -> anyhow::Result<(StatusCode, Value)> {
+        let (status, body) = get(app, uri).await?;
+        let value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+        Ok((status, value))
+    }
+}
@@ -1 +1,86 @@
+pub mod batch_correction;
+pub mod differential_expression;
 pub mod plot;
+pub mod sample_similarity;
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{Error, tasks::require_readable};
+use crate::{queue::Message, store::Principal};
+
+/// Returns an error if a task of the given kind does not exist or the principal cannot read the
+/// runs it selects.
+///
+/// Task results include sample names, metadata, and counts of the selected runs.
+async fn require_readable_task(
+    pool: &PgPool,
+    principal: &Principal,
+    id: Uuid,
+    kind: &str,
+) -> super::Result<()> {
+    let message = sqlx::query_scalar!(
+        r#"select message as "message: sqlx::types::Json<Message>" from tasks where id = $1 and kind = $2"#,
+        id,
+        kind,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    require_readable(pool, principal, &message).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode, header},
+    };
+    use serde::Serialize;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::{Queue, queue::Message};
+
+    /// Posts a JSON payload and returns the response status.
+    pub(super) async fn post(
+        app: Router,
+        uri: &str,
+        payload: serde_json::Value,
+    ) -> anyhow::Result<StatusCode> {
+        let request = Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(payload.to_string()))?;
+
+        let response = app.oneshot(request).await?;
+
+        Ok(response.status())
+    }
+
+    /// Returns the message of the only task.
+    pub(super) async fn find_message(pool: &PgPool) -> sqlx::Result<Message> {
+        sqlx::query_scalar!(r#"select message as "message: sqlx::types::Json<Message>" from tasks"#)
+            .fetch_one(pool)
+            .await
+            .map(|message| message.0)
+    }
+
+    /// Pushes a task that succeeded with the given result body and returns its ID.
+    pub(super) async fn push_successful_task<T>(
+        pool: &PgPool,
+        message: Message,
+        body: T,
+    ) -> sqlx::Result<Uuid>
+    where
+        T: Serialize + Send + Sync,
+    {
+        let queue = Queue::new(pool.clone());
+        let id = queue.push_back(message).await?;
+        queue.pull_front().await?;
+        queue.success(id, body).await?;
+        Ok(id)
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    queue,
+    server::{self, Context, Error},
+    store::Principal,
+};
+
+pub fn router() -> Router<Context> {
+    Router::new()
+        .route("/analyses/batch-correction", post(create))
+        .route("/analyses/batch-correction/{id}", get(show))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct CreateRequest {
+    dataset_id: i32,
+    /// Batch by sample name. Every sample in the dataset, and only those, must be assigned a
+    /// batch.
+    batches: HashMap<String, String>,
+    /// Biological covariates to preserve: values by sample name, keyed by covariate name.
+    ///
+    /// Covariates with only numeric values are used as is. Others are treated as categorical.
+    covariates: Option<BTreeMap<String, HashMap<String, String>>>,
+}
+
+#[derive(Serialize)]
+struct CreateResponse {
+    id: Uuid,
+}
+
+/// Submits a task to correct batch effects in the raw counts of a dataset using ComBat-seq.
+///
+/// If an identical request over the same runs is pending or succeeded, the ID of that task is
+/// returned instead.
+#[utoipa::path(
+    post,
+    path = "/analyses/batch-correction",
+    operation_id = "analyses-batch-correction-create",
+    request_body = inline(CreateRequest),
+    responses(
+        (status = OK, description = "The ID of the task submitted"),
+        (status = BAD_REQUEST, description = "The batches do not match the samples, or the dataset is nonhomogeneous"),
+        (status = NOT_FOUND, description = "The dataset ID does not exist or is not readable"),
+    ),
+)]
+async fn create(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Json(body): Json<CreateRequest>,
+) -> server::Result<Json<CreateResponse>> {
+    use crate::{
+        queue::{BatchCorrectionMessage, Message},
+        store::{dataset, run, sample},
+    };
+
+    let CreateRequest {
+        dataset_id,
+        batches,
+        covariates,
+    } = body;
+
+    if !dataset::is_readable(&ctx.pool, &principal, dataset_id).await? {
+        return Err(Error::NotFound);
+    }
+
+    let configuration_ids = dataset::configuration_ids(&ctx.pool, dataset_id).await?;
+
+    if configuration_ids.len() != 1 {
+        return Err(Error::BadRequest(String::from("dataset is nonhomogeneous")));
+    }
+
+    let sample_names: HashSet<_> = sample::names_where_dataset_id(&ctx.pool, dataset_id)
+        .await?
+        .into_iter()
+        .collect();
+
+    if let Some(sample_name) = sample_names
+        .iter()
+        .find(|name| !batches.contains_key(*name))
+    {
+        return Err(Error::BadRequest(format!("missing batch: {sample_name}")));
+    }
+
+    if let Some(sample_name) = batches.keys().find(|name| !sample_names.contains(*name)) {
+        return Err(Error::BadRequest(format!(
+            "sample not in dataset: {sample_name}"
+        )));
+    }
+
+    let message = Message::BatchCorrection(BatchCorrectionMessage {
+        dataset_id,
+        batches,
+        covariates: covariates.unwrap_or_default(),
+    });
+
+    let run_ids = run::select_ids(&ctx.pool, &[dataset_id], &[]).await?;
+    let key = message.key(&run_ids);
+    let id = ctx
+        .queue
+        .find_or_push_back(message, &key, principal.subject())
+        .await?;
+
+    Ok(Json(CreateResponse { id }))
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Body {
+    sample_names: Vec<String>,
+    feature_names: Vec<String>,
+    counts: Vec<Vec<u32>>,
+}
+
+#[derive(Serialize)]
+struct Task {
+    id: Uuid,
+    status: queue::Status,
+    /// The reason the last attempt failed, if any.
+    error: Option<String>,
+    body: Option<sqlx::types::Json<Body>>,
+}
+
+/// Returns the status of a batch correction task.
+///
+/// On success, the body includes the corrected counts of each sample.
+#[utoipa::path(
+    get,
+    path = "/analyses/batch-correction/{id}",
+    operation_id = "analyses-batch-correction-show",
+    params(
+        ("id" = Uuid, Path, description = "Task ID"),
+    ),
+    responses(
+        (status = OK, description = "Batch correction task status"),
+        (status = NOT_FOUND, description = "The task ID does not exist or its runs are not readable"),
+    ),
+)]
+async fn show(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(task_id): Path<Uuid>,
+) -> server::Result<Json<Task>> {
+    super::require_readable_task(&ctx.pool, &principal, task_id, "batch_correction").await?;
+
+    let task = sqlx::query_as!(
+        Task,
+        r#"
+        select
+            tasks.id,
+            status as "status: queue::Status",
+            error,
+            results.body as "body: sqlx::types::Json<Body>"
+        from tasks
+        left join results
+            on tasks.id = results.id
+        where tasks.id = $1
+        "#,
+        task_id
+    )
+    .fetch_optional(&ctx.pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    Ok(Json(task))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use hyper::{Request, StatusCode, header};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        Queue,
+        queue::{BatchCorrectionMessage, Message},
+        server::analyses::tests::{find_message, post},
+    };
+
+    fn app(pool: PgPool) -> Router {
+        let queue = Queue::new(pool.clone());
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    async fn create(pool: &PgPool, payload: serde_json::Value) -> anyhow::Result<StatusCode> {
+        post(app(pool.clone()), "/analyses/batch-correction", payload).await
+    }
+
+    #[sqlx::test(fixtures("batch_correction"))]
+    async fn test_create(pool: PgPool) -> anyhow::Result<()> {
+        let batches = json!({
+            "sample_1": "a",
+            "sample_2": "a",
+            "sample_3": "b",
+            "sample_4": "b",
+        });
+
+        let status = create(&pool, json!({ "datasetId": 1, "batches": batches })).await?;
+        assert_eq!(status, StatusCode::OK);
+
+        let Message::BatchCorrection(BatchCorrectionMessage {
+            dataset_id,
+            batches,
+            covariates,
+        }) = find_message(&pool).await?
+        else {
+            panic!("invalid message");
+        };
+
+        assert_eq!(dataset_id, 1);
+        assert_eq!(batches.len(), 4);
+        assert_eq!(batches["sample_3"], "b");
+        assert!(covariates.is_empty());
+
+        let batches = json!({ "sample_1": "a", "sample_2": "a", "sample_3": "b" });
+        assert_eq!(
+            create(&pool, json!({ "datasetId": 1, "batches": batches })).await?,
+            StatusCode::BAD_REQUEST
+        );
+
+        let batches = json!({
+            "sample_1": "a",
+            "sample_2": "a",
+            "sample_3": "b",
+            "sample_4": "b",
+            "sample_5": "b",
+        });
+        assert_eq!(
+            create(&pool, json!({ "datasetId": 1, "batches": batches })).await?,
+            StatusCode::BAD_REQUEST
+        );
+
+        let batches = json!({ "sample_1": "a" });
+        assert_eq!(
+            create(&pool, json!({ "datasetId": 2, "batches": batches })).await?,
+            StatusCode::BAD_REQUEST
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_create_with_invalid_dataset_id(pool: PgPool) -> anyhow::Result<()> {
+        let payload = json!({ "datasetId": -1, "batches": {} });
+        let body = Body::from(payload.to_string());
+        let request = Request::post("/analyses/batch-correction")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body)?;
+
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_show_with_invalid_task_id(pool: PgPool) -> anyhow::Result<()> {
+        let request =
+            Request::get("/analyses/batch-correction/5970136c-f1bf-405a-aa79-a81595101864")
+                .body(Body::empty())?;
+
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+}
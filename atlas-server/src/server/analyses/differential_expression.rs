@@ -0,0 +1,451 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    queue::{self, task::differential_expression::Method},
+    server::{self, Context, Error},
+    store::Principal,
+};
+
+pub fn router() -> Router<Context> {
+    Router::new()
+        .route("/analyses/differential-expression", post(create))
+        .route("/analyses/differential-expression/{id}", get(show))
+}
+
+/// Groups of runs by the value of a sample or run metadata key.
+#[derive(Deserialize, ToSchema)]
+struct MetadataGroups {
+    key: String,
+    /// The value of the runs in the reference group.
+    reference: String,
+    /// The value of the runs in the treatment group.
+    treatment: String,
+}
+
+/// Groups of run IDs.
+#[derive(Deserialize, ToSchema)]
+struct RunGroups {
+    reference: Vec<i32>,
+    treatment: Vec<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct CreateRequest {
+    dataset_id: i32,
+    /// The test. The default is "wald".
+    #[schema(inline)]
+    method: Option<Method>,
+    /// Groups the runs of the dataset by metadata. Runs with other values are excluded.
+    #[schema(inline)]
+    group_by: Option<MetadataGroups>,
+    /// Groups runs of the dataset explicitly.
+    #[schema(inline)]
+    runs: Option<RunGroups>,
+}
+
+#[derive(Serialize)]
+struct CreateResponse {
+    id: Uuid,
+}
+
+/// Submits a task to test the features of a dataset for differential expression between two
+/// groups of runs.
+///
+/// Exactly one of `groupBy` or `runs` must be given. Each group must have at least 2 runs.
+///
+/// If an identical request over the same runs is pending or succeeded, the ID of that task is
+/// returned instead.
+#[utoipa::path(
+    post,
+    path = "/analyses/differential-expression",
+    operation_id = "analyses-differential-expression-create",
+    request_body = inline(CreateRequest),
+    responses(
+        (status = OK, description = "The ID of the task submitted"),
+        (status = BAD_REQUEST, description = "The groups are invalid, or the dataset is nonhomogeneous"),
+        (status = NOT_FOUND, description = "The dataset ID does not exist or is not readable"),
+    ),
+)]
+async fn create(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Json(body): Json<CreateRequest>,
+) -> server::Result<Json<CreateResponse>> {
+    use atlas_core::counts::differential_expression::MIN_GROUP_SIZE;
+
+    use crate::{
+        queue::{DifferentialExpressionMessage, Message},
+        store::{dataset, run},
+    };
+
+    let CreateRequest {
+        dataset_id,
+        method,
+        group_by,
+        runs,
+    } = body;
+
+    if !dataset::is_readable(&ctx.pool, &principal, dataset_id).await? {
+        return Err(Error::NotFound);
+    }
+
+    let configuration_ids = dataset::configuration_ids(&ctx.pool, dataset_id).await?;
+
+    if configuration_ids.len() != 1 {
+        return Err(Error::BadRequest(String::from("dataset is nonhomogeneous")));
+    }
+
+    let (mut reference_run_ids, mut treatment_run_ids) = match (group_by, runs) {
+        (Some(groups), None) => {
+            let mut reference_run_ids = Vec::new();
+            let mut treatment_run_ids = Vec::new();
+
+            for (id, metadata) in run::metadata_where_dataset_id(&ctx.pool, dataset_id).await? {
+                match metadata.get(&groups.key) {
+                    Some(value) if *value == groups.reference => reference_run_ids.push(id),
+                    Some(value) if *value == groups.treatment => treatment_run_ids.push(id),
+                    _ => {}
+                }
+            }
+
+            (reference_run_ids, treatment_run_ids)
+        }
+        (None, Some(groups)) => {
+            let dataset_run_ids = run::select_ids(&ctx.pool, &[dataset_id], &[]).await?;
+
+            if groups
+                .reference
+                .iter()
+                .chain(&groups.treatment)
+                .any(|id| dataset_run_ids.binary_search(id).is_err())
+            {
+                return Err(Error::BadRequest(String::from(
+                    "runs must be in the dataset",
+                )));
+            }
+
+            (groups.reference, groups.treatment)
+        }
+        _ => {
+            return Err(Error::BadRequest(String::from(
+                "exactly one of groupBy or runs is required",
+            )));
+        }
+    };
+
+    reference_run_ids.sort_unstable();
+    reference_run_ids.dedup();
+    treatment_run_ids.sort_unstable();
+    treatment_run_ids.dedup();
+
+    if reference_run_ids
+        .iter()
+        .any(|id| treatment_run_ids.binary_search(id).is_ok())
+    {
+        return Err(Error::BadRequest(String::from(
+            "reference and treatment runs must be disjoint",
+        )));
+    }
+
+    if reference_run_ids.len() < MIN_GROUP_SIZE || treatment_run_ids.len() < MIN_GROUP_SIZE {
+        return Err(Error::BadRequest(format!(
+            "each group requires at least {MIN_GROUP_SIZE} runs"
+        )));
+    }
+
+    let mut run_ids: Vec<_> = reference_run_ids
+        .iter()
+        .chain(&treatment_run_ids)
+        .copied()
+        .collect();
+    run_ids.sort_unstable();
+
+    let message = Message::DifferentialExpression(DifferentialExpressionMessage {
+        dataset_id,
+        method: method.unwrap_or_default(),
+        reference_run_ids,
+        treatment_run_ids,
+    });
+
+    let key = message.key(&run_ids);
+    let id = ctx
+        .queue
+        .find_or_push_back(message, &key, principal.subject())
+        .await?;
+
+    Ok(Json(CreateResponse { id }))
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Feature {
+    name: String,
+    log2_fold_change: Option<f64>,
+    p_value: Option<f64>,
+    adjusted_p_value: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Body {
+    features: Vec<Feature>,
+}
+
+#[derive(Serialize)]
+struct Task {
+    id: Uuid,
+    status: queue::Status,
+    /// The reason the last attempt failed, if any.
+    error: Option<String>,
+    body: Option<sqlx::types::Json<Body>>,
+}
+
+/// Returns the status of a differential expression task.
+///
+/// On success, the body includes the log2 fold change, p-value, and Benjamini-Hochberg adjusted
+/// p-value of each feature, ordered by feature ID. Values are null for features that cannot be
+/// tested, e.g., those without counts.
+#[utoipa::path(
+    get,
+    path = "/analyses/differential-expression/{id}",
+    operation_id = "analyses-differential-expression-show",
+    params(
+        ("id" = Uuid, Path, description = "Task ID"),
+    ),
+    responses(
+        (status = OK, description = "Differential expression task status"),
+        (status = NOT_FOUND, description = "The task ID does not exist or its runs are not readable"),
+    ),
+)]
+async fn show(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(task_id): Path<Uuid>,
+) -> server::Result<Json<Task>> {
+    super::require_readable_task(&ctx.pool, &principal, task_id, "differential_expression").await?;
+
+    let task = sqlx::query_as!(
+        Task,
+        r#"
+        select
+            tasks.id,
+            status as "status: queue::Status",
+            error,
+            results.body as "body: sqlx::types::Json<Body>"
+        from tasks
+        left join results
+            on tasks.id = results.id
+        where tasks.id = $1
+        "#,
+        task_id
+    )
+    .fetch_optional(&ctx.pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    Ok(Json(task))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use http_body_util::BodyExt;
+    use hyper::{Request, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        Queue,
+        queue::{DifferentialExpressionMessage, Message},
+        server::analyses::tests::{self, post, push_successful_task},
+    };
+
+    fn app(pool: PgPool) -> Router {
+        let queue = Queue::new(pool.clone());
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    async fn create(pool: &PgPool, payload: serde_json::Value) -> anyhow::Result<StatusCode> {
+        post(
+            app(pool.clone()),
+            "/analyses/differential-expression",
+            payload,
+        )
+        .await
+    }
+
+    async fn find_message(pool: &PgPool) -> anyhow::Result<DifferentialExpressionMessage> {
+        match tests::find_message(pool).await? {
+            Message::DifferentialExpression(message) => Ok(message),
+            _ => panic!("invalid message"),
+        }
+    }
+
+    #[sqlx::test(fixtures("differential_expression"))]
+    async fn test_create_with_metadata_groups(pool: PgPool) -> anyhow::Result<()> {
+        let status = create(
+            &pool,
+            json!({
+                "datasetId": 1,
+                "method": "voom",
+                "groupBy": { "key": "tissue", "reference": "liver", "treatment": "lung" },
+            }),
+        )
+        .await?;
+
+        assert_eq!(status, StatusCode::OK);
+
+        let message = find_message(&pool).await?;
+        assert_eq!(message.method, Method::Voom);
+        assert_eq!(message.reference_run_ids, [1, 2]);
+        assert_eq!(message.treatment_run_ids, [3, 4]);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("differential_expression"))]
+    async fn test_create_with_run_groups(pool: PgPool) -> anyhow::Result<()> {
+        let status = create(
+            &pool,
+            json!({ "datasetId": 1, "runs": { "reference": [5, 1], "treatment": [4, 3] } }),
+        )
+        .await?;
+
+        assert_eq!(status, StatusCode::OK);
+
+        let message = find_message(&pool).await?;
+        assert_eq!(message.method, Method::Wald);
+        assert_eq!(message.reference_run_ids, [1, 5]);
+        assert_eq!(message.treatment_run_ids, [3, 4]);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("differential_expression"))]
+    async fn test_create_with_invalid_request(pool: PgPool) -> anyhow::Result<()> {
+        let groups = json!({ "reference": [1, 2], "treatment": [3, 4] });
+
+        assert_eq!(
+            create(&pool, json!({ "datasetId": 2, "runs": groups })).await?,
+            StatusCode::NOT_FOUND
+        );
+
+        assert_eq!(
+            create(&pool, json!({ "datasetId": 1 })).await?,
+            StatusCode::BAD_REQUEST
+        );
+
+        assert_eq!(
+            create(
+                &pool,
+                json!({
+                    "datasetId": 1,
+                    "runs": groups,
+                    "groupBy": { "key": "tissue", "reference": "liver", "treatment": "lung" },
+                })
+            )
+            .await?,
+            StatusCode::BAD_REQUEST
+        );
+
+        assert_eq!(
+            create(
+                &pool,
+                json!({ "datasetId": 1, "runs": { "reference": [1, 6], "treatment": [3, 4] } })
+            )
+            .await?,
+            StatusCode::BAD_REQUEST
+        );
+
+        assert_eq!(
+            create(
+                &pool,
+                json!({ "datasetId": 1, "runs": { "reference": [1, 2], "treatment": [2, 3] } })
+            )
+            .await?,
+            StatusCode::BAD_REQUEST
+        );
+
+        assert_eq!(
+            create(
+                &pool,
+                json!({
+                    "datasetId": 1,
+                    "groupBy": { "key": "tissue", "reference": "liver", "treatment": "heart" },
+                })
+            )
+            .await?,
+            StatusCode::BAD_REQUEST
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("differential_expression"))]
+    async fn test_show(pool: PgPool) -> anyhow::Result<()> {
+        let message = |dataset_id| {
+            Message::DifferentialExpression(DifferentialExpressionMessage {
+                dataset_id,
+                method: Method::Wald,
+                reference_run_ids: vec![1, 2],
+                treatment_run_ids: vec![3, 4],
+            })
+        };
+
+        let result = json!({
+            "features": [{
+                "name": "feature_1",
+                "log2FoldChange": 1.5,
+                "pValue": 0.01,
+                "adjustedPValue": 0.02,
+            }],
+        });
+
+        let id = push_successful_task(&pool, message(1), &result).await?;
+
+        let request =
+            Request::get(format!("/analyses/differential-expression/{id}")).body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: serde_json::Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["status"], "success");
+        assert_eq!(actual["body"]["features"][0]["log2FoldChange"], 1.5);
+
+        let request = Request::get(format!(
+            "/analyses/differential-expression/{}",
+            Uuid::new_v4()
+        ))
+        .body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Tasks of unreadable datasets and of other kinds are not found.
+        for message in [message(2), Message::Noop] {
+            let id = push_successful_task(&pool, message, &result).await?;
+
+            let request = Request::get(format!("/analyses/differential-expression/{id}"))
+                .body(Body::empty())?;
+            let response = app(pool.clone()).oneshot(request).await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+insert into annotations
+  (name, genome_build)
+values
+  ('GENCODE 40', 'GRCh38.p13');
+
+insert into configurations
+  (annotation_id, feature_type, feature_name)
+values
+  (1, 'exon', 'gene_name');
+
+insert into features
+  (configuration_id, name, length)
+values
+  (1, 'feature_1', 8),
+  (1, 'feature_2', 13);
+
+insert into samples
+  (name, metadata)
+values
+  ('sample_1', '{"tissue": "liver"}'),
+  ('sample_2', '{"tissue": "liver"}'),
+  ('sample_3', '{"tissue": "lung"}'),
+  ('sample_4', '{"tissue": "lung"}'),
+  ('sample_5', '{"tissue": "heart"}'),
+  ('sample_6', '{"tissue": "liver"}');
+
+insert into runs
+  (sample_id, configuration_id, strand_specification, data_type)
+values
+  (1, 1, 'reverse', 'RNA-Seq'),
+  (2, 1, 'reverse', 'RNA-Seq'),
+  (3, 1, 'reverse', 'RNA-Seq'),
+  (4, 1, 'reverse', 'RNA-Seq'),
+  (5, 1, 'reverse', 'RNA-Seq'),
+  (6, 1, 'reverse', 'RNA-Seq');
+
+insert into datasets (name, is_public) values ('dataset_1', true), ('dataset_2', false);
+insert into datasets_runs
+  (dataset_id, run_id)
+values
+  (1, 1), (1, 2), (1, 3), (1, 4), (1, 5),
+  (2, 6);
@@ -2,7 +2,6 @@ mod create;
 
 use std::collections::{HashMap, HashSet};
 
-use anyhow::anyhow;
 use axum::{
     Json, Router,
     extract::{Path, State},
@@ -13,9 +12,9 @@ use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
-    queue,
+    queue::{self, task::plot},
     server::{self, Context, Error},
-    store::feature::find_features,
+    store::{Metadata, Principal, feature::find_features},
 };
 
 pub fn router() -> Router<Context> {
@@ -27,10 +26,21 @@ pub fn router() -> Router<Context> {
 #[derive(Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 struct CreateRequest {
-    dataset_id: i32,
+    /// The ID of a dataset to plot.
+    dataset_id: Option<i32>,
+    /// The IDs of datasets to plot. Runs in any of the datasets are plotted.
+    dataset_ids: Option<Vec<i32>>,
+    /// The IDs of runs to plot.
+    run_ids: Option<Vec<i32>>,
     additional_runs: Option<HashMap<String, HashMap<String, i32>>>,
     #[schema(inline)]
     options: Option<create::Options>,
+    /// How samples are placed. The default is "embed".
+    ///
+    /// "project" places the additional runs onto the stored reference embedding of a single
+    /// dataset, which is computed on first use. Other runs cannot be selected.
+    #[schema(inline)]
+    mode: Option<plot::Mode>,
 }
 
 #[derive(Serialize)]
@@ -38,7 +48,17 @@ struct CreateResponse {
     id: Uuid,
 }
 
-/// Submits a task to perform dimension reduction on all samples in a configuation.
+/// Submits a task to perform dimension reduction on the runs of datasets and/or a list of runs.
+///
+/// The selected runs are the union of the runs in the given datasets and the given runs. They
+/// must all be of the same configuration.
+///
+/// In "project" mode, the additional runs are placed onto a reference embedding of a single
+/// dataset rather than embedding all runs again. The reference embedding is stored and reused
+/// until the runs in the dataset or the options change, so its samples keep their positions.
+///
+/// If an identical request over the same runs is pending or succeeded, the ID of that task is
+/// returned instead.
 #[utoipa::path(
     post,
     path = "/analyses/plot",
@@ -46,38 +66,74 @@ struct CreateResponse {
     request_body = inline(CreateRequest),
     responses(
         (status = OK, description = "The ID of the task submitted"),
-        (status = NOT_FOUND, description = "The dataset ID does not exist"),
+        (status = BAD_REQUEST, description = "No runs are selected, or the selected runs are of more than one configuration"),
+        (status = NOT_FOUND, description = "A dataset or run ID does not exist"),
         (status = INTERNAL_SERVER_ERROR, description = "The additional runs input is invalid"),
     ),
 )]
 async fn create(
+    principal: Principal,
     State(ctx): State<Context>,
     Json(body): Json<CreateRequest>,
 ) -> server::Result<Json<CreateResponse>> {
     use self::create::{merge_options, validate_run};
     use crate::{
-        queue::{Message, PlotMessage},
-        store::dataset,
+        queue::{Message, PlotMessage, RunSelection},
+        store::{dataset, run},
     };
 
     let CreateRequest {
         dataset_id,
+        dataset_ids,
+        run_ids,
         additional_runs,
         options,
+        mode,
     } = body;
 
-    if !dataset::exists(&ctx.pool, dataset_id).await? {
+    let mode = mode.unwrap_or_default();
+
+    let mut dataset_ids = dataset_ids.unwrap_or_default();
+    dataset_ids.extend(dataset_id);
+    dataset_ids.sort_unstable();
+    dataset_ids.dedup();
+
+    let mut run_ids = run_ids.unwrap_or_default();
+    run_ids.sort_unstable();
+    run_ids.dedup();
+
+    for &dataset_id in &dataset_ids {
+        if !dataset::is_readable(&ctx.pool, &principal, dataset_id).await? {
+            return Err(Error::NotFound);
+        }
+    }
+
+    if run::filter_readable(&ctx.pool, &principal, &run_ids).await? != run_ids {
         return Err(Error::NotFound);
     }
 
+    if mode == plot::Mode::Project && (dataset_ids.len() != 1 || !run_ids.is_empty()) {
+        return Err(Error::BadRequest(String::from(
+            "projection requires exactly one dataset and no runs",
+        )));
+    }
+
+    let selected_run_ids = run::select_ids(&ctx.pool, &dataset_ids, &run_ids).await?;
+
+    if selected_run_ids.is_empty() {
+        return Err(Error::BadRequest(String::from("no runs selected")));
+    }
+
     let additional_runs: Vec<_> = additional_runs
         .map(|runs| runs.into_iter().collect())
         .unwrap_or_default();
 
-    let configuration_ids = dataset::configuration_ids(&ctx.pool, dataset_id).await?;
+    let configuration_ids = run::configuration_ids(&ctx.pool, &selected_run_ids).await?;
 
     if configuration_ids.len() != 1 {
-        return Err(Error::Anyhow(anyhow!("dataset is nonhomogeneous")));
+        return Err(Error::BadRequest(String::from(
+            "selected runs are of more than one configuration",
+        )));
     }
 
     // SAFETY: `configuration_ids` is non-empty.
@@ -90,19 +146,27 @@ async fn create(
         validate_run(&feature_names, run).map_err(anyhow::Error::new)?;
     }
 
-    let mut message_options = crate::queue::task::plot::Options::default();
+    let mut message_options = plot::Options::default();
 
     if let Some(arguments) = options {
         merge_options(&mut message_options, &arguments);
     }
 
     let message = Message::Plot(PlotMessage {
-        configuration_id,
+        runs: RunSelection {
+            dataset_ids,
+            run_ids,
+        },
         additional_runs,
         options: message_options,
+        mode,
     });
 
-    let id = ctx.queue.push_back(message).await?;
+    let key = message.key(&selected_run_ids);
+    let id = ctx
+        .queue
+        .find_or_push_back(message, &key, principal.subject())
+        .await?;
 
     Ok(Json(CreateResponse { id }))
 }
@@ -111,6 +175,8 @@ async fn create(
 #[serde(rename_all = "camelCase")]
 struct Body {
     sample_names: Vec<String>,
+    #[serde(default)]
+    metadata: Vec<Metadata>,
     x: Vec<f32>,
     y: Vec<f32>,
 }
@@ -119,6 +185,8 @@ struct Body {
 struct Task {
     id: Uuid,
     status: queue::Status,
+    /// The reason the last attempt failed, if any.
+    error: Option<String>,
     body: Option<sqlx::types::Json<Body>>,
 }
 
@@ -132,26 +200,34 @@ struct Task {
     ),
     responses(
         (status = OK, description = "Plot task status"),
-        (status = INTERNAL_SERVER_ERROR, description = "The task ID does not exist"),
+        (status = NOT_FOUND, description = "The task ID does not exist or its runs are not readable"),
     ),
 )]
-async fn show(State(ctx): State<Context>, Path(task_id): Path<Uuid>) -> server::Result<Json<Task>> {
+async fn show(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(task_id): Path<Uuid>,
+) -> server::Result<Json<Task>> {
+    super::require_readable_task(&ctx.pool, &principal, task_id, "plot").await?;
+
     let task = sqlx::query_as!(
         Task,
         r#"
         select
             tasks.id,
             status as "status: queue::Status",
+            error,
             results.body as "body: sqlx::types::Json<Body>"
         from tasks
         left join results
-            on tasks.id  = results.id
+            on tasks.id = results.id
         where tasks.id = $1
         "#,
         task_id
     )
-    .fetch_one(&ctx.pool)
-    .await?;
+    .fetch_optional(&ctx.pool)
+    .await?
+    .ok_or(Error::NotFound)?;
 
     Ok(Json(task))
 }
@@ -159,6 +235,7 @@ async fn show(State(ctx): State<Context>, Path(task_id): Path<Uuid>) -> server::
 #[cfg(test)]
 mod tests {
     use axum::body::Body;
+    use http_body_util::BodyExt;
     use hyper::{Request, StatusCode, header};
     use serde_json::json;
     use sqlx::PgPool;
@@ -169,7 +246,144 @@ mod tests {
 
     fn app(pool: PgPool) -> Router {
         let queue = Queue::new(pool.clone());
-        router().with_state(Context { pool, queue })
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    fn build_create_request(payload: serde_json::Value) -> anyhow::Result<Request<Body>> {
+        let body = Body::from(payload.to_string());
+
+        Ok(Request::post("/analyses/plot")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body)?)
+    }
+
+    #[sqlx::test(fixtures("plot"))]
+    async fn test_create(pool: PgPool) -> anyhow::Result<()> {
+        use crate::queue::{Message, PlotMessage};
+
+        let request = build_create_request(json!({ "datasetId": 1, "runIds": [2] }))?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let message = sqlx::query_scalar!(
+            r#"select message as "message: sqlx::types::Json<Message>" from tasks"#
+        )
+        .fetch_one(&pool)
+        .await?;
+
+        let sqlx::types::Json(Message::Plot(PlotMessage { runs, .. })) = message else {
+            panic!("invalid message");
+        };
+
+        assert_eq!(runs.dataset_ids, [1]);
+        assert_eq!(runs.run_ids, [2]);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("plot"))]
+    async fn test_create_with_project_mode(pool: PgPool) -> anyhow::Result<()> {
+        use crate::queue::{Message, PlotMessage};
+
+        let request = build_create_request(json!({ "datasetId": 1, "mode": "project" }))?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let message = sqlx::query_scalar!(
+            r#"select message as "message: sqlx::types::Json<Message>" from tasks"#
+        )
+        .fetch_one(&pool)
+        .await?;
+
+        let sqlx::types::Json(Message::Plot(PlotMessage { mode, .. })) = message else {
+            panic!("invalid message");
+        };
+
+        assert_eq!(mode, plot::Mode::Project);
+
+        let request = build_create_request(json!({ "datasetIds": [1, 2], "mode": "project" }))?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request =
+            build_create_request(json!({ "datasetId": 1, "runIds": [2], "mode": "project" }))?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("plot"))]
+    async fn test_create_with_identical_request(pool: PgPool) -> anyhow::Result<()> {
+        async fn create(pool: &PgPool, payload: serde_json::Value) -> anyhow::Result<Uuid> {
+            let request = build_create_request(payload)?;
+            let response = app(pool.clone()).oneshot(request).await?;
+            let body = response.into_body().collect().await?.to_bytes();
+            let actual: serde_json::Value = serde_json::from_slice(&body)?;
+            Ok(actual["id"].as_str().unwrap_or_default().parse()?)
+        }
+
+        let id = create(&pool, json!({ "datasetId": 1 })).await?;
+        assert_eq!(create(&pool, json!({ "datasetId": 1 })).await?, id);
+        assert_eq!(create(&pool, json!({ "runIds": [1] })).await?, id);
+
+        let other_id = create(
+            &pool,
+            json!({ "datasetId": 1, "options": { "perplexity": 5 } }),
+        )
+        .await?;
+        assert_ne!(other_id, id);
+
+        sqlx::query!("insert into datasets_runs (dataset_id, run_id) values (1, 2)")
+            .execute(&pool)
+            .await?;
+
+        assert_ne!(create(&pool, json!({ "datasetId": 1 })).await?, id);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("plot"))]
+    async fn test_create_with_invalid_selection(pool: PgPool) -> anyhow::Result<()> {
+        let request = build_create_request(json!({}))?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = build_create_request(json!({ "datasetIds": [1, 2] }))?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = build_create_request(json!({ "runIds": [1, 4] }))?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = build_create_request(json!({ "datasetIds": [1, 3] }))?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("plot"))]
+    async fn test_create_with_unreadable_selection(pool: PgPool) -> anyhow::Result<()> {
+        sqlx::query("update datasets set is_public = false where id = 2")
+            .execute(&pool)
+            .await?;
+
+        let request = build_create_request(json!({ "datasetId": 2 }))?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = build_create_request(json!({ "datasetId": 1, "runIds": [2] }))?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
     }
 
     #[sqlx::test]
@@ -186,13 +400,65 @@ mod tests {
         Ok(())
     }
 
+    #[sqlx::test(fixtures("plot"))]
+    async fn test_show(pool: PgPool) -> anyhow::Result<()> {
+        use crate::{
+            queue::{Message, PlotMessage, RunSelection},
+            server::analyses::tests::push_successful_task,
+        };
+
+        let message = Message::Plot(PlotMessage {
+            runs: RunSelection {
+                dataset_ids: vec![2],
+                run_ids: Vec::new(),
+            },
+            additional_runs: Vec::new(),
+            options: plot::Options::default(),
+            mode: plot::Mode::default(),
+        });
+
+        let id = push_successful_task(
+            &pool,
+            message,
+            json!({ "sampleNames": ["sample_2", "sample_3"], "x": [0.0, 1.0], "y": [1.0, 0.0] }),
+        )
+        .await?;
+
+        let request = Request::get(format!("/analyses/plot/{id}")).body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(
+            actual["body"]["sampleNames"],
+            json!(["sample_2", "sample_3"])
+        );
+
+        sqlx::query!("update datasets set is_public = false where id = 2")
+            .execute(&pool)
+            .await?;
+
+        let request = Request::get(format!("/analyses/plot/{id}")).body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let id = push_successful_task(&pool, Message::Noop, json!({})).await?;
+
+        let request = Request::get(format!("/analyses/plot/{id}")).body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
     #[sqlx::test]
     async fn test_show_with_invalid_task_id(pool: PgPool) -> anyhow::Result<()> {
         let request = Request::get("/analyses/plot/5970136c-f1bf-405a-aa79-a81595101864")
             .body(Body::empty())?;
 
         let response = app(pool).oneshot(request).await?;
-        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
         Ok(())
     }
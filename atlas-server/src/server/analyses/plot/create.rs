@@ -35,11 +35,23 @@ pub(super) fn validate_run(
 }
 
 #[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct Options {
-    #[schema(default = 50.0)]
+    #[schema(default = 30.0)]
     perplexity: Option<f64>,
     #[schema(default = 0.5)]
     theta: Option<f64>,
+    #[schema(default = 0)]
+    seed: Option<u64>,
+    /// Whether to remove features with low counts before selecting variable features.
+    #[schema(default = false)]
+    filter_by_expression: Option<bool>,
+    /// The number of most variable features to keep. Set to 0 to use all features.
+    #[schema(default = 2000)]
+    variable_feature_count: Option<usize>,
+    /// The number of principal components to reduce to. Set to 0 to skip PCA.
+    #[schema(default = 50)]
+    principal_component_count: Option<usize>,
 }
 
 pub(super) fn merge_options(options: &mut plot::Options, arguments: &Options) {
@@ -50,6 +62,22 @@ pub(super) fn merge_options(options: &mut plot::Options, arguments: &Options) {
     if let Some(theta) = arguments.theta {
         options.theta = theta;
     }
+
+    if let Some(seed) = arguments.seed {
+        options.seed = seed;
+    }
+
+    if let Some(filter_by_expression) = arguments.filter_by_expression {
+        options.filter_by_expression = filter_by_expression;
+    }
+
+    if let Some(n) = arguments.variable_feature_count {
+        options.variable_feature_count = (n > 0).then_some(n);
+    }
+
+    if let Some(n) = arguments.principal_component_count {
+        options.principal_component_count = (n > 0).then_some(n);
+    }
 }
 
 #[cfg(test)]
@@ -108,18 +136,43 @@ mod tests {
         let arguments = Options {
             perplexity: None,
             theta: None,
+            seed: None,
+            filter_by_expression: None,
+            variable_feature_count: None,
+            principal_component_count: None,
         };
         merge_options(&mut options, &arguments);
         assert_eq!(options.perplexity, defualt_options.perplexity);
         assert_eq!(options.theta, defualt_options.theta);
+        assert_eq!(options.seed, defualt_options.seed);
+        assert_eq!(
+            options.filter_by_expression,
+            defualt_options.filter_by_expression
+        );
+        assert_eq!(
+            options.variable_feature_count,
+            defualt_options.variable_feature_count
+        );
+        assert_eq!(
+            options.principal_component_count,
+            defualt_options.principal_component_count
+        );
 
         let mut options = plot::Options::default();
         let arguments = Options {
             perplexity: Some(10.0),
             theta: Some(0.3),
+            seed: Some(8),
+            filter_by_expression: Some(true),
+            variable_feature_count: Some(500),
+            principal_component_count: Some(0),
         };
         merge_options(&mut options, &arguments);
         assert_eq!(options.perplexity, 10.0);
         assert_eq!(options.theta, 0.3);
+        assert_eq!(options.seed, 8);
+        assert!(options.filter_by_expression);
+        assert_eq!(options.variable_feature_count, Some(500));
+        assert!(options.principal_component_count.is_none());
     }
 }
@@ -0,0 +1,266 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    queue::{self, task::sample_similarity::Metric},
+    server::{self, Context, Error},
+    store::{Metadata, Principal},
+};
+
+pub fn router() -> Router<Context> {
+    Router::new()
+        .route("/analyses/sample-similarity", post(create))
+        .route("/analyses/sample-similarity/{id}", get(show))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct CreateRequest {
+    dataset_id: i32,
+    /// The measure of similarity. The default is "pearson".
+    #[schema(inline)]
+    metric: Option<Metric>,
+}
+
+#[derive(Serialize)]
+struct CreateResponse {
+    id: Uuid,
+}
+
+/// Submits a task to compare all pairs of samples in a dataset.
+///
+/// Samples are compared by the Pearson or Spearman correlation, or the Euclidean distance, of
+/// their log-normalized counts, i.e., `ln(1 + CPM)`.
+///
+/// If an identical request over the same runs is pending or succeeded, the ID of that task is
+/// returned instead.
+#[utoipa::path(
+    post,
+    path = "/analyses/sample-similarity",
+    operation_id = "analyses-sample-similarity-create",
+    request_body = inline(CreateRequest),
+    responses(
+        (status = OK, description = "The ID of the task submitted"),
+        (status = BAD_REQUEST, description = "The dataset is empty or nonhomogeneous"),
+        (status = NOT_FOUND, description = "The dataset ID does not exist or is not readable"),
+    ),
+)]
+async fn create(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Json(body): Json<CreateRequest>,
+) -> server::Result<Json<CreateResponse>> {
+    use crate::{
+        queue::{Message, SampleSimilarityMessage},
+        store::{dataset, run},
+    };
+
+    let CreateRequest { dataset_id, metric } = body;
+
+    if !dataset::is_readable(&ctx.pool, &principal, dataset_id).await? {
+        return Err(Error::NotFound);
+    }
+
+    let configuration_ids = dataset::configuration_ids(&ctx.pool, dataset_id).await?;
+
+    match configuration_ids.len() {
+        0 => return Err(Error::BadRequest(String::from("dataset is empty"))),
+        1 => {}
+        _ => return Err(Error::BadRequest(String::from("dataset is nonhomogeneous"))),
+    }
+
+    let message = Message::SampleSimilarity(SampleSimilarityMessage {
+        dataset_id,
+        metric: metric.unwrap_or_default(),
+    });
+
+    let run_ids = run::select_ids(&ctx.pool, &[dataset_id], &[]).await?;
+    let key = message.key(&run_ids);
+    let id = ctx
+        .queue
+        .find_or_push_back(message, &key, principal.subject())
+        .await?;
+
+    Ok(Json(CreateResponse { id }))
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Body {
+    sample_names: Vec<String>,
+    metadata: Vec<Metadata>,
+    values: Vec<Vec<Option<f64>>>,
+    order: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Task {
+    id: Uuid,
+    status: queue::Status,
+    /// The reason the last attempt failed, if any.
+    error: Option<String>,
+    body: Option<sqlx::types::Json<Body>>,
+}
+
+/// Returns the status of a sample similarity task.
+///
+/// On success, the body includes the samples × samples matrix of values, with samples ordered
+/// by run ID, and `order`, the sample indices in average linkage hierarchical clustering order.
+/// Correlations are null for samples with constant values, e.g., those without counts.
+#[utoipa::path(
+    get,
+    path = "/analyses/sample-similarity/{id}",
+    operation_id = "analyses-sample-similarity-show",
+    params(
+        ("id" = Uuid, Path, description = "Task ID"),
+    ),
+    responses(
+        (status = OK, description = "Sample similarity task status"),
+        (status = NOT_FOUND, description = "The task ID does not exist or its runs are not readable"),
+    ),
+)]
+async fn show(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(task_id): Path<Uuid>,
+) -> server::Result<Json<Task>> {
+    super::require_readable_task(&ctx.pool, &principal, task_id, "sample_similarity").await?;
+
+    let task = sqlx::query_as!(
+        Task,
+        r#"
+        select
+            tasks.id,
+            status as "status: queue::Status",
+            error,
+            results.body as "body: sqlx::types::Json<Body>"
+        from tasks
+        left join results
+            on tasks.id = results.id
+        where tasks.id = $1
+        "#,
+        task_id
+    )
+    .fetch_optional(&ctx.pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    Ok(Json(task))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use http_body_util::BodyExt;
+    use hyper::{Request, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        Queue,
+        queue::{Message, SampleSimilarityMessage},
+        server::analyses::tests::{find_message, post, push_successful_task},
+    };
+
+    fn app(pool: PgPool) -> Router {
+        let queue = Queue::new(pool.clone());
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    async fn create(pool: &PgPool, payload: serde_json::Value) -> anyhow::Result<StatusCode> {
+        post(app(pool.clone()), "/analyses/sample-similarity", payload).await
+    }
+
+    #[sqlx::test(fixtures("sample_similarity"))]
+    async fn test_create(pool: PgPool) -> anyhow::Result<()> {
+        let status = create(&pool, json!({ "datasetId": 1, "metric": "spearman" })).await?;
+        assert_eq!(status, StatusCode::OK);
+
+        let Message::SampleSimilarity(SampleSimilarityMessage { dataset_id, metric }) =
+            find_message(&pool).await?
+        else {
+            panic!("invalid message");
+        };
+
+        assert_eq!(dataset_id, 1);
+        assert_eq!(metric, Metric::Spearman);
+
+        assert_eq!(
+            create(&pool, json!({ "datasetId": 2 })).await?,
+            StatusCode::BAD_REQUEST
+        );
+
+        assert_eq!(
+            create(&pool, json!({ "datasetId": 3 })).await?,
+            StatusCode::BAD_REQUEST
+        );
+
+        assert_eq!(
+            create(&pool, json!({ "datasetId": 4 })).await?,
+            StatusCode::NOT_FOUND
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("sample_similarity"))]
+    async fn test_show(pool: PgPool) -> anyhow::Result<()> {
+        let message = |dataset_id| {
+            Message::SampleSimilarity(SampleSimilarityMessage {
+                dataset_id,
+                metric: Metric::default(),
+            })
+        };
+
+        let result = json!({
+            "sampleNames": ["sample_1", "sample_2"],
+            "metadata": [{}, {}],
+            "values": [[1.0, 0.5], [0.5, 1.0]],
+            "order": [1, 0],
+        });
+
+        let id = push_successful_task(&pool, message(1), &result).await?;
+
+        let request =
+            Request::get(format!("/analyses/sample-similarity/{id}")).body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: serde_json::Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["status"], "success");
+        assert_eq!(actual["body"]["values"][0][1], 0.5);
+        assert_eq!(actual["body"]["order"], json!([1, 0]));
+
+        let request = Request::get(format!("/analyses/sample-similarity/{}", Uuid::new_v4()))
+            .body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Tasks of unreadable datasets and of other kinds are not found.
+        for message in [message(4), Message::Noop] {
+            let id = push_successful_task(&pool, message, &result).await?;
+
+            let request =
+                Request::get(format!("/analyses/sample-similarity/{id}")).body(Body::empty())?;
+            let response = app(pool.clone()).oneshot(request).await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+use sqlx::PgPool;
+use tracing::warn;
+
+use super::{Context, Error};
+use crate::{
+    auth::{TOKEN_PREFIX, hash_token},
+    store::{Principal, api_token, dataset, principal},
+};
+
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// Authenticates the principal of a request.
+///
+/// Requests without an `Authorization` header are anonymous. Otherwise, the header must have a
+/// bearer token that is either an API token or, if the server validates JWTs, a valid JWT.
+impl FromRequestParts<Context> for Principal {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, ctx: &Context) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(header::AUTHORIZATION) else {
+            return Ok(Principal::anonymous());
+        };
+
+        let token = value
+            .to_str()
+            .ok()
+            .and_then(|s| s.strip_prefix(BEARER_PREFIX))
+            .ok_or(Error::Unauthorized)?;
+
+        let subject = if token.starts_with(TOKEN_PREFIX) {
+            let token_hash = hash_token(token);
+
+            api_token::find_subject(&ctx.pool, &token_hash)
+                .await?
+                .ok_or(Error::Unauthorized)?
+        } else if let Some(validator) = ctx.jwt.as_deref() {
+            validator.validate(token).map_err(|e| {
+                warn!(error = %e, "invalid JWT");
+                Error::Unauthorized
+            })?
+        } else {
+            return Err(Error::Unauthorized);
+        };
+
+        principal::find(&ctx.pool, &subject)
+            .await
+            .map_err(Error::from)
+    }
+}
+
+/// Returns an error if the principal is not authenticated.
+pub(super) fn require_authenticated(principal: &Principal) -> super::Result<()> {
+    if principal.is_authenticated() {
+        Ok(())
+    } else {
+        Err(Error::Unauthorized)
+    }
+}
+
+/// Returns an error if the principal is not an administrator.
+pub(super) fn require_admin(principal: &Principal) -> super::Result<()> {
+    require_authenticated(principal)?;
+
+    if principal.is_admin() {
+        Ok(())
+    } else {
+        Err(Error::Forbidden)
+    }
+}
+
+/// Returns an error if the principal cannot write to a dataset.
+///
+/// Datasets that the principal cannot read are reported as not found.
+pub(super) async fn require_dataset_write(
+    pool: &PgPool,
+    principal: &Principal,
+    dataset_id: i32,
+) -> super::Result<()> {
+    require_authenticated(principal)?;
+
+    if dataset::is_writable(pool, principal, dataset_id).await? {
+        Ok(())
+    } else if dataset::is_readable(pool, principal, dataset_id).await? {
+        Err(Error::Forbidden)
+    } else {
+        Err(Error::NotFound)
+    }
+}
+
+/// Creates an API token for a subject and returns it.
+#[cfg(test)]
+pub(super) async fn create_api_token(
+    pool: &PgPool,
+    subject: &str,
+    is_admin: bool,
+) -> sqlx::Result<String> {
+    let token = crate::auth::generate_token();
+    api_token::create(pool, subject, &hash_token(&token)).await?;
+
+    if is_admin {
+        principal::add_administrator(pool, subject).await?;
+    }
+
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{Json, Router, body::Body, http::Request, routing::get};
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        Queue,
+        auth::jwt::{self, tests::KEY_ID},
+    };
+
+    async fn whoami(principal: Principal) -> Json<Value> {
+        Json(json!({
+            "subject": principal.subject(),
+            "isAdmin": principal.is_admin(),
+        }))
+    }
+
+    fn app(pool: PgPool) -> anyhow::Result<Router> {
+        let queue = Queue::new(pool.clone());
+        let validator = jwt::Validator::new(&jwt::tests::jwks(), None, None)?;
+
+        Ok(Router::new()
+            .route("/whoami", get(whoami))
+            .with_state(Context {
+                pool,
+                queue,
+                jwt: Some(Arc::new(validator)),
+                status_changes: Default::default(),
+            }))
+    }
+
+    async fn request(
+        pool: &PgPool,
+        authorization: Option<&str>,
+    ) -> anyhow::Result<(StatusCode, Value)> {
+        let mut builder = Request::get("/whoami");
+
+        if let Some(authorization) = authorization {
+            builder = builder.header(header::AUTHORIZATION, authorization);
+        }
+
+        let response = app(pool.clone())?
+            .oneshot(builder.body(Body::empty())?)
+            .await?;
+
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        let value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+        Ok((status, value))
+    }
+
+    #[sqlx::test]
+    async fn test_from_request_parts(pool: PgPool) -> anyhow::Result<()> {
+        let (status, body) = request(&pool, None).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "subject": null, "isAdmin": false }));
+
+        let token = create_api_token(&pool, "admin", true).await?;
+        let (status, body) = request(&pool, Some(&format!("Bearer {token}"))).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "subject": "admin", "isAdmin": true }));
+
+        let token = jwt::tests::encode(
+            json!({ "sub": "user", "exp": jwt::tests::expiration() }),
+            Some(KEY_ID),
+        );
+        let (status, body) = request(&pool, Some(&format!("Bearer {token}"))).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "subject": "user", "isAdmin": false }));
+
+        let (status, _) = request(&pool, Some("Bearer atlas_0123")).await?;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = request(&pool, Some("Bearer a.b.c")).await?;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = request(&pool, Some(&format!("Basic {token}"))).await?;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_require_admin() {
+        assert!(matches!(
+            require_admin(&Principal::anonymous()),
+            Err(Error::Unauthorized)
+        ));
+        assert!(require_admin(&Principal::administrator("admin")).is_ok());
+    }
+}
@@ -1,39 +1,192 @@
+pub mod expression;
 pub mod features;
 
 use axum::{
     Json, Router,
-    extract::{Path, State},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
+    http::{StatusCode, Uri},
     routing::get,
 };
 use serde::Serialize;
+use serde_json::json;
+use utoipa::ToSchema;
 
-use super::{Context, Error};
-use crate::store::configuration::{self, Configuration};
+use super::{
+    Context, Error,
+    auth::require_admin,
+    error::is_unique_violation,
+    pagination::{PageQuery, next_link},
+    upload::{self, Form, MAX_UPLOAD_SIZE},
+};
+use crate::store::{
+    Principal,
+    audit_log::{self, Action},
+    configuration::{self, Configuration, Deletion},
+    page::ById,
+};
 
 pub fn router() -> Router<Context> {
     Router::new()
-        .route("/configurations", get(index))
-        .route("/configurations/{id}", get(show))
+        .route(
+            "/configurations",
+            get(index)
+                .post(create)
+                .layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE)),
+        )
+        .route("/configurations/{id}", get(show).delete(delete))
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
 struct IndexResponse {
     #[schema(inline)]
     configurations: Vec<Configuration>,
+    /// The number of configurations across all pages.
+    total: i64,
+    /// A link to the next page, if any.
+    next: Option<String>,
 }
 
-/// Lists all configurations.
+/// Lists configurations sorted by ID.
 #[utoipa::path(
     get,
     path = "/configurations",
     operation_id = "configurations-index",
+    params(PageQuery),
     responses(
-        (status = OK, description = "A list of configurations", body = inline(IndexResponse)),
+        (status = OK, description = "A page of configurations", body = inline(IndexResponse)),
+        (status = BAD_REQUEST, description = "The limit or cursor is invalid"),
     ),
 )]
-async fn index(State(ctx): State<Context>) -> super::Result<Json<IndexResponse>> {
-    let configurations = configuration::all(&ctx.pool).await?;
-    Ok(Json(IndexResponse { configurations }))
+async fn index(
+    State(ctx): State<Context>,
+    uri: Uri,
+    Query(page_query): Query<PageQuery>,
+) -> super::Result<Json<IndexResponse>> {
+    let request = page_query.into_request(ById)?;
+    let page = configuration::page(&ctx.pool, &request).await?;
+
+    Ok(Json(IndexResponse {
+        next: next_link(&uri, page.next.as_ref()),
+        total: page.total,
+        configurations: page.items,
+    }))
+}
+
+#[derive(ToSchema)]
+#[schema(rename_all = "camelCase")]
+struct CreateRequest {
+    /// The name of the annotations, e.g., "GENCODE 40", etc.
+    annotations_name: String,
+    /// The assembly name of the genome used to create the annotations, e.g., "GRCh38.p13", etc.
+    annotations_genome_build: String,
+    /// The type of feature used in the annotations, e.g., "exon", "gene", etc.
+    feature_type: String,
+    /// The display name of the feature, e.g., "gene_name", "gene_id", etc.
+    feature_name: String,
+    /// Annotations (GFF3). This can be uncompressed or gzip-compressed.
+    #[schema(value_type = String, format = Binary)]
+    file: Bytes,
+}
+
+impl CreateRequest {
+    async fn read(multipart: &mut Multipart) -> super::Result<Self> {
+        let mut form = Form::read(multipart).await?;
+
+        Ok(Self {
+            annotations_name: form.take_required("annotationsName")?,
+            annotations_genome_build: form.take_required("annotationsGenomeBuild")?,
+            feature_type: form.take_required("featureType")?,
+            feature_name: form.take_required("featureName")?,
+            file: form.take_file("file")?,
+        })
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateResponse {
+    id: i32,
+    feature_count: usize,
+}
+
+/// Creates a configuration and its features from uploaded annotations.
+///
+/// This requires an administrator.
+#[utoipa::path(
+    post,
+    path = "/configurations",
+    operation_id = "configurations-create",
+    request_body(content = inline(CreateRequest), content_type = "multipart/form-data"),
+    responses(
+        (status = CREATED, description = "The ID of the configuration created"),
+        (status = BAD_REQUEST, description = "The form or annotations are invalid"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = FORBIDDEN, description = "The principal is not an administrator"),
+        (status = CONFLICT, description = "The configuration already exists"),
+    ),
+    security(("bearer" = [])),
+)]
+async fn create(
+    principal: Principal,
+    State(ctx): State<Context>,
+    mut multipart: Multipart,
+) -> super::Result<(StatusCode, Json<CreateResponse>)> {
+    use crate::import::{self, import_configuration, read_features};
+
+    require_admin(&principal)?;
+
+    let CreateRequest {
+        annotations_name,
+        annotations_genome_build,
+        feature_type,
+        feature_name,
+        file,
+    } = CreateRequest::read(&mut multipart).await?;
+
+    let features = {
+        let feature_type = feature_type.clone();
+        let feature_name = feature_name.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut reader = upload::reader(file);
+            read_features(&mut reader, &feature_type, &feature_name)
+        })
+        .await
+        .map_err(anyhow::Error::from)?
+        .map_err(|e| Error::BadRequest(format!("invalid annotations: {e}")))?
+    };
+
+    if features.is_empty() {
+        return Err(Error::BadRequest(format!(
+            "no features of type {feature_type} found"
+        )));
+    }
+
+    let mut tx = ctx.pool.begin().await?;
+
+    let (id, feature_count) = import_configuration(
+        &mut tx,
+        &annotations_name,
+        &annotations_genome_build,
+        &feature_type,
+        &feature_name,
+        &features,
+    )
+    .await
+    .map_err(|e| match e {
+        import::Error::Database(e) if is_unique_violation(&e) => {
+            Error::Conflict(String::from("configuration already exists"))
+        }
+        e => e.into(),
+    })?;
+
+    tx.commit().await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateResponse { id, feature_count }),
+    ))
 }
 
 #[derive(Serialize)]
@@ -65,11 +218,61 @@ async fn show(
     Ok(Json(ShowResponse { configuration }))
 }
 
+/// Deletes a configuration and its features.
+///
+/// This requires an administrator. Only configurations without runs can be deleted.
+#[utoipa::path(
+    delete,
+    path = "/configurations/{id}",
+    operation_id = "configurations-delete",
+    params(
+        ("id" = i32, Path, description = "Configuration ID"),
+    ),
+    responses(
+        (status = NO_CONTENT, description = "The configuration was deleted"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = FORBIDDEN, description = "The principal is not an administrator"),
+        (status = NOT_FOUND, description = "The configuration does not exist"),
+        (status = CONFLICT, description = "The configuration has runs"),
+    ),
+    security(("bearer" = [])),
+)]
+async fn delete(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(id): Path<i32>,
+) -> super::Result<StatusCode> {
+    require_admin(&principal)?;
+
+    let mut tx = ctx.pool.begin().await?;
+
+    match configuration::delete(&mut tx, id).await? {
+        Deletion::Deleted => {}
+        Deletion::NotFound => return Err(Error::NotFound),
+        Deletion::InUse => {
+            return Err(Error::Conflict(String::from("configuration has runs")));
+        }
+    }
+
+    audit_log::create(
+        &mut *tx,
+        principal.subject(),
+        Action::DeleteConfiguration,
+        id,
+        &json!({}),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[cfg(test)]
 mod tests {
     use axum::{
         body::Body,
-        http::{Request, StatusCode},
+        http::{Request, StatusCode, header},
     };
     use http_body_util::BodyExt;
     use serde_json::{Value, json};
@@ -77,11 +280,95 @@ mod tests {
     use tower::ServiceExt;
 
     use super::*;
-    use crate::Queue;
+    use crate::{Queue, server::auth::create_api_token};
 
     fn app(pool: PgPool) -> Router {
         let queue = Queue::new(pool.clone());
-        router().with_state(Context { pool, queue })
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    fn build_create_request(
+        api_token: Option<&str>,
+        fields: &[(&str, &str)],
+    ) -> anyhow::Result<Request<Body>> {
+        const DATA: &[u8] = b"\
+##gff-version 3
+sq0\t.\texon\t1\t10\t.\t+\t.\tgene_id=g0;gene_name=f0
+sq0\t.\texon\t21\t25\t.\t+\t.\tgene_id=g0;gene_name=f0
+sq0\t.\texon\t31\t38\t.\t-\t.\tgene_id=g1;gene_name=f1
+";
+
+        let (content_type, body) = upload::build_form(fields, &[("file", "in.gff3", DATA)]);
+
+        let mut builder =
+            Request::post("/configurations").header(header::CONTENT_TYPE, content_type);
+
+        if let Some(api_token) = api_token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {api_token}"));
+        }
+
+        Ok(builder.body(Body::from(body))?)
+    }
+
+    const CREATE_FIELDS: [(&str, &str); 4] = [
+        ("annotationsName", "GENCODE 40"),
+        ("annotationsGenomeBuild", "GRCh38.p13"),
+        ("featureType", "exon"),
+        ("featureName", "gene_name"),
+    ];
+
+    #[sqlx::test]
+    async fn test_create(pool: PgPool) -> anyhow::Result<()> {
+        let api_token = create_api_token(&pool, "admin", true).await?;
+
+        let request = build_create_request(Some(&api_token), &CREATE_FIELDS)?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+        assert_eq!(actual, json!({ "id": 1, "featureCount": 2 }));
+
+        let request = build_create_request(Some(&api_token), &CREATE_FIELDS)?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_create_with_invalid_request(pool: PgPool) -> anyhow::Result<()> {
+        let api_token = create_api_token(&pool, "admin", true).await?;
+        let user_api_token = create_api_token(&pool, "user", false).await?;
+
+        let request = build_create_request(None, &CREATE_FIELDS)?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let request = build_create_request(Some("atlas_0"), &CREATE_FIELDS)?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let request = build_create_request(Some(&user_api_token), &CREATE_FIELDS)?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let request = build_create_request(Some(&api_token), &CREATE_FIELDS[1..])?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let mut fields = CREATE_FIELDS;
+        fields[2] = ("featureType", "gene");
+        let request = build_create_request(Some(&api_token), &fields)?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
     }
 
     #[sqlx::test(fixtures("configurations"))]
@@ -115,13 +402,41 @@ mod tests {
                     },
                     "featureType": "exon",
                     "featureName": "gene_name",
-                }]
+                }],
+                "total": 2,
+                "next": null,
             })
         );
 
         Ok(())
     }
 
+    #[sqlx::test(fixtures("configurations"))]
+    async fn test_index_with_pagination(pool: PgPool) -> anyhow::Result<()> {
+        let request = Request::builder()
+            .uri("/configurations?limit=1&order=desc")
+            .body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["configurations"][0]["id"], 2);
+        assert_eq!(actual["total"], 2);
+
+        let next = actual["next"].as_str().unwrap();
+        let request = Request::builder().uri(next).body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["configurations"][0]["id"], 1);
+        assert_eq!(actual["next"], Value::Null);
+
+        Ok(())
+    }
+
     #[sqlx::test(fixtures("configurations"))]
     async fn test_show(pool: PgPool) -> anyhow::Result<()> {
         let request = Request::builder()
@@ -133,4 +448,51 @@ mod tests {
 
         Ok(())
     }
+
+    #[sqlx::test(fixtures("configurations"))]
+    async fn test_delete(pool: PgPool) -> anyhow::Result<()> {
+        let delete = |id: i32, api_token: &str| {
+            Request::delete(format!("/configurations/{id}"))
+                .header(header::AUTHORIZATION, format!("Bearer {api_token}"))
+                .body(Body::empty())
+        };
+
+        let user_api_token = create_api_token(&pool, "user", false).await?;
+        let response = app(pool.clone())
+            .oneshot(delete(2, &user_api_token)?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let api_token = create_api_token(&pool, "admin", true).await?;
+
+        sqlx::query!("insert into samples (name) values ('sample_1')")
+            .execute(&pool)
+            .await?;
+        sqlx::query!(
+            "
+            insert into runs (sample_id, configuration_id, strand_specification, data_type)
+            values (1, 1, 'reverse', 'RNA-Seq')
+            "
+        )
+        .execute(&pool)
+        .await?;
+
+        let response = app(pool.clone()).oneshot(delete(1, &api_token)?).await?;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = app(pool.clone()).oneshot(delete(2, &api_token)?).await?;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = app(pool.clone()).oneshot(delete(2, &api_token)?).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let entry = sqlx::query!("select subject, action, target_id from audit_log")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(entry.subject.as_deref(), Some("admin"));
+        assert_eq!(entry.action, "delete_configuration");
+        assert_eq!(entry.target_id, 2);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,508 @@
+use std::collections::BTreeMap;
+
+use atlas_core::{
+    counts::normalization::{Factors, Method},
+    stats::summary::Summary as FiveNumberSummary,
+};
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    routing::get,
+};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json as SqlxJson;
+use utoipa::IntoParams;
+
+use crate::{
+    server::{
+        self, Context, Error,
+        normalization::{self, Matrix, Normalize},
+    },
+    store::{Metadata, Principal, count},
+};
+
+/// The maximum number of feature names in a query.
+const MAX_FEATURE_NAMES: usize = 100;
+
+const DELIMITER: char = ',';
+
+pub fn router() -> Router<Context> {
+    Router::new().route("/configurations/{configuration_id}/expression", get(index))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct IndexQuery {
+    /// A comma-separated list of feature names, e.g., gene names.
+    names: String,
+    /// The normalization method: "cpm", "fpkm", "median_of_ratios", "tmm", "tpm", or "vst". By
+    /// default, raw counts are returned. Factors are calculated over the runs of each dataset.
+    #[param(value_type = Option<String>)]
+    normalize: Option<Normalize>,
+    /// A sample or run metadata key to group runs by within each dataset.
+    group_by: Option<String>,
+    /// A comma-separated list of dataset IDs to include. By default, all readable datasets with
+    /// runs in the configuration are included.
+    dataset_ids: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    count: usize,
+    min: f64,
+    q1: f64,
+    median: f64,
+    q3: f64,
+    max: f64,
+}
+
+impl From<FiveNumberSummary> for Summary {
+    fn from(summary: FiveNumberSummary) -> Self {
+        Self {
+            count: summary.count,
+            min: summary.min,
+            q1: summary.q1,
+            median: summary.median,
+            q3: summary.q3,
+            max: summary.max,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Run {
+    id: i32,
+    sample_name: String,
+    value: f64,
+}
+
+#[derive(Serialize)]
+struct Group {
+    /// The metadata value of the runs in the group, if grouped.
+    value: Option<String>,
+    runs: Vec<Run>,
+    summary: Option<Summary>,
+}
+
+#[derive(Serialize)]
+struct Dataset {
+    id: i32,
+    name: String,
+    groups: Vec<Group>,
+}
+
+#[derive(Serialize)]
+struct Feature {
+    name: String,
+    datasets: Vec<Dataset>,
+}
+
+#[derive(Serialize)]
+struct Expression {
+    features: Vec<Feature>,
+}
+
+#[derive(Serialize)]
+struct IndexBody {
+    expression: Expression,
+}
+
+struct DatasetRuns {
+    id: i32,
+    name: String,
+    run_ids: Vec<i32>,
+    /// Whether all runs in the dataset are in the configuration.
+    is_complete: bool,
+}
+
+struct RunSample {
+    sample_name: String,
+    metadata: SqlxJson<Metadata>,
+}
+
+/// Shows the expression of features across readable datasets.
+///
+/// Features are resolved by name within the configuration. For each feature, runs are grouped by
+/// dataset and, optionally, a sample or run metadata value, and each group is summarized by its
+/// minimum, quartiles, and maximum. Features are ordered by ID; datasets, by ID; groups, by
+/// value, with runs missing the value first; and runs, by ID.
+#[utoipa::path(
+    get,
+    path = "/configurations/{configuration_id}/expression",
+    operation_id = "configurations-expression-index",
+    params(
+        ("configuration_id" = i32, Path, description = "Configuration ID"),
+        IndexQuery,
+    ),
+    responses(
+        (status = OK, description = "The expression of the given features grouped by dataset"),
+        (status = BAD_REQUEST, description = "A feature name is unknown, too many names are given, or the runs cannot be normalized"),
+        (status = NOT_FOUND, description = "The configuration does not exist"),
+    ),
+)]
+async fn index(
+    principal: Principal,
+    Path(configuration_id): Path<i32>,
+    Query(query): Query<IndexQuery>,
+    State(ctx): State<Context>,
+) -> server::Result<Json<IndexBody>> {
+    use crate::store::configuration;
+
+    if !configuration::exists(&ctx.pool, configuration_id).await? {
+        return Err(Error::NotFound);
+    }
+
+    let mut names: Vec<String> = query
+        .names
+        .split(DELIMITER)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect();
+
+    names.sort_unstable();
+    names.dedup();
+
+    if names.is_empty() {
+        return Err(Error::BadRequest(String::from("missing feature names")));
+    } else if names.len() > MAX_FEATURE_NAMES {
+        return Err(Error::BadRequest(format!(
+            "too many feature names (max {MAX_FEATURE_NAMES})"
+        )));
+    }
+
+    let known_names = sqlx::query_scalar!(
+        "
+        select name
+        from features
+        where configuration_id = $1
+            and name = any($2)
+        order by id
+        ",
+        configuration_id,
+        &names,
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+
+    if known_names.len() < names.len() {
+        let unknown_names: Vec<_> = names
+            .iter()
+            .filter(|name| !known_names.contains(name))
+            .map(String::as_str)
+            .collect();
+
+        return Err(Error::BadRequest(format!(
+            "unknown feature names: {}",
+            unknown_names.join(", ")
+        )));
+    }
+
+    let dataset_ids = query
+        .dataset_ids
+        .as_deref()
+        .map(|s| {
+            s.split(DELIMITER)
+                .map(|t| t.trim().parse())
+                .collect::<Result<Vec<i32>, _>>()
+                .map_err(|e| Error::BadRequest(format!("invalid dataset ID: {e}")))
+        })
+        .transpose()?;
+
+    let datasets = sqlx::query_as!(
+        DatasetRuns,
+        r#"
+        select
+            datasets.id,
+            datasets.name,
+            array_agg(runs.id order by runs.id) as "run_ids!",
+            count(*) = (
+                select count(*)
+                from datasets_runs
+                where datasets_runs.dataset_id = datasets.id
+            ) as "is_complete!"
+        from datasets
+        inner join datasets_runs
+            on datasets_runs.dataset_id = datasets.id
+        inner join runs
+            on runs.id = datasets_runs.run_id
+        where runs.configuration_id = $1
+            and dataset_is_readable(datasets.id, $2, $3)
+            and ($4::integer[] is null or datasets.id = any($4))
+        group by datasets.id
+        order by datasets.id
+        "#,
+        configuration_id,
+        principal.subject(),
+        principal.is_admin(),
+        dataset_ids.as_deref(),
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+
+    let mut features: Vec<Feature> = known_names
+        .into_iter()
+        .map(|name| Feature {
+            name,
+            datasets: Vec::new(),
+        })
+        .collect();
+
+    let method = query.normalize.map(Method::from);
+
+    for dataset in datasets {
+        let factors = match method {
+            Some(method) => Some(dataset_factors(&ctx, configuration_id, &dataset, method).await?),
+            None => None,
+        };
+
+        let samples = sqlx::query_as!(
+            RunSample,
+            r#"
+            select
+                samples.name as sample_name,
+                samples.metadata || runs.metadata as "metadata!: SqlxJson<Metadata>"
+            from runs
+            inner join samples
+                on runs.sample_id = samples.id
+            where runs.id = any($1)
+            order by runs.id
+            "#,
+            &dataset.run_ids,
+        )
+        .fetch_all(&ctx.pool)
+        .await?;
+
+        let rows: Vec<_> =
+            count::stream_matrix(&ctx.pool, configuration_id, &dataset.run_ids, Some(&names))
+                .try_collect()
+                .await?;
+
+        // Rows and features are both ordered by feature ID.
+        for (feature, row) in features.iter_mut().zip(rows) {
+            let mut groups: BTreeMap<Option<&str>, Vec<Run>> = BTreeMap::new();
+
+            let values: Vec<_> = match &factors {
+                Some(factors) => normalization::integer_values(&row.values)?
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &n)| factors.apply(i, n as u32, row.feature_length as u32))
+                    .collect(),
+                None => row.values.into_real(),
+            };
+
+            for ((&id, sample), value) in dataset.run_ids.iter().zip(&samples).zip(values) {
+                let key = query
+                    .group_by
+                    .as_ref()
+                    .and_then(|key| sample.metadata.get(key))
+                    .map(String::as_str);
+
+                groups.entry(key).or_default().push(Run {
+                    id,
+                    sample_name: sample.sample_name.clone(),
+                    value,
+                });
+            }
+
+            let groups = groups
+                .into_iter()
+                .map(|(value, runs)| {
+                    let values: Vec<_> = runs.iter().map(|run| run.value).collect();
+
+                    Group {
+                        value: value.map(String::from),
+                        summary: FiveNumberSummary::new(&values).map(Summary::from),
+                        runs,
+                    }
+                })
+                .collect();
+
+            feature.datasets.push(Dataset {
+                id: dataset.id,
+                name: dataset.name.clone(),
+                groups,
+            });
+        }
+    }
+
+    Ok(Json(IndexBody {
+        expression: Expression { features },
+    }))
+}
+
+async fn dataset_factors(
+    ctx: &Context,
+    configuration_id: i32,
+    dataset: &DatasetRuns,
+    method: Method,
+) -> server::Result<Factors> {
+    if dataset.is_complete {
+        return normalization::dataset_factors(
+            &ctx.pool,
+            dataset.id,
+            configuration_id,
+            &dataset.run_ids,
+            method,
+        )
+        .await;
+    }
+
+    // Factors of datasets with runs in multiple configurations are not cached.
+    let matrix = Matrix::read(&ctx.pool, configuration_id, &dataset.run_ids).await?;
+
+    tokio::task::spawn_blocking(move || matrix.factors(method))
+        .await
+        .map_err(anyhow::Error::from)?
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use serde_json::json;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{Queue, server::tests::get_json};
+
+    fn app(pool: PgPool) -> Router {
+        let queue = Queue::new(pool.clone());
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    #[sqlx::test(fixtures("expression"))]
+    async fn test_index(pool: PgPool) -> anyhow::Result<()> {
+        let (status, actual) = get_json(
+            app(pool.clone()),
+            "/configurations/1/expression?names=feature_1",
+        )
+        .await?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            actual,
+            json!({
+                "expression": {
+                    "features": [{
+                        "name": "feature_1",
+                        "datasets": [{
+                            "id": 1,
+                            "name": "dataset_1",
+                            "groups": [{
+                                "value": null,
+                                "runs": [
+                                    { "id": 1, "sampleName": "sample_1", "value": 8.0 },
+                                    { "id": 2, "sampleName": "sample_2", "value": 21.0 },
+                                    { "id": 3, "sampleName": "sample_3", "value": 2.0 },
+                                ],
+                                "summary": {
+                                    "count": 3,
+                                    "min": 2.0,
+                                    "q1": 5.0,
+                                    "median": 8.0,
+                                    "q3": 14.5,
+                                    "max": 21.0,
+                                },
+                            }],
+                        }, {
+                            "id": 3,
+                            "name": "dataset_3",
+                            "groups": [{
+                                "value": null,
+                                "runs": [
+                                    { "id": 1, "sampleName": "sample_1", "value": 8.0 },
+                                ],
+                                "summary": {
+                                    "count": 1,
+                                    "min": 8.0,
+                                    "q1": 8.0,
+                                    "median": 8.0,
+                                    "q3": 8.0,
+                                    "max": 8.0,
+                                },
+                            }],
+                        }],
+                    }],
+                },
+            })
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("expression"))]
+    async fn test_index_with_grouping_and_normalization(pool: PgPool) -> anyhow::Result<()> {
+        let (status, actual) = get_json(
+            app(pool.clone()),
+            "/configurations/1/expression?names=feature_2,feature_1&normalize=cpm&group_by=tissue&dataset_ids=1",
+        )
+        .await?;
+
+        assert_eq!(status, StatusCode::OK);
+
+        let features = &actual["expression"]["features"];
+        assert_eq!(features[0]["name"], "feature_1");
+        assert_eq!(features[1]["name"], "feature_2");
+
+        let groups = &features[0]["datasets"][0]["groups"];
+        assert_eq!(groups[0]["value"], "liver");
+        assert_eq!(groups[0]["runs"][0]["id"], 1);
+        assert_eq!(groups[0]["runs"][0]["value"], json!(8e6 / 21.0));
+        assert_eq!(groups[1]["value"], "lung");
+        assert_eq!(groups[1]["summary"]["count"], 2);
+        let median = groups[1]["summary"]["median"].as_f64().unwrap_or_default();
+        assert!((median - 5e6 / 6.0).abs() < 1e-6);
+
+        let cached_count: i64 = sqlx::query_scalar(
+            "select count(*) from normalization_factors where dataset_id = 1 and method = 'cpm'",
+        )
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(cached_count, 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("expression"))]
+    async fn test_index_with_invalid_query(pool: PgPool) -> anyhow::Result<()> {
+        let (status, _) = get_json(
+            app(pool.clone()),
+            "/configurations/3/expression?names=feature_1",
+        )
+        .await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let request = Request::builder()
+            .uri("/configurations/1/expression?names=feature_1,feature_9")
+            .body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = response.into_body().collect().await?.to_bytes();
+        assert_eq!(body, "bad request: unknown feature names: feature_9");
+
+        let (status, _) =
+            get_json(app(pool.clone()), "/configurations/1/expression?names=,").await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = get_json(
+            app(pool.clone()),
+            "/configurations/1/expression?names=feature_1&dataset_ids=x",
+        )
+        .await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+}
@@ -1,11 +1,22 @@
 use axum::{
     Json, Router,
     extract::{Path, Query, State},
+    http::Uri,
     routing::get,
 };
 use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
 
-use crate::server::{self, Context, Error};
+use crate::{
+    server::{
+        self, Context, Error,
+        pagination::{PageQuery, next_link},
+    },
+    store::{
+        Principal,
+        feature::{Feature, SortBy},
+    },
+};
 
 pub fn router() -> Router<Context> {
     Router::new()
@@ -16,21 +27,23 @@ pub fn router() -> Router<Context> {
         )
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct IndexQuery {
+    /// The key to sort by. The default is `id`.
+    #[param(inline)]
+    sort: Option<SortBy>,
+    /// A case-insensitive search pattern of the feature name.
     q: Option<String>,
+    /// A case-insensitive prefix of the feature name.
+    name_prefix: Option<String>,
 }
 
 #[derive(Serialize)]
-struct IndexBody<T> {
-    features: T,
-}
-
-#[derive(Serialize, sqlx::FromRow)]
-struct Feature {
-    id: i32,
-    name: String,
-    length: i32,
+struct IndexBody {
+    features: Vec<Feature>,
+    total: i64,
+    next: Option<String>,
 }
 
 /// Lists features in a configuration.
@@ -40,63 +53,58 @@ struct Feature {
     operation_id = "configurations-features-index",
     params(
         ("configuration_id" = i32, Path, description = "Configuration ID"),
-        ("q" = String, Query, description = "A search pattern of the feature name"),
+        PageQuery,
+        IndexQuery,
     ),
     responses(
-        (status = OK, description = "Features associated with the given configuration"),
+        (status = OK, description = "A page of features associated with the given configuration"),
+        (status = BAD_REQUEST, description = "The limit or cursor is invalid"),
         (status = NOT_FOUND, description = "The configuration ID does not exist"),
     ),
 )]
 async fn index(
     Path(configuration_id): Path<i32>,
-    Query(params): Query<IndexQuery>,
+    uri: Uri,
+    Query(page_query): Query<PageQuery>,
+    Query(query): Query<IndexQuery>,
     State(ctx): State<Context>,
-) -> server::Result<Json<IndexBody<Vec<Feature>>>> {
-    use crate::store::configuration;
+) -> server::Result<Json<IndexBody>> {
+    use crate::store::{configuration, feature};
 
     if !configuration::exists(&ctx.pool, configuration_id).await? {
         return Err(Error::NotFound);
     }
 
-    let features = if let Some(q) = params.q {
-        sqlx::query_as(
-            r#"
-            select
-                id,
-                name,
-                length
-            from features
-            where configuration_id = $1
-                and name ilike concat('%', $2, '%')
-            "#,
-        )
-        .bind(configuration_id)
-        .bind(q)
-        .fetch_all(&ctx.pool)
-        .await?
-    } else {
-        sqlx::query_as(
-            r#"
-            select
-                id,
-                name,
-                length
-            from features
-            where configuration_id = $1
-            "#,
-        )
-        .bind(configuration_id)
-        .fetch_all(&ctx.pool)
-        .await?
+    let sort = query.sort.unwrap_or_default();
+    let request = page_query.into_request(sort)?;
+
+    let filter = feature::Filter {
+        q: query.q,
+        name_prefix: query.name_prefix,
     };
 
-    Ok(Json(IndexBody { features }))
+    let page =
+        feature::page_where_configuration_id(&ctx.pool, configuration_id, &filter, sort, &request)
+            .await?;
+
+    Ok(Json(IndexBody {
+        next: next_link(&uri, page.next.as_ref()),
+        total: page.total,
+        features: page.items,
+    }))
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Count {
+    Integer(i32),
+    Real(f64),
 }
 
 #[derive(Serialize)]
 struct Run {
     id: i32,
-    count: i32,
+    count: Count,
 }
 
 #[derive(Serialize)]
@@ -111,7 +119,7 @@ struct ShowResponse {
     feature: ShowFeature,
 }
 
-/// Shows counts for samples with the given configuration ID and feature name.
+/// Shows counts for readable runs with the given configuration ID and feature name.
 #[utoipa::path(
     get,
     path = "/configurations/{configuration_id}/features/{id}",
@@ -125,27 +133,41 @@ struct ShowResponse {
     ),
 )]
 async fn show(
+    principal: Principal,
     Path((configuration_id, id)): Path<(i32, i32)>,
     State(ctx): State<Context>,
 ) -> server::Result<Json<ShowResponse>> {
     let rows = sqlx::query!(
-        "
+        r#"
         select
             features.name,
             runs.id,
-            counts.value
-        from counts
-        inner join features
-            on features.id = counts.feature_id
+            run_counts.values[features.position] as "value?",
+            run_counts.real_values[features.position] as "real_value?"
+        from (
+            select
+                id,
+                configuration_id,
+                name,
+                row_number() over (order by id) as position
+            from features
+            where configuration_id = $2
+        ) as features
         inner join runs
-            on runs.id = counts.run_id
-        inner join samples
-            on samples.id = runs.sample_id
-        where counts.feature_id = $1
-            and runs.configuration_id = $2
-        ",
+            on runs.configuration_id = features.configuration_id
+        inner join run_counts
+            on run_counts.run_id = runs.id
+        where features.id = $1
+            and coalesce(
+                run_counts.values[features.position],
+                run_counts.real_values[features.position]
+            ) <> 0
+            and run_is_readable(runs.id, $3, $4)
+        "#,
         id,
         configuration_id,
+        principal.subject(),
+        principal.is_admin(),
     )
     .fetch_all(&ctx.pool)
     .await?;
@@ -161,7 +183,10 @@ async fn show(
         .into_iter()
         .map(|row| Run {
             id: row.id,
-            count: row.value,
+            count: match (row.value, row.real_value) {
+                (_, Some(n)) => Count::Real(n),
+                (n, None) => Count::Integer(n.unwrap_or(0)),
+            },
         })
         .collect();
 
@@ -186,7 +211,12 @@ mod tests {
 
     fn app(pool: PgPool) -> Router {
         let queue = Queue::new(pool.clone());
-        router().with_state(Context { pool, queue })
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
     }
 
     #[sqlx::test(fixtures("features"))]
@@ -211,13 +241,44 @@ mod tests {
                     "id": 2,
                     "name": "39_feature_2",
                     "length": 13,
-                }]
+                }],
+                "total": 2,
+                "next": null,
             })
         );
 
         Ok(())
     }
 
+    #[sqlx::test(fixtures("features"))]
+    async fn test_index_with_filters(pool: PgPool) -> anyhow::Result<()> {
+        let request = Request::builder()
+            .uri("/configurations/1/features?name_prefix=39_F&sort=name&order=desc&limit=1")
+            .body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["features"][0]["name"], "39_feature_2");
+        assert_eq!(actual["total"], 2);
+        assert!(actual["next"].is_string());
+
+        let request = Request::builder()
+            .uri("/configurations/1/features?q=feature_1")
+            .body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["features"][0]["name"], "39_feature_1");
+        assert_eq!(actual["total"], 1);
+
+        Ok(())
+    }
+
     #[sqlx::test]
     async fn test_index_with_invalid_configuration_id(pool: PgPool) -> anyhow::Result<()> {
         let request = Request::builder()
@@ -0,0 +1,58 @@
+insert into annotations
+  (name, genome_build)
+values
+  ('GENCODE 40', 'GRCh38.p13'),
+  ('GENCODE 19', 'GRCh37.p13');
+
+insert into configurations
+  (annotation_id, feature_type, feature_name)
+values
+  (1, 'exon', 'gene_name'),
+  (2, 'exon', 'gene_name');
+
+insert into features
+  (configuration_id, name, length)
+values
+  (1, 'feature_1', 8),
+  (1, 'feature_2', 13),
+  (2, 'feature_1', 8);
+
+insert into samples
+  (name, metadata)
+values
+  ('sample_1', '{"tissue": "liver"}'),
+  ('sample_2', '{"tissue": "lung"}'),
+  ('sample_3', '{"tissue": "lung"}');
+
+insert into runs
+  (sample_id, configuration_id, strand_specification, data_type)
+values
+  (1, 1, 'reverse', 'RNA-Seq'),
+  (2, 1, 'reverse', 'RNA-Seq'),
+  (3, 1, 'reverse', 'RNA-Seq'),
+  (1, 2, 'reverse', 'RNA-Seq');
+
+insert into run_counts
+  (run_id, values)
+values
+  (1, '{8, 13}'),
+  (2, '{21, 0}'),
+  (3, '{2, 1}'),
+  (4, '{34}');
+
+insert into datasets
+  (name, is_public)
+values
+  ('dataset_1', true),
+  ('dataset_2', false),
+  ('dataset_3', true);
+
+insert into datasets_runs
+  (dataset_id, run_id)
+values
+  (1, 1),
+  (1, 2),
+  (1, 3),
+  (2, 1),
+  (3, 1),
+  (3, 4);
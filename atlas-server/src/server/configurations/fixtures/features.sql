@@ -30,10 +30,11 @@ values
   (1, 1, 'reverse', 'RNA-Seq'),
   (2, 1, 'reverse', 'RNA-Seq');
 
-insert into counts
-  (run_id, feature_id, value)
+insert into run_counts
+  (run_id, values)
 values
-  (1, 1, 5),
-  (1, 2, 8),
-  (2, 1, 13),
-  (2, 2, 21);
+  (1, '{5, 8}'),
+  (2, '{13, 21}');
+
+insert into datasets (name, is_public) values ('dataset_1', true);
+insert into datasets_runs (dataset_id, run_id) values (1, 1), (1, 2);
@@ -1,25 +1,23 @@
 use std::num::ParseIntError;
 
 use anyhow::anyhow;
+use atlas_core::counts::normalization::Method;
 use axum::{
     Json, Router,
     extract::{Query, State},
     routing::get,
 };
-use ndarray::{Array2, Axis};
 use serde::{Deserialize, Serialize};
 
-use crate::server::Error;
-
-use super::Context;
+use crate::{
+    server::Error,
+    store::{Principal, count},
+};
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "snake_case")]
-enum Normalize {
-    Fpkm,
-    MedianOfRatios,
-    Tpm,
-}
+use super::{
+    Context,
+    normalization::{self, Matrix, Normalize},
+};
 
 #[derive(Debug, Deserialize)]
 struct IndexQuery {
@@ -33,6 +31,7 @@ struct IndexQuery {
 enum Values {
     Normalized(Vec<f64>),
     Raw(Vec<i32>),
+    Real(Vec<f64>),
 }
 
 #[derive(Serialize)]
@@ -63,23 +62,26 @@ pub fn router() -> Router<Context> {
     path = "/counts",
     operation_id = "counts-index",
     params(
-        ("run_ids" = String, Query, description = "A comma-separated list of run IDs"),
+        ("run_ids" = Option<String>, Query, description = "A comma-separated list of run IDs"),
+        ("dataset_id" = Option<i32>, Query, description = "A dataset ID, used when `run_ids` is not given"),
+        ("normalize" = Option<String>, Query, description = "The normalization method: \"cpm\", \"fpkm\", \"median_of_ratios\", \"tmm\", \"tpm\", or \"vst\""),
     ),
     responses(
         (status = OK, description = "Counts associated with the given run IDs"),
+        (status = BAD_REQUEST, description = "The runs are not in exactly one configuration or cannot be normalized"),
+        (status = NOT_FOUND, description = "A run or the dataset does not exist or is not readable"),
     ),
 )]
 async fn index(
+    principal: Principal,
     State(ctx): State<Context>,
     Query(params): Query<IndexQuery>,
 ) -> super::Result<Json<IndexBody>> {
-    use atlas_core::counts::normalization::median_of_ratios;
-
-    use crate::store::feature;
+    use crate::store::{dataset, run};
 
     const DELIMITER: char = ',';
 
-    let run_ids: Vec<i32> = if let Some(run_ids) = params.run_ids {
+    let run_ids: Vec<i32> = if let Some(run_ids) = &params.run_ids {
         run_ids
             .split(DELIMITER)
             .map(|s| {
@@ -88,15 +90,11 @@ async fn index(
             })
             .collect::<Result<_, _>>()?
     } else if let Some(dataset_id) = params.dataset_id {
-        sqlx::query!(
-            "select run_id from datasets_runs where dataset_id = $1",
-            dataset_id
-        )
-        .fetch_all(&ctx.pool)
-        .await?
-        .into_iter()
-        .map(|row| row.run_id)
-        .collect()
+        if !dataset::is_readable(&ctx.pool, &principal, dataset_id).await? {
+            return Err(Error::NotFound);
+        }
+
+        run::select_ids(&ctx.pool, &[dataset_id], &[]).await?
     } else {
         return Err(Error::Anyhow(anyhow!(
             "missing either run_ids or dataset_id"
@@ -107,114 +105,228 @@ async fn index(
         return Err(Error::NotFound);
     }
 
-    // SAFETY: `run_ids` is non-empty.
-    let feature_names = feature::find_names_by_run_id(&ctx.pool, run_ids[0]).await?;
-
-    let counts: Vec<_> = sqlx::query!(
-        r#"
-        select
-            coalesce(counts.value, 0) as "value!"
-        from runs
-        inner join configurations
-            on runs.configuration_id = configurations.id
-        inner join features
-            on runs.configuration_id = features.configuration_id
-        left join counts
-            on runs.id = counts.run_id and counts.feature_id = features.id
-        where runs.id in (select unnest($1::integer[]))
-        "#,
-        &run_ids[..]
-    )
-    .fetch_all(&ctx.pool)
-    .await?
-    .into_iter()
-    .map(|record| record.value)
-    .collect();
-
-    if counts.is_empty() {
+    // Runs are returned in ascending order.
+    let mut run_ids = run_ids;
+    run_ids.sort_unstable();
+    run_ids.dedup();
+
+    if run::filter_readable(&ctx.pool, &principal, &run_ids).await? != run_ids {
         return Err(Error::NotFound);
     }
 
-    let mut runs = Vec::with_capacity(run_ids.len());
+    let groups = run::group_by_configuration(&ctx.pool, &run_ids).await?;
 
-    if let Some(normalization_method) = params.normalize {
-        if matches!(normalization_method, Normalize::MedianOfRatios) {
-            let values: Vec<_> = counts.into_iter().map(|n| n as u32).collect();
-            let counts = Array2::from_shape_vec((run_ids.len(), feature_names.len()), values)
-                .map_err(|e| super::Error::Anyhow(e.into()))?;
+    let configuration_id = match &groups[..] {
+        [(configuration_id, _)] => *configuration_id,
+        _ => {
+            return Err(Error::BadRequest(format!(
+                "runs must be in exactly one configuration: {}",
+                format_groups(&groups)
+            )));
+        }
+    };
 
-            let normalized_counts = median_of_ratios::normalize(counts);
+    let matrix = Matrix::read(&ctx.pool, configuration_id, &run_ids).await?;
+    let feature_count = matrix.feature_names.len();
 
-            for (id, row) in run_ids
-                .into_iter()
-                .zip(normalized_counts.axis_iter(Axis(0)))
-            {
-                runs.push(Run {
-                    id,
-                    values: Values::Normalized(row.to_vec()),
-                });
-            }
-        } else {
-            let chunks = counts.chunks_exact(feature_names.len());
+    if feature_count == 0 {
+        return Err(Error::NotFound);
+    }
 
-            // SAFETY: `run_ids` is non-empty.
-            let features = feature::find_lengths_by_run_id(&ctx.pool, run_ids[0])
+    let runs = if let Some(normalize) = params.normalize {
+        let method = Method::from(normalize);
+
+        let factors = match params.dataset_id {
+            Some(dataset_id) if params.run_ids.is_none() => {
+                normalization::dataset_factors(
+                    &ctx.pool,
+                    dataset_id,
+                    configuration_id,
+                    &run_ids,
+                    method,
+                )
                 .await?
-                .into_iter()
-                .map(|(name, length)| (name.clone(), length as u32))
-                .collect();
-
-            for (id, chunk) in run_ids.into_iter().zip(chunks) {
-                let normalized_counts_map = match normalization_method {
-                    Normalize::Fpkm => {
-                        let counts = feature_names
-                            .iter()
-                            .zip(chunk)
-                            .map(|(name, count)| (name.clone(), *count as u32))
-                            .collect();
-
-                        atlas_core::counts::normalization::fpkm::normalize_map(&features, &counts)
-                            .unwrap()
-                    }
-                    Normalize::MedianOfRatios => unreachable!(),
-                    Normalize::Tpm => {
-                        let counts = feature_names
-                            .iter()
-                            .zip(chunk)
-                            .map(|(name, count)| (name.clone(), *count as u32))
-                            .collect();
-
-                        atlas_core::counts::normalization::tpm::normalize_map(&features, &counts)
-                            .unwrap()
-                    }
-                };
-
-                let normalized_counts = feature_names
+            }
+            _ => matrix.factors(method)?,
+        };
+
+        let counts = normalization::integer_values(&matrix.counts)?;
+
+        run_ids
+            .into_iter()
+            .zip(counts.chunks_exact(feature_count))
+            .enumerate()
+            .map(|(i, (id, row))| {
+                let values = row
                     .iter()
-                    .map(|name| normalized_counts_map[name])
+                    .zip(&matrix.feature_lengths)
+                    .map(|(&n, &length)| factors.apply(i, n as u32, length))
                     .collect();
 
-                runs.push(Run {
+                Run {
                     id,
-                    values: Values::Normalized(normalized_counts),
-                })
-            }
-        }
+                    values: Values::Normalized(values),
+                }
+            })
+            .collect()
     } else {
-        let chunks = counts.chunks_exact(feature_names.len());
-
-        for (id, chunk) in run_ids.into_iter().zip(chunks) {
-            runs.push(Run {
-                id,
-                values: Values::Raw(chunk.to_vec()),
-            });
+        match &matrix.counts {
+            count::Values::Integer(counts) => run_ids
+                .into_iter()
+                .zip(counts.chunks_exact(feature_count))
+                .map(|(id, row)| Run {
+                    id,
+                    values: Values::Raw(row.to_vec()),
+                })
+                .collect(),
+            count::Values::Real(counts) => run_ids
+                .into_iter()
+                .zip(counts.chunks_exact(feature_count))
+                .map(|(id, row)| Run {
+                    id,
+                    values: Values::Real(row.to_vec()),
+                })
+                .collect(),
         }
-    }
+    };
 
     Ok(Json(IndexBody {
         counts: Counts {
-            feature_names,
+            feature_names: matrix.feature_names,
             runs,
         },
     }))
 }
+
+/// Formats configuration groups as, e.g., "configuration 1 (runs 1, 2), configuration 2 (runs 3)".
+fn format_groups(groups: &[(i32, Vec<i32>)]) -> String {
+    groups
+        .iter()
+        .map(|(configuration_id, run_ids)| {
+            let run_ids: Vec<_> = run_ids.iter().map(|id| id.to_string()).collect();
+            format!(
+                "configuration {configuration_id} (runs {})",
+                run_ids.join(", ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use serde_json::json;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{Queue, server::tests::get_json};
+
+    fn app(pool: PgPool) -> Router {
+        let queue = Queue::new(pool.clone());
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    #[sqlx::test(fixtures("counts"))]
+    async fn test_index(pool: PgPool) -> anyhow::Result<()> {
+        let (status, actual) = get_json(app(pool.clone()), "/counts?run_ids=2,1,2").await?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            actual,
+            json!({
+                "counts": {
+                    "featureNames": ["feature_1", "feature_2"],
+                    "runs": [
+                        { "id": 1, "values": [8, 13] },
+                        { "id": 2, "values": [21, 0] },
+                    ],
+                },
+            })
+        );
+
+        let request = Request::builder()
+            .uri("/counts?run_ids=1,3")
+            .body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        assert_eq!(
+            body,
+            "bad request: runs must be in exactly one configuration: \
+            configuration 1 (runs 1), configuration 2 (runs 3)"
+        );
+
+        let (status, _) = get_json(app(pool.clone()), "/counts?dataset_id=2").await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("counts"))]
+    async fn test_index_with_normalization(pool: PgPool) -> anyhow::Result<()> {
+        let (status, actual) =
+            get_json(app(pool.clone()), "/counts?run_ids=1&normalize=cpm").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            actual["counts"]["runs"][0]["values"],
+            json!([8e6 / 21.0, 13e6 / 21.0])
+        );
+
+        let (status, actual) =
+            get_json(app(pool.clone()), "/counts?dataset_id=1&normalize=vst").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(actual["counts"]["runs"].as_array().map(Vec::len), Some(2));
+
+        let cached_count: i64 = sqlx::query_scalar(
+            "select count(*) from normalization_factors where dataset_id = 1 and method = 'vst'",
+        )
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(cached_count, 1);
+
+        let (status, _) = get_json(app(pool.clone()), "/counts?dataset_id=1&normalize=tmm").await?;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = get_json(app(pool.clone()), "/counts?run_ids=1&normalize=vst").await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("counts"))]
+    async fn test_index_with_real_values(pool: PgPool) -> anyhow::Result<()> {
+        sqlx::query(
+            "update run_counts set values = null, real_values = '{1.5, 0.25}' where run_id = 2",
+        )
+        .execute(&pool)
+        .await?;
+
+        let (status, actual) = get_json(app(pool.clone()), "/counts?run_ids=2").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            actual["counts"]["runs"],
+            json!([{ "id": 2, "values": [1.5, 0.25] }])
+        );
+
+        let (status, _) = get_json(app(pool.clone()), "/counts?run_ids=2&normalize=cpm").await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Integer and real-valued counts are not mixed.
+        let (status, _) = get_json(app(pool.clone()), "/counts?run_ids=1,2").await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+}
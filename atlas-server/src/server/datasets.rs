@@ -1,41 +1,154 @@
+pub mod counts;
 pub mod runs;
 
 use axum::{
     Json, Router,
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::{StatusCode, Uri},
     routing::get,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
 
-use super::Context;
+use super::{
+    Context,
+    auth::{require_authenticated, require_dataset_write},
+    error::is_unique_violation,
+    pagination::{PageQuery, next_link},
+};
 use crate::{
     server::Error,
-    store::dataset::{self, Dataset},
+    store::{
+        DatasetPermission, Principal,
+        audit_log::{self, Action},
+        dataset::{self, Dataset, SortBy},
+    },
 };
 
 pub fn router() -> Router<Context> {
     Router::new()
-        .route("/datasets", get(index))
-        .route("/datasets/{id}", get(show))
+        .route("/datasets", get(index).post(create))
+        .route("/datasets/{id}", get(show).patch(update))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct IndexQuery {
+    /// The key to sort by. The default is `id`.
+    #[param(inline)]
+    sort: Option<SortBy>,
+    /// A case-insensitive prefix of the dataset name.
+    name_prefix: Option<String>,
+    /// The inclusive lower bound of the creation time (RFC 3339).
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    created_after: Option<OffsetDateTime>,
+    /// The exclusive upper bound of the creation time (RFC 3339).
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    created_before: Option<OffsetDateTime>,
 }
 
 #[derive(Serialize)]
 struct IndexResponse {
     datasets: Vec<Dataset>,
+    total: i64,
+    next: Option<String>,
 }
 
-/// Lists all datasets.
+/// Lists readable datasets.
 #[utoipa::path(
     get,
     path = "/datasets",
     operation_id = "datasets-index",
+    params(PageQuery, IndexQuery),
+    responses(
+        (status = OK, description = "A page of datasets"),
+        (status = BAD_REQUEST, description = "The limit or cursor is invalid"),
+    ),
+)]
+async fn index(
+    principal: Principal,
+    State(ctx): State<Context>,
+    uri: Uri,
+    Query(page_query): Query<PageQuery>,
+    Query(query): Query<IndexQuery>,
+) -> super::Result<Json<IndexResponse>> {
+    let sort = query.sort.unwrap_or_default();
+    let request = page_query.into_request(sort)?;
+
+    let filter = dataset::Filter {
+        name_prefix: query.name_prefix,
+        created_after: query.created_after,
+        created_before: query.created_before,
+    };
+
+    let page = dataset::page(&ctx.pool, &principal, &filter, sort, &request).await?;
+
+    Ok(Json(IndexResponse {
+        next: next_link(&uri, page.next.as_ref()),
+        total: page.total,
+        datasets: page.items,
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct CreateRequest {
+    /// The name of the dataset.
+    name: String,
+    /// Whether the dataset is readable by anyone, including anonymous users.
+    #[serde(default)]
+    is_public: bool,
+}
+
+#[derive(Serialize)]
+struct CreateResponse {
+    id: i32,
+}
+
+/// Creates a dataset.
+///
+/// The creator is granted write permission on the dataset.
+#[utoipa::path(
+    post,
+    path = "/datasets",
+    operation_id = "datasets-create",
+    request_body = inline(CreateRequest),
     responses(
-        (status = OK, description = "A list of datasets"),
+        (status = CREATED, description = "The ID of the dataset created"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = CONFLICT, description = "A dataset with the given name already exists"),
     ),
+    security(("bearer" = [])),
 )]
-async fn index(State(ctx): State<Context>) -> super::Result<Json<IndexResponse>> {
-    let datasets = dataset::all(&ctx.pool).await?;
-    Ok(Json(IndexResponse { datasets }))
+async fn create(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Json(body): Json<CreateRequest>,
+) -> super::Result<(StatusCode, Json<CreateResponse>)> {
+    require_authenticated(&principal)?;
+
+    // SAFETY: `principal` is authenticated.
+    let subject = principal.subject().unwrap();
+
+    let mut tx = ctx.pool.begin().await?;
+
+    let id = dataset::create(&mut *tx, &body.name, body.is_public)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                Error::Conflict(String::from("dataset already exists"))
+            } else {
+                e.into()
+            }
+        })?;
+
+    dataset::grant(&mut *tx, id, subject, DatasetPermission::Write).await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(CreateResponse { id })))
 }
 
 #[derive(Serialize)]
@@ -53,13 +166,248 @@ struct ShowResponse {
     ),
     responses(
         (status = OK, description = "The dataset of the given ID"),
-        (status = NOT_FOUND, description = "The dataset does not exist"),
+        (status = NOT_FOUND, description = "The dataset does not exist or is not readable"),
     ),
 )]
 async fn show(
+    principal: Principal,
     State(ctx): State<Context>,
     Path(id): Path<i32>,
 ) -> super::Result<Json<ShowResponse>> {
-    let dataset = dataset::find(&ctx.pool, id).await?.ok_or(Error::NotFound)?;
+    let dataset = dataset::find(&ctx.pool, &principal, id)
+        .await?
+        .ok_or(Error::NotFound)?;
     Ok(Json(ShowResponse { dataset }))
 }
+
+#[derive(Deserialize, ToSchema)]
+struct UpdateRequest {
+    /// The new name of the dataset.
+    name: String,
+}
+
+/// Renames a dataset.
+#[utoipa::path(
+    patch,
+    path = "/datasets/{id}",
+    operation_id = "datasets-update",
+    params(
+        ("id" = i32, Path, description = "Dataset ID"),
+    ),
+    request_body = inline(UpdateRequest),
+    responses(
+        (status = NO_CONTENT, description = "The dataset was renamed"),
+        (status = BAD_REQUEST, description = "The name is empty"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = FORBIDDEN, description = "The dataset is not writable"),
+        (status = NOT_FOUND, description = "The dataset does not exist or is not readable"),
+        (status = CONFLICT, description = "A dataset with the given name already exists"),
+    ),
+    security(("bearer" = [])),
+)]
+async fn update(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(id): Path<i32>,
+    Json(body): Json<UpdateRequest>,
+) -> super::Result<StatusCode> {
+    require_dataset_write(&ctx.pool, &principal, id).await?;
+
+    if body.name.trim().is_empty() {
+        return Err(Error::BadRequest(String::from("name is empty")));
+    }
+
+    let mut tx = ctx.pool.begin().await?;
+
+    let previous_name = dataset::rename(&mut *tx, id, &body.name)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                Error::Conflict(String::from("dataset already exists"))
+            } else {
+                e.into()
+            }
+        })?
+        .ok_or(Error::NotFound)?;
+
+    audit_log::create(
+        &mut *tx,
+        principal.subject(),
+        Action::RenameDataset,
+        id,
+        &json!({ "from": previous_name, "to": body.name }),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, header},
+    };
+    use http_body_util::BodyExt;
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{Queue, server::auth::create_api_token};
+
+    fn app(pool: PgPool) -> Router {
+        let queue = Queue::new(pool.clone());
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    fn build_create_request(api_token: Option<&str>, name: &str) -> anyhow::Result<Request<Body>> {
+        let mut builder =
+            Request::post("/datasets").header(header::CONTENT_TYPE, "application/json");
+
+        if let Some(api_token) = api_token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {api_token}"));
+        }
+
+        let body = serde_json::to_vec(&json!({ "name": name }))?;
+
+        Ok(builder.body(Body::from(body))?)
+    }
+
+    #[sqlx::test]
+    async fn test_create(pool: PgPool) -> anyhow::Result<()> {
+        let request = build_create_request(None, "dataset_1")?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let api_token = create_api_token(&pool, "user", false).await?;
+
+        let request = build_create_request(Some(&api_token), "dataset_1")?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+        assert_eq!(actual, json!({ "id": 1 }));
+
+        let user = crate::store::principal::find(&pool, "user").await?;
+        assert!(dataset::is_writable(&pool, &user, 1).await?);
+        assert!(!dataset::is_readable(&pool, &Principal::anonymous(), 1).await?);
+
+        let request = build_create_request(Some(&api_token), "dataset_1")?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("../store/fixtures/dataset_permissions.sql"))]
+    async fn test_index(pool: PgPool) -> anyhow::Result<()> {
+        let request = Request::get("/datasets").body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+        assert_eq!(
+            actual,
+            json!({
+                "datasets": [{ "id": 1, "name": "public", "isPublic": true }],
+                "total": 1,
+                "next": null,
+            })
+        );
+
+        let api_token = create_api_token(&pool, "reader", false).await?;
+        let request = Request::get("/datasets")
+            .header(header::AUTHORIZATION, format!("Bearer {api_token}"))
+            .body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+        assert_eq!(actual["datasets"].as_array().map(Vec::len), Some(2));
+
+        let request = Request::get("/datasets?sort=name&order=desc&limit=1")
+            .header(header::AUTHORIZATION, format!("Bearer {api_token}"))
+            .body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+        assert_eq!(actual["datasets"][0]["name"], "restricted");
+        assert_eq!(actual["total"], 2);
+        assert!(actual["next"].is_string());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("../store/fixtures/dataset_permissions.sql"))]
+    async fn test_show(pool: PgPool) -> anyhow::Result<()> {
+        let request = Request::get("/datasets/1").body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::get("/datasets/2").body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("../store/fixtures/dataset_permissions.sql"))]
+    async fn test_update(pool: PgPool) -> anyhow::Result<()> {
+        let update = |id: i32, api_token: &str, name: &str| {
+            Request::patch(format!("/datasets/{id}"))
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, format!("Bearer {api_token}"))
+                .body(Body::from(json!({ "name": name }).to_string()))
+        };
+
+        let api_token = create_api_token(&pool, "reader", false).await?;
+        let response = app(pool.clone())
+            .oneshot(update(2, &api_token, "renamed")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let api_token = create_api_token(&pool, "writer", false).await?;
+
+        let response = app(pool.clone())
+            .oneshot(update(3, &api_token, "renamed")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app(pool.clone())
+            .oneshot(update(2, &api_token, "public")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = app(pool.clone())
+            .oneshot(update(2, &api_token, "renamed")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let name = sqlx::query_scalar!("select name from datasets where id = 2")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(name, "renamed");
+
+        let entry = sqlx::query!("select subject, details from audit_log where target_id = 2")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(entry.subject.as_deref(), Some("writer"));
+        assert_eq!(
+            entry.details,
+            json!({ "from": "restricted", "to": "renamed" })
+        );
+
+        Ok(())
+    }
+}
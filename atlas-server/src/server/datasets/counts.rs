@@ -0,0 +1,383 @@
+mod format;
+
+use atlas_core::counts::normalization::{Factors, Method};
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path, Query, State},
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use futures::StreamExt;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use utoipa::IntoParams;
+
+use self::format::{Encoder, Format, Values};
+use crate::{
+    server::{
+        Context, Error,
+        normalization::{self, Normalize},
+    },
+    store::{Principal, count, dataset, run},
+};
+
+/// The number of encoded chunks buffered ahead of the client.
+const CHANNEL_CAPACITY: usize = 4;
+
+pub fn router() -> Router<Context> {
+    Router::new()
+        .route("/datasets/{dataset_id}/counts.tsv", get(tsv))
+        .route("/datasets/{dataset_id}/counts.bin", get(columnar))
+        .route("/datasets/{dataset_id}/counts.json", get(json))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ExportQuery {
+    /// The normalization method: "cpm", "fpkm", "median_of_ratios", "tmm", "tpm", or "vst". By
+    /// default, raw counts are returned. Factors are calculated over all runs in the dataset.
+    #[param(value_type = Option<String>)]
+    normalize: Option<Normalize>,
+    /// A comma-separated list of feature names to include. By default, all features are
+    /// included. Unknown names are ignored.
+    feature_names: Option<String>,
+}
+
+/// Streams the features × runs count matrix of a dataset as tab-separated values.
+///
+/// The header is `feature_name` followed by the run IDs, ordered by ID.
+#[utoipa::path(
+    get,
+    path = "/datasets/{dataset_id}/counts.tsv",
+    operation_id = "datasets-counts-tsv",
+    params(
+        ("dataset_id" = i32, Path, description = "Dataset ID"),
+        ExportQuery,
+    ),
+    responses(
+        (status = OK, description = "The count matrix", content_type = "text/tab-separated-values"),
+        (status = BAD_REQUEST, description = "The dataset does not have runs in exactly one configuration, has both integer and real-valued counts, or cannot be normalized"),
+        (status = NOT_FOUND, description = "The dataset does not exist or is not readable"),
+    ),
+)]
+async fn tsv(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(dataset_id): Path<i32>,
+    Query(query): Query<ExportQuery>,
+) -> crate::server::Result<Response> {
+    export(&principal, ctx, dataset_id, query, Format::Tsv).await
+}
+
+/// Streams the features × runs count matrix of a dataset in a binary, columnar format.
+///
+/// The matrix is split into row groups of features, each storing the values of one run
+/// contiguously. All integers are little-endian.
+///
+/// 1. The magic number `ATLASCM1`.
+/// 2. The value type (`u8`): 0 for `i32` counts and 1 for `f64` normalized values or
+///    real-valued counts.
+/// 3. The number of runs, n (`u32`), followed by n run IDs (`i32`), ordered by ID.
+/// 4. Row groups, each with the number of features, m (`u32`); m feature names, each a length
+///    (`u32`) followed by UTF-8 bytes; and n columns of m values, one per run.
+/// 5. An empty row group, i.e., a feature count of 0.
+#[utoipa::path(
+    get,
+    path = "/datasets/{dataset_id}/counts.bin",
+    operation_id = "datasets-counts-columnar",
+    params(
+        ("dataset_id" = i32, Path, description = "Dataset ID"),
+        ExportQuery,
+    ),
+    responses(
+        (status = OK, description = "The count matrix", content_type = "application/octet-stream"),
+        (status = BAD_REQUEST, description = "The dataset does not have runs in exactly one configuration, has both integer and real-valued counts, or cannot be normalized"),
+        (status = NOT_FOUND, description = "The dataset does not exist or is not readable"),
+    ),
+)]
+async fn columnar(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(dataset_id): Path<i32>,
+    Query(query): Query<ExportQuery>,
+) -> crate::server::Result<Response> {
+    export(&principal, ctx, dataset_id, query, Format::Columnar).await
+}
+
+/// Streams the features × runs count matrix of a dataset as JSON.
+///
+/// The body is an object with `runIds`, ordered by ID, and `features`, each with a `name` and
+/// `values` in the order of `runIds`.
+#[utoipa::path(
+    get,
+    path = "/datasets/{dataset_id}/counts.json",
+    operation_id = "datasets-counts-json",
+    params(
+        ("dataset_id" = i32, Path, description = "Dataset ID"),
+        ExportQuery,
+    ),
+    responses(
+        (status = OK, description = "The count matrix", content_type = "application/json"),
+        (status = BAD_REQUEST, description = "The dataset does not have runs in exactly one configuration, has both integer and real-valued counts, or cannot be normalized"),
+        (status = NOT_FOUND, description = "The dataset does not exist or is not readable"),
+    ),
+)]
+async fn json(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(dataset_id): Path<i32>,
+    Query(query): Query<ExportQuery>,
+) -> crate::server::Result<Response> {
+    export(&principal, ctx, dataset_id, query, Format::Json).await
+}
+
+async fn export(
+    principal: &Principal,
+    ctx: Context,
+    dataset_id: i32,
+    query: ExportQuery,
+    format: Format,
+) -> crate::server::Result<Response> {
+    if !dataset::is_readable(&ctx.pool, principal, dataset_id).await? {
+        return Err(Error::NotFound);
+    }
+
+    let configuration_ids = dataset::configuration_ids(&ctx.pool, dataset_id).await?;
+
+    let [configuration_id] = configuration_ids[..] else {
+        return Err(Error::BadRequest(String::from(
+            "dataset must have runs in exactly one configuration",
+        )));
+    };
+
+    let run_ids = run::select_ids(&ctx.pool, &[dataset_id], &[]).await?;
+    let is_real = count::is_real_valued(&ctx.pool, configuration_id, &run_ids).await?;
+
+    let feature_names: Option<Vec<String>> = query
+        .feature_names
+        .map(|s| s.split(',').map(String::from).collect());
+
+    // Normalization methods require integer counts, so fail before calculating factors.
+    if is_real && query.normalize.is_some() {
+        return Err(normalization::real_valued_error());
+    }
+
+    let factors = match query.normalize {
+        Some(normalize) => Some(
+            normalization::dataset_factors(
+                &ctx.pool,
+                dataset_id,
+                configuration_id,
+                &run_ids,
+                Method::from(normalize),
+            )
+            .await?,
+        ),
+        None => None,
+    };
+
+    let (tx, mut rx) = mpsc::channel(CHANNEL_CAPACITY);
+
+    tokio::spawn(async move {
+        let mut rows = count::stream_matrix(
+            &ctx.pool,
+            configuration_id,
+            &run_ids,
+            feature_names.as_deref(),
+        );
+
+        let mut encoder = Encoder::new(format, &run_ids, factors.is_some() || is_real);
+
+        while let Some(result) = rows.next().await {
+            let row = match result {
+                Ok(row) => row,
+                Err(e) => {
+                    // The client sees a truncated response.
+                    tx.send(Err(Error::from(e))).await.ok();
+                    return;
+                }
+            };
+
+            let values = match (&factors, row.values) {
+                (Some(factors), count::Values::Integer(values)) => {
+                    Values::Normalized(normalize(factors, &values, row.feature_length))
+                }
+                (None, count::Values::Integer(values)) if !is_real => Values::Raw(values),
+                (None, count::Values::Real(values)) if is_real => Values::Real(values),
+                (None, _) => {
+                    // The counts of a run were replaced after their value type was read.
+                    let e = Error::Conflict(String::from("counts changed during export"));
+                    tx.send(Err(e)).await.ok();
+                    return;
+                }
+                (Some(_), count::Values::Real(_)) => {
+                    tx.send(Err(normalization::real_valued_error())).await.ok();
+                    return;
+                }
+            };
+
+            if let Some(chunk) = encoder.push(row.feature_name, values)
+                && tx.send(Ok(chunk)).await.is_err()
+            {
+                // The client disconnected.
+                return;
+            }
+        }
+
+        tx.send(Ok(encoder.finish())).await.ok();
+    });
+
+    let stream = futures::stream::poll_fn(move |cx| rx.poll_recv(cx));
+    let body = Body::from_stream(stream);
+
+    Ok(([(CONTENT_TYPE, format.content_type())], body).into_response())
+}
+
+fn normalize(factors: &Factors, values: &[i32], feature_length: i32) -> Vec<f64> {
+    let length = feature_length as u32;
+
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, &n)| factors.apply(i, n as u32, length))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{Queue, server::tests::get};
+
+    fn app(pool: PgPool) -> Router {
+        let queue = Queue::new(pool.clone());
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    #[sqlx::test(fixtures("counts"))]
+    async fn test_tsv(pool: PgPool) -> anyhow::Result<()> {
+        let (status, body) = get(app(pool.clone()), "/datasets/1/counts.tsv").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            b"feature_name\t1\t2\nfeature_1\t8\t21\nfeature_2\t13\t0\n"
+        );
+
+        let (_, body) = get(
+            app(pool.clone()),
+            "/datasets/1/counts.tsv?feature_names=feature_2",
+        )
+        .await?;
+        assert_eq!(body, b"feature_name\t1\t2\nfeature_2\t13\t0\n");
+
+        let (_, body) = get(app(pool.clone()), "/datasets/1/counts.tsv?normalize=fpkm").await?;
+        let expected = format!(
+            "feature_name\t1\t2\nfeature_1\t{}\t{}\nfeature_2\t{}\t0\n",
+            8e9 / (8.0 * 21.0),
+            21e9 / (8.0 * 21.0),
+            13e9 / (13.0 * 21.0),
+        );
+        assert_eq!(String::from_utf8(body)?, expected);
+
+        let (status, _) = get(app(pool.clone()), "/datasets/1/counts.tsv?normalize=rpkm").await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = get(app(pool.clone()), "/datasets/2/counts.tsv").await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get(app(pool.clone()), "/datasets/3/counts.tsv").await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("counts"))]
+    async fn test_columnar(pool: PgPool) -> anyhow::Result<()> {
+        let (status, body) = get(app(pool.clone()), "/datasets/1/counts.bin?normalize=tpm").await?;
+        assert_eq!(status, StatusCode::OK);
+
+        assert!(body.starts_with(b"ATLASCM1\x01"));
+        // header + group (count, 2 names, 2 columns of 2 values) + empty group
+        assert_eq!(
+            body.len(),
+            8 + 1 + 4 + 2 * 4 + 4 + 2 * (4 + 9) + 2 * 2 * 8 + 4
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("counts"))]
+    async fn test_json(pool: PgPool) -> anyhow::Result<()> {
+        let (status, body) = get(app(pool.clone()), "/datasets/1/counts.json").await?;
+        assert_eq!(status, StatusCode::OK);
+
+        let actual: serde_json::Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(
+            actual,
+            serde_json::json!({
+                "runIds": [1, 2],
+                "features": [
+                    { "name": "feature_1", "values": [8, 21] },
+                    { "name": "feature_2", "values": [13, 0] },
+                ],
+            })
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("counts"))]
+    async fn test_export_with_real_values(pool: PgPool) -> anyhow::Result<()> {
+        // Caches the normalization factors of the integer counts.
+        let (status, _) = get(app(pool.clone()), "/datasets/1/counts.tsv?normalize=cpm").await?;
+        assert_eq!(status, StatusCode::OK);
+
+        sqlx::query(
+            "update run_counts set values = null, real_values = '{1.5, 0.25}' where run_id = 2",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Integer and real-valued counts are not mixed.
+        let (status, _) = get(app(pool.clone()), "/datasets/1/counts.json").await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        sqlx::query(
+            "update run_counts set values = null, real_values = '{8.5, 13}' where run_id = 1",
+        )
+        .execute(&pool)
+        .await?;
+
+        let (status, body) = get(app(pool.clone()), "/datasets/1/counts.json").await?;
+        assert_eq!(status, StatusCode::OK);
+
+        let actual: serde_json::Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(
+            actual["features"],
+            serde_json::json!([
+                { "name": "feature_1", "values": [8.5, 1.5] },
+                { "name": "feature_2", "values": [13.0, 0.25] },
+            ])
+        );
+
+        let (status, body) = get(app(pool.clone()), "/datasets/1/counts.bin").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with(b"ATLASCM1\x01"));
+
+        let (status, _) = get(app(pool.clone()), "/datasets/1/counts.tsv?normalize=cpm").await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+}
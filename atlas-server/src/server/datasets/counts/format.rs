@@ -0,0 +1,292 @@
+use std::io::Write;
+
+use axum::body::Bytes;
+
+/// The number of rows encoded per chunk.
+///
+/// In the columnar format, this is also the number of rows per row group.
+const GROUP_SIZE: usize = 1024;
+
+/// The magic number of the columnar format.
+const MAGIC_NUMBER: &[u8; 8] = b"ATLASCM1";
+
+/// An output format of a features × runs count matrix.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum Format {
+    /// Tab-separated values with a header of run IDs.
+    Tsv,
+    /// A binary format split into row groups, each storing the values of one run contiguously.
+    ///
+    /// The layout is documented on the `/datasets/{dataset_id}/counts.bin` endpoint.
+    Columnar,
+    /// A JSON object with `runIds` and a list of `features`, each with a `name` and `values`.
+    Json,
+}
+
+impl Format {
+    pub(super) fn content_type(self) -> &'static str {
+        match self {
+            Self::Tsv => "text/tab-separated-values",
+            Self::Columnar => "application/octet-stream",
+            Self::Json => "application/json",
+        }
+    }
+}
+
+/// The values of a feature across runs.
+#[derive(Debug)]
+pub(super) enum Values {
+    Raw(Vec<i32>),
+    /// Raw real-valued counts.
+    Real(Vec<f64>),
+    Normalized(Vec<f64>),
+}
+
+impl Values {
+    fn write_tsv(&self, dst: &mut Vec<u8>) {
+        match self {
+            Self::Raw(values) => values.iter().for_each(|n| write!(dst, "\t{n}").unwrap()),
+            Self::Real(values) | Self::Normalized(values) => {
+                values.iter().for_each(|n| write!(dst, "\t{n}").unwrap())
+            }
+        }
+    }
+
+    fn write_le(&self, i: usize, dst: &mut Vec<u8>) {
+        match self {
+            Self::Raw(values) => dst.extend(values[i].to_le_bytes()),
+            Self::Real(values) | Self::Normalized(values) => dst.extend(values[i].to_le_bytes()),
+        }
+    }
+
+    fn write_json(&self, dst: &mut Vec<u8>) {
+        // SAFETY: Numbers are always serializable.
+        match self {
+            Self::Raw(values) => serde_json::to_writer(dst, values).unwrap(),
+            Self::Real(values) | Self::Normalized(values) => {
+                serde_json::to_writer(dst, values).unwrap()
+            }
+        }
+    }
+}
+
+/// Encodes a count matrix in chunks of rows.
+pub(super) struct Encoder {
+    format: Format,
+    run_count: usize,
+    buf: Vec<u8>,
+    group: Vec<(String, Values)>,
+    is_first_row: bool,
+}
+
+impl Encoder {
+    /// Creates an encoder and writes the header.
+    ///
+    /// `is_real` is whether values are `f64`, i.e., normalized or real-valued counts.
+    pub(super) fn new(format: Format, run_ids: &[i32], is_real: bool) -> Self {
+        let mut buf = Vec::new();
+
+        match format {
+            Format::Tsv => {
+                buf.extend(b"feature_name");
+
+                for id in run_ids {
+                    write!(buf, "\t{id}").unwrap();
+                }
+
+                buf.push(b'\n');
+            }
+            Format::Columnar => {
+                buf.extend(MAGIC_NUMBER);
+                buf.push(u8::from(is_real));
+                write_len(&mut buf, run_ids.len());
+
+                for id in run_ids {
+                    buf.extend(id.to_le_bytes());
+                }
+            }
+            Format::Json => {
+                buf.extend(b"{\"runIds\":");
+                serde_json::to_writer(&mut buf, run_ids).unwrap();
+                buf.extend(b",\"features\":[");
+            }
+        }
+
+        Self {
+            format,
+            run_count: run_ids.len(),
+            buf,
+            group: Vec::with_capacity(GROUP_SIZE),
+            is_first_row: true,
+        }
+    }
+
+    /// Adds a row and returns the encoded chunk once enough rows are buffered.
+    pub(super) fn push(&mut self, feature_name: String, values: Values) -> Option<Bytes> {
+        self.group.push((feature_name, values));
+
+        if self.group.len() < GROUP_SIZE {
+            return None;
+        }
+
+        self.flush_group();
+        Some(Bytes::from(std::mem::take(&mut self.buf)))
+    }
+
+    /// Encodes the remaining rows and the trailer.
+    pub(super) fn finish(mut self) -> Bytes {
+        self.flush_group();
+
+        match self.format {
+            Format::Tsv => {}
+            Format::Columnar => write_len(&mut self.buf, 0),
+            Format::Json => self.buf.extend(b"]}"),
+        }
+
+        Bytes::from(self.buf)
+    }
+
+    fn flush_group(&mut self) {
+        if self.group.is_empty() {
+            return;
+        }
+
+        let group = std::mem::take(&mut self.group);
+        let buf = &mut self.buf;
+
+        match self.format {
+            Format::Tsv => {
+                for (name, values) in &group {
+                    buf.extend(name.as_bytes());
+                    values.write_tsv(buf);
+                    buf.push(b'\n');
+                }
+            }
+            Format::Columnar => {
+                write_len(buf, group.len());
+
+                for (name, _) in &group {
+                    write_len(buf, name.len());
+                    buf.extend(name.as_bytes());
+                }
+
+                for i in 0..self.run_count {
+                    for (_, values) in &group {
+                        values.write_le(i, buf);
+                    }
+                }
+            }
+            Format::Json => {
+                for (name, values) in &group {
+                    if !self.is_first_row {
+                        buf.push(b',');
+                    }
+
+                    self.is_first_row = false;
+
+                    buf.extend(b"{\"name\":");
+                    serde_json::to_writer(&mut *buf, name).unwrap();
+                    buf.extend(b",\"values\":");
+                    values.write_json(buf);
+                    buf.push(b'}');
+                }
+            }
+        }
+
+        self.group = group;
+        self.group.clear();
+    }
+}
+
+fn write_len(dst: &mut Vec<u8>, len: usize) {
+    // SAFETY: Row groups, names, and run lists are all far smaller than 4 GiB.
+    let n = u32::try_from(len).unwrap();
+    dst.extend(n.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(format: Format, rows: Vec<(&str, Values)>, is_normalized: bool) -> Vec<u8> {
+        let mut encoder = Encoder::new(format, &[2, 1], is_normalized);
+        let mut dst = Vec::new();
+
+        for (name, values) in rows {
+            if let Some(chunk) = encoder.push(name.into(), values) {
+                dst.extend(chunk);
+            }
+        }
+
+        dst.extend(encoder.finish());
+        dst
+    }
+
+    #[test]
+    fn test_encode_tsv() {
+        let rows = vec![
+            ("feature_1", Values::Raw(vec![21, 8])),
+            ("feature_2", Values::Raw(vec![0, 13])),
+        ];
+
+        assert_eq!(
+            encode(Format::Tsv, rows, false),
+            b"feature_name\t2\t1\nfeature_1\t21\t8\nfeature_2\t0\t13\n"
+        );
+
+        let rows = vec![("feature_1", Values::Normalized(vec![0.5, 1.0]))];
+
+        assert_eq!(
+            encode(Format::Tsv, rows, true),
+            b"feature_name\t2\t1\nfeature_1\t0.5\t1\n"
+        );
+    }
+
+    #[test]
+    fn test_encode_columnar() {
+        let rows = vec![
+            ("feature_1", Values::Raw(vec![21, 8])),
+            ("feature_2", Values::Raw(vec![0, 13])),
+        ];
+
+        let mut expected = Vec::new();
+        expected.extend(b"ATLASCM1");
+        expected.push(0);
+        expected.extend([2, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0]);
+        expected.extend([2, 0, 0, 0]);
+        expected.extend([9, 0, 0, 0]);
+        expected.extend(b"feature_1");
+        expected.extend([9, 0, 0, 0]);
+        expected.extend(b"feature_2");
+        expected.extend([21, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend([8, 0, 0, 0, 13, 0, 0, 0]);
+        expected.extend([0, 0, 0, 0]);
+
+        assert_eq!(encode(Format::Columnar, rows, false), expected);
+    }
+
+    #[test]
+    fn test_encode_json() -> serde_json::Result<()> {
+        let rows = (0..GROUP_SIZE + 1)
+            .map(|_| ("feature_1", Values::Raw(vec![21, 8])))
+            .collect();
+
+        let dst = encode(Format::Json, rows, false);
+        let actual: serde_json::Value = serde_json::from_slice(&dst)?;
+
+        assert_eq!(actual["runIds"], serde_json::json!([2, 1]));
+        assert_eq!(
+            actual["features"].as_array().map(Vec::len),
+            Some(GROUP_SIZE + 1)
+        );
+        assert_eq!(
+            actual["features"][0],
+            serde_json::json!({ "name": "feature_1", "values": [21, 8] })
+        );
+
+        let dst = encode(Format::Json, Vec::new(), false);
+        assert_eq!(dst, br#"{"runIds":[2,1],"features":[]}"#);
+
+        Ok(())
+    }
+}
@@ -1,22 +1,61 @@
+use std::collections::HashMap;
+
 use axum::{
     Json, Router,
-    extract::{Path, State},
-    routing::get,
+    extract::{Path, Query, State},
+    http::{StatusCode, Uri},
+    routing::{get, put},
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::IntoParams;
 
 use crate::{
-    server::{Context, Error},
-    store::run::{self, Run},
+    server::{
+        Context, Error,
+        auth::require_dataset_write,
+        metadata,
+        pagination::{PageQuery, next_link},
+    },
+    store::{
+        Principal, StrandSpecification, dataset,
+        run::{self, Run, SortBy},
+    },
 };
 
 pub fn router() -> Router<Context> {
-    Router::new().route("/datasets/{dataset_id}/runs", get(index))
+    Router::new()
+        .route("/datasets/{dataset_id}/runs", get(index))
+        .route(
+            "/datasets/{dataset_id}/runs/{run_id}",
+            put(add).delete(remove),
+        )
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct IndexQuery {
+    /// The key to sort by. The default is `id`.
+    #[param(inline)]
+    sort: Option<SortBy>,
+    /// The data type, e.g., "RNA-Seq".
+    data_type: Option<String>,
+    /// The strand specification.
+    #[param(inline)]
+    strand_specification: Option<StrandSpecification>,
+    /// The inclusive lower bound of the creation time (RFC 3339).
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    created_after: Option<OffsetDateTime>,
+    /// The exclusive upper bound of the creation time (RFC 3339).
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    created_before: Option<OffsetDateTime>,
 }
 
 #[derive(Serialize)]
 struct IndexBody {
     runs: Vec<Run>,
+    total: i64,
+    next: Option<String>,
 }
 
 /// List run in a dataset.
@@ -26,21 +65,244 @@ struct IndexBody {
     operation_id = "datasets-runs-index",
     params(
         ("dataset_id" = i32, Path, description = "Dataset ID"),
+        PageQuery,
+        IndexQuery,
+        ("meta.{key}" = Option<String>, Query, description = "A metadata attribute the run must have, e.g., `meta.batch=2`"),
     ),
     responses(
-        (status = OK, description = "Runs associated with the given dataset"),
-        (status = NOT_FOUND, description = "The dataset does not exist"),
+        (status = OK, description = "A page of runs associated with the given dataset"),
+        (status = BAD_REQUEST, description = "The limit or cursor is invalid"),
+        (status = NOT_FOUND, description = "The dataset does not exist or is not readable"),
     ),
 )]
 async fn index(
+    principal: Principal,
     State(ctx): State<Context>,
     Path(dataset_id): Path<i32>,
+    uri: Uri,
+    Query(page_query): Query<PageQuery>,
+    Query(query): Query<IndexQuery>,
+    Query(params): Query<HashMap<String, String>>,
 ) -> crate::server::Result<Json<IndexBody>> {
-    let runs = run::where_dataset_id(&ctx.pool, dataset_id).await?;
+    if !dataset::is_readable(&ctx.pool, &principal, dataset_id).await? {
+        return Err(Error::NotFound);
+    }
 
-    if runs.is_empty() {
-        Err(Error::NotFound)
+    let sort = query.sort.unwrap_or_default();
+    let request = page_query.into_request(sort)?;
+
+    let filter = run::Filter {
+        data_type: query.data_type,
+        strand_specification: query.strand_specification,
+        created_after: query.created_after,
+        created_before: query.created_before,
+        metadata: metadata::filter(params),
+    };
+
+    let page =
+        run::page_where_dataset_id(&ctx.pool, &principal, dataset_id, &filter, sort, &request)
+            .await?;
+
+    Ok(Json(IndexBody {
+        next: next_link(&uri, page.next.as_ref()),
+        total: page.total,
+        runs: page.items,
+    }))
+}
+
+/// Adds a run to a dataset.
+///
+/// Adding a run that is already in the dataset has no effect.
+#[utoipa::path(
+    put,
+    path = "/datasets/{dataset_id}/runs/{run_id}",
+    operation_id = "datasets-runs-add",
+    params(
+        ("dataset_id" = i32, Path, description = "Dataset ID"),
+        ("run_id" = i32, Path, description = "Run ID"),
+    ),
+    responses(
+        (status = NO_CONTENT, description = "The run is in the dataset"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = FORBIDDEN, description = "The dataset is not writable"),
+        (status = NOT_FOUND, description = "The dataset or run does not exist or is not readable"),
+    ),
+    security(("bearer" = [])),
+)]
+async fn add(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path((dataset_id, run_id)): Path<(i32, i32)>,
+) -> crate::server::Result<StatusCode> {
+    require_dataset_write(&ctx.pool, &principal, dataset_id).await?;
+
+    let mut tx = ctx.pool.begin().await?;
+
+    if run::find(&mut *tx, &principal, run_id).await?.is_none() {
+        return Err(Error::NotFound);
+    }
+
+    if !dataset::contains(&mut *tx, dataset_id, run_id).await? {
+        dataset::add(&mut *tx, dataset_id, run_id).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Removes a run from a dataset.
+#[utoipa::path(
+    delete,
+    path = "/datasets/{dataset_id}/runs/{run_id}",
+    operation_id = "datasets-runs-remove",
+    params(
+        ("dataset_id" = i32, Path, description = "Dataset ID"),
+        ("run_id" = i32, Path, description = "Run ID"),
+    ),
+    responses(
+        (status = NO_CONTENT, description = "The run was removed from the dataset"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = FORBIDDEN, description = "The dataset is not writable"),
+        (status = NOT_FOUND, description = "The run is not in the dataset"),
+    ),
+    security(("bearer" = [])),
+)]
+async fn remove(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path((dataset_id, run_id)): Path<(i32, i32)>,
+) -> crate::server::Result<StatusCode> {
+    require_dataset_write(&ctx.pool, &principal, dataset_id).await?;
+
+    if dataset::remove(&ctx.pool, dataset_id, run_id).await? {
+        Ok(StatusCode::NO_CONTENT)
     } else {
-        Ok(Json(IndexBody { runs }))
+        Err(Error::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Method, Request, header},
+    };
+    use http_body_util::BodyExt;
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{Queue, server::auth::create_api_token, store::DatasetPermission};
+
+    fn app(pool: PgPool) -> Router {
+        let queue = Queue::new(pool.clone());
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    fn build_request(api_token: &str, method: Method, uri: &str) -> anyhow::Result<Request<Body>> {
+        Ok(Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {api_token}"))
+            .body(Body::empty())?)
+    }
+
+    #[sqlx::test(fixtures("../../store/fixtures/dataset_runs.sql"))]
+    async fn test_index(pool: PgPool) -> anyhow::Result<()> {
+        let request = Request::get("/datasets/1/runs").body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+        assert_eq!(actual["runs"].as_array().map(Vec::len), Some(1));
+        assert_eq!(actual["total"], 1);
+
+        let request =
+            Request::get("/datasets/1/runs?strand_specification=forward").body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+        assert_eq!(actual, json!({ "runs": [], "total": 0, "next": null }));
+
+        sqlx::query("update datasets set is_public = false where id = 1")
+            .execute(&pool)
+            .await?;
+
+        let request = Request::get("/datasets/1/runs").body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("../../store/fixtures/dataset_runs.sql"))]
+    async fn test_add(pool: PgPool) -> anyhow::Result<()> {
+        let request = Request::put("/datasets/1/runs/2").body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let api_token = create_api_token(&pool, "admin", true).await?;
+
+        for _ in 0..2 {
+            let request = build_request(&api_token, Method::PUT, "/datasets/1/runs/2")?;
+            let response = app(pool.clone()).oneshot(request).await?;
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        }
+
+        assert!(dataset::contains(&pool, 1, 2).await?);
+
+        let request = build_request(&api_token, Method::PUT, "/datasets/2/runs/1")?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = build_request(&api_token, Method::PUT, "/datasets/1/runs/3")?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("../../store/fixtures/dataset_runs.sql"))]
+    async fn test_add_with_insufficient_permissions(pool: PgPool) -> anyhow::Result<()> {
+        let api_token = create_api_token(&pool, "reader", false).await?;
+        let request = build_request(&api_token, Method::PUT, "/datasets/1/runs/1")?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Run 2 is not in a dataset, so it is only readable by administrators.
+        let api_token = create_api_token(&pool, "writer", false).await?;
+        dataset::grant(&pool, 1, "writer", DatasetPermission::Write).await?;
+        let request = build_request(&api_token, Method::PUT, "/datasets/1/runs/2")?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("../../store/fixtures/dataset_runs.sql"))]
+    async fn test_remove(pool: PgPool) -> anyhow::Result<()> {
+        let api_token = create_api_token(&pool, "admin", true).await?;
+
+        let request = build_request(&api_token, Method::DELETE, "/datasets/1/runs/1")?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        assert!(!dataset::contains(&pool, 1, 1).await?);
+
+        let request = build_request(&api_token, Method::DELETE, "/datasets/1/runs/1")?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
     }
 }
@@ -1,4 +1,6 @@
+use atlas_core::counts::normalization::NormalizeError;
 use axum::{
+    extract::multipart::MultipartError,
     http::StatusCode,
     response::{IntoResponse, Response},
 };
@@ -6,8 +8,16 @@ use tracing::error;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error("bad request: {0}")]
+    BadRequest(String),
+    #[error("unauthorized")]
+    Unauthorized,
+    #[error("forbidden")]
+    Forbidden,
     #[error("not found")]
     NotFound,
+    #[error("conflict: {0}")]
+    Conflict(String),
     #[error("database error")]
     Sqlx(#[from] sqlx::Error),
     #[error("internal server error")]
@@ -17,12 +27,54 @@ pub enum Error {
 impl Error {
     fn status_code(&self) -> StatusCode {
         match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
             Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
             Self::Sqlx(_) | Self::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
 
+impl From<crate::import::Error> for Error {
+    fn from(e: crate::import::Error) -> Self {
+        use crate::import::Error as ImportError;
+
+        match e {
+            ImportError::Database(e) => Self::Sqlx(e),
+            ImportError::RunExists => Self::Conflict(e.to_string()),
+            ImportError::Io(_)
+            | ImportError::MissingFeatures(_)
+            | ImportError::FeatureNameSetMismatch(_)
+            | ImportError::InvalidCounts(_) => Self::BadRequest(e.to_string()),
+        }
+    }
+}
+
+impl From<crate::store::count::Error> for Error {
+    fn from(e: crate::store::count::Error) -> Self {
+        use crate::store::count::Error as CountError;
+
+        match e {
+            CountError::Database(e) => Self::Sqlx(e),
+            CountError::MixedValues => Self::BadRequest(e.to_string()),
+        }
+    }
+}
+
+impl From<NormalizeError> for Error {
+    fn from(e: NormalizeError) -> Self {
+        Self::BadRequest(e.to_string())
+    }
+}
+
+impl From<MultipartError> for Error {
+    fn from(e: MultipartError) -> Self {
+        Self::BadRequest(e.body_text())
+    }
+}
+
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         match &self {
@@ -34,3 +86,7 @@ impl IntoResponse for Error {
         (self.status_code(), self.to_string()).into_response()
     }
 }
+
+pub(super) fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(e) if e.is_unique_violation())
+}
@@ -5,7 +5,10 @@ use axum::{
 };
 use serde::Serialize;
 
-use crate::server::{self, Context};
+use crate::{
+    server::{self, Context},
+    store::Principal,
+};
 
 pub fn router() -> Router<Context> {
     Router::new().route("/features/{feature_id}/runs", get(index))
@@ -29,10 +32,11 @@ struct Run {
         ("feature_id" = i32, Path, description = "Feature ID"),
     ),
     responses(
-        (status = OK, description = "Run IDs associated with the given feature ID"),
+        (status = OK, description = "Readable run IDs associated with the given feature ID"),
     ),
 )]
 async fn index(
+    principal: Principal,
     Path(feature_id): Path<i32>,
     State(ctx): State<Context>,
 ) -> server::Result<Json<IndexBody<Vec<Run>>>> {
@@ -41,18 +45,85 @@ async fn index(
         "
         select
             runs.id
-        from runs
-        inner join counts
-            on counts.run_id = runs.id
-        inner join features
-            on features.id = counts.feature_id
+        from (
+            select
+                features.id,
+                features.configuration_id,
+                row_number() over (order by features.id) as position
+            from features
+            inner join features as feature
+                on feature.configuration_id = features.configuration_id
+            where feature.id = $1
+        ) as features
+        inner join runs
+            on runs.configuration_id = features.configuration_id
+        inner join run_counts
+            on run_counts.run_id = runs.id
         where
             features.id = $1
+            and coalesce(
+                run_counts.values[features.position],
+                run_counts.real_values[features.position]
+            ) <> 0
+            and run_is_readable(runs.id, $2, $3)
         ",
         feature_id,
+        principal.subject(),
+        principal.is_admin(),
     )
     .fetch_all(&ctx.pool)
     .await?;
 
     Ok(Json(IndexBody { runs }))
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::Queue;
+
+    fn app(pool: PgPool) -> Router {
+        let queue = Queue::new(pool.clone());
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    async fn index(pool: &PgPool) -> anyhow::Result<Value> {
+        let request = Request::get("/features/1/runs").body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    #[sqlx::test(fixtures("../configurations/fixtures/features.sql"))]
+    async fn test_index(pool: PgPool) -> anyhow::Result<()> {
+        assert_eq!(
+            index(&pool).await?,
+            json!({ "runs": [{ "id": 1 }, { "id": 2 }] })
+        );
+
+        sqlx::query!("update datasets set is_public = false")
+            .execute(&pool)
+            .await?;
+
+        assert_eq!(index(&pool).await?, json!({ "runs": [] }));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,14 @@
+insert into annotations (name, genome_build) values ('GENCODE 40', 'GRCh38.p13');
+
+insert into configurations
+  (annotation_id, feature_type, feature_name)
+values
+  (1, 'exon', 'gene_name');
+
+insert into features
+  (configuration_id, name, length)
+values
+  (1, 'feature_1', 8),
+  (1, 'feature_2', 13);
+
+insert into datasets (name) values ('dataset_1');
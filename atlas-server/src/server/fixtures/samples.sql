@@ -22,3 +22,6 @@ values
   (1, 1, 'reverse', 'RNA-Seq'),
   (1, 2, 'reverse', 'RNA-Seq'),
   (2, 1, 'reverse', 'RNA-Seq');
+
+insert into datasets (name, is_public) values ('dataset_1', true);
+insert into datasets_runs (dataset_id, run_id) values (1, 1), (1, 2), (1, 3);
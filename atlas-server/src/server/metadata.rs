@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+use crate::store::Metadata;
+
+/// The prefix of query parameters that filter by a metadata attribute, e.g., `meta.tissue=liver`.
+const FILTER_PREFIX: &str = "meta.";
+
+/// Collects the metadata attributes to filter by from the query parameters.
+///
+/// Parameters without the `meta.` prefix are ignored.
+pub(super) fn filter(params: HashMap<String, String>) -> Metadata {
+    params
+        .into_iter()
+        .filter_map(|(key, value)| {
+            key.strip_prefix(FILTER_PREFIX)
+                .filter(|name| !name.is_empty())
+                .map(|name| (name.into(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let params = HashMap::from([
+            (String::from("meta.tissue"), String::from("liver")),
+            (String::from("meta."), String::from("lung")),
+            (String::from("sort"), String::from("name")),
+        ]);
+
+        assert_eq!(
+            filter(params),
+            Metadata::from([(String::from("tissue"), String::from("liver"))])
+        );
+    }
+}
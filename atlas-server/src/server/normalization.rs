@@ -0,0 +1,133 @@
+use atlas_core::counts::normalization::{Factors, Method};
+use futures::TryStreamExt;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::store::{
+    count::{self, Values},
+    normalization_factors,
+};
+
+/// A normalization method given as a query parameter.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum Normalize {
+    Cpm,
+    Fpkm,
+    MedianOfRatios,
+    Tmm,
+    Tpm,
+    Vst,
+}
+
+impl From<Normalize> for Method {
+    fn from(normalize: Normalize) -> Self {
+        match normalize {
+            Normalize::Cpm => Self::Cpm,
+            Normalize::Fpkm => Self::Fpkm,
+            Normalize::MedianOfRatios => Self::MedianOfRatios,
+            Normalize::Tmm => Self::Tmm,
+            Normalize::Tpm => Self::Tpm,
+            Normalize::Vst => Self::Vst,
+        }
+    }
+}
+
+/// A features × runs count matrix.
+pub(super) struct Matrix {
+    pub feature_names: Vec<String>,
+    pub feature_lengths: Vec<u32>,
+    pub run_count: usize,
+    /// A row-major runs × features matrix.
+    pub counts: Values,
+}
+
+impl Matrix {
+    /// Reads the counts of the given runs in a configuration.
+    pub(super) async fn read(
+        pool: &PgPool,
+        configuration_id: i32,
+        run_ids: &[i32],
+    ) -> Result<Self, count::Error> {
+        let (feature_names, feature_lengths): (Vec<_>, _) = sqlx::query!(
+            "select name, length from features where configuration_id = $1 order by id",
+            configuration_id,
+        )
+        .fetch(pool)
+        .map_ok(|row| (row.name, row.length as u32))
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .unzip();
+
+        let runs = count::find_values(pool, configuration_id, run_ids).await?;
+        let counts = Values::concat(runs, feature_names.len())?;
+
+        Ok(Self {
+            feature_names,
+            feature_lengths,
+            run_count: run_ids.len(),
+            counts,
+        })
+    }
+
+    /// Calculates the normalization factors of the runs.
+    pub(super) fn factors(&self, method: Method) -> super::Result<Factors> {
+        let counts: Vec<_> = integer_values(&self.counts)?
+            .iter()
+            .map(|&n| n as u32)
+            .collect();
+
+        Factors::calculate(
+            method,
+            self.run_count,
+            self.feature_names.len(),
+            &counts,
+            &self.feature_lengths,
+        )
+        .map_err(super::Error::from)
+    }
+}
+
+/// Returns integer counts to be normalized.
+///
+/// Real-valued counts, e.g., TPMs, cannot be normalized.
+pub(super) fn integer_values(values: &Values) -> super::Result<&[i32]> {
+    match values {
+        Values::Integer(values) => Ok(values),
+        Values::Real(_) => Err(real_valued_error()),
+    }
+}
+
+/// Returns the error for normalizing real-valued counts.
+pub(super) fn real_valued_error() -> super::Error {
+    super::Error::BadRequest(String::from("real-valued counts cannot be normalized"))
+}
+
+/// Returns the normalization factors of all runs in a dataset.
+///
+/// Factors are cached per dataset and method and recalculated when the runs in the dataset
+/// change. `run_ids` must be the sorted IDs of the runs in the dataset.
+pub(super) async fn dataset_factors(
+    pool: &PgPool,
+    dataset_id: i32,
+    configuration_id: i32,
+    run_ids: &[i32],
+    method: Method,
+) -> super::Result<Factors> {
+    if let Some(factors) = normalization_factors::find(pool, dataset_id, method, run_ids).await? {
+        return Ok(factors);
+    }
+
+    info!(dataset_id, ?method, "calculating normalization factors");
+
+    let matrix = Matrix::read(pool, configuration_id, run_ids).await?;
+    let factors = tokio::task::spawn_blocking(move || matrix.factors(method))
+        .await
+        .map_err(anyhow::Error::from)??;
+
+    normalization_factors::upsert(pool, dataset_id, run_ids, &factors).await?;
+
+    Ok(factors)
+}
@@ -0,0 +1,114 @@
+use axum::http::Uri;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use super::Error;
+use crate::store::page::{Cursor, DEFAULT_LIMIT, MAX_LIMIT, PageRequest, SortKey, SortOrder};
+
+const CURSOR_KEY: &str = "cursor";
+
+/// Query parameters to select a page of a list.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(super) struct PageQuery {
+    /// The maximum number of items to return, from 1 to 1000. The default is 100.
+    limit: Option<u32>,
+    /// The position to continue from, as given by the `next` link of the previous page.
+    cursor: Option<String>,
+    /// The sort direction. The default is ascending.
+    #[param(inline)]
+    order: Option<SortOrder>,
+}
+
+impl PageQuery {
+    /// Validates the query for a list sorted by the given key.
+    pub(super) fn into_request<S>(self, sort: S) -> super::Result<PageRequest>
+    where
+        S: SortKey,
+    {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(Error::BadRequest(format!(
+                "limit must be between 1 and {MAX_LIMIT}"
+            )));
+        }
+
+        let order = self.order.unwrap_or_default();
+
+        let after = self
+            .cursor
+            .map(|s| {
+                Cursor::decode(&s)
+                    .filter(|cursor| cursor.is_compatible(sort, order))
+                    .ok_or_else(|| Error::BadRequest(String::from("invalid cursor")))
+            })
+            .transpose()?;
+
+        Ok(PageRequest {
+            limit,
+            order,
+            after,
+        })
+    }
+}
+
+/// Returns a link to the page at the given cursor.
+///
+/// The link keeps all query parameters of the current request except its cursor.
+pub(super) fn next_link(uri: &Uri, cursor: Option<&Cursor>) -> Option<String> {
+    let cursor = cursor?;
+
+    let mut params: Vec<String> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| {
+            let key = param.split_once('=').map_or(*param, |(key, _)| key);
+            !param.is_empty() && key != CURSOR_KEY
+        })
+        .map(String::from)
+        .collect();
+
+    params.push(format!("{CURSOR_KEY}={}", cursor.encode()));
+
+    Some(format!("{}?{}", uri.path(), params.join("&")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::page::ById;
+
+    #[test]
+    fn test_into_request() -> anyhow::Result<()> {
+        let request = PageQuery::default().into_request(ById)?;
+        assert_eq!(request.limit, DEFAULT_LIMIT);
+        assert_eq!(request.order, SortOrder::Asc);
+        assert!(request.after.is_none());
+
+        for limit in [0, MAX_LIMIT + 1] {
+            let query = PageQuery {
+                limit: Some(limit),
+                ..Default::default()
+            };
+
+            assert!(matches!(
+                query.into_request(ById),
+                Err(Error::BadRequest(_))
+            ));
+        }
+
+        let query = PageQuery {
+            cursor: Some(String::from("atlas")),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            query.into_request(ById),
+            Err(Error::BadRequest(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -1,17 +1,236 @@
 pub mod counts;
 
+use std::collections::HashSet;
+
 use axum::{
     Json, Router,
-    extract::{Path, State},
-    routing::get,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    http::StatusCode,
+    routing::{get, post, put},
 };
+use clap::ValueEnum;
 use serde::Serialize;
+use serde_json::json;
+use utoipa::ToSchema;
+
+use super::{
+    Context, Error,
+    auth::{require_admin, require_authenticated, require_dataset_write},
+    error::is_unique_violation,
+    upload::{self, Form, MAX_UPLOAD_SIZE},
+};
+use crate::{
+    counts::{Counts, Format},
+    import::BATCH_CHUNK_SIZE,
+    store::{
+        Metadata, Principal, StrandSpecification,
+        audit_log::{self, Action},
+        configuration,
+        run::{self, Run},
+    },
+};
 
-use super::{Context, Error};
-use crate::store::run::{self, Run};
+const DEFAULT_SAMPLE_NAME_DELIMITER: &str = ".";
 
 pub fn router() -> Router<Context> {
-    Router::new().route("/runs/{id}", get(show))
+    Router::new()
+        .route(
+            "/runs",
+            post(create).layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE)),
+        )
+        .route("/runs/{id}", get(show).delete(delete))
+        .route("/runs/{id}/metadata", put(update_metadata))
+}
+
+#[derive(ToSchema)]
+#[schema(rename_all = "camelCase")]
+struct CreateRequest {
+    /// The configuration ID of the counts.
+    configuration_id: i32,
+    /// The ID of a dataset to add the runs to.
+    dataset_id: Option<i32>,
+    strand_specification: StrandSpecification,
+    /// The type of data, e.g., "RNA-Seq", "scRNA-Seq", etc.
+    data_type: String,
+    /// The input format, i.e., "htseq-count", "star", or "real". By default, the format is
+    /// autodetected as either "htseq-count" or "star".
+    #[schema(value_type = Option<String>)]
+    format: Option<Format>,
+    /// The sample name delimiter. The sample name is the filename up to the first delimiter.
+    /// Defaults to ".".
+    sample_name_delimiter: Option<String>,
+    /// Feature counts (htseq-count, STAR, or real-valued), one file per sample. These can be
+    /// uncompressed or gzip-compressed.
+    #[schema(value_type = Vec<String>, format = Binary)]
+    files: Vec<(String, Bytes)>,
+}
+
+impl CreateRequest {
+    async fn read(multipart: &mut Multipart) -> super::Result<Self> {
+        let mut form = Form::read(multipart).await?;
+
+        let configuration_id = form
+            .take_parsed("configurationId")?
+            .ok_or_else(|| Error::BadRequest(String::from("missing field: configurationId")))?;
+
+        let strand_specification = form
+            .take_required("strandSpecification")
+            .and_then(|s| parse_value_enum("strandSpecification", &s))?;
+
+        let format = form
+            .take("format")
+            .map(|s| parse_value_enum("format", &s))
+            .transpose()?;
+
+        let files = form.take_files("files");
+
+        if files.is_empty() {
+            return Err(Error::BadRequest(String::from("missing file: files")));
+        }
+
+        Ok(Self {
+            configuration_id,
+            dataset_id: form.take_parsed("datasetId")?,
+            strand_specification,
+            data_type: form.take_required("dataType")?,
+            format,
+            sample_name_delimiter: form.take("sampleNameDelimiter"),
+            files,
+        })
+    }
+}
+
+fn parse_value_enum<T>(name: &str, s: &str) -> super::Result<T>
+where
+    T: ValueEnum,
+{
+    T::from_str(s, true).map_err(|_| Error::BadRequest(format!("invalid field: {name}")))
+}
+
+fn sample_name(filename: &str, delimiter: &str) -> super::Result<String> {
+    // SAFETY: `str::Split` always has at least one item.
+    let sample_name = filename.split(delimiter).next().unwrap();
+
+    if sample_name.is_empty() {
+        Err(Error::BadRequest(format!("invalid filename: {filename}")))
+    } else {
+        Ok(sample_name.into())
+    }
+}
+
+#[derive(Serialize)]
+struct CreateResponse {
+    ids: Vec<i32>,
+}
+
+/// Creates samples, runs, and counts from uploaded feature counts.
+///
+/// This requires write permission on the dataset, if given, and otherwise an administrator.
+#[utoipa::path(
+    post,
+    path = "/runs",
+    operation_id = "runs-create",
+    request_body(content = inline(CreateRequest), content_type = "multipart/form-data"),
+    responses(
+        (status = CREATED, description = "The IDs of the runs created"),
+        (status = BAD_REQUEST, description = "The form or counts are invalid"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = FORBIDDEN, description = "The principal cannot write to the dataset"),
+        (status = NOT_FOUND, description = "The configuration or dataset does not exist"),
+        (status = CONFLICT, description = "A run already exists for a sample and the configuration"),
+    ),
+    security(("bearer" = [])),
+)]
+async fn create(
+    principal: Principal,
+    State(ctx): State<Context>,
+    mut multipart: Multipart,
+) -> super::Result<(StatusCode, Json<CreateResponse>)> {
+    use crate::import::{self, import_batch, read_counts};
+
+    require_authenticated(&principal)?;
+
+    let CreateRequest {
+        configuration_id,
+        dataset_id,
+        strand_specification,
+        data_type,
+        format,
+        sample_name_delimiter,
+        files,
+    } = CreateRequest::read(&mut multipart).await?;
+
+    match dataset_id {
+        Some(dataset_id) => require_dataset_write(&ctx.pool, &principal, dataset_id).await?,
+        None => require_admin(&principal)?,
+    }
+
+    let delimiter = sample_name_delimiter
+        .as_deref()
+        .unwrap_or(DEFAULT_SAMPLE_NAME_DELIMITER);
+
+    let mut sample_names = HashSet::with_capacity(files.len());
+    let mut samples = Vec::with_capacity(files.len());
+
+    for (filename, data) in files {
+        let sample_name = sample_name(&filename, delimiter)?;
+
+        if !sample_names.insert(sample_name.clone()) {
+            return Err(Error::BadRequest(format!(
+                "duplicate sample name: {sample_name}"
+            )));
+        }
+
+        samples.push((sample_name, data));
+    }
+
+    let feature_name = configuration::feature_name(&ctx.pool, configuration_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let samples = tokio::task::spawn_blocking(move || {
+        samples
+            .into_iter()
+            .map(|(sample_name, data)| {
+                let mut reader = upload::reader(data);
+
+                read_counts(&mut reader, format, &feature_name, strand_specification)
+                    .map(|counts| (sample_name.clone(), counts))
+                    .map_err(|e| Error::BadRequest(format!("invalid counts: {sample_name}: {e}")))
+            })
+            .collect::<super::Result<Vec<(String, Counts)>>>()
+    })
+    .await
+    .map_err(anyhow::Error::from)??;
+
+    let mut tx = ctx.pool.begin().await?;
+    let mut ids = Vec::with_capacity(samples.len());
+
+    for chunk in samples.chunks(BATCH_CHUNK_SIZE) {
+        let run_ids = import_batch(
+            &mut tx,
+            configuration_id,
+            dataset_id,
+            strand_specification,
+            &data_type,
+            chunk,
+        )
+        .await
+        .map_err(|e| match e {
+            // A concurrent import created a run for the same sample and configuration.
+            import::Error::Database(e) if is_unique_violation(&e) => {
+                Error::Conflict(import::Error::RunExists.to_string())
+            }
+            e => e.into(),
+        })?;
+
+        ids.extend(run_ids);
+    }
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(CreateResponse { ids })))
 }
 
 #[derive(Serialize)]
@@ -29,13 +248,309 @@ struct ShowResponse {
     ),
     responses(
         (status = OK, description = "The run of the given ID"),
-        (status = NOT_FOUND, description = "The run does not exist"),
+        (status = NOT_FOUND, description = "The run does not exist or is not readable"),
     ),
 )]
 async fn show(
+    principal: Principal,
     State(ctx): State<Context>,
     Path(id): Path<i32>,
 ) -> super::Result<Json<ShowResponse>> {
-    let run = run::find(&ctx.pool, id).await?.ok_or(Error::NotFound)?;
+    let run = run::find(&ctx.pool, &principal, id)
+        .await?
+        .ok_or(Error::NotFound)?;
     Ok(Json(ShowResponse { run }))
 }
+
+/// Deletes a run with its counts and removes it from all datasets.
+///
+/// This requires write permission on all of the run's datasets. Runs not in a dataset can only
+/// be deleted by an administrator.
+#[utoipa::path(
+    delete,
+    path = "/runs/{id}",
+    operation_id = "runs-delete",
+    params(
+        ("id" = i32, Path, description = "Run ID"),
+    ),
+    responses(
+        (status = NO_CONTENT, description = "The run was deleted"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = FORBIDDEN, description = "The run is not deletable"),
+        (status = NOT_FOUND, description = "The run does not exist or is not readable"),
+    ),
+    security(("bearer" = [])),
+)]
+async fn delete(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(id): Path<i32>,
+) -> super::Result<StatusCode> {
+    require_authenticated(&principal)?;
+
+    if run::find(&ctx.pool, &principal, id).await?.is_none() {
+        return Err(Error::NotFound);
+    }
+
+    let mut tx = ctx.pool.begin().await?;
+
+    if !run::is_deletable(&mut *tx, &principal, id).await? {
+        return Err(Error::Forbidden);
+    }
+
+    let dataset_ids = run::delete(&mut tx, id).await?.ok_or(Error::NotFound)?;
+
+    audit_log::create(
+        &mut *tx,
+        principal.subject(),
+        Action::DeleteRun,
+        id,
+        &json!({ "dataset_ids": dataset_ids }),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Replaces the metadata of a run.
+#[utoipa::path(
+    put,
+    path = "/runs/{id}/metadata",
+    operation_id = "runs-update-metadata",
+    params(
+        ("id" = i32, Path, description = "Run ID"),
+    ),
+    request_body(
+        content = Object,
+        description = "The attributes of the run, e.g., `{\"batch\": \"2\"}`",
+    ),
+    responses(
+        (status = NO_CONTENT, description = "The metadata was replaced"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = FORBIDDEN, description = "The run is not writable"),
+        (status = NOT_FOUND, description = "The run does not exist or is not readable"),
+    ),
+    security(("bearer" = [])),
+)]
+async fn update_metadata(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(id): Path<i32>,
+    Json(body): Json<Metadata>,
+) -> super::Result<StatusCode> {
+    require_authenticated(&principal)?;
+
+    if run::find(&ctx.pool, &principal, id).await?.is_none() {
+        return Err(Error::NotFound);
+    }
+
+    if !run::is_writable(&ctx.pool, &principal, id).await? {
+        return Err(Error::Forbidden);
+    }
+
+    run::set_metadata(&ctx.pool, id, &body).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, header},
+    };
+    use http_body_util::BodyExt;
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        Queue,
+        server::auth::create_api_token,
+        store::{DatasetPermission, dataset},
+    };
+
+    fn app(pool: PgPool) -> Router {
+        let queue = Queue::new(pool.clone());
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    fn build_create_request(
+        api_token: &str,
+        fields: &[(&str, &str)],
+        files: &[(&str, &str, &[u8])],
+    ) -> anyhow::Result<Request<Body>> {
+        let (content_type, body) = upload::build_form(fields, files);
+
+        Ok(Request::post("/runs")
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::AUTHORIZATION, format!("Bearer {api_token}"))
+            .body(Body::from(body))?)
+    }
+
+    const CREATE_FIELDS: [(&str, &str); 4] = [
+        ("configurationId", "1"),
+        ("datasetId", "1"),
+        ("strandSpecification", "reverse"),
+        ("dataType", "RNA-Seq"),
+    ];
+
+    const COUNTS: &[u8] = b"feature_1\t8\nfeature_2\t13\n__no_feature\t0\n";
+
+    #[sqlx::test(fixtures("runs"))]
+    async fn test_create(pool: PgPool) -> anyhow::Result<()> {
+        let api_token = create_api_token(&pool, "writer", false).await?;
+        dataset::grant(&pool, 1, "writer", DatasetPermission::Write).await?;
+
+        let files = [
+            ("files", "sample_1.htseq.txt", COUNTS),
+            ("files", "sample_2.htseq.txt", COUNTS),
+        ];
+
+        let request = build_create_request(&api_token, &CREATE_FIELDS, &files)?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+        assert_eq!(actual, json!({ "ids": [1, 2] }));
+
+        assert!(dataset::contains(&pool, 1, 2).await?);
+
+        let request = build_create_request(&api_token, &CREATE_FIELDS, &files[..1])?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("runs"))]
+    async fn test_create_with_invalid_request(pool: PgPool) -> anyhow::Result<()> {
+        let api_token = create_api_token(&pool, "admin", true).await?;
+
+        let files = [
+            ("files", "sample_1.htseq.txt", COUNTS),
+            ("files", "sample_1.star.txt", COUNTS),
+        ];
+
+        let request = build_create_request(&api_token, &CREATE_FIELDS, &files)?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let files = [("files", "sample_1.txt", &b"feature_1\t8\n"[..])];
+        let request = build_create_request(&api_token, &CREATE_FIELDS, &files)?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let files = [("files", "sample_1.txt", COUNTS)];
+
+        let mut fields = CREATE_FIELDS;
+        fields[2] = ("strandSpecification", "both");
+        let request = build_create_request(&api_token, &fields, &files)?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let mut fields = CREATE_FIELDS;
+        fields[0] = ("configurationId", "2");
+        let request = build_create_request(&api_token, &fields, &files)?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let mut fields = CREATE_FIELDS;
+        fields[1] = ("datasetId", "2");
+        let request = build_create_request(&api_token, &fields, &files)?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("runs"))]
+    async fn test_create_with_insufficient_permissions(pool: PgPool) -> anyhow::Result<()> {
+        let files = [("files", "sample_1.txt", COUNTS)];
+
+        let (content_type, body) = upload::build_form(&CREATE_FIELDS, &files);
+        let request = Request::post("/runs")
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body))?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let api_token = create_api_token(&pool, "reader", false).await?;
+        dataset::grant(&pool, 1, "reader", DatasetPermission::Read).await?;
+
+        let request = build_create_request(&api_token, &CREATE_FIELDS, &files)?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Runs not added to a dataset require an administrator.
+        let fields = [CREATE_FIELDS[0], CREATE_FIELDS[2], CREATE_FIELDS[3]];
+        let request = build_create_request(&api_token, &fields, &files)?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("runs"))]
+    async fn test_delete(pool: PgPool) -> anyhow::Result<()> {
+        let api_token = create_api_token(&pool, "writer", false).await?;
+        dataset::grant(&pool, 1, "writer", DatasetPermission::Write).await?;
+
+        let files = [("files", "sample_1.htseq.txt", COUNTS)];
+        let request = build_create_request(&api_token, &CREATE_FIELDS, &files)?;
+        app(pool.clone()).oneshot(request).await?;
+
+        let delete = |id: i32, api_token: &str| {
+            Request::delete(format!("/runs/{id}"))
+                .header(header::AUTHORIZATION, format!("Bearer {api_token}"))
+                .body(Body::empty())
+        };
+
+        // The run is also in a dataset the writer cannot write to.
+        let dataset_id = dataset::create(&pool, "dataset_2", true).await?;
+        dataset::add(&pool, dataset_id, 1).await?;
+
+        let response = app(pool.clone()).oneshot(delete(1, &api_token)?).await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        dataset::grant(&pool, dataset_id, "writer", DatasetPermission::Write).await?;
+
+        let response = app(pool.clone()).oneshot(delete(1, &api_token)?).await?;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let count_count = sqlx::query_scalar!(r#"select count(*) as "count!" from run_counts"#)
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count_count, 0);
+        assert!(!dataset::contains(&pool, 1, 1).await?);
+
+        let entry =
+            sqlx::query!("select subject, details from audit_log where action = 'delete_run'")
+                .fetch_one(&pool)
+                .await?;
+        assert_eq!(entry.subject.as_deref(), Some("writer"));
+        assert_eq!(entry.details, json!({ "dataset_ids": [1, dataset_id] }));
+
+        let response = app(pool).oneshot(delete(1, &api_token)?).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_name() -> anyhow::Result<()> {
+        assert_eq!(sample_name("sample_1.htseq.txt", ".")?, "sample_1");
+        assert_eq!(sample_name("sample_1_counts.txt", "_counts")?, "sample_1");
+        assert!(sample_name(".txt", ".").is_err());
+        Ok(())
+    }
+}
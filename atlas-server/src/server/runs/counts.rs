@@ -1,5 +1,4 @@
-use std::collections::HashMap;
-
+use atlas_core::counts::normalization::{Factors, Method};
 use axum::{
     Json, Router,
     extract::{Path, Query, State},
@@ -7,20 +6,18 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::server::{self, Context, Error};
+use crate::{
+    server::{
+        self, Context, Error,
+        normalization::{self, Normalize},
+    },
+    store::{Principal, count},
+};
 
 pub fn router() -> Router<Context> {
     Router::new().route("/runs/{run_id}/counts", get(index))
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "snake_case")]
-enum Normalize {
-    Fpkm,
-    MedianOfRatios,
-    Tpm,
-}
-
 #[derive(Debug, Deserialize)]
 struct IndexQuery {
     normalize: Option<Normalize>,
@@ -31,6 +28,7 @@ struct IndexQuery {
 enum Values {
     Normalized(Vec<f64>),
     Raw(Vec<i32>),
+    Real(Vec<f64>),
 }
 
 #[derive(Serialize)]
@@ -53,7 +51,9 @@ struct IndexBody {
 
 struct Count {
     name: String,
-    value: i32,
+    length: i32,
+    value: Option<i32>,
+    real_value: Option<f64>,
 }
 
 /// Shows counts for a given run.
@@ -63,35 +63,53 @@ struct Count {
     operation_id = "runs-counts-index",
     params(
         ("run_id" = i32, Path, description = "Run ID"),
+        ("normalize" = Option<String>, Query, description = "The normalization method: \"cpm\", \"fpkm\", \"median_of_ratios\", \"tmm\", or \"tpm\""),
     ),
     responses(
         (status = OK, description = "Counts associated with the given run"),
-        (status = NOT_FOUND, description = "The run ID does not exist")
+        (status = BAD_REQUEST, description = "The counts cannot be normalized with the given method"),
+        (status = NOT_FOUND, description = "The run ID does not exist or is not readable")
     )
 )]
 async fn index(
+    principal: Principal,
     State(ctx): State<Context>,
     Path(run_id): Path<i32>,
     Query(params): Query<IndexQuery>,
 ) -> server::Result<Json<IndexBody>> {
-    use crate::store::feature;
-
     let rows = sqlx::query_as!(
         Count,
         r#"
         select
             features.name,
-            coalesce(counts.value, 0) as "value!"
-        from runs
-        inner join configurations
-            on runs.configuration_id = configurations.id
-        inner join features
-            on runs.configuration_id = features.configuration_id
-        left join counts
-            on runs.id = counts.run_id and counts.feature_id = features.id
-        where runs.id = $1
+            features.length,
+            counts.value as "value?",
+            counts.real_value as "real_value?"
+        from (
+            select
+                name,
+                length,
+                row_number() over (order by id) as position
+            from features
+            where configuration_id = (
+                select configuration_id
+                from runs
+                where id = $1 and run_is_readable(id, $2, $3)
+            )
+        ) as features
+        left join (
+            select value, real_value, position
+            from run_counts
+            cross join unnest(run_counts.values, run_counts.real_values)
+                with ordinality as values(value, real_value, position)
+            where run_counts.run_id = $1
+        ) as counts
+            on counts.position = features.position
+        order by features.position
         "#,
-        run_id
+        run_id,
+        principal.subject(),
+        principal.is_admin(),
     )
     .fetch_all(&ctx.pool)
     .await?;
@@ -101,49 +119,44 @@ async fn index(
     }
 
     let feature_names: Vec<_> = rows.iter().map(|row| row.name.clone()).collect();
+    let lengths: Vec<_> = rows.iter().map(|row| row.length as u32).collect();
+
+    let counts = if rows.iter().any(|row| row.real_value.is_some()) {
+        count::Values::Real(
+            rows.iter()
+                .map(|row| row.real_value.unwrap_or(0.0))
+                .collect(),
+        )
+    } else {
+        count::Values::Integer(rows.iter().map(|row| row.value.unwrap_or(0)).collect())
+    };
 
-    let values = if let Some(normalization_method) = params.normalize {
-        let features = feature::find_lengths_by_run_id(&ctx.pool, run_id)
-            .await?
-            .into_iter()
-            .map(|(name, count)| (name, count as u32))
+    let values = if let Some(normalize) = params.normalize {
+        let counts: Vec<_> = normalization::integer_values(&counts)?
+            .iter()
+            .map(|&n| n as u32)
             .collect();
 
-        let normalized_counts = match normalization_method {
-            Normalize::Fpkm => {
-                let counts = rows
-                    .into_iter()
-                    .map(|row| (row.name, row.value as u32))
-                    .collect();
-
-                atlas_core::counts::normalization::fpkm::normalize_map(&features, &counts).unwrap()
-            }
-            Normalize::MedianOfRatios => {
-                // Applying median of ratios to a single sample is a no-op.
-                rows.into_iter()
-                    .map(|row| (row.name, f64::from(row.value)))
-                    .collect()
-            }
-            Normalize::Tpm => {
-                let counts = rows
-                    .into_iter()
-                    .map(|row| (row.name, row.value as u32))
-                    .collect();
-
-                atlas_core::counts::normalization::tpm::normalize_map(&features, &counts).unwrap()
-            }
-        };
-
-        let values = feature_names
-            .iter()
-            .map(|name| normalized_counts[name])
+        let factors = Factors::calculate(
+            Method::from(normalize),
+            1,
+            feature_names.len(),
+            &counts,
+            &lengths,
+        )?;
+
+        let values = counts
+            .into_iter()
+            .zip(lengths)
+            .map(|(n, length)| factors.apply(0, n, length))
             .collect();
 
         Values::Normalized(values)
     } else {
-        let counts: HashMap<_, _> = rows.into_iter().map(|row| (row.name, row.value)).collect();
-        let values = feature_names.iter().map(|name| counts[name]).collect();
-        Values::Raw(values)
+        match counts {
+            count::Values::Integer(values) => Values::Raw(values),
+            count::Values::Real(values) => Values::Real(values),
+        }
     };
 
     Ok(Json(IndexBody {
@@ -170,7 +183,12 @@ mod tests {
 
     fn app(pool: PgPool) -> Router {
         let queue = Queue::new(pool.clone());
-        router().with_state(Context { pool, queue })
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
     }
 
     #[sqlx::test(fixtures("counts"))]
@@ -201,6 +219,73 @@ mod tests {
         Ok(())
     }
 
+    #[sqlx::test(fixtures("counts"))]
+    async fn test_show_with_normalization(pool: PgPool) -> anyhow::Result<()> {
+        let request = Request::builder()
+            .uri("/runs/1/counts?normalize=cpm")
+            .body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["counts"]["run"]["values"], json!([1e6, 0.0]));
+
+        let request = Request::builder()
+            .uri("/runs/1/counts?normalize=vst")
+            .body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("counts"))]
+    async fn test_show_with_real_values(pool: PgPool) -> anyhow::Result<()> {
+        sqlx::query(
+            "update run_counts set values = null, real_values = '{1.5, 0.25}' where run_id = 1",
+        )
+        .execute(&pool)
+        .await?;
+
+        let request = Request::builder()
+            .uri("/runs/1/counts")
+            .body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["counts"]["run"]["values"], json!([1.5, 0.25]));
+
+        let request = Request::builder()
+            .uri("/runs/1/counts?normalize=cpm")
+            .body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("counts"))]
+    async fn test_show_with_an_unreadable_run(pool: PgPool) -> anyhow::Result<()> {
+        let request = Request::builder()
+            .uri("/runs/3/counts")
+            .body(Body::empty())?;
+
+        let response = app(pool).oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
     #[sqlx::test]
     async fn test_show_with_an_invalid_id(pool: PgPool) -> anyhow::Result<()> {
         let request = Request::builder()
@@ -27,4 +27,7 @@ values
   (1, 2, 'reverse', 'RNA-Seq'),
   (2, 1, 'reverse', 'RNA-Seq');
 
-insert into counts (run_id, feature_id, value) values (1, 1, 8);
+insert into run_counts (run_id, values) values (1, '{8, 0}');
+
+insert into datasets (name, is_public) values ('dataset_1', true);
+insert into datasets_runs (dataset_id, run_id) values (1, 1), (1, 2);
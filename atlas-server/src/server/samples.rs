@@ -1,40 +1,105 @@
 pub mod runs;
 
+use std::collections::HashMap;
+
 use axum::{
     Json, Router,
-    extract::{Path, State},
-    routing::get,
+    extract::{Path, Query, State},
+    http::{StatusCode, Uri},
+    routing::{get, put},
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use time::OffsetDateTime;
+use utoipa::IntoParams;
 
-use crate::store::sample::{self, Sample};
+use crate::store::{
+    Metadata, Principal,
+    audit_log::{self, Action},
+    sample::{self, Sample, SortBy},
+};
 
-use super::{Context, Error};
+use super::{
+    Context, Error,
+    auth::require_authenticated,
+    error::is_unique_violation,
+    metadata,
+    pagination::{PageQuery, next_link},
+};
 
 pub fn router() -> Router<Context> {
     Router::new()
         .route("/samples", get(index))
-        .route("/samples/{id}", get(show))
+        .route("/samples/{id}", get(show).patch(update))
+        .route("/samples/{id}/metadata", put(update_metadata))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct IndexQuery {
+    /// The key to sort by. The default is `id`.
+    #[param(inline)]
+    sort: Option<SortBy>,
+    /// A case-insensitive prefix of the sample name.
+    name_prefix: Option<String>,
+    /// The inclusive lower bound of the creation time (RFC 3339).
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    created_after: Option<OffsetDateTime>,
+    /// The exclusive upper bound of the creation time (RFC 3339).
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    created_before: Option<OffsetDateTime>,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
 struct IndexResponse {
     #[schema(inline)]
     samples: Vec<Sample>,
+    /// The number of samples across all pages.
+    total: i64,
+    /// A link to the next page, if any.
+    next: Option<String>,
 }
 
-/// Lists all samples with runs.
+/// Lists samples with readable runs.
 #[utoipa::path(
     get,
     path = "/samples",
     operation_id = "samples-index",
+    params(
+        PageQuery,
+        IndexQuery,
+        ("meta.{key}" = Option<String>, Query, description = "A metadata attribute the sample must have, e.g., `meta.tissue=liver`"),
+    ),
     responses(
-        (status = OK, description = "Samples with runs", body = inline(IndexResponse)),
+        (status = OK, description = "A page of samples with runs", body = inline(IndexResponse)),
+        (status = BAD_REQUEST, description = "The limit or cursor is invalid"),
     )
 )]
-async fn index(State(ctx): State<Context>) -> super::Result<Json<IndexResponse>> {
-    let samples = sample::all(&ctx.pool).await?;
-    Ok(Json(IndexResponse { samples }))
+async fn index(
+    principal: Principal,
+    State(ctx): State<Context>,
+    uri: Uri,
+    Query(page_query): Query<PageQuery>,
+    Query(query): Query<IndexQuery>,
+    Query(params): Query<HashMap<String, String>>,
+) -> super::Result<Json<IndexResponse>> {
+    let sort = query.sort.unwrap_or_default();
+    let request = page_query.into_request(sort)?;
+
+    let filter = sample::Filter {
+        name_prefix: query.name_prefix,
+        created_after: query.created_after,
+        created_before: query.created_before,
+        metadata: metadata::filter(params),
+    };
+
+    let page = sample::page(&ctx.pool, &principal, &filter, sort, &request).await?;
+
+    Ok(Json(IndexResponse {
+        next: next_link(&uri, page.next.as_ref()),
+        total: page.total,
+        samples: page.items,
+    }))
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -53,22 +118,142 @@ struct ShowResponse {
     ),
     responses(
         (status = OK, description = "The sample has runs", body = inline(ShowResponse)),
-        (status = NOT_FOUND, description = "The sample does not exist")
+        (status = NOT_FOUND, description = "The sample does not exist or has no readable runs")
     ),
 )]
 async fn show(
+    principal: Principal,
     State(ctx): State<Context>,
     Path(id): Path<i32>,
 ) -> super::Result<Json<ShowResponse>> {
-    let sample = sample::find(&ctx.pool, id).await?.ok_or(Error::NotFound)?;
+    let sample = sample::find(&ctx.pool, &principal, id)
+        .await?
+        .ok_or(Error::NotFound)?;
     Ok(Json(ShowResponse { sample }))
 }
 
+#[derive(Deserialize, utoipa::ToSchema)]
+struct UpdateRequest {
+    /// The new name of the sample.
+    name: String,
+}
+
+/// Renames a sample.
+///
+/// Requires write access to every dataset with a run of the sample.
+#[utoipa::path(
+    patch,
+    path = "/samples/{id}",
+    operation_id = "samples-update",
+    params(
+        ("id" = i32, Path, description = "Sample ID"),
+    ),
+    request_body = inline(UpdateRequest),
+    responses(
+        (status = NO_CONTENT, description = "The sample was renamed"),
+        (status = BAD_REQUEST, description = "The name is empty"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = FORBIDDEN, description = "The sample is not writable"),
+        (status = NOT_FOUND, description = "The sample does not exist or has no readable runs"),
+        (status = CONFLICT, description = "A sample with the given name already exists"),
+    ),
+    security(("bearer" = [])),
+)]
+async fn update(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(id): Path<i32>,
+    Json(body): Json<UpdateRequest>,
+) -> super::Result<StatusCode> {
+    require_authenticated(&principal)?;
+
+    if body.name.trim().is_empty() {
+        return Err(Error::BadRequest(String::from("name is empty")));
+    }
+
+    if sample::find(&ctx.pool, &principal, id).await?.is_none() {
+        return Err(Error::NotFound);
+    }
+
+    if !sample::is_writable(&ctx.pool, &principal, id).await? {
+        return Err(Error::Forbidden);
+    }
+
+    let mut tx = ctx.pool.begin().await?;
+
+    let previous_name = sample::rename(&mut *tx, id, &body.name)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                Error::Conflict(String::from("sample already exists"))
+            } else {
+                e.into()
+            }
+        })?
+        .ok_or(Error::NotFound)?;
+
+    audit_log::create(
+        &mut *tx,
+        principal.subject(),
+        Action::RenameSample,
+        id,
+        &json!({ "from": previous_name, "to": body.name }),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Replaces the metadata of a sample.
+///
+/// Requires write access to every dataset with a run of the sample.
+#[utoipa::path(
+    put,
+    path = "/samples/{id}/metadata",
+    operation_id = "samples-update-metadata",
+    params(
+        ("id" = i32, Path, description = "Sample ID"),
+    ),
+    request_body(
+        content = Object,
+        description = "The attributes of the sample, e.g., `{\"tissue\": \"liver\"}`",
+    ),
+    responses(
+        (status = NO_CONTENT, description = "The metadata was replaced"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = FORBIDDEN, description = "The sample is not writable"),
+        (status = NOT_FOUND, description = "The sample does not exist or has no readable runs"),
+    ),
+    security(("bearer" = [])),
+)]
+async fn update_metadata(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(id): Path<i32>,
+    Json(body): Json<Metadata>,
+) -> super::Result<StatusCode> {
+    require_authenticated(&principal)?;
+
+    if sample::find(&ctx.pool, &principal, id).await?.is_none() {
+        return Err(Error::NotFound);
+    }
+
+    if !sample::is_writable(&ctx.pool, &principal, id).await? {
+        return Err(Error::Forbidden);
+    }
+
+    sample::set_metadata(&ctx.pool, id, &body).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[cfg(test)]
 mod tests {
     use axum::{
         body::Body,
-        http::{Request, StatusCode},
+        http::{Request, StatusCode, header},
     };
     use http_body_util::BodyExt;
     use serde_json::{Value, json};
@@ -80,7 +265,12 @@ mod tests {
 
     fn app(pool: PgPool) -> Router {
         let queue = Queue::new(pool.clone());
-        router().with_state(Context { pool, queue })
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
     }
 
     #[sqlx::test(fixtures("samples"))]
@@ -100,17 +290,71 @@ mod tests {
                     "id": 1,
                     "name": "sample_1",
                     "createdAt": "2022-02-18T21:05:05Z",
+                    "metadata": {},
                 }, {
                     "id": 2,
                     "name": "sample_2",
                     "createdAt": "2022-02-18T21:05:06Z",
-                }]
+                    "metadata": {},
+                }],
+                "total": 2,
+                "next": null,
             })
         );
 
         Ok(())
     }
 
+    #[sqlx::test(fixtures("samples"))]
+    async fn test_index_with_pagination(pool: PgPool) -> anyhow::Result<()> {
+        let request = Request::builder()
+            .uri("/samples?limit=1&sort=created_at&order=desc")
+            .body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["samples"][0]["id"], 2);
+        assert_eq!(actual["total"], 2);
+
+        let next = actual["next"].as_str().unwrap();
+        assert!(next.starts_with("/samples?limit=1&sort=created_at&order=desc&cursor="));
+
+        let request = Request::builder().uri(next).body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["samples"][0]["id"], 1);
+        assert_eq!(actual["next"], Value::Null);
+
+        // A cursor is only valid for the sort key and order it was created with.
+        let uri = next.replace("order=desc", "order=asc");
+        let request = Request::builder().uri(uri).body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = Request::builder()
+            .uri("/samples?name_prefix=SAMPLE_2&created_after=2022-02-18T21:05:06Z")
+            .body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["samples"].as_array().map(Vec::len), Some(1));
+        assert_eq!(actual["total"], 1);
+
+        let request = Request::builder()
+            .uri("/samples?limit=0")
+            .body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
     #[sqlx::test(fixtures("samples"))]
     async fn test_show(pool: PgPool) -> anyhow::Result<()> {
         let request = Request::builder().uri("/samples/1").body(Body::empty())?;
@@ -126,6 +370,7 @@ mod tests {
                     "id": 1,
                     "name": "sample_1",
                     "createdAt": "2022-02-18T21:05:05Z",
+                    "metadata": {},
                 },
             })
         );
@@ -133,6 +378,124 @@ mod tests {
         Ok(())
     }
 
+    #[sqlx::test(fixtures("samples"))]
+    async fn test_update_metadata(pool: PgPool) -> anyhow::Result<()> {
+        use crate::{
+            server::auth::create_api_token,
+            store::{DatasetPermission, dataset},
+        };
+
+        let update = |id: i32, token: Option<&str>| {
+            let mut builder = Request::put(format!("/samples/{id}/metadata"))
+                .header(header::CONTENT_TYPE, "application/json");
+
+            if let Some(token) = token {
+                builder = builder.header(header::AUTHORIZATION, format!("Bearer {token}"));
+            }
+
+            builder.body(Body::from(r#"{"tissue":"liver"}"#))
+        };
+
+        let response = app(pool.clone()).oneshot(update(1, None)?).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let token = create_api_token(&pool, "writer", false).await?;
+
+        let response = app(pool.clone()).oneshot(update(1, Some(&token))?).await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app(pool.clone()).oneshot(update(8, Some(&token))?).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        dataset::grant(&pool, 1, "writer", DatasetPermission::Write).await?;
+
+        let response = app(pool.clone()).oneshot(update(1, Some(&token))?).await?;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let request = Request::builder()
+            .uri("/samples?meta.tissue=liver")
+            .body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["total"], 1);
+        assert_eq!(
+            actual["samples"][0]["metadata"],
+            json!({ "tissue": "liver" })
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("samples"))]
+    async fn test_update(pool: PgPool) -> anyhow::Result<()> {
+        use crate::{
+            server::auth::create_api_token,
+            store::{DatasetPermission, dataset},
+        };
+
+        let update = |id: i32, token: Option<&str>, name: &str| {
+            let mut builder = Request::patch(format!("/samples/{id}"))
+                .header(header::CONTENT_TYPE, "application/json");
+
+            if let Some(token) = token {
+                builder = builder.header(header::AUTHORIZATION, format!("Bearer {token}"));
+            }
+
+            builder.body(Body::from(json!({ "name": name }).to_string()))
+        };
+
+        let response = app(pool.clone())
+            .oneshot(update(1, None, "sample_3")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let token = create_api_token(&pool, "writer", false).await?;
+
+        let response = app(pool.clone())
+            .oneshot(update(1, Some(&token), "sample_3")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        dataset::grant(&pool, 1, "writer", DatasetPermission::Write).await?;
+
+        let response = app(pool.clone())
+            .oneshot(update(1, Some(&token), " ")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app(pool.clone())
+            .oneshot(update(1, Some(&token), "sample_2")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = app(pool.clone())
+            .oneshot(update(1, Some(&token), "sample_3")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let request = Request::builder().uri("/samples/1").body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+        assert_eq!(actual["sample"]["name"], "sample_3");
+
+        let entry = sqlx::query!(
+            "select subject, target_id, details from audit_log where action = 'rename_sample'"
+        )
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(entry.subject.as_deref(), Some("writer"));
+        assert_eq!(entry.target_id, 1);
+        assert_eq!(
+            entry.details,
+            json!({ "from": "sample_1", "to": "sample_3" })
+        );
+
+        Ok(())
+    }
+
     #[sqlx::test(fixtures("samples"))]
     async fn test_show_with_an_invalid_id(pool: PgPool) -> anyhow::Result<()> {
         let request = Request::builder()
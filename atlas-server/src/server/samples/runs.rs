@@ -7,7 +7,7 @@ use serde::Serialize;
 
 use crate::{
     server::{Context, Error},
-    store::run::Run,
+    store::{Principal, run::Run},
 };
 
 pub fn router() -> Router<Context> {
@@ -29,16 +29,17 @@ struct IndexBody {
     ),
     responses(
         (status = OK, description = "Runs associated with the given sample"),
-        (status = NOT_FOUND, description = "The sample does not exist"),
+        (status = NOT_FOUND, description = "The sample does not exist or has no readable runs"),
     ),
 )]
 async fn index(
+    principal: Principal,
     Path(sample_id): Path<i32>,
     State(ctx): State<Context>,
 ) -> crate::server::Result<Json<IndexBody>> {
     use crate::store::run;
 
-    let runs = run::where_sample_id(&ctx.pool, sample_id).await?;
+    let runs = run::where_sample_id(&ctx.pool, &principal, sample_id).await?;
 
     if runs.is_empty() {
         Err(Error::NotFound)
@@ -0,0 +1,557 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+};
+use std::{sync::Arc, time::Duration};
+
+use futures::{Stream, stream};
+use serde::Serialize;
+use sqlx::{PgPool, postgres::PgListener, types::Json as SqlxJson};
+use time::OffsetDateTime;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task::AbortHandle,
+};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    queue::{Message, STATUS_CHANNEL, Status},
+    server::{self, Context, Error, auth::require_authenticated},
+    store::{Principal, dataset, run},
+};
+
+pub fn router() -> Router<Context> {
+    Router::new()
+        .route("/tasks/{id}", get(show).delete(cancel))
+        .route("/tasks/{id}/events", get(events))
+}
+
+// The number of status changes buffered for each subscriber before it lags.
+const STATUS_CHANGES_CAPACITY: usize = 256;
+
+// The delay before listening again after the listener fails.
+const LISTEN_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Task status changes received on a single shared listener.
+///
+/// Each change is the ID of the task or `None` if changes may have been missed, e.g., after the
+/// listener reconnected. The listener stops when the last clone is dropped.
+#[derive(Clone)]
+pub(super) struct StatusChanges {
+    sender: broadcast::Sender<Option<Uuid>>,
+    _listener: Option<Arc<ListenerGuard>>,
+}
+
+impl StatusChanges {
+    /// Listens for task status changes on a single dedicated connection.
+    pub(super) async fn listen(pool: &PgPool) -> sqlx::Result<Self> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(STATUS_CHANNEL).await?;
+
+        let (sender, _) = broadcast::channel(STATUS_CHANGES_CAPACITY);
+        let task_sender = sender.clone();
+
+        let handle = tokio::spawn(async move {
+            loop {
+                // `try_recv` returns `None` when the connection was reestablished.
+                let id = match listener.try_recv().await {
+                    Ok(Some(notification)) => match notification.payload().parse() {
+                        Ok(id) => Some(id),
+                        Err(e) => {
+                            warn!(error = %e, "invalid task status notification");
+                            continue;
+                        }
+                    },
+                    Ok(None) => None,
+                    Err(e) => {
+                        warn!(error = ?e, "failed to receive task status notification");
+                        tokio::time::sleep(LISTEN_RETRY_DELAY).await;
+                        None
+                    }
+                };
+
+                // Sending only fails when there are no subscribers.
+                let _ = task_sender.send(id);
+            }
+        });
+
+        Ok(Self {
+            sender,
+            _listener: Some(Arc::new(ListenerGuard(handle.abort_handle()))),
+        })
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Option<Uuid>> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for StatusChanges {
+    /// Returns status changes without a listener, i.e., none are ever sent.
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(STATUS_CHANGES_CAPACITY);
+
+        Self {
+            sender,
+            _listener: None,
+        }
+    }
+}
+
+// Stops the listener when dropped.
+struct ListenerGuard(AbortHandle);
+
+impl Drop for ListenerGuard {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Task {
+    id: Uuid,
+    /// The kind of task, e.g., "plot" or "batch_correction".
+    kind: &'static str,
+    status: Status,
+    /// The number of times the task was started.
+    attempts: i32,
+    /// The progress of the current or last attempt, from 0 to 1.
+    progress: f32,
+    /// The reason the last attempt failed, if any.
+    error: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    started_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    finished_at: Option<OffsetDateTime>,
+}
+
+struct Row {
+    id: Uuid,
+    message: SqlxJson<Message>,
+    subject: Option<String>,
+    status: Status,
+    attempts: i32,
+    progress: f32,
+    error: Option<String>,
+    created_at: OffsetDateTime,
+    started_at: Option<OffsetDateTime>,
+    finished_at: Option<OffsetDateTime>,
+}
+
+/// Shows the status of a task.
+///
+/// Results are returned by the endpoint of the analysis that submitted the task.
+#[utoipa::path(
+    get,
+    path = "/tasks/{id}",
+    operation_id = "tasks-show",
+    params(
+        ("id" = Uuid, Path, description = "Task ID"),
+    ),
+    responses(
+        (status = OK, description = "The task status, attempts, progress, and last error"),
+        (status = NOT_FOUND, description = "The task does not exist or is not readable"),
+    ),
+)]
+async fn show(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(id): Path<Uuid>,
+) -> server::Result<Json<Task>> {
+    let row = find(&ctx.pool, id).await?.ok_or(Error::NotFound)?;
+    require_readable(&ctx.pool, &principal, &row.message).await?;
+    Ok(Json(Task::from(row)))
+}
+
+/// Returns an error if the principal cannot read the runs selected by a task.
+///
+/// Identical requests share a task, so knowing a task ID does not grant access to its result.
+/// Tasks that are not readable are reported as not found.
+pub(super) async fn require_readable(
+    pool: &PgPool,
+    principal: &Principal,
+    message: &Message,
+) -> server::Result<()> {
+    let (dataset_ids, run_ids) = match message {
+        Message::Noop => return Ok(()),
+        Message::Plot(message) => (
+            message.runs.dataset_ids.clone(),
+            message.runs.run_ids.clone(),
+        ),
+        Message::BatchCorrection(message) => (vec![message.dataset_id], Vec::new()),
+        Message::DifferentialExpression(message) => (vec![message.dataset_id], Vec::new()),
+        Message::SampleSimilarity(message) => (vec![message.dataset_id], Vec::new()),
+    };
+
+    for &dataset_id in &dataset_ids {
+        if !dataset::is_readable(pool, principal, dataset_id).await? {
+            return Err(Error::NotFound);
+        }
+    }
+
+    let run_ids = run::select_ids(pool, &dataset_ids, &run_ids).await?;
+
+    if run::filter_readable(pool, principal, &run_ids).await? == run_ids {
+        Ok(())
+    } else {
+        Err(Error::NotFound)
+    }
+}
+
+/// Streams the status of a task as server-sent events.
+///
+/// A `status` event with the same body as `GET /tasks/{id}` is sent on connection and each time
+/// the task finishes or is re-queued. The stream ends after the task finishes.
+#[utoipa::path(
+    get,
+    path = "/tasks/{id}/events",
+    operation_id = "tasks-events",
+    params(
+        ("id" = Uuid, Path, description = "Task ID"),
+    ),
+    responses(
+        (status = OK, description = "A stream of task status events", content_type = "text/event-stream"),
+        (status = NOT_FOUND, description = "The task does not exist or is not readable"),
+    ),
+)]
+async fn events(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(id): Path<Uuid>,
+) -> server::Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>> {
+    // Subscribe before reading the task so that no status change is missed.
+    let status_changes = ctx.status_changes.subscribe();
+
+    let row = find(&ctx.pool, id).await?.ok_or(Error::NotFound)?;
+    require_readable(&ctx.pool, &principal, &row.message).await?;
+    let task = Task::from(row);
+
+    let subscription = Subscription {
+        pool: ctx.pool,
+        status_changes,
+        id,
+        pending_task: Some(task),
+        is_finished: false,
+    };
+
+    let stream = stream::unfold(subscription, |mut subscription| async move {
+        match subscription.next().await {
+            Ok(Some(task)) => {
+                let event = Event::default().event("status").json_data(&task);
+                Some((event, subscription))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!(error = ?e, "failed to read task status");
+                None
+            }
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+struct Subscription {
+    pool: PgPool,
+    status_changes: broadcast::Receiver<Option<Uuid>>,
+    id: Uuid,
+    pending_task: Option<Task>,
+    is_finished: bool,
+}
+
+impl Subscription {
+    /// Waits for the next status of the task.
+    ///
+    /// Returns `None` after the task finished or if it was deleted.
+    async fn next(&mut self) -> sqlx::Result<Option<Task>> {
+        if self.is_finished {
+            return Ok(None);
+        }
+
+        let task = match self.pending_task.take() {
+            Some(task) => Some(task),
+            None => {
+                // Changes may have been missed if the subscriber lagged.
+                loop {
+                    match self.status_changes.recv().await {
+                        Ok(Some(id)) if id != self.id => {}
+                        Ok(_) | Err(RecvError::Lagged(_)) => break,
+                        Err(RecvError::Closed) => return Ok(None),
+                    }
+                }
+
+                find(&self.pool, self.id).await?.map(Task::from)
+            }
+        };
+
+        self.is_finished = task.as_ref().is_none_or(|task| task.status.is_finished());
+
+        Ok(task)
+    }
+}
+
+async fn find(pool: &PgPool, id: Uuid) -> sqlx::Result<Option<Row>> {
+    sqlx::query_as!(
+        Row,
+        r#"
+        select
+            id,
+            message as "message: SqlxJson<Message>",
+            subject,
+            status as "status: Status",
+            attempts,
+            progress,
+            error,
+            created_at,
+            started_at,
+            finished_at
+        from tasks
+        where id = $1
+        "#,
+        id,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+impl From<Row> for Task {
+    fn from(row: Row) -> Self {
+        Self {
+            id: row.id,
+            kind: row.message.kind(),
+            status: row.status,
+            attempts: row.attempts,
+            progress: row.progress,
+            error: row.error,
+            created_at: row.created_at,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+        }
+    }
+}
+
+/// Cancels a queued or running task.
+///
+/// A running task is stopped by its worker at the next heartbeat. Only the subject that submitted
+/// the task or an administrator can cancel it.
+#[utoipa::path(
+    delete,
+    path = "/tasks/{id}",
+    operation_id = "tasks-cancel",
+    params(
+        ("id" = Uuid, Path, description = "Task ID"),
+    ),
+    responses(
+        (status = NO_CONTENT, description = "The task was cancelled"),
+        (status = UNAUTHORIZED, description = "The request is not authenticated"),
+        (status = FORBIDDEN, description = "The task was submitted by another subject"),
+        (status = NOT_FOUND, description = "The task does not exist or is not readable"),
+        (status = CONFLICT, description = "The task already finished"),
+    ),
+)]
+async fn cancel(
+    principal: Principal,
+    State(ctx): State<Context>,
+    Path(id): Path<Uuid>,
+) -> server::Result<StatusCode> {
+    require_authenticated(&principal)?;
+
+    let row = find(&ctx.pool, id).await?.ok_or(Error::NotFound)?;
+    require_readable(&ctx.pool, &principal, &row.message).await?;
+
+    if !principal.is_admin() && row.subject.as_deref() != principal.subject() {
+        return Err(Error::Forbidden);
+    }
+
+    match ctx.queue.cancel(id).await? {
+        Some(status) if status.is_finished() => {
+            Err(Error::Conflict(String::from("task already finished")))
+        }
+        Some(_) => Ok(StatusCode::NO_CONTENT),
+        None => Err(Error::NotFound),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use http_body_util::BodyExt;
+    use hyper::{Request, header};
+    use serde_json::Value;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{Queue, queue::SampleSimilarityMessage, server::auth::create_api_token};
+
+    fn app(pool: PgPool) -> Router {
+        let queue = Queue::new(pool.clone());
+        router().with_state(Context {
+            pool,
+            queue,
+            jwt: None,
+            status_changes: Default::default(),
+        })
+    }
+
+    #[sqlx::test]
+    async fn test_show(pool: PgPool) -> anyhow::Result<()> {
+        let queue = Queue::new(pool.clone());
+        let id = queue.push_back(Message::Noop).await?;
+        queue.pull_front().await?;
+        queue.heartbeat(id, 0.5).await?;
+        queue.failed(id, "no runs selected").await?;
+
+        let request = Request::get(format!("/tasks/{id}")).body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let actual: Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(actual["kind"], "noop");
+        assert_eq!(actual["status"], "failed");
+        assert_eq!(actual["attempts"], 1);
+        assert_eq!(actual["progress"], 0.5);
+        assert_eq!(actual["error"], "no runs selected");
+        assert!(actual["startedAt"].is_string());
+        assert!(actual["finishedAt"].is_string());
+
+        let request = Request::get(format!("/tasks/{}", Uuid::new_v4())).body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("../store/fixtures/dataset_permissions.sql"))]
+    async fn test_show_with_unreadable_runs(pool: PgPool) -> anyhow::Result<()> {
+        let queue = Queue::new(pool.clone());
+        let id = queue
+            .push_back(Message::SampleSimilarity(SampleSimilarityMessage {
+                dataset_id: 3,
+                metric: Default::default(),
+            }))
+            .await?;
+
+        let request = Request::get(format!("/tasks/{id}")).body(Body::empty())?;
+        let response = app(pool.clone()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let api_token = create_api_token(&pool, "admin", true).await?;
+        let request = Request::get(format!("/tasks/{id}"))
+            .header(header::AUTHORIZATION, format!("Bearer {api_token}"))
+            .body(Body::empty())?;
+        let response = app(pool).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_events(pool: PgPool) -> anyhow::Result<()> {
+        let queue = Queue::new(pool.clone());
+        let id = queue.push_back(Message::Noop).await?;
+        queue.pull_front().await?;
+
+        let app = router().with_state(Context {
+            pool: pool.clone(),
+            queue: queue.clone(),
+            jwt: None,
+            status_changes: StatusChanges::listen(&pool).await?,
+        });
+
+        let request = Request::get(format!("/tasks/{id}/events")).body(Body::empty())?;
+        let response = app.clone().oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        queue.success(id, Option::<()>::None).await?;
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let body = std::str::from_utf8(&body)?;
+
+        let statuses: Vec<_> = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(serde_json::from_str::<Value>)
+            .map(|event| event.map(|event| event["status"].clone()))
+            .collect::<Result<_, _>>()?;
+
+        assert_eq!(statuses, ["running", "success"]);
+
+        let request =
+            Request::get(format!("/tasks/{}/events", Uuid::new_v4())).body(Body::empty())?;
+        let response = app.oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    async fn cancel(
+        pool: &PgPool,
+        id: Uuid,
+        api_token: Option<&str>,
+    ) -> anyhow::Result<StatusCode> {
+        let mut builder = Request::delete(format!("/tasks/{id}"));
+
+        if let Some(api_token) = api_token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {api_token}"));
+        }
+
+        let response = app(pool.clone())
+            .oneshot(builder.body(Body::empty())?)
+            .await?;
+
+        Ok(response.status())
+    }
+
+    #[sqlx::test]
+    async fn test_cancel(pool: PgPool) -> anyhow::Result<()> {
+        let queue = Queue::new(pool.clone());
+        let key = Message::Noop.key(&[]);
+        let id = queue
+            .find_or_push_back(Message::Noop, &key, Some("user"))
+            .await?;
+
+        let user_api_token = create_api_token(&pool, "user", false).await?;
+        let other_api_token = create_api_token(&pool, "other", false).await?;
+
+        assert_eq!(cancel(&pool, id, None).await?, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            cancel(&pool, id, Some(&other_api_token)).await?,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            cancel(&pool, id, Some(&user_api_token)).await?,
+            StatusCode::NO_CONTENT
+        );
+
+        assert!(queue.pull_front().await?.is_none());
+
+        assert_eq!(
+            cancel(&pool, id, Some(&user_api_token)).await?,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            cancel(&pool, Uuid::new_v4(), Some(&user_api_token)).await?,
+            StatusCode::NOT_FOUND
+        );
+
+        // Administrators can cancel any task.
+        let id = queue.push_back(Message::Noop).await?;
+        let admin_api_token = create_api_token(&pool, "admin", true).await?;
+        assert_eq!(
+            cancel(&pool, id, Some(&admin_api_token)).await?,
+            StatusCode::NO_CONTENT
+        );
+
+        Ok(())
+    }
+}
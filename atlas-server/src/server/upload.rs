@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Cursor},
+};
+
+use axum::{body::Bytes, extract::Multipart};
+use flate2::bufread::MultiGzDecoder;
+
+use super::Error;
+
+// Uploads are buffered in memory, so this bounds the request body size of upload endpoints.
+pub(super) const MAX_UPLOAD_SIZE: usize = 512 * 1024 * 1024;
+
+const GZIP_MAGIC_NUMBER: [u8; 2] = [0x1f, 0x8b];
+
+/// A multipart form with text fields and files.
+#[derive(Default)]
+pub(super) struct Form {
+    fields: HashMap<String, String>,
+    files: Vec<(String, String, Bytes)>,
+}
+
+impl Form {
+    /// Reads all fields of a multipart form.
+    ///
+    /// Fields with a filename are read as files, and all others as text.
+    pub async fn read(multipart: &mut Multipart) -> Result<Self, Error> {
+        let mut form = Self::default();
+
+        while let Some(field) = multipart.next_field().await? {
+            let Some(name) = field.name().map(String::from) else {
+                continue;
+            };
+
+            if let Some(filename) = field.file_name().map(String::from) {
+                let data = field.bytes().await?;
+                form.files.push((name, filename, data));
+            } else {
+                let value = field.text().await?;
+                form.fields.insert(name, value);
+            }
+        }
+
+        Ok(form)
+    }
+
+    /// Removes and returns the value of a text field.
+    pub fn take(&mut self, name: &str) -> Option<String> {
+        self.fields.remove(name)
+    }
+
+    /// Removes and returns the value of a required text field.
+    pub fn take_required(&mut self, name: &str) -> Result<String, Error> {
+        self.take(name)
+            .ok_or_else(|| Error::BadRequest(format!("missing field: {name}")))
+    }
+
+    /// Removes and returns the value of a text field parsed as `T`.
+    pub fn take_parsed<T>(&mut self, name: &str) -> Result<Option<T>, Error>
+    where
+        T: std::str::FromStr,
+    {
+        self.take(name)
+            .map(|s| {
+                s.parse()
+                    .map_err(|_| Error::BadRequest(format!("invalid field: {name}")))
+            })
+            .transpose()
+    }
+
+    /// Removes and returns the data of a required file.
+    pub fn take_file(&mut self, name: &str) -> Result<Bytes, Error> {
+        self.take_files(name)
+            .pop()
+            .map(|(_, data)| data)
+            .ok_or_else(|| Error::BadRequest(format!("missing file: {name}")))
+    }
+
+    /// Removes and returns the filenames and data of all files of a field.
+    pub fn take_files(&mut self, name: &str) -> Vec<(String, Bytes)> {
+        let (files, rest) = std::mem::take(&mut self.files)
+            .into_iter()
+            .partition(|(field_name, _, _)| field_name == name);
+
+        self.files = rest;
+
+        files
+            .into_iter()
+            .map(|(_, filename, data)| (filename, data))
+            .collect()
+    }
+}
+
+/// Returns a reader of uploaded data, decompressing it if it is gzip-compressed.
+pub(super) fn reader(data: Bytes) -> Box<dyn BufRead + Send> {
+    if data.starts_with(&GZIP_MAGIC_NUMBER) {
+        Box::new(BufReader::new(MultiGzDecoder::new(Cursor::new(data))))
+    } else {
+        Box::new(Cursor::new(data))
+    }
+}
+
+/// Builds a `multipart/form-data` request body.
+///
+/// Returns the content type and body.
+#[cfg(test)]
+pub(super) fn build_form(
+    fields: &[(&str, &str)],
+    files: &[(&str, &str, &[u8])],
+) -> (String, Vec<u8>) {
+    const BOUNDARY: &str = "atlas-boundary";
+
+    let mut body = Vec::new();
+
+    for (name, value) in fields {
+        body.extend(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+            )
+            .as_bytes(),
+        );
+    }
+
+    for (name, filename, data) in files {
+        body.extend(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"; filename=\"{filename}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        body.extend(*data);
+        body.extend(b"\r\n");
+    }
+
+    body.extend(format!("--{BOUNDARY}--\r\n").as_bytes());
+
+    (format!("multipart/form-data; boundary={BOUNDARY}"), body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use flate2::{Compression, write::GzEncoder};
+
+    use super::*;
+
+    #[test]
+    fn test_reader() -> std::io::Result<()> {
+        let mut buf = String::new();
+        reader(Bytes::from_static(b"atlas")).read_to_string(&mut buf)?;
+        assert_eq!(buf, "atlas");
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"atlas")?;
+        let data = encoder.finish()?;
+
+        buf.clear();
+        reader(Bytes::from(data)).read_to_string(&mut buf)?;
+        assert_eq!(buf, "atlas");
+
+        Ok(())
+    }
+}
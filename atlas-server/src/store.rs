@@ -1,10 +1,21 @@
 pub mod annotations;
+pub mod api_token;
+pub mod audit_log;
 pub mod configuration;
 pub mod count;
 pub mod dataset;
+mod dataset_permission;
 pub mod feature;
+mod metadata;
+pub mod normalization_factors;
+pub mod page;
+pub mod principal;
+pub mod reference_embedding;
 pub mod run;
 pub mod sample;
 mod strand_specification;
 
-pub use self::strand_specification::StrandSpecification;
+pub use self::{
+    dataset_permission::DatasetPermission, metadata::Metadata, principal::Principal,
+    strand_specification::StrandSpecification,
+};
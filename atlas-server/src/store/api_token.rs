@@ -0,0 +1,64 @@
+use sqlx::PgExecutor;
+
+pub async fn create<'a, E>(executor: E, subject: &str, token_hash: &[u8]) -> sqlx::Result<i32>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        "insert into api_tokens (subject, token_hash) values ($1, $2) returning id",
+        subject,
+        token_hash,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Finds the subject of an API token by its hash.
+pub async fn find_subject<'a, E>(executor: E, token_hash: &[u8]) -> sqlx::Result<Option<String>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        "select subject from api_tokens where token_hash = $1",
+        token_hash
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Deletes all API tokens of a subject.
+///
+/// Returns the number of tokens deleted.
+pub async fn delete_by_subject<'a, E>(executor: E, subject: &str) -> sqlx::Result<u64>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!("delete from api_tokens where subject = $1", subject)
+        .execute(executor)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_find_subject(pool: PgPool) -> sqlx::Result<()> {
+        create(&pool, "user", b"hash_1").await?;
+        create(&pool, "user", b"hash_2").await?;
+
+        assert_eq!(
+            find_subject(&pool, b"hash_1").await?.as_deref(),
+            Some("user")
+        );
+        assert!(find_subject(&pool, b"hash_3").await?.is_none());
+
+        assert_eq!(delete_by_subject(&pool, "user").await?, 2);
+        assert!(find_subject(&pool, b"hash_1").await?.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use serde_json::Value;
+use sqlx::{PgExecutor, types::Json};
+
+/// A change recorded in the audit log.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    DeleteRun,
+    RenameSample,
+    RenameDataset,
+    DeleteConfiguration,
+}
+
+impl Action {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::DeleteRun => "delete_run",
+            Self::RenameSample => "rename_sample",
+            Self::RenameDataset => "rename_dataset",
+            Self::DeleteConfiguration => "delete_configuration",
+        }
+    }
+}
+
+/// Records a change to the object with the given ID.
+///
+/// `subject` is the authenticated subject that made the change, or `None` for the CLI. This
+/// should be called in the same transaction as the change.
+pub async fn create<'a, E>(
+    executor: E,
+    subject: Option<&str>,
+    action: Action,
+    target_id: i32,
+    details: &Value,
+) -> sqlx::Result<()>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!(
+        "
+        insert into audit_log (subject, action, target_id, details)
+        values ($1, $2, $3, $4)
+        ",
+        subject,
+        action.as_str(),
+        target_id,
+        Json(details) as _,
+    )
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
@@ -1,6 +1,8 @@
 use serde::Serialize;
 use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
 
+use super::page::{self, ById, Page, PageRequest, Source};
+
 #[derive(Debug, Serialize, Eq, PartialEq, sqlx::FromRow, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Annotations {
@@ -19,24 +21,21 @@ pub struct Configuration {
     feature_name: String,
 }
 
-pub async fn all<'a, E>(executor: E) -> sqlx::Result<Vec<Configuration>>
-where
-    E: PgExecutor<'a>,
-{
-    sqlx::query_as(
-        r#"
-        select
+/// Returns a page of configurations sorted by ID.
+pub async fn page(pool: &PgPool, request: &PageRequest) -> sqlx::Result<Page<Configuration>> {
+    let source = Source {
+        select: "
             configurations.id,
             annotations.name,
             annotations.genome_build,
             configurations.feature_type,
             configurations.feature_name
-        from configurations
-        inner join annotations on configurations.annotation_id = annotations.id
-        "#,
-    )
-    .fetch_all(executor)
-    .await
+        ",
+        from: "configurations inner join annotations on configurations.annotation_id = annotations.id",
+        id: "configurations.id",
+    };
+
+    page::fetch(pool, &source, |_| {}, ById, request).await
 }
 
 pub async fn find<'a, E>(executor: E, id: i32) -> sqlx::Result<Option<Configuration>>
@@ -62,6 +61,15 @@ where
     .await
 }
 
+pub async fn feature_name<'a, E>(executor: E, id: i32) -> sqlx::Result<Option<String>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!("select feature_name from configurations where id = $1", id)
+        .fetch_optional(executor)
+        .await
+}
+
 pub async fn exists(pool: &PgPool, id: i32) -> sqlx::Result<bool> {
     sqlx::query_scalar!(
         r#"select exists(select 1 from configurations where id = $1) as "exists!""#,
@@ -93,6 +101,52 @@ pub async fn create(
     .await
 }
 
+/// The result of [`delete`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Deletion {
+    Deleted,
+    NotFound,
+    /// The configuration has runs.
+    InUse,
+}
+
+/// Deletes a configuration and its features if it has no runs.
+///
+/// The annotations of the configuration are kept.
+pub async fn delete(tx: &mut Transaction<'_, Postgres>, id: i32) -> sqlx::Result<Deletion> {
+    // Locking the configuration blocks runs from being created with it until the transaction
+    // ends.
+    let exists = sqlx::query_scalar!("select id from configurations where id = $1 for update", id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .is_some();
+
+    if !exists {
+        return Ok(Deletion::NotFound);
+    }
+
+    let is_used = sqlx::query_scalar!(
+        r#"select exists(select 1 from runs where configuration_id = $1) as "exists!""#,
+        id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    if is_used {
+        return Ok(Deletion::InUse);
+    }
+
+    sqlx::query!("delete from features where configuration_id = $1", id)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query!("delete from configurations where id = $1", id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(Deletion::Deleted)
+}
+
 #[cfg(test)]
 mod tests {
     use sqlx::PgPool;
@@ -101,11 +155,14 @@ mod tests {
     use crate::store::annotations::find_or_create_annotations;
 
     #[sqlx::test(fixtures("configuration_all"))]
-    async fn test_all(pool: PgPool) -> sqlx::Result<()> {
-        let configurations = all(&pool).await?;
+    async fn test_page(pool: PgPool) -> sqlx::Result<()> {
+        let page = page(&pool, &PageRequest::default()).await?;
+
+        assert_eq!(page.total, 1);
+        assert!(page.next.is_none());
 
         assert_eq!(
-            configurations,
+            page.items,
             [Configuration {
                 id: 1,
                 annotations: Annotations {
@@ -141,6 +198,13 @@ mod tests {
         Ok(())
     }
 
+    #[sqlx::test(fixtures("configuration_all"))]
+    async fn test_feature_name(pool: PgPool) -> sqlx::Result<()> {
+        assert_eq!(feature_name(&pool, 1).await?.as_deref(), Some("gene_name"));
+        assert!(feature_name(&pool, 2).await?.is_none());
+        Ok(())
+    }
+
     #[sqlx::test(fixtures("configuration_exists"))]
     async fn test_exists(pool: PgPool) -> sqlx::Result<()> {
         assert!(exists(&pool, 1).await?);
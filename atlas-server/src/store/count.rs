@@ -1,48 +1,486 @@
-use std::{collections::HashMap, io};
+use std::{collections::HashMap, io, ops::Range};
 
-use sqlx::{Postgres, Transaction};
+use futures::{
+    StreamExt, TryStreamExt,
+    stream::{self, BoxStream},
+};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
 
-pub async fn create_counts(
-    tx: &mut Transaction<'_, Postgres>,
-    run_id: i32,
-    features: &Vec<(i32, String)>,
-    counts: &HashMap<String, u32>,
-) -> anyhow::Result<()> {
-    let mut run_ids = Vec::new();
-    let mut feature_ids = Vec::new();
-    let mut values = Vec::new();
-
-    for (feature_id, name) in features {
-        let count = counts
-            .get(name)
-            .copied()
-            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))
-            .and_then(|n| {
-                i32::try_from(n).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
-            })?;
-
-        if count == 0 {
-            continue;
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("database error")]
+    Database(#[from] sqlx::Error),
+    /// Integer and real-valued counts, e.g., TPMs, are not in the same units.
+    #[error("runs have both integer and real-valued counts")]
+    MixedValues,
+}
+
+/// Dense counts aligned to features.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Values {
+    Integer(Vec<i32>),
+    /// Real-valued counts, e.g., expected counts or TPMs.
+    Real(Vec<f64>),
+}
+
+impl Values {
+    /// Concatenates the values of runs that each have `len` values.
+    ///
+    /// Runs without values are filled with zeros. The result is real-valued if any run is. Fails
+    /// if runs have both integer and real-valued counts.
+    pub fn concat(runs: Vec<Self>, len: usize) -> Result<Self, Error> {
+        if is_real(&runs)? {
+            let mut values = Vec::with_capacity(runs.len() * len);
+
+            for run_values in runs {
+                match run_values {
+                    Self::Integer(_) => values.resize(values.len() + len, 0.0),
+                    Self::Real(run_values) => values.extend(run_values),
+                }
+            }
+
+            Ok(Self::Real(values))
+        } else {
+            let mut values = Vec::with_capacity(runs.len() * len);
+
+            for run_values in runs {
+                if let Self::Integer(run_values) = run_values {
+                    if run_values.is_empty() {
+                        values.resize(values.len() + len, 0);
+                    } else {
+                        values.extend(run_values);
+                    }
+                }
+            }
+
+            Ok(Self::Integer(values))
+        }
+    }
+
+    pub fn is_real(&self) -> bool {
+        matches!(self, Self::Real(_))
+    }
+
+    /// Converts the values to real numbers.
+    pub fn into_real(self) -> Vec<f64> {
+        match self {
+            Self::Integer(values) => values.into_iter().map(f64::from).collect(),
+            Self::Real(values) => values,
+        }
+    }
+
+    fn get_integer(&self, i: usize) -> Option<i32> {
+        match self {
+            Self::Integer(values) => values.get(i).copied(),
+            Self::Real(_) => None,
         }
+    }
 
-        run_ids.push(run_id);
-        feature_ids.push(*feature_id);
-        values.push(count);
+    fn get_real(&self, i: usize) -> Option<f64> {
+        match self {
+            Self::Integer(_) => None,
+            Self::Real(values) => values.get(i).copied(),
+        }
     }
+}
+
+// Returns whether the counts of runs are real-valued.
+//
+// Runs without counts have empty integer values and are ignored.
+fn is_real(runs: &[Values]) -> Result<bool, Error> {
+    let has_integer = runs
+        .iter()
+        .any(|values| matches!(values, Values::Integer(values) if !values.is_empty()));
+    let has_real = runs.iter().any(Values::is_real);
+
+    if has_integer && has_real {
+        Err(Error::MixedValues)
+    } else {
+        Ok(has_real)
+    }
+}
+
+/// A feature row of a features × runs count matrix.
+#[derive(Debug, PartialEq)]
+pub struct MatrixRow {
+    pub feature_name: String,
+    pub feature_length: i32,
+    /// The count of the feature in each run.
+    ///
+    /// These are real-valued if the counts of any run are.
+    pub values: Values,
+}
 
-    sqlx::query!(
-        "
-        insert into counts (run_id, feature_id, value)
-        select * from unnest($1::integer[], $2::integer[], $3::integer[])
-        ",
-        &run_ids[..],
-        &feature_ids[..],
-        &values[..],
+/// The maximum number of feature positions whose counts are read at once when streaming a matrix.
+///
+/// This bounds the memory of a stream to about this many values per run.
+const MATRIX_WINDOW_SIZE: usize = 1024;
+
+/// Streams the counts of runs in a configuration as a features × runs matrix.
+///
+/// Rows are ordered by feature ID, and values are in the order of the given run IDs. If feature
+/// names are given, only those features are included; unknown names are ignored. The stream
+/// fails if runs have both integer and real-valued counts.
+pub fn stream_matrix<'a>(
+    pool: &'a PgPool,
+    configuration_id: i32,
+    run_ids: &'a [i32],
+    feature_names: Option<&'a [String]>,
+) -> BoxStream<'a, Result<MatrixRow, Error>> {
+    stream_matrix_in_windows(
+        pool,
+        configuration_id,
+        run_ids,
+        feature_names,
+        MATRIX_WINDOW_SIZE,
     )
-    .execute(&mut **tx)
+}
+
+// A feature of a count matrix and its 0-based position in the counts of a run.
+struct MatrixFeature {
+    name: String,
+    length: i32,
+    position: usize,
+}
+
+// Streams a count matrix, reading the counts of features that are at most `window_size`
+// positions apart at once.
+fn stream_matrix_in_windows<'a>(
+    pool: &'a PgPool,
+    configuration_id: i32,
+    run_ids: &'a [i32],
+    feature_names: Option<&'a [String]>,
+    window_size: usize,
+) -> BoxStream<'a, Result<MatrixRow, Error>> {
+    stream::once(async move {
+        let is_real = is_real_valued(pool, configuration_id, run_ids).await?;
+
+        let features = sqlx::query!(
+            r#"
+            select
+                name,
+                length,
+                position as "position!"
+            from (
+                select
+                    name,
+                    length,
+                    row_number() over (order by id) as position
+                from features
+                where configuration_id = $1
+            ) as features
+            where $2::text[] is null or name = any($2)
+            order by position
+            "#,
+            configuration_id,
+            feature_names,
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| MatrixFeature {
+            name: row.name,
+            length: row.length,
+            position: (row.position - 1) as usize,
+        })
+        .collect();
+
+        let rows = stream::iter(split_windows(features, window_size))
+            .then(move |features| read_window(pool, configuration_id, run_ids, is_real, features))
+            .map_ok(|rows| stream::iter(rows).map(Ok))
+            .try_flatten();
+
+        Ok::<_, Error>(rows)
+    })
+    .try_flatten()
+    .boxed()
+}
+
+// Splits features ordered by position into windows that each span at most `size` positions.
+fn split_windows(features: Vec<MatrixFeature>, size: usize) -> Vec<Vec<MatrixFeature>> {
+    let mut windows: Vec<Vec<MatrixFeature>> = Vec::new();
+
+    for feature in features {
+        match windows.last_mut() {
+            Some(window) if feature.position - window[0].position < size => window.push(feature),
+            _ => windows.push(vec![feature]),
+        }
+    }
+
+    windows
+}
+
+async fn read_window(
+    pool: &PgPool,
+    configuration_id: i32,
+    run_ids: &[i32],
+    is_real: bool,
+    features: Vec<MatrixFeature>,
+) -> Result<Vec<MatrixRow>, Error> {
+    // SAFETY: Windows are not empty.
+    let start = features[0].position;
+    let end = features[features.len() - 1].position + 1;
+
+    let runs = find_value_slices(pool, configuration_id, run_ids, start..end).await?;
+
+    let rows = features
+        .into_iter()
+        .map(|feature| {
+            let i = feature.position - start;
+
+            let values = if is_real {
+                Values::Real(
+                    runs.iter()
+                        .map(|run_values| run_values.get_real(i).unwrap_or(0.0))
+                        .collect(),
+                )
+            } else {
+                Values::Integer(
+                    runs.iter()
+                        .map(|run_values| run_values.get_integer(i).unwrap_or(0))
+                        .collect(),
+                )
+            };
+
+            MatrixRow {
+                feature_name: feature.name,
+                feature_length: feature.length,
+                values,
+            }
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+/// Returns whether the counts of runs in a configuration are real-valued.
+///
+/// This reads only the value type of each run. Fails if runs have both integer and real-valued
+/// counts.
+pub async fn is_real_valued<'a, E>(
+    executor: E,
+    configuration_id: i32,
+    run_ids: &[i32],
+) -> Result<bool, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let row = sqlx::query!(
+        r#"
+        select
+            coalesce(bool_or(run_counts.values is not null), false) as "has_integer!",
+            coalesce(bool_or(run_counts.real_values is not null), false) as "has_real!"
+        from runs
+        inner join run_counts
+            on run_counts.run_id = runs.id
+        where runs.configuration_id = $1
+            and runs.id = any($2)
+        "#,
+        configuration_id,
+        run_ids,
+    )
+    .fetch_one(executor)
     .await?;
 
-    Ok(())
+    if row.has_integer && row.has_real {
+        Err(Error::MixedValues)
+    } else {
+        Ok(row.has_real)
+    }
+}
+
+/// Finds the dense counts of runs in a configuration.
+///
+/// Values are in the order of the given run IDs. Runs not in the configuration or without counts
+/// have empty integer values.
+pub async fn find_values<'a, E>(
+    executor: E,
+    configuration_id: i32,
+    run_ids: &[i32],
+) -> sqlx::Result<Vec<Values>>
+where
+    E: PgExecutor<'a>,
+{
+    let rows = sqlx::query!(
+        r#"
+        select
+            run_counts.values as "values?",
+            run_counts.real_values as "real_values?"
+        from unnest($2::integer[]) with ordinality as ids(id, i)
+        left join runs
+            on runs.id = ids.id and runs.configuration_id = $1
+        left join run_counts
+            on run_counts.run_id = runs.id
+        order by ids.i
+        "#,
+        configuration_id,
+        run_ids,
+    )
+    .fetch_all(executor)
+    .await?;
+
+    let runs = rows
+        .into_iter()
+        .map(|row| into_values(row.values, row.real_values))
+        .collect();
+
+    Ok(runs)
+}
+
+// Finds the dense counts of runs in a configuration at the given 0-based feature positions.
+//
+// Values are in the order of the given run IDs. Runs not in the configuration or without counts
+// have empty integer values.
+async fn find_value_slices<'a, E>(
+    executor: E,
+    configuration_id: i32,
+    run_ids: &[i32],
+    positions: Range<usize>,
+) -> sqlx::Result<Vec<Values>>
+where
+    E: PgExecutor<'a>,
+{
+    let rows = sqlx::query!(
+        r#"
+        select
+            run_counts.values[$3:$4] as "values?",
+            run_counts.real_values[$3:$4] as "real_values?"
+        from unnest($2::integer[]) with ordinality as ids(id, i)
+        left join runs
+            on runs.id = ids.id and runs.configuration_id = $1
+        left join run_counts
+            on run_counts.run_id = runs.id
+        order by ids.i
+        "#,
+        configuration_id,
+        run_ids,
+        // Arrays are 1-based, and slice bounds are inclusive.
+        (positions.start + 1) as i32,
+        positions.end as i32,
+    )
+    .fetch_all(executor)
+    .await?;
+
+    let runs = rows
+        .into_iter()
+        .map(|row| into_values(row.values, row.real_values))
+        .collect();
+
+    Ok(runs)
+}
+
+fn into_values(values: Option<Vec<i32>>, real_values: Option<Vec<f64>>) -> Values {
+    match (values, real_values) {
+        (_, Some(values)) => Values::Real(values),
+        (values, None) => Values::Integer(values.unwrap_or_default()),
+    }
+}
+
+/// Aligns the counts of a sample to the given features ordered by ID.
+pub fn align_counts<T>(
+    features: &[(i32, String)],
+    counts: &HashMap<String, T>,
+) -> io::Result<Vec<T>>
+where
+    T: Copy,
+{
+    let mut features: Vec<_> = features.iter().collect();
+    features.sort_unstable_by_key(|(id, _)| *id);
+
+    features
+        .into_iter()
+        .map(|(_, name)| {
+            counts
+                .get(name)
+                .copied()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))
+        })
+        .collect()
+}
+
+/// Bulk loads the dense counts of runs using a binary `COPY`.
+///
+/// The values of each run must be aligned to the features of its configuration ordered by ID.
+pub async fn copy_counts(
+    tx: &mut Transaction<'_, Postgres>,
+    runs: &[(i32, Values)],
+) -> sqlx::Result<u64> {
+    const SIGNATURE: &[u8] = b"PGCOPY\n\xff\r\n\0";
+
+    let mut copy = tx
+        .copy_in_raw(
+            "copy run_counts (run_id, values, real_values) from stdin with (format binary)",
+        )
+        .await?;
+
+    let mut buf = Vec::new();
+
+    // header: signature, flags, and header extension length
+    buf.extend_from_slice(SIGNATURE);
+    buf.extend_from_slice(&0i32.to_be_bytes());
+    buf.extend_from_slice(&0i32.to_be_bytes());
+
+    for (run_id, values) in runs {
+        buf.extend_from_slice(&3i16.to_be_bytes());
+
+        buf.extend_from_slice(&4i32.to_be_bytes());
+        buf.extend_from_slice(&run_id.to_be_bytes());
+
+        match values {
+            Values::Integer(values) => {
+                write_array(&mut buf, INT4_OID, values.iter().map(|n| n.to_be_bytes()));
+                write_null(&mut buf);
+            }
+            Values::Real(values) => {
+                write_null(&mut buf);
+                write_array(&mut buf, FLOAT8_OID, values.iter().map(|n| n.to_be_bytes()));
+            }
+        }
+
+        copy.send(buf.as_slice()).await?;
+        buf.clear();
+    }
+
+    // trailer
+    buf.extend_from_slice(&(-1i16).to_be_bytes());
+    copy.send(buf).await?;
+
+    copy.finish().await
+}
+
+const INT4_OID: u32 = 23;
+const FLOAT8_OID: u32 = 701;
+
+fn write_null(dst: &mut Vec<u8>) {
+    dst.extend_from_slice(&(-1i32).to_be_bytes());
+}
+
+// Writes a one-dimensional array field in the binary `COPY` format.
+fn write_array<I, const N: usize>(dst: &mut Vec<u8>, element_oid: u32, elements: I)
+where
+    I: ExactSizeIterator<Item = [u8; N]>,
+{
+    let len = elements.len();
+    let dimension_count = usize::from(len > 0);
+    let array_len = 12 + dimension_count * 8 + len * (4 + N);
+
+    dst.extend_from_slice(&(array_len as i32).to_be_bytes());
+
+    // header: dimension count, has nulls, and element type
+    dst.extend_from_slice(&(dimension_count as i32).to_be_bytes());
+    dst.extend_from_slice(&0i32.to_be_bytes());
+    dst.extend_from_slice(&element_oid.to_be_bytes());
+
+    if dimension_count > 0 {
+        // dimension: length and lower bound
+        dst.extend_from_slice(&(len as i32).to_be_bytes());
+        dst.extend_from_slice(&1i32.to_be_bytes());
+    }
+
+    for element in elements {
+        dst.extend_from_slice(&(N as i32).to_be_bytes());
+        dst.extend_from_slice(&element);
+    }
 }
 
 #[cfg(test)]
@@ -52,20 +490,171 @@ mod tests {
     use super::*;
     use crate::store::feature::find_features;
 
-    #[sqlx::test(fixtures("count_create_counts"))]
-    async fn test_create_counts(pool: PgPool) -> anyhow::Result<()> {
+    #[test]
+    fn test_values_concat() {
+        let runs = vec![Values::Integer(vec![8, 13]), Values::Integer(Vec::new())];
+        assert_eq!(
+            Values::concat(runs, 2).ok(),
+            Some(Values::Integer(vec![8, 13, 0, 0]))
+        );
+
+        let runs = vec![Values::Integer(Vec::new()), Values::Real(vec![1.5, 2.5])];
+        assert_eq!(
+            Values::concat(runs, 2).ok(),
+            Some(Values::Real(vec![0.0, 0.0, 1.5, 2.5]))
+        );
+
+        let runs = vec![Values::Integer(vec![8, 13]), Values::Real(vec![1.5, 2.5])];
+        assert!(matches!(Values::concat(runs, 2), Err(Error::MixedValues)));
+    }
+
+    #[sqlx::test(fixtures("count_copy_counts"))]
+    async fn test_copy_counts(pool: PgPool) -> anyhow::Result<()> {
         let mut tx = pool.begin().await?;
 
         let configuration_id = 1;
-        let run_id = 1;
 
         let features = find_features(&mut *tx, configuration_id).await?;
 
-        let counts = [(String::from("feature1"), 8), (String::from("feature2"), 0)]
+        let counts = [(String::from("feature2"), 0), (String::from("feature1"), 8)]
+            .into_iter()
+            .collect();
+        let values = align_counts(&features, &counts)?;
+        assert_eq!(values, [8, 0]);
+
+        let real_counts = [
+            (String::from("feature1"), 1.5),
+            (String::from("feature2"), 0.25),
+        ]
+        .into_iter()
+        .collect();
+        let real_values = align_counts(&features, &real_counts)?;
+
+        let runs = [(1, Values::Integer(values)), (2, Values::Real(real_values))];
+        assert_eq!(copy_counts(&mut tx, &runs).await?, 2);
+
+        let values = find_values(&mut *tx, configuration_id, &[2, 1, 3]).await?;
+        assert_eq!(
+            values,
+            [
+                Values::Real(vec![1.5, 0.25]),
+                Values::Integer(vec![8, 0]),
+                Values::Integer(Vec::new()),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_align_counts_with_missing_features() {
+        let features = [(1, String::from("feature1")), (2, String::from("feature2"))];
+        let counts = [(String::from("feature1"), 8)].into_iter().collect();
+
+        assert!(matches!(
+            align_counts(&features, &counts),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData
+        ));
+    }
+
+    #[sqlx::test(fixtures("count_matrix"))]
+    async fn test_stream_matrix(pool: PgPool) -> anyhow::Result<()> {
+        use futures::TryStreamExt;
+
+        let rows: Vec<_> = stream_matrix(&pool, 1, &[2, 1], None).try_collect().await?;
+
+        assert_eq!(
+            rows,
+            [
+                MatrixRow {
+                    feature_name: String::from("feature_1"),
+                    feature_length: 8,
+                    values: Values::Integer(vec![21, 8]),
+                },
+                MatrixRow {
+                    feature_name: String::from("feature_2"),
+                    feature_length: 13,
+                    values: Values::Integer(vec![0, 13]),
+                },
+            ]
+        );
+
+        let feature_names = [String::from("feature_2"), String::from("feature_3")];
+        let rows: Vec<_> = stream_matrix(&pool, 1, &[1], Some(&feature_names))
+            .try_collect()
+            .await?;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].values, Values::Integer(vec![13]));
+
+        let rows: Vec<_> = stream_matrix(&pool, 1, &[3], None).try_collect().await?;
+        let values: Vec<_> = rows.into_iter().map(|row| row.values).collect();
+        assert_eq!(values, [Values::Real(vec![1.5]), Values::Real(vec![0.25])]);
+
+        let result: Result<Vec<_>, _> = stream_matrix(&pool, 1, &[3, 1], None).try_collect().await;
+        assert!(matches!(result, Err(Error::MixedValues)));
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("count_matrix"))]
+    async fn test_stream_matrix_in_windows(pool: PgPool) -> anyhow::Result<()> {
+        use futures::TryStreamExt;
+
+        let expected: Vec<_> = stream_matrix(&pool, 1, &[2, 1], None).try_collect().await?;
+        let actual: Vec<_> = stream_matrix_in_windows(&pool, 1, &[2, 1], None, 1)
+            .try_collect()
+            .await?;
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_windows() {
+        let features = [0, 1, 2, 5, 9, 10]
             .into_iter()
+            .map(|position| MatrixFeature {
+                name: format!("feature_{position}"),
+                length: 8,
+                position,
+            })
+            .collect();
+
+        let windows = split_windows(features, 3);
+        let positions: Vec<Vec<_>> = windows
+            .iter()
+            .map(|window| window.iter().map(|feature| feature.position).collect())
             .collect();
+        assert_eq!(positions, [vec![0, 1, 2], vec![5], vec![9, 10]]);
+
+        assert!(split_windows(Vec::new(), 3).is_empty());
+    }
+
+    #[sqlx::test(fixtures("count_matrix"))]
+    async fn test_find_value_slices(pool: PgPool) -> sqlx::Result<()> {
+        let values = find_value_slices(&pool, 1, &[2, 3, 1, 4], 1..2).await?;
+
+        assert_eq!(
+            values,
+            [
+                Values::Integer(vec![0]),
+                Values::Real(vec![0.25]),
+                Values::Integer(vec![13]),
+                Values::Integer(Vec::new()),
+            ]
+        );
+
+        Ok(())
+    }
 
-        create_counts(&mut tx, run_id, &features, &counts).await?;
+    #[sqlx::test(fixtures("count_matrix"))]
+    async fn test_is_real_valued(pool: PgPool) -> anyhow::Result<()> {
+        assert!(!is_real_valued(&pool, 1, &[1, 2]).await?);
+        assert!(is_real_valued(&pool, 1, &[3]).await?);
+        assert!(matches!(
+            is_real_valued(&pool, 1, &[1, 3]).await,
+            Err(Error::MixedValues)
+        ));
 
         Ok(())
     }
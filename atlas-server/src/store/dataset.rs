@@ -1,38 +1,241 @@
 use futures::{StreamExt, TryStreamExt};
-use serde::Serialize;
-use sqlx::{PgExecutor, postgres::PgQueryResult};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool, postgres::PgQueryResult};
+use time::OffsetDateTime;
 
-#[derive(Serialize)]
+use super::{
+    DatasetPermission, Principal,
+    page::{self, Page, PageRequest, SortKey, Source},
+};
+
+#[derive(Debug, Eq, PartialEq, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
 pub struct Dataset {
     id: i32,
     name: String,
+    is_public: bool,
+}
+
+/// A key to sort datasets by.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    #[default]
+    Id,
+    Name,
+    CreatedAt,
+}
+
+impl SortKey for SortBy {
+    fn column(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Id => None,
+            Self::Name => Some(("datasets.name", "text")),
+            Self::CreatedAt => Some(("datasets.created_at", "timestamptz")),
+        }
+    }
+}
+
+/// Conditions datasets must match.
+#[derive(Debug, Default)]
+pub struct Filter {
+    /// A case-insensitive prefix of the name.
+    pub name_prefix: Option<String>,
+    /// The inclusive lower bound of the creation time.
+    pub created_after: Option<OffsetDateTime>,
+    /// The exclusive upper bound of the creation time.
+    pub created_before: Option<OffsetDateTime>,
+}
+
+/// Returns a page of datasets readable by the principal.
+pub async fn page(
+    pool: &PgPool,
+    principal: &Principal,
+    filter: &Filter,
+    sort: SortBy,
+    request: &PageRequest,
+) -> sqlx::Result<Page<Dataset>> {
+    let source = Source {
+        select: "datasets.id, datasets.name, datasets.is_public",
+        from: "datasets",
+        id: "datasets.id",
+    };
+
+    page::fetch(
+        pool,
+        &source,
+        |builder| {
+            builder
+                .push(" and dataset_is_readable(datasets.id, ")
+                .push_bind(principal.subject().map(String::from))
+                .push(", ")
+                .push_bind(principal.is_admin())
+                .push(")");
+
+            if let Some(prefix) = &filter.name_prefix {
+                builder
+                    .push(" and starts_with(lower(datasets.name), lower(")
+                    .push_bind(prefix.clone())
+                    .push("))");
+            }
+
+            if let Some(created_after) = filter.created_after {
+                builder
+                    .push(" and datasets.created_at >= ")
+                    .push_bind(created_after);
+            }
+
+            if let Some(created_before) = filter.created_before {
+                builder
+                    .push(" and datasets.created_at < ")
+                    .push_bind(created_before);
+            }
+        },
+        sort,
+        request,
+    )
+    .await
+}
+
+/// Finds a dataset by ID if it is readable by the principal.
+pub async fn find<'a, E>(
+    executor: E,
+    principal: &Principal,
+    id: i32,
+) -> sqlx::Result<Option<Dataset>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_as!(
+        Dataset,
+        "
+        select id, name, is_public
+        from datasets
+        where id = $1 and dataset_is_readable(id, $2, $3)
+        ",
+        id,
+        principal.subject(),
+        principal.is_admin(),
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn create<'a, E>(executor: E, name: &str, is_public: bool) -> sqlx::Result<i32>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        "insert into datasets (name, is_public) values ($1, $2) returning id",
+        name,
+        is_public,
+    )
+    .fetch_one(executor)
+    .await
 }
 
-pub async fn all<'a, E>(executor: E) -> sqlx::Result<Vec<Dataset>>
+/// Returns whether a dataset exists and is readable by the principal.
+pub async fn is_readable<'a, E>(executor: E, principal: &Principal, id: i32) -> sqlx::Result<bool>
 where
     E: PgExecutor<'a>,
 {
-    sqlx::query_as!(Dataset, "select id, name from datasets")
-        .fetch_all(executor)
-        .await
+    sqlx::query_scalar!(
+        r#"
+        select exists(
+            select 1 from datasets where id = $1 and dataset_is_readable(id, $2, $3)
+        ) as "exists!"
+        "#,
+        id,
+        principal.subject(),
+        principal.is_admin(),
+    )
+    .fetch_one(executor)
+    .await
 }
 
-pub async fn find<'a, E>(executor: E, id: i32) -> sqlx::Result<Option<Dataset>>
+/// Returns whether a dataset exists and is writable by the principal.
+pub async fn is_writable<'a, E>(executor: E, principal: &Principal, id: i32) -> sqlx::Result<bool>
 where
     E: PgExecutor<'a>,
 {
-    sqlx::query_as!(Dataset, "select id, name from datasets where id = $1", id)
-        .fetch_optional(executor)
-        .await
+    sqlx::query_scalar!(
+        r#"
+        select exists(
+            select 1 from datasets where id = $1 and dataset_is_writable(id, $2, $3)
+        ) as "exists!"
+        "#,
+        id,
+        principal.subject(),
+        principal.is_admin(),
+    )
+    .fetch_one(executor)
+    .await
 }
 
-pub async fn create<'a, E>(executor: E, name: &str) -> sqlx::Result<i32>
+/// Grants a subject a permission on a dataset, replacing any existing permission.
+pub async fn grant<'a, E>(
+    executor: E,
+    id: i32,
+    subject: &str,
+    permission: DatasetPermission,
+) -> sqlx::Result<()>
 where
     E: PgExecutor<'a>,
 {
-    sqlx::query_scalar!("insert into datasets (name) values ($1) returning id", name)
-        .fetch_one(executor)
-        .await
+    sqlx::query!(
+        "
+        insert into dataset_permissions (dataset_id, subject, permission)
+        values ($1, $2, $3)
+        on conflict (dataset_id, subject) do update
+            set permission = excluded.permission
+        ",
+        id,
+        subject,
+        permission as DatasetPermission,
+    )
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+/// Revokes all permissions of a subject on a dataset.
+///
+/// Returns whether the subject had a permission.
+pub async fn revoke<'a, E>(executor: E, id: i32, subject: &str) -> sqlx::Result<bool>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!(
+        "delete from dataset_permissions where dataset_id = $1 and subject = $2",
+        id,
+        subject
+    )
+    .execute(executor)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+/// Renames a dataset.
+///
+/// Returns the previous name, or `None` if the dataset does not exist. Fails with a unique
+/// violation if another dataset has the name.
+pub async fn rename<'a, E>(executor: E, id: i32, name: &str) -> sqlx::Result<Option<String>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        "
+        update datasets
+        set name = $2
+        from datasets as previous
+        where datasets.id = $1 and previous.id = datasets.id
+        returning previous.name
+        ",
+        id,
+        name,
+    )
+    .fetch_optional(executor)
+    .await
 }
 
 pub async fn create_runs<'a, E>(executor: E, dataset_id: i32, run_ids: &[i32]) -> sqlx::Result<()>
@@ -79,6 +282,40 @@ where
     .await
 }
 
+pub async fn contains<'a, E>(executor: E, dataset_id: i32, run_id: i32) -> sqlx::Result<bool>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        r#"
+        select exists(
+            select 1 from datasets_runs where dataset_id = $1 and run_id = $2
+        ) as "exists!"
+        "#,
+        dataset_id,
+        run_id
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Removes a run from a dataset.
+///
+/// Returns whether the run was in the dataset.
+pub async fn remove<'a, E>(executor: E, dataset_id: i32, run_id: i32) -> sqlx::Result<bool>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!(
+        "delete from datasets_runs where dataset_id = $1 and run_id = $2",
+        dataset_id,
+        run_id
+    )
+    .execute(executor)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
 pub async fn configuration_ids<'a, E>(executor: E, id: i32) -> sqlx::Result<Vec<i32>>
 where
     E: PgExecutor<'a>,
@@ -107,11 +344,11 @@ mod tests {
 
     #[sqlx::test]
     async fn test_create(pool: PgPool) -> sqlx::Result<()> {
-        let id = create(&pool, "dataset_1").await?;
+        let id = create(&pool, "dataset_1", false).await?;
         assert_eq!(id, 1);
 
         assert!(matches!(
-            create(&pool, "dataset_1").await,
+            create(&pool, "dataset_1", true).await,
             Err(sqlx::Error::Database(e)) if e.is_unique_violation()
         ));
 
@@ -124,4 +361,136 @@ mod tests {
         assert!(!exists(&pool, 2).await?);
         Ok(())
     }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_contains(pool: PgPool) -> sqlx::Result<()> {
+        assert!(contains(&pool, 1, 1).await?);
+        assert!(!contains(&pool, 1, 2).await?);
+        assert!(!contains(&pool, 2, 1).await?);
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_remove(pool: PgPool) -> sqlx::Result<()> {
+        assert!(remove(&pool, 1, 1).await?);
+        assert!(!contains(&pool, 1, 1).await?);
+        assert!(!remove(&pool, 1, 1).await?);
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_permissions"))]
+    async fn test_page(pool: PgPool) -> sqlx::Result<()> {
+        use crate::store::{page::SortOrder, principal};
+
+        let ids = |page: Page<Dataset>| -> Vec<i32> { page.items.iter().map(|d| d.id).collect() };
+
+        let filter = Filter::default();
+        let request = PageRequest::default();
+
+        let actual = page(
+            &pool,
+            &Principal::anonymous(),
+            &filter,
+            SortBy::Id,
+            &request,
+        )
+        .await?;
+        assert_eq!(actual.total, 1);
+        assert_eq!(ids(actual), [1]);
+
+        let reader = principal::find(&pool, "reader").await?;
+        let actual = page(&pool, &reader, &filter, SortBy::Id, &request).await?;
+        assert_eq!(ids(actual), [1, 2]);
+
+        let admin = Principal::administrator("admin");
+        let actual = page(&pool, &admin, &filter, SortBy::Id, &request).await?;
+        assert_eq!(ids(actual), [1, 2, 3]);
+
+        let request = PageRequest {
+            order: SortOrder::Desc,
+            ..Default::default()
+        };
+        let actual = page(&pool, &admin, &filter, SortBy::Name, &request).await?;
+        assert_eq!(ids(actual), [2, 1, 3]);
+
+        let filter = Filter {
+            name_prefix: Some(String::from("p")),
+            ..Default::default()
+        };
+        let actual = page(
+            &pool,
+            &admin,
+            &filter,
+            SortBy::Name,
+            &PageRequest::default(),
+        )
+        .await?;
+        assert_eq!(actual.total, 2);
+        assert_eq!(ids(actual), [3, 1]);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_permissions"))]
+    async fn test_find(pool: PgPool) -> sqlx::Result<()> {
+        let anonymous = Principal::anonymous();
+
+        assert_eq!(
+            find(&pool, &anonymous, 1).await?,
+            Some(Dataset {
+                id: 1,
+                name: String::from("public"),
+                is_public: true,
+            })
+        );
+
+        assert!(find(&pool, &anonymous, 2).await?.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_permissions"))]
+    async fn test_is_readable_and_is_writable(pool: PgPool) -> sqlx::Result<()> {
+        use crate::store::principal;
+
+        let anonymous = Principal::anonymous();
+        assert!(is_readable(&pool, &anonymous, 1).await?);
+        assert!(!is_writable(&pool, &anonymous, 1).await?);
+        assert!(!is_readable(&pool, &anonymous, 2).await?);
+
+        let reader = principal::find(&pool, "reader").await?;
+        assert!(is_readable(&pool, &reader, 2).await?);
+        assert!(!is_writable(&pool, &reader, 2).await?);
+        assert!(!is_readable(&pool, &reader, 3).await?);
+
+        let writer = principal::find(&pool, "writer").await?;
+        assert!(is_readable(&pool, &writer, 2).await?);
+        assert!(is_writable(&pool, &writer, 2).await?);
+
+        let admin = Principal::administrator("admin");
+        assert!(is_writable(&pool, &admin, 3).await?);
+        assert!(!is_readable(&pool, &admin, 4).await?);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_permissions"))]
+    async fn test_grant_and_revoke(pool: PgPool) -> sqlx::Result<()> {
+        use crate::store::principal;
+
+        let reader = principal::find(&pool, "reader").await?;
+
+        grant(&pool, 3, "reader", DatasetPermission::Read).await?;
+        assert!(is_readable(&pool, &reader, 3).await?);
+        assert!(!is_writable(&pool, &reader, 3).await?);
+
+        grant(&pool, 3, "reader", DatasetPermission::Write).await?;
+        assert!(is_writable(&pool, &reader, 3).await?);
+
+        assert!(revoke(&pool, 3, "reader").await?);
+        assert!(!is_readable(&pool, &reader, 3).await?);
+        assert!(!revoke(&pool, 3, "reader").await?);
+
+        Ok(())
+    }
 }
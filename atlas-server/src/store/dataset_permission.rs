@@ -0,0 +1,13 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// A permission on a dataset.
+///
+/// Write permission implies read permission.
+#[derive(ValueEnum, Clone, Serialize, Copy, Debug, Eq, PartialEq, sqlx::Type, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "dataset_permission", rename_all = "lowercase")]
+pub enum DatasetPermission {
+    Read,
+    Write,
+}
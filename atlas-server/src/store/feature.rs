@@ -1,7 +1,84 @@
-use std::collections::HashMap;
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
 
-use futures::{StreamExt, TryStreamExt};
-use sqlx::{PgExecutor, Postgres, Transaction};
+use super::page::{self, Page, PageRequest, SortKey, Source};
+
+#[derive(Debug, Eq, PartialEq, Serialize, sqlx::FromRow)]
+pub struct Feature {
+    id: i32,
+    name: String,
+    length: i32,
+}
+
+/// A key to sort features by.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    #[default]
+    Id,
+    Name,
+}
+
+impl SortKey for SortBy {
+    fn column(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Id => None,
+            Self::Name => Some(("features.name", "text")),
+        }
+    }
+}
+
+/// Conditions features must match.
+#[derive(Debug, Default)]
+pub struct Filter {
+    /// A case-insensitive substring of the name.
+    pub q: Option<String>,
+    /// A case-insensitive prefix of the name.
+    pub name_prefix: Option<String>,
+}
+
+/// Returns a page of features in a configuration.
+pub async fn page_where_configuration_id(
+    pool: &PgPool,
+    configuration_id: i32,
+    filter: &Filter,
+    sort: SortBy,
+    request: &PageRequest,
+) -> sqlx::Result<Page<Feature>> {
+    let source = Source {
+        select: "features.id, features.name, features.length",
+        from: "features",
+        id: "features.id",
+    };
+
+    page::fetch(
+        pool,
+        &source,
+        |builder| {
+            builder
+                .push(" and features.configuration_id = ")
+                .push_bind(configuration_id);
+
+            if let Some(q) = &filter.q {
+                builder
+                    .push(" and features.name ilike concat('%', ")
+                    .push_bind(q.clone())
+                    .push(", '%')");
+            }
+
+            if let Some(prefix) = &filter.name_prefix {
+                builder
+                    .push(" and starts_with(lower(features.name), lower(")
+                    .push_bind(prefix.clone())
+                    .push("))");
+            }
+        },
+        sort,
+        request,
+    )
+    .await
+}
 
 pub async fn count<'a, E>(executor: E, configuration_id: i32) -> sqlx::Result<i64>
 where
@@ -67,52 +144,6 @@ pub async fn create_features(
     Ok(names)
 }
 
-pub async fn find_lengths_by_run_id<'a, E>(
-    executor: E,
-    run_id: i32,
-) -> sqlx::Result<HashMap<String, i32>>
-where
-    E: PgExecutor<'a>,
-{
-    sqlx::query!(
-        "
-        select features.name, features.length
-        from runs
-        inner join features
-            on runs.configuration_id = features.configuration_id
-        where runs.id = $1
-        ",
-        run_id
-    )
-    .fetch(executor)
-    .map(|result| result.map(|row| (row.name, row.length)))
-    .try_collect()
-    .await
-}
-
-pub async fn find_names_by_run_id<'a, E>(executor: E, run_id: i32) -> sqlx::Result<Vec<String>>
-where
-    E: PgExecutor<'a>,
-{
-    sqlx::query!(
-        r#"
-        select
-            features.name
-        from features
-        inner join configurations
-            on features.configuration_id = configurations.id
-        inner join runs
-            on configurations.id = runs.configuration_id
-        where runs.id = $1
-        "#,
-        run_id
-    )
-    .fetch(executor)
-    .map(|result| result.map(|row| row.name))
-    .try_collect()
-    .await
-}
-
 #[cfg(test)]
 mod tests {
     use sqlx::PgPool;
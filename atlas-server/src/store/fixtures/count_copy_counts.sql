@@ -8,12 +8,13 @@ insert into configurations
 values
   (1, 'gene', 'gene_name');
 
-insert into samples (name) values ('sample1');
+insert into samples (name) values ('sample1'), ('sample2');
 
 insert into runs
   (sample_id, configuration_id, strand_specification, data_type)
 values
-  (1, 1, 'reverse', 'RNA-Seq');
+  (1, 1, 'reverse', 'RNA-Seq'),
+  (2, 1, 'reverse', 'RNA-Seq');
 
 insert into features
   (configuration_id, name, length)
@@ -0,0 +1,28 @@
+insert into annotations (name, genome_build) values ('GENCODE 40', 'GRCh38.p13');
+
+insert into configurations
+  (annotation_id, feature_type, feature_name)
+values
+  (1, 'exon', 'gene_name');
+
+insert into features
+  (configuration_id, name, length)
+values
+  (1, 'feature_1', 8),
+  (1, 'feature_2', 13);
+
+insert into samples (name) values ('sample_1'), ('sample_2'), ('sample_3');
+
+insert into runs
+  (sample_id, configuration_id, strand_specification, data_type)
+values
+  (1, 1, 'reverse', 'RNA-Seq'),
+  (2, 1, 'reverse', 'RNA-Seq'),
+  (3, 1, 'reverse', 'RNA-Seq');
+
+insert into run_counts
+  (run_id, values, real_values)
+values
+  (1, '{8, 13}', null),
+  (2, '{21, 0}', null),
+  (3, null, '{1.5, 0.25}');
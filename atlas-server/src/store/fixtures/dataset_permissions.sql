@@ -0,0 +1,12 @@
+insert into datasets
+  (name, is_public)
+values
+  ('public', true),
+  ('restricted', false),
+  ('private', false);
+
+insert into dataset_permissions
+  (dataset_id, subject, permission)
+values
+  (2, 'reader', 'read'),
+  (2, 'writer', 'write');
@@ -3,3 +3,19 @@ insert into samples
 values
   ('sample_1', '2022-02-18T21:05:05+00:00'),
   ('sample_2', '2022-02-18T21:05:06+00:00');
+
+insert into annotations (name, genome_build) values ('GENCODE 40', 'GRCh38.p13');
+
+insert into configurations
+  (annotation_id, feature_type, feature_name)
+values
+  (1, 'exon', 'gene_name');
+
+insert into runs
+  (sample_id, configuration_id, strand_specification, data_type)
+values
+  (1, 1, 'reverse', 'RNA-Seq'),
+  (2, 1, 'reverse', 'RNA-Seq');
+
+insert into datasets (name, is_public) values ('dataset_1', true);
+insert into datasets_runs (dataset_id, run_id) values (1, 1);
@@ -0,0 +1,15 @@
+use std::collections::BTreeMap;
+
+use sqlx::{Postgres, QueryBuilder, types::Json};
+
+/// User-defined attributes of a sample or run, e.g., tissue or diagnosis.
+pub type Metadata = BTreeMap<String, String>;
+
+/// Appends a condition that the metadata in the given column contains all the given attributes.
+pub(super) fn push_contains(builder: &mut QueryBuilder<Postgres>, column: &str, filter: &Metadata) {
+    if !filter.is_empty() {
+        builder
+            .push(format!(" and {column} @> "))
+            .push_bind(Json(filter.clone()));
+    }
+}
@@ -0,0 +1,100 @@
+use atlas_core::counts::normalization::{Factors, Method};
+use sqlx::PgExecutor;
+
+fn method_name(method: Method) -> &'static str {
+    match method {
+        Method::Cpm => "cpm",
+        Method::Fpkm => "fpkm",
+        Method::MedianOfRatios => "median_of_ratios",
+        Method::Tmm => "tmm",
+        Method::Tpm => "tpm",
+        Method::Vst => "vst",
+    }
+}
+
+/// Finds the cached normalization factors of a dataset.
+///
+/// The factors are only returned if they were calculated over exactly the given sorted run IDs.
+pub async fn find<'a, E>(
+    executor: E,
+    dataset_id: i32,
+    method: Method,
+    run_ids: &[i32],
+) -> sqlx::Result<Option<Factors>>
+where
+    E: PgExecutor<'a>,
+{
+    let row = sqlx::query!(
+        "
+        select factors, dispersion
+        from normalization_factors
+        where dataset_id = $1 and method = $2 and run_ids = $3
+        ",
+        dataset_id,
+        method_name(method),
+        run_ids,
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(row.map(|row| Factors::from_parts(method, row.factors, row.dispersion)))
+}
+
+/// Caches the normalization factors of a dataset calculated over the given sorted run IDs.
+pub async fn upsert<'a, E>(
+    executor: E,
+    dataset_id: i32,
+    run_ids: &[i32],
+    factors: &Factors,
+) -> sqlx::Result<()>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!(
+        "
+        insert into normalization_factors (dataset_id, method, run_ids, factors, dispersion)
+        values ($1, $2, $3, $4, $5)
+        on conflict (dataset_id, method) do update
+            set run_ids = excluded.run_ids,
+                factors = excluded.factors,
+                dispersion = excluded.dispersion,
+                created_at = now()
+        ",
+        dataset_id,
+        method_name(factors.method()),
+        run_ids,
+        factors.values(),
+        factors.dispersion(),
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_find_and_upsert(pool: PgPool) -> sqlx::Result<()> {
+        assert!(find(&pool, 1, Method::Tmm, &[1]).await?.is_none());
+
+        let factors = Factors::from_parts(Method::Tmm, vec![1.5], None);
+        upsert(&pool, 1, &[1], &factors).await?;
+
+        assert_eq!(find(&pool, 1, Method::Tmm, &[1]).await?, Some(factors));
+        assert!(find(&pool, 1, Method::Tmm, &[1, 2]).await?.is_none());
+        assert!(find(&pool, 1, Method::Cpm, &[1]).await?.is_none());
+
+        let factors = Factors::from_parts(Method::Tmm, vec![0.5, 2.0], None);
+        upsert(&pool, 1, &[1, 2], &factors).await?;
+
+        assert!(find(&pool, 1, Method::Tmm, &[1]).await?.is_none());
+        assert_eq!(find(&pool, 1, Method::Tmm, &[1, 2]).await?, Some(factors));
+
+        Ok(())
+    }
+}
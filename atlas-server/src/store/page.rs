@@ -0,0 +1,272 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, Row, postgres::PgRow};
+
+/// The number of items in a page when no limit is given.
+pub const DEFAULT_LIMIT: u32 = 100;
+
+/// The maximum number of items in a page.
+pub const MAX_LIMIT: u32 = 1000;
+
+/// A sort direction.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn keyword(self) -> &'static str {
+        match self {
+            Self::Asc => "asc",
+            Self::Desc => "desc",
+        }
+    }
+
+    fn operator(self) -> &'static str {
+        match self {
+            Self::Asc => ">",
+            Self::Desc => "<",
+        }
+    }
+}
+
+/// A key to sort a list by.
+///
+/// Rows are always additionally sorted by ID, so the order is stable when keys are equal.
+pub trait SortKey: Copy {
+    /// Returns the SQL expression of the key and its type, or `None` to sort by ID alone.
+    ///
+    /// The expression must not be nullable.
+    fn column(self) -> Option<(&'static str, &'static str)>;
+}
+
+/// Sorts by ID alone.
+#[derive(Clone, Copy, Debug)]
+pub struct ById;
+
+impl SortKey for ById {
+    fn column(self) -> Option<(&'static str, &'static str)> {
+        None
+    }
+}
+
+/// The position after the last item of a page.
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Cursor {
+    sort: Option<String>,
+    order: SortOrder,
+    key: Option<String>,
+    id: i32,
+}
+
+impl Cursor {
+    /// Decodes an opaque cursor.
+    pub fn decode(s: &str) -> Option<Self> {
+        let buf = URL_SAFE_NO_PAD.decode(s).ok()?;
+        serde_json::from_slice(&buf).ok()
+    }
+
+    /// Encodes the cursor as an opaque, URL-safe string.
+    pub fn encode(&self) -> String {
+        // SAFETY: `Cursor` is always serializable.
+        let buf = serde_json::to_vec(self).unwrap();
+        URL_SAFE_NO_PAD.encode(buf)
+    }
+
+    /// Returns whether the cursor was created for a list with the given sort key and order.
+    pub fn is_compatible<S>(&self, sort: S, order: SortOrder) -> bool
+    where
+        S: SortKey,
+    {
+        self.sort.as_deref() == sort.column().map(|(expr, _)| expr) && self.order == order
+    }
+}
+
+/// Parameters to select a page of a sorted list.
+#[derive(Debug)]
+pub struct PageRequest {
+    pub limit: u32,
+    pub order: SortOrder,
+    pub after: Option<Cursor>,
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_LIMIT,
+            order: SortOrder::default(),
+            after: None,
+        }
+    }
+}
+
+/// A page of a list.
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// The number of items in the list across all pages.
+    pub total: i64,
+    /// The position of the next page, if any.
+    pub next: Option<Cursor>,
+}
+
+/// The source of a list query.
+pub(super) struct Source<'a> {
+    /// The columns to select.
+    pub select: &'a str,
+    /// The `from` clause, including joins.
+    pub from: &'a str,
+    /// The SQL expression of the unique, non-null row ID.
+    pub id: &'a str,
+}
+
+struct Keyed<T> {
+    item: T,
+    id: i32,
+    key: Option<String>,
+}
+
+impl<'r, T> FromRow<'r, PgRow> for Keyed<T>
+where
+    T: FromRow<'r, PgRow>,
+{
+    fn from_row(row: &'r PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            item: T::from_row(row)?,
+            id: row.try_get("page_id")?,
+            key: row.try_get("page_key")?,
+        })
+    }
+}
+
+/// Fetches a page of a list and the total number of items in the list.
+///
+/// `push_filters` is called for each query to append conditions, each prefixed with `and`, to
+/// the `where` clause.
+pub(super) async fn fetch<T, S, F>(
+    pool: &PgPool,
+    source: &Source<'_>,
+    push_filters: F,
+    sort: S,
+    request: &PageRequest,
+) -> sqlx::Result<Page<T>>
+where
+    T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    S: SortKey,
+    F: Fn(&mut QueryBuilder<Postgres>),
+{
+    let Source { select, from, id } = source;
+
+    let mut builder = QueryBuilder::new(format!("select count(*) from {from} where true"));
+    push_filters(&mut builder);
+    let total: i64 = builder.build_query_scalar().fetch_one(pool).await?;
+
+    let column = sort.column();
+    let key = column.map_or("null", |(expr, _)| expr);
+
+    let mut builder = QueryBuilder::new(format!(
+        "select {select}, {id} as page_id, ({key})::text as page_key from {from} where true"
+    ));
+
+    push_filters(&mut builder);
+
+    let order = request.order;
+
+    if let Some(cursor) = &request.after {
+        let operator = order.operator();
+
+        if let Some((expr, ty)) = column {
+            builder
+                .push(format!(" and ({expr}, {id}) {operator} ("))
+                .push_bind(cursor.key.clone())
+                .push(format!("::{ty}, "))
+                .push_bind(cursor.id)
+                .push(")");
+        } else {
+            builder
+                .push(format!(" and {id} {operator} "))
+                .push_bind(cursor.id);
+        }
+    }
+
+    let direction = order.keyword();
+
+    builder.push(" order by ");
+
+    if let Some((expr, _)) = column {
+        builder.push(format!("{expr} {direction}, "));
+    }
+
+    // Fetch one more row than the limit to determine whether there is a next page.
+    builder
+        .push(format!("{id} {direction} limit "))
+        .push_bind(i64::from(request.limit) + 1);
+
+    let mut rows: Vec<Keyed<T>> = builder.build_query_as().fetch_all(pool).await?;
+
+    let limit = request.limit as usize;
+
+    let next = if rows.len() > limit {
+        rows.truncate(limit);
+
+        rows.last().map(|row| Cursor {
+            sort: column.map(|(expr, _)| expr.into()),
+            order,
+            key: row.key.clone(),
+            id: row.id,
+        })
+    } else {
+        None
+    };
+
+    let items = rows.into_iter().map(|row| row.item).collect();
+
+    Ok(Page { items, total, next })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    struct Name;
+
+    impl SortKey for Name {
+        fn column(self) -> Option<(&'static str, &'static str)> {
+            Some(("samples.name", "text"))
+        }
+    }
+
+    #[test]
+    fn test_encode_and_decode() {
+        let cursor = Cursor {
+            sort: Some(String::from("samples.name")),
+            order: SortOrder::Desc,
+            key: Some(String::from("sample_1")),
+            id: 8,
+        };
+
+        let s = cursor.encode();
+        assert!(!s.contains(['+', '/', '=']));
+        assert_eq!(Cursor::decode(&s), Some(cursor));
+
+        assert!(Cursor::decode("").is_none());
+        assert!(Cursor::decode("atlas").is_none());
+    }
+
+    #[test]
+    fn test_is_compatible() {
+        let cursor = Cursor {
+            sort: Some(String::from("samples.name")),
+            order: SortOrder::Asc,
+            key: Some(String::from("sample_1")),
+            id: 8,
+        };
+
+        assert!(cursor.is_compatible(Name, SortOrder::Asc));
+        assert!(!cursor.is_compatible(Name, SortOrder::Desc));
+    }
+}
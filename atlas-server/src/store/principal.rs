@@ -0,0 +1,107 @@
+use sqlx::PgExecutor;
+
+/// The identity on whose behalf data is read or written.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Principal {
+    subject: Option<String>,
+    is_admin: bool,
+}
+
+impl Principal {
+    /// Returns an unauthenticated principal.
+    pub fn anonymous() -> Self {
+        Self::default()
+    }
+
+    /// Returns an administrator, who can read and write all data.
+    #[cfg(test)]
+    pub fn administrator(subject: &str) -> Self {
+        Self {
+            subject: Some(subject.into()),
+            is_admin: true,
+        }
+    }
+
+    /// Returns the subject, if authenticated.
+    pub fn subject(&self) -> Option<&str> {
+        self.subject.as_deref()
+    }
+
+    /// Returns whether the principal is authenticated.
+    pub fn is_authenticated(&self) -> bool {
+        self.subject.is_some()
+    }
+
+    /// Returns whether the principal is an administrator.
+    pub fn is_admin(&self) -> bool {
+        self.is_admin
+    }
+}
+
+/// Loads the principal of an authenticated subject.
+pub async fn find<'a, E>(executor: E, subject: &str) -> sqlx::Result<Principal>
+where
+    E: PgExecutor<'a>,
+{
+    let is_admin = sqlx::query_scalar!(
+        r#"select exists(select 1 from administrators where subject = $1) as "exists!""#,
+        subject
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(Principal {
+        subject: Some(subject.into()),
+        is_admin,
+    })
+}
+
+pub async fn add_administrator<'a, E>(executor: E, subject: &str) -> sqlx::Result<()>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!(
+        "insert into administrators (subject) values ($1) on conflict do nothing",
+        subject
+    )
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+/// Removes an administrator.
+///
+/// Returns whether the subject was an administrator.
+pub async fn remove_administrator<'a, E>(executor: E, subject: &str) -> sqlx::Result<bool>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!("delete from administrators where subject = $1", subject)
+        .execute(executor)
+        .await
+        .map(|result| result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_find(pool: PgPool) -> sqlx::Result<()> {
+        let principal = find(&pool, "user").await?;
+        assert_eq!(principal.subject(), Some("user"));
+        assert!(!principal.is_admin());
+
+        add_administrator(&pool, "user").await?;
+        add_administrator(&pool, "user").await?;
+        assert_eq!(find(&pool, "user").await?, Principal::administrator("user"));
+
+        assert!(remove_administrator(&pool, "user").await?);
+        assert!(!find(&pool, "user").await?.is_admin());
+        assert!(!remove_administrator(&pool, "user").await?);
+
+        Ok(())
+    }
+}
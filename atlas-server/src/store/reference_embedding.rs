@@ -0,0 +1,204 @@
+use atlas_core::counts::dimension_reduction::{
+    pca::Components,
+    tsne::{Dimensions, Preprocessing, Reference},
+};
+use sqlx::{PgExecutor, types::Json};
+
+fn to_indices(values: Vec<i32>) -> Vec<usize> {
+    values.into_iter().map(|n| n as usize).collect()
+}
+
+fn from_indices(indices: &[usize]) -> Vec<i32> {
+    indices.iter().map(|&i| i as i32).collect()
+}
+
+/// Finds the reference embedding of a dataset.
+///
+/// The embedding is only returned if it was fitted to exactly the given sorted run IDs with the
+/// given options.
+pub async fn find<'a, E>(
+    executor: E,
+    dataset_id: i32,
+    run_ids: &[i32],
+    options: &serde_json::Value,
+) -> sqlx::Result<Option<Reference>>
+where
+    E: PgExecutor<'a>,
+{
+    let row = sqlx::query!(
+        "
+        select
+            feature_count,
+            expressed_feature_indices,
+            variable_feature_indices,
+            means,
+            loadings,
+            component_count,
+            data,
+            embedding,
+            dimension_count
+        from reference_embeddings
+        where dataset_id = $1 and run_ids = $2 and options = $3
+        ",
+        dataset_id,
+        run_ids,
+        Json(options) as _,
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let components = match (row.means, row.loadings, row.component_count) {
+        (Some(means), Some(loadings), Some(component_count)) => Some(Components {
+            means,
+            loadings,
+            component_count: component_count as usize,
+        }),
+        _ => None,
+    };
+
+    let dimensions = if row.dimension_count == 3 {
+        Dimensions::Three
+    } else {
+        Dimensions::Two
+    };
+
+    Ok(Some(Reference {
+        dimensions,
+        preprocessing: Preprocessing {
+            feature_count: row.feature_count as usize,
+            expressed_feature_indices: row.expressed_feature_indices.map(to_indices),
+            variable_feature_indices: row.variable_feature_indices.map(to_indices),
+            components,
+        },
+        data: row.data,
+        embedding: row.embedding,
+    }))
+}
+
+/// Stores the reference embedding of a dataset fitted to the given sorted run IDs and options.
+///
+/// This replaces any existing reference embedding of the dataset.
+pub async fn upsert<'a, E>(
+    executor: E,
+    dataset_id: i32,
+    run_ids: &[i32],
+    options: &serde_json::Value,
+    reference: &Reference,
+) -> sqlx::Result<()>
+where
+    E: PgExecutor<'a>,
+{
+    let preprocessing = &reference.preprocessing;
+    let components = preprocessing.components.as_ref();
+
+    sqlx::query!(
+        "
+        insert into reference_embeddings (
+            dataset_id,
+            run_ids,
+            options,
+            feature_count,
+            expressed_feature_indices,
+            variable_feature_indices,
+            means,
+            loadings,
+            component_count,
+            data,
+            embedding,
+            dimension_count
+        )
+        values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        on conflict (dataset_id) do update
+            set run_ids = excluded.run_ids,
+                options = excluded.options,
+                feature_count = excluded.feature_count,
+                expressed_feature_indices = excluded.expressed_feature_indices,
+                variable_feature_indices = excluded.variable_feature_indices,
+                means = excluded.means,
+                loadings = excluded.loadings,
+                component_count = excluded.component_count,
+                data = excluded.data,
+                embedding = excluded.embedding,
+                dimension_count = excluded.dimension_count,
+                created_at = now()
+        ",
+        dataset_id,
+        run_ids,
+        Json(options) as _,
+        preprocessing.feature_count as i32,
+        preprocessing
+            .expressed_feature_indices
+            .as_deref()
+            .map(from_indices) as _,
+        preprocessing
+            .variable_feature_indices
+            .as_deref()
+            .map(from_indices) as _,
+        components.map(|c| c.means.as_slice()) as _,
+        components.map(|c| c.loadings.as_slice()) as _,
+        components.map(|c| c.component_count as i32),
+        &reference.data,
+        &reference.embedding,
+        reference.dimensions.count() as i32,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use super::*;
+
+    fn build_reference(feature_count: usize, embedding: Vec<f64>) -> Reference {
+        Reference {
+            dimensions: Dimensions::Two,
+            preprocessing: Preprocessing {
+                feature_count,
+                expressed_feature_indices: None,
+                variable_feature_indices: Some(vec![1]),
+                components: Some(Components {
+                    means: vec![0.5],
+                    loadings: vec![1.0],
+                    component_count: 1,
+                }),
+            },
+            data: vec![-0.5, 0.5],
+            embedding,
+        }
+    }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_find_and_upsert(pool: PgPool) -> sqlx::Result<()> {
+        let options = json!({ "perplexity": 5.0 });
+
+        assert!(find(&pool, 1, &[1], &options).await?.is_none());
+
+        let reference = build_reference(2, vec![-1.0, 0.0, 1.0, 0.0]);
+        upsert(&pool, 1, &[1], &options, &reference).await?;
+
+        assert_eq!(find(&pool, 1, &[1], &options).await?, Some(reference));
+        assert!(find(&pool, 1, &[1, 2], &options).await?.is_none());
+        assert!(
+            find(&pool, 1, &[1], &json!({ "perplexity": 8.0 }))
+                .await?
+                .is_none()
+        );
+
+        let reference = build_reference(3, vec![0.0, -1.0, 0.0, 1.0]);
+        upsert(&pool, 1, &[1, 2], &options, &reference).await?;
+
+        assert!(find(&pool, 1, &[1], &options).await?.is_none());
+        assert_eq!(find(&pool, 1, &[1, 2], &options).await?, Some(reference));
+
+        Ok(())
+    }
+}
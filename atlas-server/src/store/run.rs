@@ -1,18 +1,24 @@
-use serde::Serialize;
-use sqlx::{PgExecutor, Postgres, Transaction};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction, types::Json};
+use time::OffsetDateTime;
 
-use super::StrandSpecification;
+use super::{
+    Metadata, Principal, StrandSpecification, metadata,
+    page::{self, Page, PageRequest, SortKey, Source},
+};
 
-#[derive(Serialize)]
+#[derive(Serialize, sqlx::FromRow)]
 pub struct Run {
     id: i32,
     sample_id: i32,
     configuration_id: i32,
     strand_specification: StrandSpecification,
     data_type: String,
+    metadata: Json<Metadata>,
 }
 
-pub async fn find<'a, E>(executor: E, id: i32) -> sqlx::Result<Option<Run>>
+/// Finds a run by ID if it is readable by the principal.
+pub async fn find<'a, E>(executor: E, principal: &Principal, id: i32) -> sqlx::Result<Option<Run>>
 where
     E: PgExecutor<'a>,
 {
@@ -24,17 +30,25 @@ where
             sample_id,
             configuration_id,
             strand_specification as "strand_specification: _",
-            data_type
+            data_type,
+            metadata as "metadata: _"
         from runs
-        where id = $1
+        where id = $1 and run_is_readable(id, $2, $3)
         "#,
-        id
+        id,
+        principal.subject(),
+        principal.is_admin(),
     )
     .fetch_optional(executor)
     .await
 }
 
-pub async fn where_sample_id<'a, E>(executor: E, id: i32) -> sqlx::Result<Vec<Run>>
+/// Returns the runs of a sample that are readable by the principal.
+pub async fn where_sample_id<'a, E>(
+    executor: E,
+    principal: &Principal,
+    id: i32,
+) -> sqlx::Result<Vec<Run>>
 where
     E: PgExecutor<'a>,
 {
@@ -46,38 +60,356 @@ where
             sample_id,
             configuration_id,
             strand_specification as "strand_specification: _",
-            data_type
+            data_type,
+            metadata as "metadata: _"
         from runs
-        where sample_id = $1
+        where sample_id = $1 and run_is_readable(id, $2, $3)
         "#,
-        id
+        id,
+        principal.subject(),
+        principal.is_admin(),
     )
     .fetch_all(executor)
     .await
 }
 
-pub async fn where_dataset_id<'a, E>(executor: E, dataset_id: i32) -> sqlx::Result<Vec<Run>>
+/// A key to sort runs by.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    #[default]
+    Id,
+    CreatedAt,
+}
+
+impl SortKey for SortBy {
+    fn column(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Id => None,
+            Self::CreatedAt => Some(("runs.created_at", "timestamptz")),
+        }
+    }
+}
+
+/// Conditions runs must match.
+#[derive(Debug, Default)]
+pub struct Filter {
+    pub data_type: Option<String>,
+    pub strand_specification: Option<StrandSpecification>,
+    /// The inclusive lower bound of the creation time.
+    pub created_after: Option<OffsetDateTime>,
+    /// The exclusive upper bound of the creation time.
+    pub created_before: Option<OffsetDateTime>,
+    /// Attributes the metadata must contain.
+    pub metadata: Metadata,
+}
+
+/// Returns a page of runs in a dataset if the dataset is readable by the principal.
+pub async fn page_where_dataset_id(
+    pool: &PgPool,
+    principal: &Principal,
+    dataset_id: i32,
+    filter: &Filter,
+    sort: SortBy,
+    request: &PageRequest,
+) -> sqlx::Result<Page<Run>> {
+    let source = Source {
+        select: "
+            runs.id,
+            runs.sample_id,
+            runs.configuration_id,
+            runs.strand_specification,
+            runs.data_type,
+            runs.metadata
+        ",
+        from: "runs inner join datasets_runs on runs.id = datasets_runs.run_id",
+        id: "runs.id",
+    };
+
+    page::fetch(
+        pool,
+        &source,
+        |builder| {
+            builder
+                .push(" and datasets_runs.dataset_id = ")
+                .push_bind(dataset_id)
+                .push(" and dataset_is_readable(datasets_runs.dataset_id, ")
+                .push_bind(principal.subject().map(String::from))
+                .push(", ")
+                .push_bind(principal.is_admin())
+                .push(")");
+
+            if let Some(data_type) = &filter.data_type {
+                builder
+                    .push(" and runs.data_type = ")
+                    .push_bind(data_type.clone());
+            }
+
+            if let Some(strand_specification) = filter.strand_specification {
+                builder
+                    .push(" and runs.strand_specification = ")
+                    .push_bind(strand_specification);
+            }
+
+            if let Some(created_after) = filter.created_after {
+                builder
+                    .push(" and runs.created_at >= ")
+                    .push_bind(created_after);
+            }
+
+            if let Some(created_before) = filter.created_before {
+                builder
+                    .push(" and runs.created_at < ")
+                    .push_bind(created_before);
+            }
+
+            metadata::push_contains(builder, "runs.metadata", &filter.metadata);
+        },
+        sort,
+        request,
+    )
+    .await
+}
+
+/// Returns whether the principal can edit a run.
+///
+/// Administrators can edit all runs. Otherwise, the run must be in a dataset the principal can
+/// write to.
+pub async fn is_writable<'a, E>(executor: E, principal: &Principal, id: i32) -> sqlx::Result<bool>
 where
     E: PgExecutor<'a>,
 {
-    sqlx::query_as!(
-        Run,
+    sqlx::query_scalar!(
         r#"
-        select
-            runs.id,
-            sample_id,
-            configuration_id,
-            strand_specification as "strand_specification: _",
-            data_type
+        select $2 or exists (
+            select 1
+            from datasets_runs
+            where datasets_runs.run_id = $1
+                and dataset_is_writable(datasets_runs.dataset_id, $3, $2)
+        ) as "is_writable!"
+        "#,
+        id,
+        principal.is_admin(),
+        principal.subject(),
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Returns the metadata of a run, or `None` if the run does not exist.
+pub async fn find_metadata<'a, E>(executor: E, id: i32) -> sqlx::Result<Option<Metadata>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        r#"select metadata as "metadata: Json<Metadata>" from runs where id = $1"#,
+        id
+    )
+    .fetch_optional(executor)
+    .await
+    .map(|metadata| metadata.map(|Json(metadata)| metadata))
+}
+
+/// Replaces the metadata of a run.
+///
+/// Returns whether the run exists.
+pub async fn set_metadata<'a, E>(executor: E, id: i32, metadata: &Metadata) -> sqlx::Result<bool>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!(
+        "update runs set metadata = $2 where id = $1",
+        id,
+        Json(metadata) as _,
+    )
+    .execute(executor)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+/// Returns whether the principal can delete a run.
+///
+/// Administrators can delete all runs. Otherwise, the run must be in at least one dataset, and
+/// the principal must be able to write to all of its datasets.
+pub async fn is_deletable<'a, E>(executor: E, principal: &Principal, id: i32) -> sqlx::Result<bool>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        r#"
+        select $2 or (
+            exists (select 1 from datasets_runs where run_id = $1)
+            and not exists (
+                select 1
+                from datasets_runs
+                where datasets_runs.run_id = $1
+                    and not dataset_is_writable(datasets_runs.dataset_id, $3, $2)
+            )
+        ) as "is_deletable!"
+        "#,
+        id,
+        principal.is_admin(),
+        principal.subject(),
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Deletes a run with its counts and dataset memberships.
+///
+/// Returns the IDs of the datasets the run was removed from, or `None` if the run does not
+/// exist.
+pub async fn delete(tx: &mut Transaction<'_, Postgres>, id: i32) -> sqlx::Result<Option<Vec<i32>>> {
+    let exists = sqlx::query_scalar!("select id from runs where id = $1 for update", id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .is_some();
+
+    if !exists {
+        return Ok(None);
+    }
+
+    let dataset_ids = sqlx::query_scalar!(
+        "delete from datasets_runs where run_id = $1 returning dataset_id",
+        id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    sqlx::query!("delete from run_counts where run_id = $1", id)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query!("delete from runs where id = $1", id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(Some(dataset_ids))
+}
+
+/// Returns the given run IDs that exist and are readable by the principal.
+///
+/// The IDs are unique and in ascending order.
+pub async fn filter_readable<'a, E>(
+    executor: E,
+    principal: &Principal,
+    ids: &[i32],
+) -> sqlx::Result<Vec<i32>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        "
+        select id
         from runs
-        inner join datasets_runs
+        where id = any($1) and run_is_readable(id, $2, $3)
+        order by id
+        ",
+        ids,
+        principal.subject(),
+        principal.is_admin(),
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Returns the ID and metadata of each run in a dataset, ordered by run ID.
+///
+/// Run metadata takes precedence over sample metadata of the same key.
+pub async fn metadata_where_dataset_id<'a, E>(
+    executor: E,
+    dataset_id: i32,
+) -> sqlx::Result<Vec<(i32, Metadata)>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!(
+        r#"
+        select runs.id, samples.metadata || runs.metadata as "metadata!: Json<Metadata>"
+        from datasets_runs
+        inner join runs
             on runs.id = datasets_runs.run_id
+        inner join samples
+            on samples.id = runs.sample_id
         where datasets_runs.dataset_id = $1
+        order by runs.id
+        "#,
+        dataset_id,
+    )
+    .fetch_all(executor)
+    .await
+    .map(|rows| {
+        rows.into_iter()
+            .map(|row| (row.id, row.metadata.0))
+            .collect()
+    })
+}
+
+/// Returns the IDs of runs in any of the given datasets or with any of the given IDs.
+///
+/// The IDs are unique and in ascending order. Run IDs that do not exist are ignored.
+pub async fn select_ids<'a, E>(
+    executor: E,
+    dataset_ids: &[i32],
+    run_ids: &[i32],
+) -> sqlx::Result<Vec<i32>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        r#"
+        select id as "id!" from runs where id = any($2)
+        union
+        select run_id from datasets_runs where dataset_id = any($1)
+        order by 1
+        "#,
+        dataset_ids,
+        run_ids,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Returns the distinct configuration IDs of the given runs.
+pub async fn configuration_ids<'a, E>(executor: E, ids: &[i32]) -> sqlx::Result<Vec<i32>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        "select distinct configuration_id from runs where id = any($1) order by 1",
+        ids,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Groups the given runs by configuration.
+///
+/// Returns each configuration ID with its run IDs, both in ascending order.
+pub async fn group_by_configuration<'a, E>(
+    executor: E,
+    ids: &[i32],
+) -> sqlx::Result<Vec<(i32, Vec<i32>)>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!(
+        r#"
+        select configuration_id, array_agg(id order by id) as "run_ids!"
+        from runs
+        where id = any($1)
+        group by configuration_id
+        order by configuration_id
         "#,
-        dataset_id
+        ids,
     )
     .fetch_all(executor)
     .await
+    .map(|rows| {
+        rows.into_iter()
+            .map(|row| (row.configuration_id, row.run_ids))
+            .collect()
+    })
 }
 
 pub async fn runs_exists<'a, E>(
@@ -103,6 +435,30 @@ where
     .map(|n| n > 0)
 }
 
+/// Returns the ID of the run of a sample with a configuration, if any.
+pub async fn find_id<'a, E>(
+    executor: E,
+    configuration_id: i32,
+    sample_id: i32,
+) -> sqlx::Result<Option<i32>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        "
+        select id
+        from runs
+        where configuration_id = $1 and sample_id = $2
+        order by id
+        limit 1
+        ",
+        configuration_id,
+        sample_id,
+    )
+    .fetch_optional(executor)
+    .await
+}
+
 #[cfg(test)]
 pub async fn create_run(
     tx: &mut Transaction<'_, Postgres>,
@@ -167,6 +523,181 @@ mod tests {
         annotations::find_or_create_annotations, configuration, sample::find_or_create_sample,
     };
 
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_find(pool: PgPool) -> sqlx::Result<()> {
+        let anonymous = Principal::anonymous();
+        assert!(find(&pool, &anonymous, 1).await?.is_some());
+        assert!(find(&pool, &anonymous, 2).await?.is_none());
+
+        let admin = Principal::administrator("admin");
+        assert!(find(&pool, &admin, 2).await?.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_page_where_dataset_id(pool: PgPool) -> sqlx::Result<()> {
+        use crate::store::dataset;
+
+        dataset::add(&pool, 1, 2).await?;
+
+        let ids = |page: &Page<Run>| -> Vec<i32> { page.items.iter().map(|r| r.id).collect() };
+
+        let anonymous = Principal::anonymous();
+        let request = PageRequest::default();
+
+        let filter = Filter::default();
+        let actual =
+            page_where_dataset_id(&pool, &anonymous, 1, &filter, SortBy::Id, &request).await?;
+        assert_eq!(ids(&actual), [1, 2]);
+        assert_eq!(actual.total, 2);
+
+        let filter = Filter {
+            strand_specification: Some(StrandSpecification::Reverse),
+            data_type: Some(String::from("RNA-Seq")),
+            ..Default::default()
+        };
+        let actual =
+            page_where_dataset_id(&pool, &anonymous, 1, &filter, SortBy::CreatedAt, &request)
+                .await?;
+        assert_eq!(ids(&actual), [1, 2]);
+
+        let metadata = Metadata::from([(String::from("tissue"), String::from("liver"))]);
+        assert!(set_metadata(&pool, 2, &metadata).await?);
+
+        let filter = Filter {
+            metadata,
+            ..Default::default()
+        };
+        let actual =
+            page_where_dataset_id(&pool, &anonymous, 1, &filter, SortBy::Id, &request).await?;
+        assert_eq!(ids(&actual), [2]);
+
+        let filter = Filter {
+            strand_specification: Some(StrandSpecification::Forward),
+            ..Default::default()
+        };
+        let actual =
+            page_where_dataset_id(&pool, &anonymous, 1, &filter, SortBy::Id, &request).await?;
+        assert!(actual.items.is_empty());
+        assert_eq!(actual.total, 0);
+
+        sqlx::query("update datasets set is_public = false")
+            .execute(&pool)
+            .await?;
+
+        let filter = Filter::default();
+        let actual =
+            page_where_dataset_id(&pool, &anonymous, 1, &filter, SortBy::Id, &request).await?;
+        assert!(actual.items.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_is_writable(pool: PgPool) -> sqlx::Result<()> {
+        use crate::store::{DatasetPermission, dataset};
+
+        let writer = crate::store::principal::find(&pool, "writer").await?;
+        assert!(!is_writable(&pool, &writer, 1).await?);
+
+        dataset::grant(&pool, 1, "writer", DatasetPermission::Write).await?;
+        assert!(is_writable(&pool, &writer, 1).await?);
+        assert!(!is_writable(&pool, &writer, 2).await?);
+
+        assert!(is_writable(&pool, &Principal::administrator("admin"), 2).await?);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_is_deletable(pool: PgPool) -> sqlx::Result<()> {
+        use crate::store::{DatasetPermission, dataset};
+
+        dataset::grant(&pool, 1, "writer", DatasetPermission::Write).await?;
+        let writer = crate::store::principal::find(&pool, "writer").await?;
+        assert!(is_deletable(&pool, &writer, 1).await?);
+        assert!(!is_deletable(&pool, &writer, 2).await?);
+
+        let dataset_id = dataset::create(&pool, "dataset_2", false).await?;
+        dataset::add(&pool, dataset_id, 1).await?;
+        assert!(!is_deletable(&pool, &writer, 1).await?);
+
+        assert!(is_deletable(&pool, &Principal::administrator("admin"), 2).await?);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_delete(pool: PgPool) -> sqlx::Result<()> {
+        let mut tx = pool.begin().await?;
+        assert_eq!(delete(&mut tx, 1).await?, Some(vec![1]));
+        assert_eq!(delete(&mut tx, 2).await?, Some(Vec::new()));
+        assert!(delete(&mut tx, 3).await?.is_none());
+        tx.commit().await?;
+
+        assert!(select_ids(&pool, &[1], &[1, 2]).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_filter_readable(pool: PgPool) -> sqlx::Result<()> {
+        let anonymous = Principal::anonymous();
+        assert_eq!(filter_readable(&pool, &anonymous, &[2, 1, 3]).await?, [1]);
+
+        let admin = Principal::administrator("admin");
+        assert_eq!(filter_readable(&pool, &admin, &[2, 1, 3]).await?, [1, 2]);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_select_ids(pool: PgPool) -> sqlx::Result<()> {
+        assert_eq!(select_ids(&pool, &[1], &[]).await?, [1]);
+        assert_eq!(select_ids(&pool, &[], &[2, 3]).await?, [2]);
+        assert_eq!(select_ids(&pool, &[1, 2], &[2, 1]).await?, [1, 2]);
+        assert!(select_ids(&pool, &[], &[]).await?.is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_metadata_where_dataset_id(pool: PgPool) -> sqlx::Result<()> {
+        sqlx::query(r#"update samples set metadata = '{"tissue": "liver", "sex": "F"}'"#)
+            .execute(&pool)
+            .await?;
+        sqlx::query(r#"update runs set metadata = '{"tissue": "lung"}' where id = 1"#)
+            .execute(&pool)
+            .await?;
+
+        let expected = Metadata::from([
+            (String::from("sex"), String::from("F")),
+            (String::from("tissue"), String::from("lung")),
+        ]);
+
+        assert_eq!(metadata_where_dataset_id(&pool, 1).await?, [(1, expected)]);
+        assert!(metadata_where_dataset_id(&pool, 2).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_configuration_ids(pool: PgPool) -> sqlx::Result<()> {
+        assert_eq!(configuration_ids(&pool, &[1, 2]).await?, [1]);
+        assert!(configuration_ids(&pool, &[3]).await?.is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("dataset_runs"))]
+    async fn test_group_by_configuration(pool: PgPool) -> sqlx::Result<()> {
+        assert_eq!(
+            group_by_configuration(&pool, &[2, 1, 3]).await?,
+            [(1, vec![1, 2])]
+        );
+        assert!(group_by_configuration(&pool, &[3]).await?.is_empty());
+        Ok(())
+    }
+
     #[sqlx::test]
     async fn test_runs_exists(pool: PgPool) -> sqlx::Result<()> {
         let mut tx = pool.begin().await?;
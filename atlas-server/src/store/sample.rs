@@ -1,8 +1,13 @@
-use serde::Serialize;
-use sqlx::PgExecutor;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool, types::Json};
 use time::OffsetDateTime;
 
-#[derive(Debug, Eq, PartialEq, Serialize, utoipa::ToSchema)]
+use super::{
+    Metadata, Principal, metadata,
+    page::{self, Page, PageRequest, SortKey, Source},
+};
+
+#[derive(Debug, Eq, PartialEq, Serialize, sqlx::FromRow, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Sample {
     id: i32,
@@ -10,30 +15,257 @@ pub struct Sample {
     #[schema(inline)]
     #[serde(with = "time::serde::rfc3339")]
     created_at: OffsetDateTime,
+    #[schema(value_type = Object)]
+    metadata: Json<Metadata>,
+}
+
+/// A key to sort samples by.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    #[default]
+    Id,
+    Name,
+    CreatedAt,
+}
+
+impl SortKey for SortBy {
+    fn column(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Id => None,
+            Self::Name => Some(("samples.name", "text")),
+            Self::CreatedAt => Some(("samples.created_at", "timestamptz")),
+        }
+    }
+}
+
+/// Conditions samples must match.
+#[derive(Debug, Default)]
+pub struct Filter {
+    /// A case-insensitive prefix of the name.
+    pub name_prefix: Option<String>,
+    /// The inclusive lower bound of the creation time.
+    pub created_after: Option<OffsetDateTime>,
+    /// The exclusive upper bound of the creation time.
+    pub created_before: Option<OffsetDateTime>,
+    /// Attributes the metadata must contain.
+    pub metadata: Metadata,
+}
+
+/// Returns a page of samples with at least one run readable by the principal.
+pub async fn page(
+    pool: &PgPool,
+    principal: &Principal,
+    filter: &Filter,
+    sort: SortBy,
+    request: &PageRequest,
+) -> sqlx::Result<Page<Sample>> {
+    let source = Source {
+        select: "samples.id, samples.name, samples.created_at, samples.metadata",
+        from: "samples",
+        id: "samples.id",
+    };
+
+    page::fetch(
+        pool,
+        &source,
+        |builder| {
+            builder
+                .push(
+                    " and exists (select 1 from runs where runs.sample_id = samples.id \
+                    and run_is_readable(runs.id, ",
+                )
+                .push_bind(principal.subject().map(String::from))
+                .push(", ")
+                .push_bind(principal.is_admin())
+                .push("))");
+
+            if let Some(prefix) = &filter.name_prefix {
+                builder
+                    .push(" and starts_with(lower(samples.name), lower(")
+                    .push_bind(prefix.clone())
+                    .push("))");
+            }
+
+            if let Some(created_after) = filter.created_after {
+                builder
+                    .push(" and samples.created_at >= ")
+                    .push_bind(created_after);
+            }
+
+            if let Some(created_before) = filter.created_before {
+                builder
+                    .push(" and samples.created_at < ")
+                    .push_bind(created_before);
+            }
+
+            metadata::push_contains(builder, "samples.metadata", &filter.metadata);
+        },
+        sort,
+        request,
+    )
+    .await
 }
 
-pub async fn all<'a, E>(executor: E) -> sqlx::Result<Vec<Sample>>
+/// Finds a sample by ID if it has at least one run readable by the principal.
+pub async fn find<'a, E>(
+    executor: E,
+    principal: &Principal,
+    id: i32,
+) -> sqlx::Result<Option<Sample>>
 where
     E: PgExecutor<'a>,
 {
-    sqlx::query_as!(Sample, r#"select id, name, created_at from samples"#)
-        .fetch_all(executor)
-        .await
+    sqlx::query_as!(
+        Sample,
+        r#"
+        select id, name, created_at, metadata as "metadata: _"
+        from samples
+        where id = $1
+            and exists (
+                select 1
+                from runs
+                where runs.sample_id = samples.id and run_is_readable(runs.id, $2, $3)
+            )
+        "#,
+        id,
+        principal.subject(),
+        principal.is_admin(),
+    )
+    .fetch_optional(executor)
+    .await
 }
 
-pub async fn find<'a, E>(executor: E, id: i32) -> sqlx::Result<Option<Sample>>
+/// Returns the names of samples with a run in a dataset.
+pub async fn names_where_dataset_id<'a, E>(executor: E, id: i32) -> sqlx::Result<Vec<String>>
 where
     E: PgExecutor<'a>,
 {
-    sqlx::query_as!(
-        Sample,
-        "select id, name, created_at from samples where id = $1",
-        id
+    sqlx::query_scalar!(
+        "
+        select distinct samples.name
+        from samples
+        inner join runs
+            on runs.sample_id = samples.id
+        inner join datasets_runs
+            on datasets_runs.run_id = runs.id
+        where datasets_runs.dataset_id = $1
+        order by samples.name
+        ",
+        id,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Returns whether the principal can edit a sample.
+///
+/// Administrators can edit all samples. Otherwise, the sample must have a run in at least one
+/// dataset, and the principal must be able to write to all datasets with a run of the sample,
+/// since they share its name and metadata.
+pub async fn is_writable<'a, E>(executor: E, principal: &Principal, id: i32) -> sqlx::Result<bool>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        r#"
+        select $2 or (
+            exists (
+                select 1
+                from runs
+                inner join datasets_runs
+                    on runs.id = datasets_runs.run_id
+                where runs.sample_id = $1
+            )
+            and not exists (
+                select 1
+                from runs
+                inner join datasets_runs
+                    on runs.id = datasets_runs.run_id
+                where runs.sample_id = $1
+                    and not dataset_is_writable(datasets_runs.dataset_id, $3, $2)
+            )
+        ) as "is_writable!"
+        "#,
+        id,
+        principal.is_admin(),
+        principal.subject(),
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Replaces the metadata of a sample.
+///
+/// Returns whether the sample exists.
+pub async fn set_metadata<'a, E>(executor: E, id: i32, metadata: &Metadata) -> sqlx::Result<bool>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query!(
+        "update samples set metadata = $2 where id = $1",
+        id,
+        Json(metadata) as _,
+    )
+    .execute(executor)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+/// Renames a sample.
+///
+/// Returns the previous name, or `None` if the sample does not exist. Fails with a unique
+/// violation if another sample has the name.
+pub async fn rename<'a, E>(executor: E, id: i32, name: &str) -> sqlx::Result<Option<String>>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query_scalar!(
+        "
+        update samples
+        set name = $2
+        from samples as previous
+        where samples.id = $1 and previous.id = samples.id
+        returning previous.name
+        ",
+        id,
+        name,
     )
     .fetch_optional(executor)
     .await
 }
 
+/// Merges attributes into the metadata of samples by name.
+///
+/// Existing attributes with the same keys are overwritten. Returns the names of the samples
+/// updated; names that do not exist are ignored.
+pub async fn merge_metadata<'a, E>(
+    executor: E,
+    metadata: &[(String, Metadata)],
+) -> sqlx::Result<Vec<String>>
+where
+    E: PgExecutor<'a>,
+{
+    let (names, values): (Vec<_>, Vec<_>) = metadata
+        .iter()
+        .map(|(name, metadata)| (name.clone(), Json(metadata.clone())))
+        .unzip();
+
+    sqlx::query_scalar!(
+        "
+        update samples
+        set metadata = samples.metadata || m.metadata
+        from unnest($1::text[], $2::jsonb[]) as m (name, metadata)
+        where samples.name = m.name
+        returning samples.name
+        ",
+        &names,
+        &values as _,
+    )
+    .fetch_all(executor)
+    .await
+}
+
 #[cfg(test)]
 pub async fn find_or_create_sample(
     tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
@@ -84,8 +316,10 @@ mod tests {
 
     #[sqlx::test(fixtures("sample_find"))]
     async fn test_find(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let anonymous = Principal::anonymous();
+
         assert_eq!(
-            find(&pool, 1).await?,
+            find(&pool, &anonymous, 1).await?,
             Some(Sample {
                 id: 1,
                 name: String::from("sample_1"),
@@ -93,10 +327,173 @@ mod tests {
                     Date::from_calendar_date(2022, Month::February, 18)?,
                     Time::from_hms(21, 5, 5)?
                 ),
+                metadata: Json(Metadata::new()),
             })
         );
 
-        assert!(find(&pool, 3).await?.is_none());
+        // Sample 2 only has a run that is not in a dataset.
+        assert!(find(&pool, &anonymous, 2).await?.is_none());
+        assert!(find(&pool, &anonymous, 3).await?.is_none());
+
+        let admin = Principal::administrator("admin");
+        assert!(find(&pool, &admin, 2).await?.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("sample_find"))]
+    async fn test_page(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        use crate::store::page::SortOrder;
+
+        let ids = |page: &Page<Sample>| -> Vec<i32> { page.items.iter().map(|s| s.id).collect() };
+
+        let admin = Principal::administrator("admin");
+        let filter = Filter::default();
+
+        let request = PageRequest {
+            limit: 1,
+            ..Default::default()
+        };
+        let first = page(&pool, &admin, &filter, SortBy::Id, &request).await?;
+        assert_eq!(ids(&first), [1]);
+        assert_eq!(first.total, 2);
+        assert!(first.next.is_some());
+
+        let request = PageRequest {
+            limit: 1,
+            after: first.next,
+            ..Default::default()
+        };
+        let second = page(&pool, &admin, &filter, SortBy::Id, &request).await?;
+        assert_eq!(ids(&second), [2]);
+        assert_eq!(second.total, 2);
+        assert!(second.next.is_none());
+
+        let request = PageRequest {
+            limit: 1,
+            order: SortOrder::Desc,
+            ..Default::default()
+        };
+        let first = page(&pool, &admin, &filter, SortBy::CreatedAt, &request).await?;
+        assert_eq!(ids(&first), [2]);
+
+        let request = PageRequest {
+            limit: 1,
+            order: SortOrder::Desc,
+            after: first.next,
+        };
+        let second = page(&pool, &admin, &filter, SortBy::CreatedAt, &request).await?;
+        assert_eq!(ids(&second), [1]);
+
+        let anonymous = Principal::anonymous();
+        let actual = page(
+            &pool,
+            &anonymous,
+            &filter,
+            SortBy::Id,
+            &PageRequest::default(),
+        )
+        .await?;
+        assert_eq!(ids(&actual), [1]);
+        assert_eq!(actual.total, 1);
+
+        let filter = Filter {
+            name_prefix: Some(String::from("SAMPLE_2")),
+            ..Default::default()
+        };
+        let actual = page(
+            &pool,
+            &admin,
+            &filter,
+            SortBy::Name,
+            &PageRequest::default(),
+        )
+        .await?;
+        assert_eq!(ids(&actual), [2]);
+
+        let filter = Filter {
+            created_after: Some(OffsetDateTime::new_utc(
+                Date::from_calendar_date(2022, Month::February, 18)?,
+                Time::from_hms(21, 5, 6)?,
+            )),
+            ..Default::default()
+        };
+        let actual = page(&pool, &admin, &filter, SortBy::Id, &PageRequest::default()).await?;
+        assert_eq!(ids(&actual), [2]);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("sample_find"))]
+    async fn test_merge_metadata(pool: PgPool) -> sqlx::Result<()> {
+        let metadata = |pairs: &[(&str, &str)]| -> Metadata {
+            pairs
+                .iter()
+                .map(|(key, value)| (String::from(*key), String::from(*value)))
+                .collect()
+        };
+
+        assert!(set_metadata(&pool, 1, &metadata(&[("tissue", "lung"), ("sex", "F")])).await?);
+        assert!(!set_metadata(&pool, 3, &Metadata::new()).await?);
+
+        let names = merge_metadata(
+            &pool,
+            &[
+                (String::from("sample_1"), metadata(&[("tissue", "liver")])),
+                (String::from("sample_3"), metadata(&[("tissue", "liver")])),
+            ],
+        )
+        .await?;
+        assert_eq!(names, ["sample_1"]);
+
+        let admin = Principal::administrator("admin");
+        let sample = find(&pool, &admin, 1).await?.unwrap();
+        assert_eq!(
+            *sample.metadata,
+            metadata(&[("sex", "F"), ("tissue", "liver")])
+        );
+
+        let filter = Filter {
+            metadata: metadata(&[("tissue", "liver")]),
+            ..Default::default()
+        };
+        let actual = page(&pool, &admin, &filter, SortBy::Id, &PageRequest::default()).await?;
+        assert_eq!(actual.items, [sample]);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("sample_find"))]
+    async fn test_names_where_dataset_id(pool: PgPool) -> sqlx::Result<()> {
+        assert_eq!(names_where_dataset_id(&pool, 1).await?, ["sample_1"]);
+        assert!(names_where_dataset_id(&pool, 2).await?.is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("sample_find"))]
+    async fn test_is_writable(pool: PgPool) -> sqlx::Result<()> {
+        use crate::store::{DatasetPermission, dataset};
+
+        assert!(is_writable(&pool, &Principal::administrator("admin"), 1).await?);
+
+        let writer = crate::store::principal::find(&pool, "writer").await?;
+        assert!(!is_writable(&pool, &writer, 1).await?);
+
+        dataset::grant(&pool, 1, "writer", DatasetPermission::Write).await?;
+        assert!(is_writable(&pool, &writer, 1).await?);
+        assert!(!is_writable(&pool, &writer, 2).await?);
+
+        // The writer must be able to write to every dataset with a run of the sample.
+        sqlx::query!("insert into datasets (name, is_public) values ('dataset_2', false)")
+            .execute(&pool)
+            .await?;
+        sqlx::query!("insert into datasets_runs (dataset_id, run_id) values (2, 1)")
+            .execute(&pool)
+            .await?;
+        assert!(!is_writable(&pool, &writer, 1).await?);
+
+        dataset::grant(&pool, 2, "writer", DatasetPermission::Write).await?;
+        assert!(is_writable(&pool, &writer, 1).await?);
 
         Ok(())
     }